@@ -0,0 +1,237 @@
+using System;
+using System.Collections;
+using System.Collections.Generic;
+using System.Globalization;
+using System.Runtime.InteropServices;
+using System.Text;
+
+namespace Stache
+{
+    public static class Templates
+    {
+        private const string Library = "stache";
+
+        private static readonly HashSet<string> Names = new HashSet<string> { "escape", "includes/footer", "includes/header", "robot", "robots", "sections/dot", "sections/index", "sections/true", "types/array", "types/boolean", "types/class", "types/float", "types/hash", "types/nil", "types/string", "types/struct" };
+
+        [DllImport(Library, EntryPoint = "stache_render", CallingConvention = CallingConvention.Cdecl)]
+        private static extern IntPtr NativeRender([MarshalAs(UnmanagedType.LPUTF8Str)] string name, IntPtr context);
+
+        [DllImport(Library, EntryPoint = "stache_free", CallingConvention = CallingConvention.Cdecl)]
+        private static extern void NativeFree(IntPtr output);
+
+        private const int CtxNull = 0;
+        private const int CtxBool = 1;
+        private const int CtxInteger = 2;
+        private const int CtxString = 3;
+        private const int CtxList = 4;
+        private const int CtxMap = 5;
+
+        [StructLayout(LayoutKind.Explicit)]
+        private struct CtxValue
+        {
+            [FieldOffset(0)] public byte Boolean;
+            [FieldOffset(0)] public long Integer;
+            [FieldOffset(0)] public IntPtr Pointer;
+        }
+
+        [StructLayout(LayoutKind.Sequential)]
+        private struct Ctx
+        {
+            public int Type;
+            public UIntPtr Length;
+            public CtxValue As;
+        }
+
+        [StructLayout(LayoutKind.Sequential)]
+        private struct CtxEntry
+        {
+            public IntPtr Key;
+            public Ctx Value;
+        }
+
+        private sealed class Marshaler : IDisposable
+        {
+            private readonly List<IntPtr> allocations = new List<IntPtr>();
+
+            public IntPtr Root(object value)
+            {
+                IntPtr ptr = Allocate(Marshal.SizeOf<Ctx>());
+                Marshal.StructureToPtr(ToCtx(value), ptr, false);
+                return ptr;
+            }
+
+            public void Dispose()
+            {
+                foreach (IntPtr ptr in allocations)
+                {
+                    Marshal.FreeHGlobal(ptr);
+                }
+                allocations.Clear();
+            }
+
+            private IntPtr Allocate(int size)
+            {
+                IntPtr ptr = Marshal.AllocHGlobal(Math.Max(size, 1));
+                allocations.Add(ptr);
+                return ptr;
+            }
+
+            private IntPtr Utf8(string value, out int length)
+            {
+                byte[] bytes = Encoding.UTF8.GetBytes(value);
+                IntPtr ptr = Allocate(bytes.Length + 1);
+                Marshal.Copy(bytes, 0, ptr, bytes.Length);
+                Marshal.WriteByte(ptr, bytes.Length, 0);
+                length = bytes.Length;
+                return ptr;
+            }
+
+            private Ctx ToCtx(object value)
+            {
+                var ctx = new Ctx();
+                switch (value)
+                {
+                    case null:
+                        ctx.Type = CtxNull;
+                        break;
+                    case bool boolean:
+                        ctx.Type = CtxBool;
+                        ctx.As.Boolean = (byte)(boolean ? 1 : 0);
+                        break;
+                    case sbyte _: case byte _: case short _: case ushort _: case int _: case uint _: case long _:
+                        ctx.Type = CtxInteger;
+                        ctx.As.Integer = Convert.ToInt64(value);
+                        break;
+                    case string text:
+                        ctx.Type = CtxString;
+                        ctx.As.Pointer = Utf8(text, out int length);
+                        ctx.Length = (UIntPtr)(uint)length;
+                        break;
+                    case IDictionary map:
+                    {
+                        int size = Marshal.SizeOf<CtxEntry>();
+                        IntPtr entries = Allocate(size * map.Count);
+                        int i = 0;
+                        foreach (DictionaryEntry pair in map)
+                        {
+                            var entry = new CtxEntry
+                            {
+                                Key = Utf8(Convert.ToString(pair.Key, CultureInfo.InvariantCulture), out int _),
+                                Value = ToCtx(pair.Value),
+                            };
+                            Marshal.StructureToPtr(entry, entries + size * i++, false);
+                        }
+                        ctx.Type = CtxMap;
+                        ctx.As.Pointer = entries;
+                        ctx.Length = (UIntPtr)(uint)i;
+                        break;
+                    }
+                    case IDictionary<string, object> generic:
+                        return ToCtx(new Dictionary<string, object>(generic));
+                    case IEnumerable list:
+                    {
+                        var items = new List<Ctx>();
+                        foreach (object item in list)
+                        {
+                            items.Add(ToCtx(item));
+                        }
+                        int size = Marshal.SizeOf<Ctx>();
+                        IntPtr ptr = Allocate(size * items.Count);
+                        for (int i = 0; i < items.Count; i++)
+                        {
+                            Marshal.StructureToPtr(items[i], ptr + size * i, false);
+                        }
+                        ctx.Type = CtxList;
+                        ctx.As.Pointer = ptr;
+                        ctx.Length = (UIntPtr)(uint)items.Count;
+                        break;
+                    }
+                    default:
+                        ctx.Type = CtxString;
+                        ctx.As.Pointer = Utf8(Convert.ToString(value, CultureInfo.InvariantCulture), out int formatted);
+                        ctx.Length = (UIntPtr)(uint)formatted;
+                        break;
+                }
+                return ctx;
+            }
+        }
+
+        /// <summary>
+        /// Renders the named template with the context data.
+        /// </summary>
+        /// <exception cref="KeyNotFoundException">The template isn't found.</exception>
+        /// <exception cref="OutOfMemoryException">The output can't be allocated.</exception>
+        public static string Render(string name, IDictionary<string, object> context)
+        {
+            using (var marshaler = new Marshaler())
+            {
+                IntPtr output = NativeRender(name, marshaler.Root(context));
+                if (output == IntPtr.Zero)
+                {
+                    if (!Names.Contains(name))
+                    {
+                        throw new KeyNotFoundException("Template not found: " + name);
+                    }
+                    throw new OutOfMemoryException("Template output allocation failed: " + name);
+                }
+
+                try
+                {
+                    return Marshal.PtrToStringUTF8(output);
+                }
+                finally
+                {
+                    NativeFree(output);
+                }
+            }
+        }
+
+        /// <summary>Renders the "escape" template.</summary>
+        public static string RenderEscape(IDictionary<string, object> context) => Render("escape", context);
+
+        /// <summary>Renders the "includes/footer" template.</summary>
+        public static string RenderIncludesFooter(IDictionary<string, object> context) => Render("includes/footer", context);
+
+        /// <summary>Renders the "includes/header" template.</summary>
+        public static string RenderIncludesHeader(IDictionary<string, object> context) => Render("includes/header", context);
+
+        /// <summary>Renders the "robot" template.</summary>
+        public static string RenderRobot(IDictionary<string, object> context) => Render("robot", context);
+
+        /// <summary>Renders the "robots" template.</summary>
+        public static string RenderRobots(IDictionary<string, object> context) => Render("robots", context);
+
+        /// <summary>Renders the "sections/dot" template.</summary>
+        public static string RenderSectionsDot(IDictionary<string, object> context) => Render("sections/dot", context);
+
+        /// <summary>Renders the "sections/index" template.</summary>
+        public static string RenderSectionsIndex(IDictionary<string, object> context) => Render("sections/index", context);
+
+        /// <summary>Renders the "sections/true" template.</summary>
+        public static string RenderSectionsTrue(IDictionary<string, object> context) => Render("sections/true", context);
+
+        /// <summary>Renders the "types/array" template.</summary>
+        public static string RenderTypesArray(IDictionary<string, object> context) => Render("types/array", context);
+
+        /// <summary>Renders the "types/boolean" template.</summary>
+        public static string RenderTypesBoolean(IDictionary<string, object> context) => Render("types/boolean", context);
+
+        /// <summary>Renders the "types/class" template.</summary>
+        public static string RenderTypesClass(IDictionary<string, object> context) => Render("types/class", context);
+
+        /// <summary>Renders the "types/float" template.</summary>
+        public static string RenderTypesFloat(IDictionary<string, object> context) => Render("types/float", context);
+
+        /// <summary>Renders the "types/hash" template.</summary>
+        public static string RenderTypesHash(IDictionary<string, object> context) => Render("types/hash", context);
+
+        /// <summary>Renders the "types/nil" template.</summary>
+        public static string RenderTypesNil(IDictionary<string, object> context) => Render("types/nil", context);
+
+        /// <summary>Renders the "types/string" template.</summary>
+        public static string RenderTypesString(IDictionary<string, object> context) => Render("types/string", context);
+
+        /// <summary>Renders the "types/struct" template.</summary>
+        public static string RenderTypesStruct(IDictionary<string, object> context) => Render("types/struct", context);
+    }
+}
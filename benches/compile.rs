@@ -0,0 +1,51 @@
+#[macro_use]
+extern crate criterion;
+extern crate stache;
+
+use criterion::Criterion;
+use stache::fixtures::Set;
+use stache::{ruby, Compile, Statement};
+
+const SETS: &[Set] = &[Set::Small, Set::Deep, Set::Wide];
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for set in SETS {
+        let sources = set.sources();
+        group.bench_function(set.name(), |b| {
+            b.iter(|| {
+                for (_, text) in &sources {
+                    Statement::parse(text).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn link(c: &mut Criterion) {
+    let mut group = c.benchmark_group("link");
+    for set in SETS {
+        let templates = set.templates();
+        group.bench_function(set.name(), |b| b.iter(|| ruby::link(&templates).unwrap()));
+    }
+    group.finish();
+}
+
+fn emit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("emit");
+    for set in SETS {
+        let templates = set.templates();
+        group.bench_function(set.name(), |b| {
+            b.iter(|| {
+                let mut buf = Vec::new();
+                ruby::link(&templates).unwrap().emit(&mut buf).unwrap();
+                buf
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, link, emit);
+criterion_main!(benches);
@@ -0,0 +1,2214 @@
+//! The `stache` command, as a library so programs with their own code
+//! generators can run it with extra `--emit` targets:
+//!
+//! ```no_run
+//! # extern crate stache;
+//! # extern crate stache_cli;
+//! # use stache::ruby;
+//! # use stache::{Backend, Compile, Error, Template};
+//! struct Lua;
+//!
+//! impl Backend for Lua {
+//!     fn name(&self) -> &str {
+//!         "lua"
+//!     }
+//!
+//!     fn link(&self, _templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+//!         Err(Error::Backend(String::from("not yet")))
+//!     }
+//! }
+//!
+//! fn lua(_options: &ruby::Options) -> Box<dyn Backend> {
+//!     Box::new(Lua)
+//! }
+//!
+//! fn main() {
+//!     stache_cli::register(lua);
+//!     stache_cli::main();
+//! }
+//! ```
+
+extern crate getopts;
+extern crate notify;
+extern crate serde_json;
+extern crate stache;
+extern crate toml;
+extern crate tracing_subscriber;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use getopts::{Matches, Options};
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde_json::{Map, Value};
+use stache::graph::Graph;
+use stache::harness::{self, Case, Outcome};
+use stache::render::{self, Renderer};
+use stache::usage::{Index, Usage};
+use stache::warning::{self, Warning};
+use stache::{ast, codes, docs, explain, golang, javascript, migrate, ruby, schema, typescript};
+use stache::{Backend, Compile, Dialect, Error, LoadOptions, Symlinks, Template};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/// Builds a backend from the code generation options given on the command
+/// line.
+pub type Factory = fn(&ruby::Options) -> Box<dyn Backend>;
+
+/// The backends added with `register`, after the built-in targets.
+static REGISTERED: Mutex<Vec<Factory>> = Mutex::new(Vec::new());
+
+/// Adds a backend selectable with `--emit`, named by its `Backend::name`. A
+/// built-in target of the same name is chosen over it.
+pub fn register(factory: Factory) {
+    REGISTERED.lock().expect("registry lock").push(factory);
+}
+
+/// Runs the command with the process's arguments, exiting on failure.
+pub fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some(name) if COMMANDS.contains(&name) => run(name, &args[2..]),
+        _ => run("compile", &args[1..]),
+    }
+}
+
+/// The subcommands run by name, like `stache check`.
+const COMMANDS: &[&str] = &[
+    "compile",
+    "check",
+    "watch",
+    "explain",
+    "code",
+    "fmt",
+    "graph",
+    "list",
+    "usages",
+    "translations",
+    "render",
+    "serve",
+    "test",
+    "completions",
+    "migrate",
+];
+
+/// Runs the subcommand with its arguments. Compiling first forwards a
+/// deprecated flag that selects another command, like `--check`, to it.
+fn run(command: &str, args: &[String]) {
+    match command {
+        "check" => compile(args, Mode::Check),
+        "watch" => compile(args, Mode::Watch),
+        "fmt" => format(args),
+        "explain" => explain_file(args),
+        "code" => code(args),
+        "graph" => graph(args),
+        "list" => list(args),
+        "usages" => usages(args),
+        "translations" => translations(args),
+        "render" => render(args),
+        "serve" => serve(args),
+        "test" => test(args),
+        "completions" => completions(args),
+        "migrate" => migrate(args),
+        _ => match forwarded(args) {
+            Some((command, args)) => run(command, &args),
+            None => compile(args, Mode::Compile),
+        },
+    }
+}
+
+/// The flags that once selected another task from the compile options,
+/// with the command each is now forwarded to and its replacement.
+const FORWARDED: &[(&str, &str, &str)] = &[
+    ("--check", "check", "stache check"),
+    ("--watch", "watch", "stache watch"),
+    ("-w", "watch", "stache watch"),
+    ("--explain", "explain", "stache explain"),
+    ("--uses", "graph", "stache graph --uses"),
+    ("--usages", "usages", "stache usages"),
+    (
+        "--extract-translations",
+        "translations",
+        "stache translations",
+    ),
+];
+
+/// Finds a deprecated flag among the compile arguments, returning the
+/// command it's forwarded to and that command's arguments: the others
+/// given, and the flag's value. A warning naming the command is printed to
+/// stderr.
+///
+/// `--uses NAME` becomes `stache graph --uses NAME`, and the value of
+/// `--usages=PATH` or `--explain FILE` is the command's free argument.
+fn forwarded(args: &[String]) -> Option<(&'static str, Vec<String>)> {
+    let (position, &(flag, command, replacement)) = args
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .enumerate()
+        .find_map(|(i, arg)| {
+            let flag = arg.split('=').next().unwrap_or(arg);
+            FORWARDED
+                .iter()
+                .find(|&&(name, ..)| name == flag)
+                .map(|f| (i, f))
+        })?;
+    let value = args[position]
+        .find('=')
+        .map(|i| args[position][i + 1..].to_string());
+
+    let mut rest = args[..position].to_vec();
+    let mut after = args[position + 1..].iter().cloned();
+    let value = match flag {
+        "--uses" => value.or_else(|| after.next()),
+        _ => value,
+    };
+    rest.extend(after);
+    match (flag, value) {
+        ("--uses", Some(name)) => rest.extend(vec![String::from("--uses"), name]),
+        (_, Some(value)) => rest.push(value),
+        (_, None) => (),
+    }
+
+    eprintln!(
+        "warning: {} is deprecated, use `{}` instead",
+        flag, replacement
+    );
+    Some((command, rest))
+}
+
+/// What the compile options are used for: writing the `--emit` target's
+/// output once, validating the templates without output, or recompiling
+/// whenever they change.
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Compile,
+    Check,
+    Watch,
+}
+
+/// Defines the options of the `compile`, `check`, and `watch` commands.
+fn compile_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    opts.optopt(
+        "",
+        "config",
+        "Read default options from FILE (default: stache.toml)",
+        "FILE",
+    );
+    opts.optflag(
+        "",
+        "trace",
+        "Print the duration of each compiler phase to stderr",
+    );
+    load_opts(&mut opts);
+    opts.optopt(
+        "o",
+        "output",
+        "Write output to FILE, or stdout if -",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "message-format",
+        "Print errors as human readable text or one JSON object per line",
+        "human|json",
+    );
+    opts.optflag(
+        "",
+        "deny-warnings",
+        "Exit with an error status if any template has a warning",
+    );
+    opts.optopt(
+        "e",
+        "emit",
+        "Compile to a supported target: ruby, ruby-source, c, node, dotnet, js, ts, hogan, go, schema, dts, docs, ast",
+        "LANG",
+    );
+    opts.optmulti("", "memoize", "Cache rendered partial output", "NAME");
+    opts.optopt(
+        "",
+        "buffer-capacity",
+        "Initial render buffer size (default: 2048)",
+        "BYTES",
+    );
+    opts.optopt(
+        "",
+        "buffer-growth",
+        "Render buffer growth factor (default: 200)",
+        "PERCENT",
+    );
+    opts.optflag(
+        "",
+        "short-names",
+        "Name generated symbols with a hash of their template name",
+    );
+    opts.optflag("", "pretty", "Indent the generated source code");
+    opts.optflag(
+        "",
+        "shared-runtime",
+        "Write the runtime to separate files next to the output",
+    );
+    opts.optflag(
+        "",
+        "sanitize",
+        "Add runtime assertions for sanitizer builds",
+    );
+    opts.optflag(
+        "",
+        "string-buffer",
+        "Render directly into the returned Ruby string",
+    );
+    opts.optflag(
+        "",
+        "trace-lookups",
+        "Record context lookups in a render_trace debug method",
+    );
+    opts.optflag(
+        "",
+        "lambdas",
+        "Embed section source text to pass to Ruby Proc section values",
+    );
+    opts.optflag(
+        "",
+        "iterate-hashes",
+        "Repeat sections over each key and value of a Ruby Hash",
+    );
+    opts.optopt(
+        "",
+        "missing",
+        "Render nil and missing values as: empty, raise (default: empty)",
+        "MODE",
+    );
+    opts.optopt(
+        "",
+        "placeholder",
+        "Render nil and missing values as TEXT",
+        "TEXT",
+    );
+    opts.optopt(
+        "",
+        "trim",
+        "Remove trailing whitespace from rendered output: newline, whitespace",
+        "MODE",
+    );
+    opts.optmulti(
+        "",
+        "trim-template",
+        "Trim only the output of template NAME",
+        "NAME",
+    );
+    opts.optmulti(
+        "",
+        "alias",
+        "Render template NAME when ALIAS is requested",
+        "ALIAS=NAME",
+    );
+    define_opts(&mut opts);
+    opts.optopt(
+        "",
+        "banner",
+        "Write the text of FILE in a comment atop generated files",
+        "FILE",
+    );
+    opts.optflag(
+        "",
+        "provenance",
+        "Record the compiler version and template directory in the output",
+    );
+    opts.optopt(
+        "",
+        "revision",
+        "Record the templates' source control revision in the output",
+        "REV",
+    );
+    opts.optopt(
+        "",
+        "bundle",
+        "Name the compiled extension and its Ruby namespace",
+        "NAME",
+    );
+    opts.optopt(
+        "",
+        "module",
+        "Define the templates class in the Ruby MODULE (default: Stache)",
+        "MODULE",
+    );
+    opts.optopt(
+        "",
+        "class",
+        "Name the Ruby templates class (default: Templates)",
+        "NAME",
+    );
+    opts.optopt(
+        "",
+        "init",
+        "Name the extension's Init_NAME function (default: the bundle)",
+        "NAME",
+    );
+    opts
+}
+
+/// Compiles the template directories into the `--emit` target, or validates
+/// or watches them. Running `stache` without a subcommand compiles.
+fn compile(args: &[String], mode: Mode) {
+    let opts = compile_opts();
+
+    let mut matches = parse(&opts, args, "stache");
+    if matches.opt_present("h") {
+        usage(&opts);
+        exit(0);
+    }
+
+    let config = matches.opt_str("config").map(PathBuf::from);
+    let config = config.or_else(|| Some(PathBuf::from(CONFIG)).filter(|path| path.is_file()));
+    if let Some(path) = config {
+        let mut config = settings(&path, &matches);
+        config.extend(args.iter().cloned());
+        matches = match opts.parse(&config) {
+            Ok(matches) => matches,
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                exit(1);
+            }
+        };
+    }
+
+    if matches.opt_present("trace") {
+        tracing_subscriber::fmt()
+            .with_max_level(LevelFilter::DEBUG)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(io::stderr)
+            .init();
+    }
+
+    let bases = bases(&matches, &opts);
+    let load_options = load_options(&matches);
+
+    let format = match matches.opt_str("message-format").as_deref() {
+        None | Some("human") => MessageFormat::Human,
+        Some("json") => MessageFormat::Json,
+        Some(other) => {
+            println!("Invalid --message-format value: {}", other);
+            exit(1);
+        }
+    };
+
+    let check = mode == Mode::Check;
+    let output = matches.opt_str("o").map(|path| match path.as_str() {
+        "-" => PathBuf::from(path),
+        _ => native(&path),
+    });
+    let lang = matches.opt_str("e");
+    if !check && (output.is_none() || lang.is_none()) {
+        usage(&opts);
+        exit(1);
+    }
+
+    let bundle = matches.opt_str("bundle");
+    if let Some(ref name) = bundle {
+        // The Ruby module is named after the bundle's words, so `_` or `_1`
+        // would leave it without a valid constant name.
+        let word = name.trim_start_matches('_');
+        if !identifier(name) || !word.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            println!(
+                "Bundle name must be a C identifier beginning with a letter: {}",
+                name
+            );
+            exit(1);
+        }
+    }
+
+    let module = matches.opt_str("module");
+    if let Some(ref name) = module {
+        if !name.split("::").all(constant) {
+            println!("Module name must be a Ruby constant path: {}", name);
+            exit(1);
+        }
+    }
+
+    let class = matches.opt_str("class");
+    if let Some(ref name) = class {
+        if !constant(name) {
+            println!("Class name must be a Ruby constant: {}", name);
+            exit(1);
+        }
+    }
+
+    let init = matches.opt_str("init");
+    if let Some(ref name) = init {
+        if !identifier(name) {
+            println!("Init function name must be a C identifier: {}", name);
+            exit(1);
+        }
+    }
+
+    let mut options = ruby::Options {
+        memoize: matches.opt_strs("memoize"),
+        bundle,
+        module,
+        class,
+        init,
+        short_names: matches.opt_present("short-names"),
+        pretty: matches.opt_present("pretty"),
+        sanitize: matches.opt_present("sanitize"),
+        string_buffer: matches.opt_present("string-buffer"),
+        trace_lookups: matches.opt_present("trace-lookups"),
+        iterate_hashes: matches.opt_present("iterate-hashes"),
+        lambdas: matches.opt_present("lambdas"),
+        shared_runtime: matches.opt_present("shared-runtime"),
+        ..ruby::Options::default()
+    };
+
+    if let Some(capacity) = number(&matches, "buffer-capacity") {
+        options.capacity = capacity;
+    }
+
+    if let Some(growth) = number(&matches, "buffer-growth") {
+        if growth <= 100 {
+            println!("Buffer growth must be greater than 100 percent");
+            exit(1);
+        }
+        options.growth = growth;
+    }
+
+    options.missing = match (matches.opt_str("missing"), matches.opt_str("placeholder")) {
+        (None, Some(text)) => ruby::Missing::Placeholder(text),
+        (None, None) => ruby::Missing::Empty,
+        (Some(_), Some(_)) => {
+            println!("Only one of --missing and --placeholder may be given");
+            exit(1);
+        }
+        (Some(mode), None) => match mode.as_str() {
+            "empty" => ruby::Missing::Empty,
+            "raise" => ruby::Missing::Raise,
+            _ => {
+                println!("Invalid --missing value: {}", mode);
+                exit(1);
+            }
+        },
+    };
+
+    options.trim_only = matches.opt_strs("trim-template");
+    options.trim = match matches.opt_str("trim") {
+        Some(mode) => match mode.as_str() {
+            "newline" => ruby::Trim::Newline,
+            "whitespace" => ruby::Trim::Whitespace,
+            _ => {
+                println!("Invalid --trim value: {}", mode);
+                exit(1);
+            }
+        },
+        None if !options.trim_only.is_empty() => ruby::Trim::Newline,
+        None => ruby::Trim::Keep,
+    };
+
+    for alias in matches.opt_strs("alias") {
+        match alias.find('=') {
+            Some(i) if i > 0 && i + 1 < alias.len() => options
+                .aliases
+                .push((alias[..i].to_string(), alias[i + 1..].to_string())),
+            _ => {
+                println!("Invalid --alias value, expected ALIAS=NAME: {}", alias);
+                exit(1);
+            }
+        }
+    }
+
+    options.defines = defines(&matches);
+
+    if let Some(file) = matches.opt_str("banner") {
+        match fs::read_to_string(&file) {
+            Ok(text) => options.banner = Some(text),
+            Err(e) => {
+                println!("{}: {}", file, e);
+                exit(1);
+            }
+        }
+    }
+
+    let revision = matches.opt_str("revision");
+    if matches.opt_present("provenance") || revision.is_some() {
+        options.provenance = Some(ruby::Provenance {
+            source: bases
+                .iter()
+                .map(|base| base.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            revision,
+        });
+    }
+
+    let backend =
+        lang.map(
+            |lang| match backends(&options).into_iter().find(|b| b.name() == lang) {
+                Some(backend) => backend,
+                None => {
+                    usage(&opts);
+                    println!("Unsupported compilation target");
+                    exit(1);
+                }
+            },
+        );
+
+    if check {
+        let deny = matches.opt_present("deny-warnings");
+        let defines = &options.defines;
+        check_templates(
+            &bases,
+            backend.as_deref(),
+            &load_options,
+            defines,
+            format,
+            deny,
+        );
+    }
+
+    let backend = backend.expect("target required");
+    let output = output.expect("output required");
+    if mode == Mode::Watch {
+        watch(
+            &bases,
+            &output,
+            &*backend,
+            &load_options,
+            &options.defines,
+            format,
+        );
+    }
+
+    match build(&bases, &output, &*backend, &load_options, &options.defines) {
+        Ok((_, warnings)) => {
+            for warning in &warnings {
+                format.warn(warning);
+            }
+            if matches.opt_present("deny-warnings") && !warnings.is_empty() {
+                exit(1);
+            }
+        }
+        Err(e) => {
+            format.print(&e);
+            exit(1);
+        }
+    }
+}
+
+/// The file read for default options when `--config` isn't given.
+const CONFIG: &str = "stache.toml";
+
+/// Reads the config file's settings as command line arguments, skipping the
+/// options already given on the command line so they override the file.
+///
+/// Each key names a long option, with `dir` for `-d` and `extensions` for an
+/// `--include` pattern per file extension. Strings and numbers are option
+/// values, arrays repeat the option, and `true` gives a flag.
+fn settings(path: &Path, matches: &Matches) -> Vec<String> {
+    let table = match fs::read_to_string(path) {
+        Ok(text) => match text.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                exit(1);
+            }
+        },
+        Err(e) => {
+            println!("{}: {}", path.display(), e);
+            exit(1);
+        }
+    };
+
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let name = match key.as_str() {
+            "dir" => "d",
+            "extensions" => "include",
+            name => name,
+        };
+        if key == "config" || matches.opts_present(&[name.to_string()]) {
+            continue;
+        }
+
+        let values = match value {
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::Boolean(true) => None,
+                toml::Value::Boolean(false) => continue,
+                toml::Value::String(text) if key == "extensions" => {
+                    Some(format!("*.{}", text.trim_start_matches('.')))
+                }
+                toml::Value::String(text) => Some(text),
+                toml::Value::Integer(number) => Some(number.to_string()),
+                _ => {
+                    println!("{}: Invalid value for {}", path.display(), key);
+                    exit(1);
+                }
+            };
+            match value {
+                Some(value) if name.len() == 1 => {
+                    args.push(format!("-{}", name));
+                    args.push(value);
+                }
+                Some(value) => args.push(format!("--{}={}", name, value)),
+                None => args.push(format!("--{}", name)),
+            }
+        }
+    }
+    args
+}
+
+/// How compiler errors are printed: as the human readable message, or as
+/// one JSON object per error for editors and CI annotators to parse.
+#[derive(Clone, Copy)]
+enum MessageFormat {
+    Human,
+    Json,
+}
+
+impl MessageFormat {
+    /// Prints the warning to stderr, so it isn't mixed into output written
+    /// to stdout.
+    fn warn(self, warning: &Warning) {
+        match self {
+            MessageFormat::Human => eprintln!("warning[{}]: {}", warning.kind.code(), warning),
+            MessageFormat::Json => eprintln!("{}", warning.json()),
+        }
+    }
+
+    /// Prints each of the error's messages to stderr, like warnings.
+    fn print(self, error: &Error) {
+        match self {
+            MessageFormat::Human => {
+                for error in error.errors() {
+                    match error.code() {
+                        Some(code) => eprintln!("error[{}]: {}", code, error),
+                        None => eprintln!("error: {}", error),
+                    }
+                }
+            }
+            MessageFormat::Json => {
+                for message in error.json() {
+                    eprintln!("{}", message);
+                }
+            }
+        }
+    }
+}
+
+/// Parses and validates the template directories without writing output,
+/// linking them for the backend when one is given. Exits with every problem
+/// found printed, or with success if there are none.
+fn check_templates(
+    bases: &[PathBuf],
+    backend: Option<&dyn Backend>,
+    options: &LoadOptions,
+    defines: &HashMap<String, bool>,
+    format: MessageFormat,
+    deny: bool,
+) -> ! {
+    let checked = Template::parse_dirs(bases, options).and_then(|templates| {
+        Template::check(&templates)?;
+        if let Some(backend) = backend {
+            let defined = Template::define_all(&templates, defines);
+            backend.link(defined.as_deref().unwrap_or(&templates))?;
+        }
+        Ok(warning::check(&templates))
+    });
+
+    match checked {
+        Ok(warnings) => {
+            for warning in &warnings {
+                format.warn(warning);
+            }
+            exit(if deny && !warnings.is_empty() { 1 } else { 0 });
+        }
+        Err(e) => {
+            format.print(&e);
+            exit(1);
+        }
+    }
+}
+
+/// Parses the template directories and writes the backend's output, along
+/// with any support files it needs next to it. Sections over the defined
+/// names and feature flags are resolved before the backend links the
+/// templates, so every target builds the same branches. Returns the number
+/// of templates compiled and the warnings found in them.
+///
+/// An output path of `-` writes to stdout, with support files written to
+/// the working directory.
+fn build(
+    bases: &[PathBuf],
+    output: &Path,
+    backend: &dyn Backend,
+    options: &LoadOptions,
+    defines: &HashMap<String, bool>,
+) -> Result<(usize, Vec<Warning>), Error> {
+    let templates = Template::parse_dirs(bases, options)?;
+    let dir = output.parent().unwrap_or_else(|| Path::new(""));
+    let defined = Template::define_all(&templates, defines);
+    let program = backend.link(defined.as_deref().unwrap_or(&templates))?;
+    if output == Path::new("-") {
+        let stdout = io::stdout();
+        let mut buf = BufWriter::new(stdout.lock());
+        program
+            .emit(&mut buf)
+            .and_then(|_| buf.flush())
+            .map_err(|e| Error::io(output, e))?;
+    } else {
+        program.write(output).map_err(|e| Error::io(output, e))?;
+    }
+    program.write_support(dir).map_err(|e| Error::io(dir, e))?;
+    Ok((templates.len(), warning::check(&templates)))
+}
+
+/// Compiles the template directories, then again each time a file in one
+/// changes, until interrupted. Errors are printed rather than ending the
+/// watch, so the next save can fix them. Changes arriving together, like an
+/// editor's save or a branch checkout, are compiled once, after they've
+/// settled.
+///
+/// The output file, and the support files the build writes, may be within
+/// a watched directory, so changes to the output and those made while
+/// building are ignored rather than starting another build.
+fn watch(
+    bases: &[PathBuf],
+    output: &Path,
+    backend: &dyn Backend,
+    options: &LoadOptions,
+    defines: &HashMap<String, bool>,
+    format: MessageFormat,
+) -> ! {
+    // The output may be stdout, so errors and status go to stderr.
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("Unable to watch templates: {}", e);
+            exit(1);
+        }
+    };
+    for base in bases {
+        if let Err(e) = watcher.watch(base, RecursiveMode::Recursive) {
+            eprintln!("Unable to watch {}: {}", base.display(), e);
+            exit(1);
+        }
+    }
+
+    let settle = Duration::from_millis(100);
+    let target = resolve(output);
+    loop {
+        match build(bases, output, backend, options, defines) {
+            Ok((count, warnings)) => {
+                for warning in &warnings {
+                    format.warn(warning);
+                }
+                let noun = if count == 1 { "template" } else { "templates" };
+                eprintln!("Compiled {} {} to {}", count, noun, output.display());
+            }
+            Err(e) => format.print(&e),
+        }
+        while rx.recv_timeout(settle).is_ok() {}
+
+        loop {
+            let changed = match rx.recv() {
+                Ok(Ok(event)) => {
+                    !matches!(event.kind, EventKind::Access(_))
+                        && event.paths.iter().any(|path| resolve(path) != target)
+                }
+                Ok(Err(e)) => {
+                    eprintln!("{}", e);
+                    false
+                }
+                Err(_) => exit(1),
+            };
+            if changed {
+                break;
+            }
+        }
+        while rx.recv_timeout(settle).is_ok() {}
+    }
+}
+
+/// Resolves the directory of a file's path, so a path given on the command
+/// line compares equal to the absolute path of a file system event.
+fn resolve(path: &Path) -> PathBuf {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    match (dir.canonicalize(), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Lists the backends selectable with `--emit`, with the C targets and any
+/// registered backends sharing the code generation options given on the
+/// command line.
+fn backends(options: &ruby::Options) -> Vec<Box<dyn Backend>> {
+    let targets = [
+        ruby::Target::Extension,
+        ruby::Target::Library,
+        ruby::Target::Addon,
+    ];
+    let mut backends: Vec<Box<dyn Backend>> = targets
+        .iter()
+        .map(|&target| {
+            let options = ruby::Options {
+                target,
+                ..options.clone()
+            };
+            Box::new(ruby::Compiler { options }) as Box<dyn Backend>
+        })
+        .collect();
+
+    let package = options.bundle.as_deref().unwrap_or("templates");
+    backends.push(Box::new(ruby::source::Compiler {
+        options: options.clone(),
+    }));
+    backends.push(Box::new(ruby::dotnet::Compiler {
+        options: options.clone(),
+    }));
+    backends.push(Box::new(javascript::Compiler));
+    backends.push(Box::new(javascript::typescript::Compiler));
+    backends.push(Box::new(javascript::hogan::Compiler));
+    backends.push(Box::new(golang::Compiler {
+        package: package.to_string(),
+    }));
+    backends.push(Box::new(schema::Generator));
+    backends.push(Box::new(typescript::Generator));
+    backends.push(Box::new(docs::Generator));
+    backends.push(Box::new(ast::Generator));
+    let registered = REGISTERED.lock().expect("registry lock");
+    backends.extend(registered.iter().map(|factory| factory(options)));
+    backends
+}
+
+/// Defines the options choosing the template directories and how their
+/// files are parsed, shared by each subcommand loading templates.
+fn load_opts(opts: &mut Options) {
+    opts.optmulti(
+        "d",
+        "",
+        "Path to a template directory to compile, repeated to override templates",
+        "PATH",
+    );
+    opts.optmulti(
+        "",
+        "include",
+        "Load only template files matching the gitignore-style PATTERN",
+        "PATTERN",
+    );
+    opts.optmulti(
+        "",
+        "exclude",
+        "Skip files and directories matching the gitignore-style PATTERN",
+        "PATTERN",
+    );
+    opts.optmulti(
+        "",
+        "root",
+        "Load only template NAME and the partials it renders, repeated for each entry point",
+        "NAME",
+    );
+    opts.optflag(
+        "",
+        "ignore-symlinks",
+        "Skip symbolic links in the template directory",
+    );
+    opts.optopt(
+        "",
+        "max-template-size",
+        "Largest template file to load (default: 1048576)",
+        "BYTES",
+    );
+    opts.optopt(
+        "",
+        "max-section-depth",
+        "Deepest section nesting to parse (default: 1000)",
+        "LEVELS",
+    );
+    opts.optopt(
+        "",
+        "dialect",
+        "Template syntax: mustache, handlebars (default: mustache)",
+        "NAME",
+    );
+    opts.optflag(
+        "",
+        "translations",
+        "Parse {{t \"key\"}} tags as calls to the runtime's translation hook",
+    );
+    opts.optflag(
+        "",
+        "extended-identifiers",
+        "Allow $, @, and non-ASCII letters in context keys and partial names",
+    );
+    opts.optflag(
+        "",
+        "front-matter",
+        "Read a leading YAML block between --- lines as template metadata",
+    );
+    opts.optflag(
+        "",
+        "keep-bom",
+        "Parse a leading UTF-8 byte order mark as template content",
+    );
+    opts.optflag(
+        "",
+        "normalize-newlines",
+        "Read \\r\\n line endings in template content as \\n",
+    );
+    opts.optflag(
+        "",
+        "minify",
+        "Collapse whitespace in HTML template content, except in <pre> and similar elements",
+    );
+}
+
+/// Reads the options defined by `load_opts`, exiting with an error message
+/// if a value is invalid.
+fn load_options(matches: &Matches) -> LoadOptions {
+    let mut load_options = LoadOptions::default();
+    if matches.opt_present("ignore-symlinks") {
+        load_options.symlinks = Symlinks::Ignore;
+    }
+    if let Some(size) = number(matches, "max-template-size") {
+        load_options.max_size = size as u64;
+    }
+    if let Some(depth) = number(matches, "max-section-depth") {
+        load_options.max_depth = depth;
+    }
+    if let Some(dialect) = matches.opt_str("dialect") {
+        load_options.dialect = match dialect.as_str() {
+            "mustache" => Dialect::Mustache,
+            "handlebars" => Dialect::Handlebars,
+            _ => {
+                println!("Invalid --dialect value: {}", dialect);
+                exit(1);
+            }
+        };
+    }
+
+    load_options.translations = matches.opt_present("translations");
+    load_options.extended_identifiers = matches.opt_present("extended-identifiers");
+    load_options.front_matter = matches.opt_present("front-matter");
+    load_options.strip_bom = !matches.opt_present("keep-bom");
+    load_options.normalize_newlines = matches.opt_present("normalize-newlines");
+    load_options.include = matches.opt_strs("include");
+    load_options.exclude = matches.opt_strs("exclude");
+    load_options.minify = matches.opt_present("minify");
+    load_options.roots = matches.opt_strs("root");
+    load_options
+}
+
+/// Reads the `-d` template directories, exiting with usage if none are
+/// given or an error message if one doesn't exist.
+fn bases(matches: &Matches, opts: &Options) -> Vec<PathBuf> {
+    let bases: Vec<PathBuf> = matches
+        .opt_strs("d")
+        .iter()
+        .map(|dir| native(dir))
+        .collect();
+    if bases.is_empty() {
+        usage(opts);
+        exit(1);
+    }
+
+    if let Some(base) = bases.iter().find(|base| !base.is_dir()) {
+        println!("Directory not found: {}", base.display());
+        exit(1);
+    }
+    bases
+}
+
+/// Creates the path to a `-d` directory or `-o` file. Windows paths are made
+/// absolute, resolving drive-relative paths like `C:templates` against the
+/// drive's working directory, and paths longer than `MAX_PATH` are given the
+/// `\\?\` prefix that lifts the limit, including those on UNC shares.
+#[cfg(windows)]
+fn native(path: &str) -> PathBuf {
+    const MAX_PATH: usize = 260;
+    let absolute = match std::path::absolute(path) {
+        Ok(absolute) => absolute,
+        Err(_) => return PathBuf::from(path),
+    };
+    match absolute.to_str() {
+        Some(text) if text.len() >= MAX_PATH && !text.starts_with(r"\\?\") => {
+            match text.strip_prefix(r"\\") {
+                Some(share) => PathBuf::from(format!(r"\\?\UNC\{}", share)),
+                None => PathBuf::from(format!(r"\\?\{}", text)),
+            }
+        }
+        _ => absolute,
+    }
+}
+
+/// Creates the path to a `-d` directory or `-o` file.
+#[cfg(not(windows))]
+fn native(path: &str) -> PathBuf {
+    PathBuf::from(path)
+}
+
+/// Parses a command's arguments, exiting with the error and a pointer to
+/// the command's help if they're invalid.
+fn parse(opts: &Options, args: &[String], command: &str) -> Matches {
+    match opts.parse(args) {
+        Ok(matches) => matches,
+        Err(e) => {
+            println!("{}", e);
+            println!("Run `{} --help` for usage.", command);
+            exit(1);
+        }
+    }
+}
+
+/// Parses the template directories, exiting with the error message if any
+/// template fails to load.
+fn load(bases: &[PathBuf], options: &LoadOptions) -> Vec<Template> {
+    match Template::parse_dirs(bases, options) {
+        Ok(templates) => templates,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Defines the options of the `code` command.
+fn code_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    opts
+}
+
+/// Prints the extended description of an error or warning code, exiting
+/// with an error status if the code isn't assigned.
+fn code(args: &[String]) {
+    let opts = code_opts();
+
+    let brief = "Explain an error or warning code\n\nUsage:\n    stache code CODE";
+    let matches = parse(&opts, args, "stache code");
+    let code = match matches.free.as_slice() {
+        [code] if !matches.opt_present("h") => code,
+        _ => {
+            println!("{}", opts.usage(brief));
+            exit(if matches.opt_present("h") { 0 } else { 1 });
+        }
+    };
+
+    match codes::explain(code) {
+        Some(text) => println!("{}", text),
+        None => {
+            println!("Unknown error code {}", code);
+            exit(1);
+        }
+    }
+}
+
+/// Defines the options of the `explain` command.
+fn explain_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    opts.optflag("", "json", "Print the tree as JSON");
+    opts
+}
+
+/// Prints the parsed tree of a template file, and the whitespace removed
+/// around its standalone tags.
+fn explain_file(args: &[String]) {
+    let opts = explain_opts();
+
+    let brief = "Print a template's parsed tree\n\nUsage:\n    stache explain [options] FILE";
+    let matches = parse(&opts, args, "stache explain");
+    match matches.free.as_slice() {
+        [file] if !matches.opt_present("h") => explain(file, matches.opt_present("json")),
+        _ => {
+            println!("{}", opts.usage(brief));
+            exit(if matches.opt_present("h") { 0 } else { 1 });
+        }
+    }
+}
+
+/// Prints the parsed tree of a single template file, exiting with the error
+/// message if it can't be read or parsed.
+fn explain(file: &str, json: bool) {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+
+    match explain::explain(&text) {
+        Ok(explanation) if json => println!("{:#}", explanation.json()),
+        Ok(explanation) => print!("{}", explanation),
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Defines the options of the `usages` command.
+fn usages_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    load_opts(&mut opts);
+    opts
+}
+
+/// Prints where each context path is used in the templates, or only the
+/// path given.
+fn usages(args: &[String]) {
+    let opts = usages_opts();
+
+    let brief = "Print where context paths are used\n\nUsage:\n    stache usages [options] [PATH]";
+    let matches = parse(&opts, args, "stache usages");
+    if matches.opt_present("h") || matches.free.len() > 1 {
+        println!("{}", opts.usage(brief));
+        exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
+
+    let bases = bases(&matches, &opts);
+    let templates = load(&bases, &load_options(&matches));
+    let index = Index::build(&templates);
+    match matches.free.first() {
+        Some(path) => report(path, index.get(path)),
+        None => {
+            for (path, usages) in index.iter() {
+                report(path, usages);
+            }
+        }
+    }
+}
+
+/// Defines the options of the `translations` command.
+fn translations_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    load_opts(&mut opts);
+    opts
+}
+
+/// Prints every translation key used by the templates, one per line and
+/// sorted. Templates are parsed with `{{t "key"}}` tags enabled.
+fn translations(args: &[String]) {
+    let opts = translations_opts();
+
+    let brief = "Print translation keys\n\nUsage:\n    stache translations [options]";
+    let matches = parse(&opts, args, "stache translations");
+    if matches.opt_present("h") {
+        println!("{}", opts.usage(brief));
+        exit(0);
+    }
+
+    let bases = bases(&matches, &opts);
+    let mut options = load_options(&matches);
+    options.translations = true;
+    let templates = load(&bases, &options);
+    let mut keys: Vec<_> = templates
+        .iter()
+        .flat_map(|template| template.tree.translations())
+        .collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        println!("{}", key);
+    }
+}
+
+/// Prints each location a context path is used.
+fn report(path: &str, usages: &[Usage]) {
+    println!("{}", path);
+    for usage in usages {
+        println!("    {} ({:?})", usage, usage.kind);
+    }
+}
+
+/// Parses a numeric option value, exiting with an error message if it is not
+/// a positive integer.
+fn number(matches: &Matches, name: &str) -> Option<usize> {
+    matches.opt_str(name).map(|value| match value.parse() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            println!("Invalid --{} value: {}", name, value);
+            exit(1);
+        }
+    })
+}
+
+/// Returns true if the name may be used as a C identifier in generated
+/// source code.
+fn identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first == '_' || first.is_ascii_alphabetic() => {
+            chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if the name may be used as a Ruby constant, like a module
+/// or class name.
+fn constant(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && identifier(name)
+}
+
+/// Defines the options of the `fmt` command.
+fn format_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    load_opts(&mut opts);
+    opts.optflag(
+        "",
+        "check",
+        "List unformatted templates rather than rewriting them",
+    );
+    opts
+}
+
+/// Rewrites each template file in the directories with canonical tag
+/// spacing. With `--check`, lists the files that would change instead,
+/// exiting with an error status if there are any.
+fn format(args: &[String]) {
+    let opts = format_opts();
+
+    let brief = "Format template tags\n\nUsage:\n    stache fmt [options]";
+    let matches = parse(&opts, args, "stache fmt");
+    if matches.opt_present("h") {
+        println!("{}", opts.usage(brief));
+        exit(0);
+    }
+
+    let bases = bases(&matches, &opts);
+    let options = load_options(&matches);
+    let check = matches.opt_present("check");
+    let mut unformatted = 0;
+    for template in load(&bases, &options) {
+        let path = &template.path;
+        let formatted = fs::read_to_string(path)
+            .map_err(|e| Error::io(path, e))
+            .and_then(|source| {
+                let text = stache::format::format(&source, &options)
+                    .map_err(|e| Error::Backend(format!("{}: {}", path.display(), e)))?;
+                Ok((source, text))
+            });
+        let (source, text) = match formatted {
+            Ok(texts) => texts,
+            Err(e) => {
+                println!("{}", e);
+                exit(1);
+            }
+        };
+        if source == text {
+            continue;
+        }
+
+        unformatted += 1;
+        if check {
+            println!("{}", path.display());
+        } else if let Err(e) = fs::write(path, text) {
+            println!("{}", Error::io(path, e));
+            exit(1);
+        }
+    }
+
+    if check && unformatted > 0 {
+        exit(1);
+    }
+}
+
+/// Defines the options of the `graph` command.
+fn graph_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    load_opts(&mut opts);
+    opts.optopt(
+        "",
+        "uses",
+        "Print every template that includes the partial NAME",
+        "NAME",
+    );
+    opts.optopt(
+        "",
+        "format",
+        "Print the graph as: text, dot, json (default: text)",
+        "FORMAT",
+    );
+    opts
+}
+
+/// Prints the partials each template renders, one `template -> partial`
+/// line each, or with `--uses`, every template that includes a partial.
+fn graph(args: &[String]) {
+    let opts = graph_opts();
+
+    let brief = "Print the partial reference graph\n\nUsage:\n    stache graph [options]";
+    let matches = parse(&opts, args, "stache graph");
+    if matches.opt_present("h") {
+        println!("{}", opts.usage(brief));
+        exit(0);
+    }
+
+    let bases = bases(&matches, &opts);
+    let templates = load(&bases, &load_options(&matches));
+    let graph = Graph::build(&templates);
+    match matches.opt_str("uses") {
+        Some(name) => {
+            for template in graph.dependents(&name) {
+                println!("{}", template);
+            }
+        }
+        None => match matches.opt_str("format").as_deref() {
+            None | Some("text") => {
+                for (template, partials) in graph.iter() {
+                    for partial in partials {
+                        println!("{} -> {}", template, partial);
+                    }
+                }
+            }
+            Some("dot") => print!("{}", graph.dot()),
+            Some("json") => println!("{:#}", graph.json()),
+            Some(other) => {
+                println!("Invalid --format value: {}", other);
+                exit(1);
+            }
+        },
+    }
+}
+
+/// Defines the options of the `list` command.
+fn list_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    load_opts(&mut opts);
+    opts.optopt(
+        "",
+        "format",
+        "Print the list as: table, json (default: table)",
+        "FORMAT",
+    );
+    opts
+}
+
+/// Prints each template's name and path, with the partials it renders and
+/// the variables it reads, sorted by name.
+fn list(args: &[String]) {
+    let opts = list_opts();
+
+    let brief =
+        "List templates with their partials and variables\n\nUsage:\n    stache list [options]";
+    let matches = parse(&opts, args, "stache list");
+    if matches.opt_present("h") {
+        println!("{}", opts.usage(brief));
+        exit(0);
+    }
+
+    let bases = bases(&matches, &opts);
+    let mut templates = load(&bases, &load_options(&matches));
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    let rows: Vec<_> = templates
+        .iter()
+        .map(|template| {
+            let partials = unique(template.tree.partials().into_iter().map(String::from));
+            let variables = unique(template.tree.variables().iter().map(|p| p.to_string()));
+            (template, partials, variables)
+        })
+        .collect();
+
+    match matches.opt_str("format").as_deref() {
+        None | Some("table") => {
+            let mut table = vec![[
+                String::from("NAME"),
+                String::from("PATH"),
+                String::from("PARTIALS"),
+                String::from("VARIABLES"),
+            ]];
+            for (template, partials, variables) in &rows {
+                table.push([
+                    template.name.clone(),
+                    template.path.display().to_string(),
+                    partials.join(", "),
+                    variables.join(", "),
+                ]);
+            }
+            let widths: Vec<_> = (0..3)
+                .map(|i| table.iter().map(|row| row[i].len()).max().unwrap_or(0))
+                .collect();
+            for row in &table {
+                let line = format!(
+                    "{:w0$}  {:w1$}  {:w2$}  {}",
+                    row[0],
+                    row[1],
+                    row[2],
+                    row[3],
+                    w0 = widths[0],
+                    w1 = widths[1],
+                    w2 = widths[2]
+                );
+                println!("{}", line.trim_end());
+            }
+        }
+        Some("json") => {
+            let entries: Vec<_> = rows
+                .into_iter()
+                .map(|(template, partials, variables)| {
+                    let mut map = Map::new();
+                    map.insert(String::from("name"), Value::from(template.name.as_str()));
+                    map.insert(
+                        String::from("path"),
+                        Value::from(template.path.to_string_lossy()),
+                    );
+                    map.insert(String::from("partials"), Value::from(partials));
+                    map.insert(String::from("variables"), Value::from(variables));
+                    Value::Object(map)
+                })
+                .collect();
+            println!("{:#}", Value::Array(entries));
+        }
+        Some(other) => {
+            println!("Invalid --format value: {}", other);
+            exit(1);
+        }
+    }
+}
+
+/// Collects the names, dropping repeats of a name already seen.
+fn unique<I: Iterator<Item = String>>(names: I) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for name in names {
+        if !unique.contains(&name) {
+            unique.push(name);
+        }
+    }
+    unique
+}
+
+/// Defines the `--define` option, shared by the commands compiling or
+/// rendering templates so each resolves the same branches.
+fn define_opts(opts: &mut Options) {
+    opts.optmulti(
+        "",
+        "define",
+        "Evaluate sections over NAME or flags.NAME at compile time (default: true)",
+        "NAME[=true|false]",
+    );
+}
+
+/// Reads the `--define` values, exiting with an error message if one isn't
+/// a name, optionally followed by `=true` or `=false`.
+fn defines(matches: &Matches) -> HashMap<String, bool> {
+    let mut defines = HashMap::new();
+    for define in matches.opt_strs("define") {
+        let (name, value) = match define.find('=') {
+            Some(i) => (&define[..i], &define[i + 1..]),
+            None => (define.as_str(), "true"),
+        };
+        let value = match value {
+            "true" => true,
+            "false" => false,
+            _ => {
+                println!("Invalid --define value, expected true or false: {}", define);
+                exit(1);
+            }
+        };
+        if name.is_empty() {
+            println!("Invalid --define value, expected NAME=VALUE: {}", define);
+            exit(1);
+        }
+        defines.insert(name.to_string(), value);
+    }
+    defines
+}
+
+/// Defines the options of the `render` command.
+fn render_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    load_opts(&mut opts);
+    define_opts(&mut opts);
+    opts.optopt(
+        "",
+        "data",
+        "Render with the JSON, or YAML if named .yml or .yaml, context in FILE",
+        "FILE",
+    );
+    opts
+}
+
+/// Renders a template with the JSON context in the `--data` file, or an
+/// empty context, and prints its output.
+fn render(args: &[String]) {
+    let opts = render_opts();
+
+    let brief = "Render a template\n\nUsage:\n    stache render [options] NAME";
+    let matches = parse(&opts, args, "stache render");
+    let name = match matches.free.as_slice() {
+        [name] if !matches.opt_present("h") => name.clone(),
+        _ => {
+            println!("{}", opts.usage(brief));
+            exit(if matches.opt_present("h") { 0 } else { 1 });
+        }
+    };
+
+    let context = match matches.opt_str("data") {
+        Some(file) => match context(Path::new(&file)) {
+            Ok(context) => context,
+            Err(e) => {
+                println!("{}: {}", file, e);
+                exit(1);
+            }
+        },
+        None => Value::Object(Default::default()),
+    };
+
+    let bases = bases(&matches, &opts);
+    let templates = load(&bases, &load_options(&matches));
+    let rendered = Renderer::with_defines(&templates, &defines(&matches))
+        .and_then(|renderer| renderer.render(&name, &context));
+    match rendered {
+        Ok(text) => print!("{}", text),
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Reads a render context file, parsed as YAML when named `.yml` or `.yaml`
+/// and as JSON otherwise.
+fn context(file: &Path) -> Result<Value, String> {
+    let text = fs::read_to_string(file).map_err(|e| e.to_string())?;
+    match file.extension().and_then(|ext| ext.to_str()) {
+        Some("yml") | Some("yaml") => render::yaml(&text),
+        _ => serde_json::from_str(&text).map_err(|e| e.to_string()),
+    }
+}
+
+/// Defines the options of the `serve` command.
+fn serve_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    load_opts(&mut opts);
+    define_opts(&mut opts);
+    opts.optopt(
+        "",
+        "data",
+        "Render each template with the JSON or YAML context of the same name in DIR",
+        "DIR",
+    );
+    opts.optopt(
+        "",
+        "port",
+        "Listen for requests on PORT (default: 8000)",
+        "PORT",
+    );
+    opts
+}
+
+/// Serves each template rendered with its context file at `/NAME`, for
+/// previewing templates in a browser. Templates are loaded again for each
+/// request, and the script added to rendered pages reloads them when a
+/// template or context file changes.
+fn serve(args: &[String]) {
+    let opts = serve_opts();
+
+    let brief = "Preview rendered templates in a browser\n\nUsage:\n    stache serve [options]";
+    let matches = parse(&opts, args, "stache serve");
+    if matches.opt_present("h") {
+        println!("{}", opts.usage(brief));
+        exit(0);
+    }
+
+    let bases = bases(&matches, &opts);
+    let options = load_options(&matches);
+    let defines = defines(&matches);
+    let data = matches.opt_str("data").map(PathBuf::from);
+    let port = match matches.opt_str("port").map(|port| port.parse::<u16>()) {
+        None => PORT,
+        Some(Ok(port)) => port,
+        Some(Err(e)) => {
+            println!("Invalid --port value: {}", e);
+            exit(1);
+        }
+    };
+
+    let version = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            println!("Unable to watch templates: {}", e);
+            exit(1);
+        }
+    };
+    for dir in bases.iter().chain(data.as_ref()) {
+        if let Err(e) = watcher.watch(dir, RecursiveMode::Recursive) {
+            println!("Unable to watch {}: {}", dir.display(), e);
+            exit(1);
+        }
+    }
+    let changes = Arc::clone(&version);
+    thread::spawn(move || {
+        for event in rx.into_iter().flatten() {
+            if !matches!(event.kind, EventKind::Access(_)) {
+                changes.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    });
+
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("Unable to listen on port {}: {}", port, e);
+            exit(1);
+        }
+    };
+    println!("Serving templates at http://127.0.0.1:{}/", port);
+    thread::scope(|scope| {
+        for stream in listener.incoming().flatten() {
+            let version = version.load(Ordering::SeqCst);
+            let site = Site {
+                bases: &bases,
+                options: &options,
+                defines: &defines,
+                data: data.as_deref(),
+                version,
+            };
+            // Each connection is answered on its own thread, so one opened
+            // without a request, like a browser's preconnect, doesn't hold
+            // up the others.
+            scope.spawn(move || {
+                // A browser closing the connection early isn't a server error.
+                let _ = site.respond(stream);
+            });
+        }
+    });
+}
+
+/// How long the `serve` command waits for a connection's request before
+/// closing it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The port the `serve` command listens on, unless given a `--port`.
+const PORT: u16 = 8000;
+
+/// The script added to pages the `serve` command renders, polling the
+/// server's template version and reloading the page when it changes.
+const RELOAD: &str = "<script>
+setInterval(function() {
+  fetch('/__stache/version')
+    .then(function(response) { return response.text(); })
+    .then(function(version) { if (version !== 'VERSION') location.reload(); })
+    .catch(function() {});
+}, 1000);
+</script>
+";
+
+/// The templates and contexts the `serve` command renders, as of a version
+/// counting the changes made to their files.
+struct Site<'a> {
+    bases: &'a [PathBuf],
+    options: &'a LoadOptions,
+    defines: &'a HashMap<String, bool>,
+    data: Option<&'a Path>,
+    version: usize,
+}
+
+impl<'a> Site<'a> {
+    /// Reads an HTTP request from the stream and writes the page it asks
+    /// for, closing the connection. A connection sending no request within
+    /// the timeout is closed unanswered.
+    fn respond(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut reader = BufReader::new(&stream);
+        let mut request = String::new();
+        reader.read_line(&mut request)?;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? <= 2 {
+                break;
+            }
+        }
+
+        let target = request.split_whitespace().nth(1).unwrap_or("/");
+        let target = target.split('?').next().unwrap_or("/");
+        let (status, content_type, body) = self.page(target);
+        write!(
+            &stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )
+    }
+
+    /// Returns the status, content type, and body of the page at the request
+    /// target: the template version, an index of the templates, or a
+    /// rendered template.
+    fn page(&self, target: &str) -> (&'static str, &'static str, String) {
+        const HTML: &str = "text/html; charset=utf-8";
+        if target == "/__stache/version" {
+            return ("200 OK", "text/plain", self.version.to_string());
+        }
+
+        let templates = match Template::parse_dirs(self.bases, self.options) {
+            Ok(templates) => templates,
+            Err(e) => {
+                return (
+                    "500 Internal Server Error",
+                    HTML,
+                    self.error(&e.to_string()),
+                )
+            }
+        };
+        let name = target.trim_start_matches('/');
+        if name.is_empty() {
+            let mut names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+            names.sort_unstable();
+            let mut body = String::from("<!DOCTYPE html>\n<title>Templates</title>\n<ul>\n");
+            for name in names {
+                let mut escaped = String::new();
+                render::escape(name, &mut escaped);
+                body.push_str(&format!("<li><a href=\"/{0}\">{0}</a></li>\n", escaped));
+            }
+            body.push_str("</ul>\n");
+            return ("200 OK", HTML, self.reloading(body));
+        }
+        if !templates.iter().any(|t| t.name == name) {
+            let message = format!("Template `{}` not found", name);
+            return ("404 Not Found", HTML, self.error(&message));
+        }
+
+        let context = match self.context(name) {
+            Ok(context) => context,
+            Err(e) => return ("500 Internal Server Error", HTML, self.error(&e)),
+        };
+        let renderer = Renderer::with_defines(&templates, self.defines);
+        match renderer.and_then(|renderer| renderer.render(name, &context)) {
+            Ok(text) => ("200 OK", HTML, self.reloading(text)),
+            Err(e) => (
+                "500 Internal Server Error",
+                HTML,
+                self.error(&e.to_string()),
+            ),
+        }
+    }
+
+    /// Reads the context file named for the template in the data directory,
+    /// or returns an empty context if there's none.
+    fn context(&self, name: &str) -> Result<Value, String> {
+        let files = ["json", "yml", "yaml"]
+            .iter()
+            .filter_map(|ext| self.data.map(|dir| dir.join(format!("{}.{}", name, ext))));
+        for file in files {
+            if file.is_file() {
+                return context(&file).map_err(|e| format!("{}: {}", file.display(), e));
+            }
+        }
+        Ok(Value::Object(Default::default()))
+    }
+
+    /// Formats an error message as a page, which reloads once the error is
+    /// fixed.
+    fn error(&self, message: &str) -> String {
+        let mut body = String::from("<!DOCTYPE html>\n<title>Error</title>\n<pre>");
+        render::escape(message, &mut body);
+        body.push_str("</pre>\n");
+        self.reloading(body)
+    }
+
+    /// Adds the reload script to the page, before its closing body tag if
+    /// it has one.
+    fn reloading(&self, mut page: String) -> String {
+        let script = RELOAD.replace("VERSION", &self.version.to_string());
+        match page.rfind("</body>") {
+            Some(index) => page.insert_str(index, &script),
+            None => page.push_str(&script),
+        }
+        page
+    }
+}
+
+/// Defines the options of the `test` command.
+fn test_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    load_opts(&mut opts);
+    define_opts(&mut opts);
+    opts.optopt(
+        "",
+        "tests",
+        "Read context and expected output files from DIR (default: tests)",
+        "DIR",
+    );
+    opts.optflag(
+        "",
+        "update",
+        "Write each template's rendered output as its expected output",
+    );
+    opts
+}
+
+/// Renders each template with its context fixture in the tests directory
+/// and compares the output to the expected output beside it, printing a
+/// diff for each mismatch. With `--update`, writes the rendered output as
+/// the expected output instead.
+fn test(args: &[String]) {
+    let opts = test_opts();
+
+    let brief = "Test templates against fixtures\n\nUsage:\n    stache test [options]";
+    let matches = parse(&opts, args, "stache test");
+    if matches.opt_present("h") {
+        println!("{}", opts.usage(brief));
+        exit(0);
+    }
+
+    let bases = bases(&matches, &opts);
+    let templates = load(&bases, &load_options(&matches));
+    let renderer = match Renderer::with_defines(&templates, &defines(&matches)) {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+
+    let dir = PathBuf::from(
+        matches
+            .opt_str("tests")
+            .unwrap_or_else(|| String::from("tests")),
+    );
+    let cases = match Case::discover(&dir) {
+        Ok(cases) => cases,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+
+    let update = matches.opt_present("update");
+    let mut failed = 0;
+    for case in &cases {
+        let outcome = match case.run(&renderer) {
+            Outcome::Fail { actual, .. } | Outcome::Missing { actual } if update => {
+                match fs::write(&case.expected, actual) {
+                    Ok(_) => {
+                        println!("test {} ... updated", case.name);
+                        continue;
+                    }
+                    Err(e) => Outcome::Error(format!("{}: {}", case.expected.display(), e)),
+                }
+            }
+            outcome => outcome,
+        };
+        match outcome {
+            Outcome::Pass => println!("test {} ... ok", case.name),
+            Outcome::Fail { expected, actual } => {
+                println!("test {} ... FAILED", case.name);
+                for line in harness::diff(&expected, &actual) {
+                    println!("    {}", line);
+                }
+                failed += 1;
+            }
+            Outcome::Missing { .. } => {
+                let path = case.expected.display();
+                println!("test {} ... FAILED (missing {})", case.name, path);
+                failed += 1;
+            }
+            Outcome::Error(e) => {
+                println!("test {} ... FAILED", case.name);
+                println!("    {}", e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("\n{} passed; {} failed", cases.len() - failed, failed);
+    exit(if failed > 0 { 1 } else { 0 });
+}
+
+/// Defines the options of the `migrate` command.
+fn migrate_opts() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "Print this message");
+    opts.optopt(
+        "o",
+        "output",
+        "Write to FILE, or to DIR for a template directory",
+        "PATH",
+    );
+    opts
+}
+
+/// Rewrites a Handlebars template, or a directory of `.hbs` and
+/// `.handlebars` templates, into Mustache, reporting each construct that
+/// couldn't be translated and exiting with an error status if there are any.
+fn migrate(args: &[String]) {
+    let opts = migrate_opts();
+
+    let brief =
+        "Rewrite Handlebars templates as Mustache\n\nUsage:\n    stache migrate [options] PATH";
+    let matches = parse(&opts, args, "stache migrate");
+
+    let input = match matches.free.as_slice() {
+        [input] if !matches.opt_present("h") => PathBuf::from(input),
+        _ => {
+            println!("{}", opts.usage(brief));
+            exit(if matches.opt_present("h") { 0 } else { 1 });
+        }
+    };
+    let output = matches.opt_str("o").map(PathBuf::from);
+
+    let mut files = Vec::new();
+    if input.is_dir() {
+        if output.is_none() {
+            println!("Migrating a directory requires an --output directory");
+            exit(1);
+        }
+        handlebars(&input, &mut files);
+    } else {
+        files.push(input.clone());
+    }
+
+    let mut issues = 0;
+    for file in files {
+        let text = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(e) => {
+                println!("{}: {}", file.display(), e);
+                exit(1);
+            }
+        };
+
+        let migration = migrate::migrate(&text);
+        for issue in &migration.issues {
+            eprintln!("{}:{}", file.display(), issue);
+        }
+        issues += migration.issues.len();
+        for note in &migration.notes {
+            eprintln!(
+                "{}:{}:{} {}: note: {}",
+                file.display(),
+                note.span.line,
+                note.span.column,
+                note.tag,
+                note.message
+            );
+        }
+
+        let target = match output {
+            Some(ref dir) if input.is_dir() => {
+                let relative = file.strip_prefix(&input).expect("file within input");
+                dir.join(relative).with_extension("mustache")
+            }
+            Some(ref path) => path.clone(),
+            None => {
+                print!("{}", migration.text);
+                continue;
+            }
+        };
+
+        let written = target
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&target, &migration.text));
+        if let Err(e) = written {
+            println!("{}", Error::io(&target, e));
+            exit(1);
+        }
+    }
+
+    if issues > 0 {
+        let noun = if issues == 1 {
+            "construct"
+        } else {
+            "constructs"
+        };
+        eprintln!("{} {} must be rewritten by hand", issues, noun);
+        exit(1);
+    }
+}
+
+/// Collects the Handlebars template files in the directory tree, in sorted
+/// order.
+fn handlebars(dir: &Path, files: &mut Vec<PathBuf>) {
+    let mut entries: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|e| e.path())
+            .collect(),
+        Err(e) => {
+            println!("{}", Error::io(dir, e));
+            exit(1);
+        }
+    };
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            handlebars(&path, files);
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == "hbs" || ext == "handlebars")
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Lists each subcommand with the options it accepts, for completions.
+fn commands() -> Vec<(&'static str, Options)> {
+    vec![
+        ("compile", compile_opts()),
+        ("check", compile_opts()),
+        ("watch", compile_opts()),
+        ("explain", explain_opts()),
+        ("code", code_opts()),
+        ("fmt", format_opts()),
+        ("graph", graph_opts()),
+        ("list", list_opts()),
+        ("usages", usages_opts()),
+        ("translations", translations_opts()),
+        ("render", render_opts()),
+        ("serve", serve_opts()),
+        ("test", test_opts()),
+        ("migrate", migrate_opts()),
+    ]
+}
+
+/// Returns the short and long names an option set defines, like `-o` and
+/// `--output`, read from the option column of its usage rows.
+fn flags(opts: &Options) -> Vec<String> {
+    let rows = opts.usage_with_format(|rows| rows.collect::<Vec<_>>().join("\n"));
+    let mut flags = Vec::new();
+    for row in rows.lines() {
+        let mut words = row.split_whitespace();
+        let first = match words.next() {
+            Some(word) if row.trim_start().starts_with('-') => word,
+            _ => continue,
+        };
+        if first.starts_with("--") {
+            flags.push(first.to_string());
+        } else {
+            flags.push(first.trim_end_matches(',').to_string());
+            if first.ends_with(',') {
+                flags.extend(words.next().map(String::from));
+            }
+        }
+    }
+    flags
+}
+
+/// Prints a completion script for the shell, completing subcommands and
+/// each subcommand's options. Hidden from the usage message, as it's run by
+/// package scripts rather than people: `stache completions bash`.
+fn completions(args: &[String]) {
+    let commands = commands();
+    let names: Vec<_> = commands.iter().map(|&(name, _)| name).collect();
+    let compile = flags(&commands[0].1).join(" ");
+    match args.first().map(String::as_str) {
+        Some("bash") => {
+            println!("_stache() {{");
+            println!("    local cur=${{COMP_WORDS[COMP_CWORD]}} opts");
+            println!("    case ${{COMP_WORDS[1]}} in");
+            for (name, opts) in &commands {
+                println!("        {}) opts=\"{}\" ;;", name, flags(opts).join(" "));
+            }
+            println!("        *) opts=\"{}\" ;;", compile);
+            println!("    esac");
+            println!("    if [ \"$COMP_CWORD\" -eq 1 ]; then");
+            println!("        opts=\"{} $opts\"", names.join(" "));
+            println!("    fi");
+            println!("    COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))");
+            println!("}}");
+            println!("complete -o default -F _stache stache");
+        }
+        Some("zsh") => {
+            println!("#compdef stache");
+            println!();
+            println!("_stache() {{");
+            println!("    local -a opts");
+            println!("    case $words[2] in");
+            for (name, opts) in &commands {
+                println!("        {}) opts=({}) ;;", name, flags(opts).join(" "));
+            }
+            println!("        *) opts=({}) ;;", compile);
+            println!("    esac");
+            println!("    if (( CURRENT == 2 )); then");
+            println!("        compadd -- {}", names.join(" "));
+            println!("    fi");
+            println!("    compadd -- $opts");
+            println!("    _files");
+            println!("}}");
+            println!();
+            println!("_stache \"$@\"");
+        }
+        Some("fish") => {
+            println!(
+                "complete -c stache -f -n __fish_use_subcommand -a \"{}\"",
+                names.join(" ")
+            );
+            // Running `stache` without a subcommand, or as `check` or
+            // `watch`, takes the compile options.
+            let others: Vec<_> = commands
+                .iter()
+                .filter(|(_, opts)| flags(opts) != flags(&commands[0].1))
+                .map(|&(name, _)| name)
+                .collect();
+            for (name, opts) in &commands {
+                let condition = match *name {
+                    "compile" => format!("not __fish_seen_subcommand_from {}", others.join(" ")),
+                    "check" | "watch" => continue,
+                    name => format!("__fish_seen_subcommand_from {}", name),
+                };
+                for flag in flags(opts) {
+                    let option = match flag.strip_prefix("--") {
+                        Some(long) => format!("-l {}", long),
+                        None => format!("-s {}", &flag[1..]),
+                    };
+                    println!("complete -c stache -n \"{}\" {}", condition, option);
+                }
+            }
+        }
+        _ => {
+            println!("Usage: stache completions bash|zsh|fish");
+            exit(1);
+        }
+    }
+}
+
+fn usage(opts: &Options) {
+    let brief = "Mustache template compiler\n\nUsage:
+    stache [compile] [options]
+    stache check [options]
+    stache watch [options]
+    stache explain [options] FILE
+    stache code CODE
+    stache fmt [options]
+    stache graph [options]
+    stache list [options]
+    stache usages [options] [PATH]
+    stache translations [options]
+    stache render [options] NAME
+    stache serve [options]
+    stache test [options]
+    stache migrate [options] PATH";
+    println!("{}", opts.usage(brief));
+}
@@ -0,0 +1,5 @@
+extern crate stache_cli;
+
+fn main() {
+    stache_cli::main();
+}
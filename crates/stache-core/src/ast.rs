@@ -0,0 +1,99 @@
+extern crate serde_json;
+
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+
+use super::explain::{node, root};
+use super::{Backend, Compile, Error, Template};
+
+/// The parsed syntax tree of each template, for tools analyzing templates
+/// without parsing them again.
+///
+/// Each node records its type, the source span it was parsed from, and its
+/// paths, names, or text, in the same form as `stache explain --json`.
+#[derive(Debug)]
+pub struct Trees {
+    documents: Vec<Value>,
+}
+
+impl Compile for Trees {
+    /// Writes a JSON array with each template's name, file path, and tree.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *buf, &self.documents)?;
+        writeln!(buf)
+    }
+}
+
+/// The `ast` backend, serializing the parsed templates.
+#[derive(Debug)]
+pub struct Generator;
+
+impl Backend for Generator {
+    fn name(&self) -> &str {
+        "ast"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(generate(templates)))
+    }
+}
+
+/// Converts each template's tree to a JSON document.
+pub fn generate(templates: &[Template]) -> Trees {
+    let documents = templates
+        .iter()
+        .map(|template| {
+            let mut map = Map::new();
+            map.insert(String::from("name"), Value::from(template.name.as_str()));
+            map.insert(
+                String::from("path"),
+                Value::from(template.path.to_string_lossy()),
+            );
+            map.insert(
+                String::from("tree"),
+                node(&template.tree, root(&template.tree)),
+            );
+            Value::Object(map)
+        })
+        .collect();
+
+    Trees { documents }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Compile, Statement, Template};
+    use super::generate;
+    use serde_json::{self, Value};
+    use std::path::PathBuf;
+
+    #[test]
+    fn serializes_template_trees() {
+        let base = PathBuf::from("app/templates");
+        let path = base.join("robots.mustache");
+        let text = "<ul>\n{{#robots}}<li>{{ name.first }}</li>{{/robots}}";
+        let template = Template::new(&base, path, Statement::parse(text).unwrap());
+
+        let mut buf = Vec::new();
+        generate(&[template]).emit(&mut buf).unwrap();
+        let json: Value = serde_json::from_slice(&buf).unwrap();
+
+        let document = &json[0];
+        assert_eq!("robots", document["name"]);
+        assert_eq!("app/templates/robots.mustache", document["path"]);
+
+        let tree = &document["tree"];
+        assert_eq!("program", tree["type"]);
+        assert_eq!(text.len(), tree["span"]["end"]);
+        assert_eq!("<ul>\n", tree["children"][0]["text"]);
+
+        let section = &tree["children"][1];
+        assert_eq!("section", section["type"]);
+        assert_eq!(2, section["span"]["line"]);
+        assert_eq!(5, section["span"]["start"]);
+
+        let variable = &section["children"][1];
+        assert_eq!("name.first", variable["path"]);
+        assert_eq!(16, variable["span"]["column"]);
+    }
+}
@@ -0,0 +1,341 @@
+//! Extended descriptions of the compiler's error and warning codes, printed
+//! by `stache code E0002`.
+//!
+//! Each code is stable once assigned, so it may be searched for in issue
+//! trackers and CI logs. Errors are numbered `E0001` and up, and warnings
+//! `W0001` and up.
+
+/// The description of each code, in code order.
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "E0001",
+        "A tag couldn't be parsed.
+
+Erroneous example:
+
+    <p>{{name</p>
+
+The tag is missing its closing `}}`, or its name holds characters that
+aren't allowed in a context key. `--extended-identifiers` allows `$`, `@`,
+and non-ASCII letters in keys.",
+    ),
+    (
+        "E0002",
+        "A partial tag names a template that doesn't exist.
+
+Erroneous example, with no `include/header.mustache` template:
+
+    {{> include/header}}
+
+Partial names are template paths relative to the template directory, without
+their file extension. Add the template, or correct the name. A dynamic
+partial, `{{>*name}}`, is looked up when it's rendered instead.",
+    ),
+    (
+        "E0003",
+        "A close tag names a different section than the one it closes.
+
+Erroneous example:
+
+    {{#robots}}<li>{{name}}</li>{{/robot}}
+
+Sections close in the reverse of the order they're opened, and each close tag
+repeats its section's name exactly.",
+    ),
+    (
+        "E0004",
+        "A section is never closed.
+
+Erroneous example:
+
+    {{#robots}}<li>{{name}}</li>
+
+Every `{{#name}}` and `{{^name}}` section needs a matching `{{/name}}` tag
+before the end of the template.",
+    ),
+    (
+        "E0005",
+        "Sections are nested more deeply than the parser allows.
+
+The limit protects the compiler from templates generated with runaway
+nesting. Flatten the template with partials, or raise the limit with
+`--max-section-depth`. The `ruby` and `c` targets translate any depth, but
+the other targets, and rendering, may overflow the stack when the limit is
+raised far past its default of 1000.",
+    ),
+    (
+        "E0006",
+        "A pragma tag names a pragma the compiler doesn't recognize.
+
+Erroneous example:
+
+    {{%STRCT}}
+
+The supported pragmas are `UNESCAPED` and `STRICT`.",
+    ),
+    (
+        "E0007",
+        "A template file isn't valid UTF-8.
+
+The error gives the offset, line, and column of the first invalid byte
+sequence. Convert the file to UTF-8 with your editor or `iconv`.",
+    ),
+    (
+        "E0008",
+        "A template file is larger than the loader's size limit.
+
+Large files are usually build artifacts or data committed to the template
+directory by mistake. Exclude them with `.stacheignore` or `--exclude`, or
+raise the limit with `--max-template-size`.",
+    ),
+    (
+        "E0009",
+        "A symbolic link points to a file or directory that's already loaded.
+
+Loading a template through two paths would define it twice, and a link to a
+parent directory would recurse forever. Remove the link, or skip links with
+`--ignore-symlinks`.",
+    ),
+    (
+        "E0010",
+        "The template directory's `.stacheignore` file is invalid.
+
+Each line is a gitignore-style pattern. The error names the pattern that
+couldn't be parsed.",
+    ),
+    (
+        "E0011",
+        "An `--include` or `--exclude` pattern is invalid.
+
+Patterns use gitignore syntax, like `*.mustache` or `drafts/`.",
+    ),
+    (
+        "E0012",
+        "A template's front matter isn't a valid YAML mapping.
+
+Erroneous example:
+
+    ---
+    - layout
+    ---
+
+Front matter is a mapping of fields between `---` lines at the top of the
+file, like `layout: application`.",
+    ),
+    (
+        "E0013",
+        "A template inherits from itself through its parent tags.
+
+Erroneous example, in `page.mustache`:
+
+    {{<page}}{{$title}}Robots{{/title}}{{/page}}
+
+A parent may have parents of its own, but the chain must end at a template
+without a parent tag.",
+    ),
+    (
+        "E0014",
+        "Two template files have the same template name.
+
+Erroneous example:
+
+    app/templates/header.mustache
+    app/templates/header.html
+
+A template's name is its path without the file extension, so both files
+define `header`. Rename or remove one of them.",
+    ),
+    (
+        "E0015",
+        "An alias names a template that doesn't exist.
+
+Erroneous example, with no `robots` template:
+
+    --alias robots/old=robots
+
+Correct the template name after the `=`.",
+    ),
+    (
+        "E0016",
+        "A template requested for rendering, or as a `--root`, doesn't exist.
+
+Template names are paths relative to the template directory, without their
+file extension, like `include/header`.",
+    ),
+    (
+        "E0017",
+        "A variable is passed through a filter that isn't registered.
+
+Erroneous example:
+
+    {{name | shout}}
+
+Register the filter with the renderer before rendering the template.",
+    ),
+    (
+        "E0018",
+        "A template with the `STRICT` pragma rendered a missing value.
+
+Erroneous example, rendered without a `name` in the context:
+
+    {{%STRICT}}
+    <p>{{name}}</p>
+
+Strict templates fail rather than render nothing for a missing or null
+value. Pass the value, or wrap the tag in a `{{#name}}` section.",
+    ),
+    (
+        "E0019",
+        "An alias is declared twice, or has the name of a template.
+
+Erroneous example, with a `robots` template:
+
+    --alias robots=machines
+
+Each alias must be a name no template or other alias has.",
+    ),
+    (
+        "E0020",
+        "The compilation target can't translate the templates.
+
+Each target supports its own set of template features and options, and the
+error names what isn't supported. Choose another target with `--emit`, or
+remove the feature.",
+    ),
+    (
+        "E0021",
+        "A template file or directory, or an output file, couldn't be read or
+written.
+
+The error names the path and the operating system's reason, like a missing
+file or a permission problem.",
+    ),
+    (
+        "E0022",
+        "A rendered partial renders itself more deeply than the renderer allows.
+
+Erroneous example, in `loop.mustache`:
+
+    a{{> loop}}
+
+A partial may render itself to follow nested data, like a tree of comments,
+but each call must be inside a section that ends when the data does.
+`stache render`, `serve`, and `test` stop at 256 levels rather than
+overflowing the stack.",
+    ),
+    (
+        "W0001",
+        "A partial is never rendered by another template.
+
+A template whose file name begins with `_`, like `include/_header.mustache`,
+is a partial, so one no template renders is likely dead code. It may still be
+rendered by name from a dynamic partial tag.",
+    ),
+    (
+        "W0002",
+        "A section holds nothing but whitespace and comments.
+
+Example:
+
+    {{#robots}}
+    {{/robots}}
+
+The section renders nothing whether or not its value is present. Add its
+contents, or remove it.",
+    ),
+    (
+        "W0003",
+        "A value is rendered without HTML escaping.
+
+Example:
+
+    <p>{{{bio}}}</p>
+
+A `{{{name}}}` or `{{&name}}` tag writes the value as is, so text from users
+can inject markup. Escape it with `{{name}}`, or name the key after the HTML
+it holds, like `bio_html`, to mark it as intended.",
+    ),
+];
+
+/// Returns the extended description of the error or warning code, like
+/// `E0002`.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|&&(known, _)| known == code)
+        .map(|&(_, text)| text)
+}
+
+/// Tests whether the text has the form of an error or warning code, a
+/// letter followed by four digits.
+pub fn is_code(text: &str) -> bool {
+    text.len() == 5 && text.starts_with(['E', 'W']) && text[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain, is_code, EXPLANATIONS};
+    use std::path::PathBuf;
+    use warning::Kind;
+    use {Error, ParseError, Span};
+
+    #[test]
+    fn explains_every_code() {
+        let parse = [
+            ParseError::UnexpectedToken(0),
+            ParseError::TooDeep {
+                position: 0,
+                limit: 1,
+            },
+            ParseError::Mismatched {
+                name: String::from("a"),
+                opened: Span::default(),
+                found: String::from("b"),
+                closed: Span::default(),
+            },
+            ParseError::Unclosed {
+                name: String::from("a"),
+                opened: Span::default(),
+            },
+            ParseError::UnknownPragma {
+                name: String::from("a"),
+                position: 0,
+            },
+        ];
+        let errors = [
+            Error::UnknownPartial {
+                name: String::from("a"),
+                path: PathBuf::from("b"),
+            },
+            Error::Backend(String::from("a")),
+            Error::PartialDepth {
+                name: String::from("a"),
+                limit: 1,
+            },
+        ];
+        let kinds = [
+            Kind::UnusedPartial,
+            Kind::EmptySection,
+            Kind::UnescapedOutput,
+        ];
+
+        let codes = parse
+            .iter()
+            .map(ParseError::code)
+            .chain(errors.iter().filter_map(Error::code))
+            .chain(kinds.iter().map(|&kind| kind.code()));
+        for code in codes {
+            assert!(is_code(code), "{}", code);
+            assert!(explain(code).is_some(), "{}", code);
+        }
+
+        let mut sorted: Vec<_> = EXPLANATIONS.iter().map(|&(code, _)| code).collect();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(EXPLANATIONS.len(), sorted.len());
+
+        assert_eq!(Some("E0002"), errors[0].code());
+        assert!(explain("E9999").is_none());
+        assert!(!is_code("robots.mustache"));
+    }
+}
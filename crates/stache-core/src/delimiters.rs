@@ -0,0 +1,106 @@
+//! Set Delimiter support. The grammar spells every tag with the default
+//! `{{` and `}}` delimiters; the parser reads those literals as whichever
+//! delimiters are active at its position, so a template may switch to
+//! `<% %>` partway through and embed `{{` as plain text afterward.
+
+/// The delimiters in effect from a byte offset to the next switch.
+#[derive(Debug, PartialEq)]
+struct Switch {
+    start: usize,
+    open: String,
+    close: String,
+}
+
+/// The delimiter switches found in a template's source.
+pub struct Delimiters {
+    switches: Vec<Switch>,
+}
+
+impl Delimiters {
+    pub fn new(source: &str) -> Self {
+        Delimiters {
+            switches: scan(source),
+        }
+    }
+
+    /// Returns the open and close delimiters active at the byte offset.
+    pub fn at(&self, pos: usize) -> (&str, &str) {
+        match self.switches.iter().rev().find(|s| s.start <= pos) {
+            Some(switch) => (&switch.open, &switch.close),
+            None => ("{{", "}}"),
+        }
+    }
+}
+
+/// Finds each `{{=open close=}}` tag in the source, recording the new
+/// delimiters from the end of the tag onward. Tags are found with the
+/// delimiters active at that point, so a switch can only be undone with the
+/// delimiters it set.
+fn scan(source: &str) -> Vec<Switch> {
+    let mut switches = Vec::new();
+    let mut open = String::from("{{");
+    let mut close = String::from("}}");
+    let mut offset = 0;
+
+    while let Some(found) = source[offset..].find(open.as_str()) {
+        let inner = offset + found + open.len();
+        let end = match source[inner..].find(close.as_str()) {
+            Some(found) => inner + found,
+            None => break,
+        };
+        offset = end + close.len();
+
+        if let Some((left, right)) = delimiters(&source[inner..end]) {
+            open = left.into();
+            close = right.into();
+            switches.push(Switch {
+                start: offset,
+                open: open.clone(),
+                close: close.clone(),
+            });
+        }
+    }
+
+    switches
+}
+
+/// Splits a Set Delimiter tag's inner text, like `=<% %>=`, into its open
+/// and close delimiters. The `~` markers of a trimming tag are ignored.
+fn delimiters(tag: &str) -> Option<(&str, &str)> {
+    let tag = tag.strip_prefix('~').unwrap_or(tag);
+    let tag = tag.strip_suffix('~').unwrap_or(tag);
+    if tag.len() < 2 || !tag.starts_with('=') || !tag.ends_with('=') {
+        return None;
+    }
+
+    let mut parts = tag[1..tag.len() - 1].split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(open), Some(close), None) if !open.contains('=') && !close.contains('=') => {
+            Some((open, close))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scan, Switch};
+
+    #[test]
+    fn scans_delimiter_switches() {
+        let source = "{{a}}{{=<% %>=}}{{b}}<%={{ }}=%>{{! {{=| |=}} }}";
+        let expected = vec![
+            Switch {
+                start: 16,
+                open: "<%".into(),
+                close: "%>".into(),
+            },
+            Switch {
+                start: 32,
+                open: "{{".into(),
+                close: "}}".into(),
+            },
+        ];
+        assert_eq!(expected, scan(source));
+    }
+}
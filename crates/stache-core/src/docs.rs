@@ -0,0 +1,213 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::slice;
+
+use super::usage::{Index, Kind};
+use super::{Backend, Compile, Error, Statement, Template};
+
+/// A Markdown reference document describing each template: its doc
+/// comments, the context paths it expects, and the partials it renders.
+///
+/// Documentation is written in comment tags beginning with `doc:`, in either
+/// the `{{! doc: ... }}` or `{{!-- doc: ... --}}` style.
+#[derive(Debug)]
+pub struct Reference {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    path: PathBuf,
+    docs: Vec<String>,
+    variables: Vec<(String, Kind)>,
+    partials: BTreeSet<String>,
+}
+
+impl Compile for Reference {
+    /// Writes the Markdown document to the output buffer.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        writeln!(buf, "# Templates")?;
+
+        for entry in &self.entries {
+            writeln!(buf)?;
+            writeln!(buf, "## `{}`", entry.name)?;
+            writeln!(buf)?;
+            writeln!(buf, "Source: `{}`", entry.path.display())?;
+
+            for doc in &entry.docs {
+                writeln!(buf)?;
+                writeln!(buf, "{}", doc)?;
+            }
+
+            if !entry.variables.is_empty() {
+                writeln!(buf)?;
+                writeln!(buf, "### Variables")?;
+                writeln!(buf)?;
+                for &(ref path, kind) in &entry.variables {
+                    writeln!(buf, "- `{}` ({})", path, describe(kind))?;
+                }
+            }
+
+            if !entry.partials.is_empty() {
+                writeln!(buf)?;
+                writeln!(buf, "### Partials")?;
+                writeln!(buf)?;
+                for partial in &entry.partials {
+                    writeln!(buf, "- `{}`", partial)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The `docs` backend, generating the reference document.
+#[derive(Debug)]
+pub struct Generator;
+
+impl Backend for Generator {
+    fn name(&self) -> &str {
+        "docs"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(generate(templates)))
+    }
+}
+
+/// Builds the reference document for the templates, sorted by name.
+pub fn generate(templates: &[Template]) -> Reference {
+    let mut entries: Vec<_> = templates
+        .iter()
+        .map(|template| {
+            let mut docs = Vec::new();
+            collect(&template.tree, &mut docs);
+
+            let index = Index::build(slice::from_ref(template));
+            let variables = index
+                .iter()
+                .map(|(path, usages)| (path.clone(), usages[0].kind))
+                .collect();
+
+            Entry {
+                name: template.name.clone(),
+                path: template.path.clone(),
+                docs,
+                variables,
+                partials: template
+                    .tree
+                    .partials()
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Reference { entries }
+}
+
+/// Gathers the text of each doc comment in the tree in source order.
+fn collect(node: &Statement, docs: &mut Vec<String>) {
+    match *node {
+        Statement::Program(ref block)
+        | Statement::Section(_, ref block)
+        | Statement::Inverted(_, ref block)
+        | Statement::Block(_, ref block) => {
+            for stmt in &block.statements {
+                collect(stmt, docs);
+            }
+        }
+        Statement::Comment(ref text) => {
+            if let Some(doc) = doc(text) {
+                docs.push(doc);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Extracts documentation text from a comment tag's contents, removing the
+/// optional `--` fences and the `doc:` marker.
+fn doc(comment: &str) -> Option<String> {
+    let mut text = comment.trim();
+    if text.starts_with("--") && text.ends_with("--") && text.len() >= 4 {
+        text = text[2..text.len() - 2].trim();
+    }
+
+    if !text.starts_with("doc:") {
+        return None;
+    }
+
+    let lines: Vec<_> = text["doc:".len()..]
+        .lines()
+        .map(|line| line.trim())
+        .collect();
+    Some(lines.join("\n").trim().to_string())
+}
+
+/// Describes how a template uses a context path.
+fn describe(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Variable => "escaped",
+        Kind::Html => "unescaped",
+        Kind::Section => "section",
+        Kind::Inverted => "inverted section",
+        Kind::Partial => "partial name",
+        Kind::Argument => "partial argument",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Compile, Statement, Template};
+    use super::{doc, generate};
+    use std::path::PathBuf;
+
+    #[test]
+    fn extracts_doc_comments() {
+        assert_eq!(Some(String::from("A card.")), doc("-- doc: A card. --"));
+        assert_eq!(Some(String::from("A card.")), doc("doc: A card."));
+        assert_eq!(
+            Some(String::from("First line.\nSecond line.")),
+            doc("--\n  doc: First line.\n  Second line.\n--")
+        );
+        assert_eq!(None, doc("-- a comment --"));
+        assert_eq!(None, doc("else clause"));
+    }
+
+    #[test]
+    fn generates_markdown_reference() {
+        let base = PathBuf::from("app/templates");
+        let path = base.join("robot.mustache");
+        let text =
+            "{{!-- doc: Renders a robot. --}}\n{{> includes/header}}\n<b>{{ name }}</b>{{{ bio }}}";
+        let template = Template::new(&base, path, Statement::parse(text).unwrap());
+
+        let mut buf = Vec::new();
+        generate(&[template]).emit(&mut buf).unwrap();
+
+        let expected = "# Templates
+
+## `robot`
+
+Source: `app/templates/robot.mustache`
+
+Renders a robot.
+
+### Variables
+
+- `bio` (unescaped)
+- `name` (escaped)
+
+### Partials
+
+- `includes/header`
+";
+        assert_eq!(expected, String::from_utf8(buf).unwrap());
+    }
+}
@@ -0,0 +1,515 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use pragma::Pragma;
+use span::Span;
+
+#[derive(Debug)]
+pub enum ParseError {
+    UnexpectedToken(usize),
+
+    /// A section opened at the position is nested more deeply than the
+    /// parser's limit.
+    TooDeep {
+        position: usize,
+        limit: usize,
+    },
+
+    /// A close tag names a different section than the innermost one open.
+    Mismatched {
+        name: String,
+        opened: Span,
+        found: String,
+        closed: Span,
+    },
+
+    /// A section is still open at the end of the template.
+    Unclosed {
+        name: String,
+        opened: Span,
+    },
+
+    /// A pragma tag names a pragma the compiler doesn't recognize.
+    UnknownPragma {
+        name: String,
+        position: usize,
+    },
+}
+
+impl ParseError {
+    /// Returns the byte offset in the template where parsing failed.
+    pub fn position(&self) -> usize {
+        match *self {
+            ParseError::UnexpectedToken(position)
+            | ParseError::TooDeep { position, .. }
+            | ParseError::UnknownPragma { position, .. } => position,
+            ParseError::Mismatched { closed, .. } => closed.start,
+            ParseError::Unclosed { opened, .. } => opened.start,
+        }
+    }
+
+    /// Returns the error's stable code, like `E0001`, explained by
+    /// `stache code`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ParseError::UnexpectedToken(_) => "E0001",
+            ParseError::Mismatched { .. } => "E0003",
+            ParseError::Unclosed { .. } => "E0004",
+            ParseError::TooDeep { .. } => "E0005",
+            ParseError::UnknownPragma { .. } => "E0006",
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::UnexpectedToken(position) => {
+                write!(f, "Unexpected token at position {}", position)
+            }
+            ParseError::TooDeep { position, limit } => write!(
+                f,
+                "Section at position {} is nested more than {} level{} deep",
+                position,
+                limit,
+                if limit == 1 { "" } else { "s" }
+            ),
+            ParseError::Mismatched {
+                ref name,
+                opened,
+                ref found,
+                closed,
+            } => write!(
+                f,
+                "Section '{}' opened at line {} closed as '{}' at line {}",
+                name, opened.line, found, closed.line
+            ),
+            ParseError::Unclosed { ref name, opened } => write!(
+                f,
+                "Section '{}' opened at line {} is never closed",
+                name, opened.line
+            ),
+            ParseError::UnknownPragma { ref name, .. } => write!(
+                f,
+                "Unknown pragma '{}', expected one of: {}",
+                name,
+                Pragma::ALL
+                    .iter()
+                    .map(Pragma::name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// A syntax error located in a template file, with the text of the line it's
+/// found on to point out where the template went wrong.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub source: ParseError,
+}
+
+impl Diagnostic {
+    /// Returns the whitespace leading up to the error's column in the line,
+    /// followed by a caret, to be printed beneath the line's text. Tabs are
+    /// kept so the caret lines up with the text above it.
+    pub fn marker(&self) -> String {
+        let mut marker: String = self
+            .text
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        marker.push('^');
+        marker
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        writeln!(
+            f,
+            "Error parsing {} at line {}, column {}",
+            self.path.display(),
+            self.line,
+            self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", number, self.text)?;
+        writeln!(f, "{} | {}", gutter, self.marker())?;
+        write!(f, "{}", self.source)
+    }
+}
+
+/// An error loading, linking, or compiling templates.
+#[derive(Debug)]
+pub enum Error {
+    /// A template file or directory, or an output file, couldn't be read or
+    /// written.
+    Io { path: PathBuf, source: io::Error },
+
+    /// A template file isn't valid UTF-8. The bytes are the first invalid
+    /// sequence, found at the byte offset, line, and column.
+    Encoding {
+        path: PathBuf,
+        offset: usize,
+        line: usize,
+        column: usize,
+        bytes: Vec<u8>,
+    },
+
+    /// A template file is larger than the loader's size limit.
+    TooLarge {
+        path: PathBuf,
+        size: u64,
+        limit: u64,
+    },
+
+    /// A template's syntax is invalid at the diagnostic's line and column.
+    /// Boxed, as the diagnostic is much larger than the other variants.
+    Parse(Box<Diagnostic>),
+
+    /// The symlink at the path points to the target, which is a directory
+    /// containing the link or is also loaded without following the link.
+    Symlink { path: PathBuf, target: PathBuf },
+
+    /// The template directory's `.stacheignore` file is invalid.
+    Ignore { path: PathBuf, message: String },
+
+    /// An include or exclude pattern given in the load options is invalid.
+    Glob { pattern: String, message: String },
+
+    /// A template file's front matter isn't a valid YAML mapping.
+    FrontMatter { path: PathBuf, message: String },
+
+    /// A template calls a partial that no template defines.
+    UnknownPartial { name: String, path: PathBuf },
+
+    /// A template's parent tag inherits, directly or through other parents,
+    /// from the template itself.
+    RecursiveParent { name: String, path: PathBuf },
+
+    /// Template files at both paths have the same template name, like
+    /// `header.mustache` and `header.html`.
+    DuplicateTemplate {
+        name: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+
+    /// An alias names a template that doesn't exist.
+    UnknownTemplate { name: String, alias: String },
+
+    /// A template requested for rendering doesn't exist.
+    TemplateNotFound { name: String },
+
+    /// A variable is passed through a filter that isn't registered.
+    UnknownFilter { name: String },
+
+    /// A template with the `STRICT` pragma rendered a variable whose value
+    /// is missing.
+    MissingValue { path: String },
+
+    /// A partial rendered by the interpreter renders itself, directly or
+    /// through other partials, more deeply than the limit.
+    PartialDepth { name: String, limit: usize },
+
+    /// An alias is declared twice, or has the name of a template.
+    AliasConflict { alias: String },
+
+    /// A compiler backend can't translate the templates.
+    Backend(String),
+
+    /// Every problem found in a set of templates, in template order.
+    Multiple(Vec<Error>),
+}
+
+impl Error {
+    /// Attributes an IO error to the file or directory at the path.
+    pub fn io<P: Into<PathBuf>>(path: P, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    /// Combines the errors found in a set of templates into one, returning
+    /// success if there are none.
+    pub fn all(mut errors: Vec<Error>) -> Result<(), Self> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(Error::Multiple(errors)),
+        }
+    }
+
+    /// Returns each individual error combined into this one.
+    pub fn errors(&self) -> Vec<&Error> {
+        match *self {
+            Error::Multiple(ref errors) => errors.iter().flat_map(|e| e.errors()).collect(),
+            _ => vec![self],
+        }
+    }
+
+    /// Names the kind of error, like `unknown_partial`, for tools matching on
+    /// it rather than its message.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Error::Io { .. } => "io",
+            Error::Encoding { .. } => "encoding",
+            Error::TooLarge { .. } => "too_large",
+            Error::Parse(_) => "parse",
+            Error::Symlink { .. } => "symlink",
+            Error::Ignore { .. } => "ignore",
+            Error::Glob { .. } => "glob",
+            Error::FrontMatter { .. } => "front_matter",
+            Error::UnknownPartial { .. } => "unknown_partial",
+            Error::RecursiveParent { .. } => "recursive_parent",
+            Error::DuplicateTemplate { .. } => "duplicate_template",
+            Error::UnknownTemplate { .. } => "unknown_template",
+            Error::TemplateNotFound { .. } => "template_not_found",
+            Error::UnknownFilter { .. } => "unknown_filter",
+            Error::MissingValue { .. } => "missing_value",
+            Error::PartialDepth { .. } => "partial_depth",
+            Error::AliasConflict { .. } => "alias_conflict",
+            Error::Backend(_) => "backend",
+            Error::Multiple(_) => "multiple",
+        }
+    }
+
+    /// Returns the error's stable code, like `E0002`, explained by
+    /// `stache code`. Combined errors have no code of their own.
+    pub fn code(&self) -> Option<&'static str> {
+        let code = match *self {
+            Error::Parse(ref diagnostic) => diagnostic.source.code(),
+            Error::UnknownPartial { .. } => "E0002",
+            Error::Encoding { .. } => "E0007",
+            Error::TooLarge { .. } => "E0008",
+            Error::Symlink { .. } => "E0009",
+            Error::Ignore { .. } => "E0010",
+            Error::Glob { .. } => "E0011",
+            Error::FrontMatter { .. } => "E0012",
+            Error::RecursiveParent { .. } => "E0013",
+            Error::DuplicateTemplate { .. } => "E0014",
+            Error::UnknownTemplate { .. } => "E0015",
+            Error::TemplateNotFound { .. } => "E0016",
+            Error::UnknownFilter { .. } => "E0017",
+            Error::MissingValue { .. } => "E0018",
+            Error::AliasConflict { .. } => "E0019",
+            Error::Backend(_) => "E0020",
+            Error::Io { .. } => "E0021",
+            Error::PartialDepth { .. } => "E0022",
+            Error::Multiple(_) => return None,
+        };
+        Some(code)
+    }
+
+    /// Returns the file the error was found in, if it concerns one.
+    pub fn path(&self) -> Option<&Path> {
+        match *self {
+            Error::Io { ref path, .. }
+            | Error::Encoding { ref path, .. }
+            | Error::TooLarge { ref path, .. }
+            | Error::Symlink { ref path, .. }
+            | Error::Ignore { ref path, .. }
+            | Error::FrontMatter { ref path, .. }
+            | Error::UnknownPartial { ref path, .. }
+            | Error::RecursiveParent { ref path, .. } => Some(path),
+            Error::DuplicateTemplate { ref second, .. } => Some(second),
+            Error::Parse(ref diagnostic) => Some(&diagnostic.path),
+            _ => None,
+        }
+    }
+
+    /// Converts each individual error to a JSON object with its code, kind,
+    /// file, location, and message, for editors and CI annotators reading the
+    /// compiler's output. Errors not located in a file have null `file` and
+    /// `span` fields.
+    pub fn json(&self) -> Vec<Value> {
+        self.errors()
+            .into_iter()
+            .map(|error| {
+                let span = match *error {
+                    Error::Parse(ref diagnostic) => location(
+                        diagnostic.source.position(),
+                        diagnostic.line,
+                        diagnostic.column,
+                    ),
+                    Error::Encoding {
+                        offset,
+                        line,
+                        column,
+                        ..
+                    } => location(offset, line, column),
+                    _ => Value::Null,
+                };
+                let message = match *error {
+                    Error::Parse(ref diagnostic) => diagnostic.source.to_string(),
+                    _ => error.to_string(),
+                };
+
+                let mut map = Map::new();
+                map.insert(String::from("level"), Value::from("error"));
+                map.insert(String::from("code"), Value::from(error.code()));
+                map.insert(String::from("kind"), Value::from(error.kind()));
+                map.insert(
+                    String::from("file"),
+                    error
+                        .path()
+                        .map_or(Value::Null, |path| Value::from(path.to_string_lossy())),
+                );
+                map.insert(String::from("span"), span);
+                map.insert(String::from("message"), Value::from(message));
+                Value::Object(map)
+            })
+            .collect()
+    }
+}
+
+/// Converts an error's byte offset, line, and column to JSON.
+pub fn location(offset: usize, line: usize, column: usize) -> Value {
+    let mut map = Map::new();
+    map.insert(String::from("offset"), Value::from(offset));
+    map.insert(String::from("line"), Value::from(line));
+    map.insert(String::from("column"), Value::from(column));
+    Value::Object(map)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io {
+                ref path,
+                ref source,
+            } => write!(f, "{}: {}", path.display(), source),
+            Error::Encoding {
+                ref path,
+                offset,
+                line,
+                column,
+                ref bytes,
+            } => {
+                let hex: Vec<_> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                write!(
+                    f,
+                    "{}: invalid UTF-8 at byte {}, line {}, column {}: {}",
+                    path.display(),
+                    offset,
+                    line,
+                    column,
+                    hex.join(" ")
+                )
+            }
+            Error::TooLarge {
+                ref path,
+                size,
+                limit,
+            } => write!(
+                f,
+                "{}: template is {} bytes, larger than the {} byte limit",
+                path.display(),
+                size,
+                limit
+            ),
+            Error::Parse(ref diagnostic) => write!(f, "{}", diagnostic),
+            Error::Symlink {
+                ref path,
+                ref target,
+            } if path.starts_with(target) => write!(
+                f,
+                "{} is a symlink to {}, a directory containing it, so following it would load templates forever",
+                path.display(),
+                target.display()
+            ),
+            Error::Symlink {
+                ref path,
+                ref target,
+            } => write!(
+                f,
+                "{} is a symlink to {}, which is also loaded, so following it would load the same templates twice",
+                path.display(),
+                target.display()
+            ),
+            Error::Ignore {
+                ref path,
+                ref message,
+            } => write!(f, "Error reading {}\n{}", path.display(), message),
+            Error::Glob {
+                ref pattern,
+                ref message,
+            } => write!(f, "Invalid pattern {}\n{}", pattern, message),
+            Error::FrontMatter {
+                ref path,
+                ref message,
+            } => write!(f, "Invalid front matter in {}\n{}", path.display(), message),
+            Error::UnknownPartial { ref name, ref path } => write!(
+                f,
+                "Undefined partial `{}` called in {}",
+                name,
+                path.display()
+            ),
+            Error::RecursiveParent { ref name, ref path } => write!(
+                f,
+                "Parent template `{}` inherits from itself in {}",
+                name,
+                path.display()
+            ),
+            Error::DuplicateTemplate {
+                ref name,
+                ref first,
+                ref second,
+            } => write!(
+                f,
+                "Template `{}` is defined by both {} and {}",
+                name,
+                first.display(),
+                second.display()
+            ),
+            Error::UnknownTemplate {
+                ref name,
+                ref alias,
+            } => write!(f, "Undefined template `{}` aliased as `{}`", name, alias),
+            Error::TemplateNotFound { ref name } => write!(f, "Template `{}` not found", name),
+            Error::UnknownFilter { ref name } => write!(f, "Filter `{}` is not registered", name),
+            Error::MissingValue { ref path } => write!(f, "Missing template value: {}", path),
+            Error::PartialDepth { ref name, limit } => write!(
+                f,
+                "Partial `{}` is nested more than {} levels deep",
+                name, limit
+            ),
+            Error::AliasConflict { ref alias } => write!(
+                f,
+                "Alias `{}` is already the name of a template or alias",
+                alias
+            ),
+            Error::Backend(ref message) => write!(f, "{}", message),
+            Error::Multiple(ref errors) => {
+                let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io { ref source, .. } => Some(source),
+            Error::Parse(ref diagnostic) => Some(&diagnostic.source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use serde_json::{Map, Value};
+use std::fmt;
+
+use super::parser::Parser;
+use super::{ParseError, Span, Statement};
+
+/// The parsed tree of a single template, along with each tag that stood on
+/// a line of its own, for debugging how template whitespace is rendered.
+#[derive(Debug)]
+pub struct Explanation {
+    pub tree: Statement,
+    pub lines: Vec<Line>,
+}
+
+/// A section, partial, or comment tag parsed as a standalone line candidate.
+///
+/// A tag alone on its line, with only whitespace before it, is standalone:
+/// its indentation and line ending are removed from the output. When other
+/// text precedes the tag, the whitespace is kept as content instead.
+#[derive(Debug)]
+pub struct Line {
+    pub tag: String,
+    pub span: Span,
+    pub standalone: bool,
+    pub indent: String,
+    pub terminator: String,
+}
+
+impl Line {
+    /// Describes what happened to the whitespace around the tag.
+    fn attribution(&self) -> String {
+        if !self.standalone {
+            return String::from("inline, whitespace kept");
+        }
+
+        let partial = self.tag.starts_with("{{>");
+        match (self.indent.is_empty(), partial) {
+            (true, _) => format!("standalone, removed line ending {:?}", self.terminator),
+            (false, true) => format!(
+                "standalone, removed line ending {:?}, indent {:?} applied to each partial line",
+                self.terminator, self.indent
+            ),
+            (false, false) => format!(
+                "standalone, removed indent {:?} and line ending {:?}",
+                self.indent, self.terminator
+            ),
+        }
+    }
+
+    fn json(&self) -> Value {
+        let mut map = Map::new();
+        map.insert(String::from("tag"), Value::from(self.tag.as_str()));
+        map.insert(String::from("line"), Value::from(self.span.line));
+        map.insert(String::from("column"), Value::from(self.span.column));
+        map.insert(String::from("standalone"), Value::from(self.standalone));
+        map.insert(String::from("indent"), Value::from(self.indent.as_str()));
+        map.insert(
+            String::from("terminator"),
+            Value::from(self.terminator.as_str()),
+        );
+        Value::Object(map)
+    }
+}
+
+impl Explanation {
+    /// Converts the tree and standalone lines to a JSON document.
+    pub fn json(&self) -> Value {
+        let lines = self.lines.iter().map(|line| line.json()).collect();
+
+        let mut map = Map::new();
+        map.insert(String::from("tree"), node(&self.tree, root(&self.tree)));
+        map.insert(String::from("lines"), Value::Array(lines));
+        Value::Object(map)
+    }
+}
+
+impl fmt::Display for Explanation {
+    /// Formats the tree with one indented node per line, followed by the
+    /// whitespace attribution of each standalone line candidate.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        tree(f, &self.tree, 0)?;
+
+        if !self.lines.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Standalone lines:")?;
+            for line in &self.lines {
+                writeln!(
+                    f,
+                    "  {}:{} {} {}",
+                    line.span.line,
+                    line.span.column,
+                    line.tag,
+                    line.attribution()
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the template text, recording the whitespace handling of each tag
+/// that may stand alone on its line.
+pub fn explain(text: &str) -> Result<Explanation, ParseError> {
+    let mut parser = Parser::new(text);
+    let tree = parser.program()?;
+
+    let lines = parser
+        .candidates()
+        .iter()
+        .map(|candidate| Line {
+            tag: text[candidate.tag..candidate.terminator].to_string(),
+            span: parser.locate(candidate.tag, candidate.terminator),
+            standalone: parser.locate(candidate.start, candidate.start).column == 1,
+            indent: text[candidate.start..candidate.tag].to_string(),
+            terminator: text[candidate.terminator..candidate.end].to_string(),
+        })
+        .collect();
+
+    Ok(Explanation { tree, lines })
+}
+
+/// Writes the node and its children, indented by depth.
+fn tree(f: &mut fmt::Formatter, node: &Statement, depth: usize) -> fmt::Result {
+    write!(f, "{}", "  ".repeat(depth))?;
+    match *node {
+        Statement::Program(ref block) => {
+            writeln!(f, "Program")?;
+            for stmt in &block.statements {
+                tree(f, stmt, depth + 1)?;
+            }
+        }
+        Statement::Section(ref path, ref block) | Statement::Inverted(ref path, ref block) => {
+            let kind = match *node {
+                Statement::Section(..) => "Section",
+                _ => "Inverted",
+            };
+            writeln!(
+                f,
+                "{} {} @{}:{}",
+                kind, path, path.span.line, path.span.column
+            )?;
+            for stmt in &block.statements {
+                tree(f, stmt, depth + 1)?;
+            }
+        }
+        Statement::Variable(ref path) | Statement::Html(ref path) => {
+            let kind = match *node {
+                Statement::Variable(_) => "Variable",
+                _ => "Html",
+            };
+            writeln!(
+                f,
+                "{} {} @{}:{}",
+                kind, path, path.span.line, path.span.column
+            )?;
+        }
+        Statement::Filtered(ref path, ref filters) => writeln!(
+            f,
+            "Filtered {} | {} @{}:{}",
+            path,
+            filters.join(" | "),
+            path.span.line,
+            path.span.column
+        )?,
+        Statement::Partial(ref name, ref padding, ref arguments) => {
+            write!(f, "Partial {}", name)?;
+            for (key, path) in arguments {
+                write!(f, " {}={}", key, path)?;
+            }
+            match *padding {
+                Some(ref indent) => writeln!(f, " indent {:?}", indent)?,
+                None => writeln!(f)?,
+            }
+        }
+        Statement::DynamicPartial(ref path, ref padding) => {
+            write!(
+                f,
+                "DynamicPartial {} @{}:{}",
+                path, path.span.line, path.span.column
+            )?;
+            match *padding {
+                Some(ref indent) => writeln!(f, " indent {:?}", indent)?,
+                None => writeln!(f)?,
+            }
+        }
+        Statement::Content(ref text) => writeln!(f, "Content {:?}", text)?,
+        Statement::Comment(ref text) => writeln!(f, "Comment {:?}", text)?,
+        Statement::Translation(ref key) => writeln!(f, "Translation {:?}", key)?,
+        Statement::Pragma(pragma) => writeln!(f, "Pragma {}", pragma)?,
+        Statement::Parent(ref name, ref block) | Statement::Block(ref name, ref block) => {
+            let kind = match *node {
+                Statement::Parent(..) => "Parent",
+                _ => "Block",
+            };
+            writeln!(f, "{} {}", kind, name)?;
+            for stmt in &block.statements {
+                tree(f, stmt, depth + 1)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the span of the tree's root statement: the whole source for a
+/// parsed program.
+pub fn root(tree: &Statement) -> Span {
+    match *tree {
+        Statement::Program(ref block) => block.span,
+        _ => Span::default(),
+    }
+}
+
+/// Converts the node, located by its span, and its children to JSON.
+pub fn node(stmt: &Statement, span: Span) -> Value {
+    let mut map = Map::new();
+    let (kind, children) = match *stmt {
+        Statement::Program(ref block) => ("program", Some(block)),
+        Statement::Section(ref path, ref block) | Statement::Inverted(ref path, ref block) => {
+            map.insert(String::from("path"), Value::from(path.to_string()));
+            map.insert(String::from("line"), Value::from(path.span.line));
+            map.insert(String::from("column"), Value::from(path.span.column));
+            match *stmt {
+                Statement::Section(..) => ("section", Some(block)),
+                _ => ("inverted", Some(block)),
+            }
+        }
+        Statement::Variable(ref path) | Statement::Html(ref path) => {
+            map.insert(String::from("path"), Value::from(path.to_string()));
+            map.insert(String::from("line"), Value::from(path.span.line));
+            map.insert(String::from("column"), Value::from(path.span.column));
+            match *stmt {
+                Statement::Variable(_) => ("variable", None),
+                _ => ("html", None),
+            }
+        }
+        Statement::Filtered(ref path, ref filters) => {
+            map.insert(String::from("path"), Value::from(path.to_string()));
+            map.insert(String::from("line"), Value::from(path.span.line));
+            map.insert(String::from("column"), Value::from(path.span.column));
+            map.insert(String::from("filters"), Value::from(filters.clone()));
+            ("filtered", None)
+        }
+        Statement::Partial(ref name, ref padding, ref arguments) => {
+            map.insert(String::from("name"), Value::from(name.as_str()));
+            if let Some(ref indent) = *padding {
+                map.insert(String::from("indent"), Value::from(indent.as_str()));
+            }
+            if !arguments.is_empty() {
+                let arguments = arguments
+                    .iter()
+                    .map(|(key, path)| (key.clone(), Value::from(path.to_string())))
+                    .collect();
+                map.insert(String::from("arguments"), Value::Object(arguments));
+            }
+            ("partial", None)
+        }
+        Statement::DynamicPartial(ref path, ref padding) => {
+            map.insert(String::from("path"), Value::from(path.to_string()));
+            map.insert(String::from("line"), Value::from(path.span.line));
+            map.insert(String::from("column"), Value::from(path.span.column));
+            if let Some(ref indent) = *padding {
+                map.insert(String::from("indent"), Value::from(indent.as_str()));
+            }
+            ("dynamic_partial", None)
+        }
+        Statement::Content(ref text) => {
+            map.insert(String::from("text"), Value::from(text.as_str()));
+            ("content", None)
+        }
+        Statement::Comment(ref text) => {
+            map.insert(String::from("text"), Value::from(text.as_str()));
+            ("comment", None)
+        }
+        Statement::Translation(ref key) => {
+            map.insert(String::from("key"), Value::from(key.as_str()));
+            ("translation", None)
+        }
+        Statement::Pragma(pragma) => {
+            map.insert(String::from("name"), Value::from(pragma.name()));
+            ("pragma", None)
+        }
+        Statement::Parent(ref name, ref block) | Statement::Block(ref name, ref block) => {
+            map.insert(String::from("name"), Value::from(name.as_str()));
+            match *stmt {
+                Statement::Parent(..) => ("parent", Some(block)),
+                _ => ("block", Some(block)),
+            }
+        }
+    };
+
+    map.insert(String::from("type"), Value::from(kind));
+    map.insert(String::from("span"), location(span));
+    if let Some(block) = children {
+        let nodes = block
+            .statements
+            .iter()
+            .zip(&block.spans)
+            .map(|(stmt, &span)| node(stmt, span))
+            .collect();
+        map.insert(String::from("children"), Value::Array(nodes));
+    }
+    Value::Object(map)
+}
+
+/// Converts the span to JSON.
+fn location(span: Span) -> Value {
+    let mut map = Map::new();
+    map.insert(String::from("start"), Value::from(span.start));
+    map.insert(String::from("end"), Value::from(span.end));
+    map.insert(String::from("line"), Value::from(span.line));
+    map.insert(String::from("column"), Value::from(span.column));
+    Value::Object(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::explain;
+
+    #[test]
+    fn attributes_standalone_whitespace() {
+        let text = "<ul>\n  {{#robots}}\n  <li>{{ name }}</li>\n  {{/robots}}\n</ul>{{! done }}\n";
+        let explanation = explain(text).unwrap();
+
+        let expected = "Program
+  Content \"<ul>\\n\"
+  Section robots @2:6
+    Content \"  <li>\"
+    Variable name @3:10
+    Content \"</li>\\n\"
+  Content \"</ul>\"
+  Comment \"done\"
+  Content \"\\n\"
+
+Standalone lines:
+  2:3 {{#robots}} standalone, removed indent \"  \" and line ending \"\\n\"
+  4:3 {{/robots}} standalone, removed indent \"  \" and line ending \"\\n\"
+  5:6 {{! done }} inline, whitespace kept
+";
+        assert_eq!(expected, explanation.to_string());
+
+        let json = explanation.json();
+        assert_eq!("section", json["tree"]["children"][1]["type"]);
+        assert_eq!("robots", json["tree"]["children"][1]["path"]);
+        assert_eq!(2, json["tree"]["children"][1]["span"]["line"]);
+        assert_eq!(true, json["lines"][0]["standalone"]);
+        assert_eq!("  ", json["lines"][0]["indent"]);
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::{Statement, Template};
+
+/// Synthetic template sets used to benchmark the parser and code generator.
+///
+/// Each set is generated deterministically, so timings remain comparable
+/// between releases.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Set {
+    /// A handful of pages sharing a layout, typical of a small application.
+    Small,
+    /// Deeply nested sections and a long chain of partials in nested
+    /// directories.
+    Deep,
+    /// Hundreds of independent templates, each with many tags.
+    Wide,
+}
+
+impl Set {
+    /// Returns the name of the set, used as its benchmark and directory name.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Set::Small => "small",
+            Set::Deep => "deep",
+            Set::Wide => "wide",
+        }
+    }
+
+    /// Generates the short name and source text of each template in the set.
+    pub fn sources(&self) -> Vec<(String, String)> {
+        match *self {
+            Set::Small => small(),
+            Set::Deep => deep(32),
+            Set::Wide => wide(500),
+        }
+    }
+
+    /// Generates and parses the templates in the set.
+    pub fn templates(&self) -> Vec<Template> {
+        let base = PathBuf::from(self.name());
+        self.sources()
+            .into_iter()
+            .map(|(name, text)| {
+                let path = base.join(format!("{}.mustache", name));
+                let tree = Statement::parse(&text).expect("valid fixture template");
+                Template::new(&base, path, tree)
+            })
+            .collect()
+    }
+
+    /// Writes the set's template files into the directory, to benchmark the
+    /// compiler binary.
+    pub fn write(&self, dir: &Path) -> io::Result<()> {
+        for (name, text) in self.sources() {
+            let path = dir.join(format!("{}.mustache", name));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            File::create(path)?.write_all(text.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+fn small() -> Vec<(String, String)> {
+    let mut sources = vec![
+        (
+            String::from("layouts/header"),
+            String::from("<html>\n<head><title>{{ title }}</title></head>\n<body>\n"),
+        ),
+        (
+            String::from("layouts/footer"),
+            String::from("<footer>{{{ copyright }}}</footer>\n</body>\n</html>\n"),
+        ),
+    ];
+
+    for i in 0..10 {
+        let text = format!(
+            "{{{{> layouts/header }}}}
+<h1>{{{{ heading }}}} {i}</h1>
+<ul>
+  {{{{# items }}}}
+  <li class=\"{{{{ kind }}}}\">{{{{ name }}}}: {{{{ description }}}}</li>
+  {{{{/ items }}}}
+  {{{{^ items }}}}
+  <li>Nothing here</li>
+  {{{{/ items }}}}
+</ul>
+{{{{> layouts/footer }}}}
+",
+            i = i
+        );
+        sources.push((format!("pages/page{}", i), text));
+    }
+
+    sources
+}
+
+fn deep(depth: usize) -> Vec<(String, String)> {
+    let mut sections = String::new();
+    for i in 0..depth {
+        sections.push_str(&format!("<div>{{{{# level{} }}}}{{{{ name }}}}", i));
+    }
+    for i in (0..depth).rev() {
+        sections.push_str(&format!("{{{{/ level{} }}}}</div>", i));
+    }
+
+    let mut sources = vec![(String::from("sections"), sections)];
+
+    let mut dir = String::from("partials");
+    for i in 0..depth {
+        let name = format!("{}/partial{}", dir, i);
+        let text = if i + 1 < depth {
+            format!(
+                "<p>{{{{ value{} }}}}</p>{{{{> {}/level{}/partial{} }}}}",
+                i,
+                dir,
+                i,
+                i + 1
+            )
+        } else {
+            format!("<p>{{{{ value{} }}}}</p>", i)
+        };
+        sources.push((name, text));
+        dir = format!("{}/level{}", dir, i);
+    }
+
+    sources
+}
+
+fn wide(count: usize) -> Vec<(String, String)> {
+    (0..count)
+        .map(|i| {
+            let mut text = String::new();
+            for j in 0..20 {
+                text.push_str(&format!(
+                    "<span>{{{{ field{j}.value }}}}</span>\n{{{{# list{j} }}}}<i>{{{{{{ html }}}}}}</i>{{{{/ list{j} }}}}\n",
+                    j = j
+                ));
+            }
+            (format!("components/component{}", i), text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Set;
+
+    #[test]
+    fn generates_parsable_sets() {
+        for set in &[Set::Small, Set::Deep, Set::Wide] {
+            assert!(!set.templates().is_empty(), "{}", set.name());
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Rewrites template tags with canonical spacing.
+//!
+//! Whitespace inside a tag's delimiters is removed, and runs of whitespace
+//! between the parts of a tag, like a partial's name and its arguments or a
+//! variable and its filters, are collapsed to one space:
+//!
+//! ```text
+//! {{ name }}                 => {{name}}
+//! {{# robots }}              => {{#robots}}
+//! {{>  card   title=name }}  => {{>card title=name}}
+//! ```
+//!
+//! Content, comments, and tags with `~` trim markers or quoted text are
+//! copied unchanged. Formatting stops at the first set delimiters tag, as
+//! the tags after it use other delimiters.
+
+use front_matter;
+
+use super::{LoadOptions, ParseError, Statement};
+
+/// The characters beginning a tag that select its kind.
+const SIGILS: &[char] = &['#', '^', '/', '&', '>', '<', '$', '%'];
+
+/// Formats the template source parsed with the options. The formatted text
+/// always parses into the same tree as the source; if it wouldn't, the
+/// source is returned unchanged.
+pub fn format(source: &str, options: &LoadOptions) -> Result<String, ParseError> {
+    let tree = Statement::parse_with(source, options)?;
+
+    let start = if options.front_matter {
+        front_matter::split(source).map_or(0, |(_, end)| end)
+    } else {
+        0
+    };
+    let mut text = String::with_capacity(source.len());
+    text.push_str(&source[..start]);
+
+    let mut pos = start;
+    while let Some(open) = source[pos..].find("{{").map(|i| pos + i) {
+        text.push_str(&source[pos..open]);
+        let triple = source[open..].starts_with("{{{");
+        let close = if triple { "}}}" } else { "}}" };
+        let (inner, end) = match source[open + close.len()..].find(close) {
+            Some(i) => {
+                let inner = open + close.len();
+                (&source[inner..inner + i], inner + i + close.len())
+            }
+            None => {
+                pos = open;
+                break;
+            }
+        };
+
+        if inner.trim_start().starts_with('=') {
+            pos = open;
+            break;
+        }
+        match tag(inner) {
+            Some(inner) if triple => {
+                text.push_str("{{{");
+                text.push_str(&inner);
+                text.push_str("}}}");
+            }
+            Some(inner) => {
+                text.push_str("{{");
+                text.push_str(&inner);
+                text.push_str("}}");
+            }
+            None => text.push_str(&source[open..end]),
+        }
+        pos = end;
+    }
+    text.push_str(&source[pos..]);
+
+    match Statement::parse_with(&text, options) {
+        Ok(formatted) if formatted == tree => Ok(text),
+        _ => Ok(source.to_string()),
+    }
+}
+
+/// Formats the text between a tag's delimiters, or returns none if it's
+/// copied unchanged.
+fn tag(inner: &str) -> Option<String> {
+    let trimmed = inner.trim();
+    if trimmed.starts_with('!')
+        || trimmed.starts_with('~')
+        || trimmed.ends_with('~')
+        || trimmed.contains('"')
+    {
+        return None;
+    }
+
+    // A sigil must follow the open delimiter directly; a variable whose
+    // name begins with one, like `{{ $schema }}`, keeps a space before it.
+    let (sigil, rest) = match inner.chars().next() {
+        Some(c) if SIGILS.contains(&c) => (&inner[..1], &inner[1..]),
+        _ if trimmed.starts_with(SIGILS) => (" ", inner),
+        _ => ("", inner),
+    };
+    let words: Vec<_> = rest.split_whitespace().collect();
+    Some(format!("{}{}", sigil, words.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::LoadOptions;
+    use super::format;
+
+    fn formatted(source: &str) -> String {
+        format(source, &LoadOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn removes_whitespace_inside_tags() {
+        assert_eq!(
+            "<p>{{name}} {{{bio}}} {{&html}}</p>",
+            formatted("<p>{{ name }} {{{ bio }}} {{& html }}</p>")
+        );
+        assert_eq!(
+            "  {{#robots}}\n  {{>card title=name}}\n  {{/robots}}\n",
+            formatted("  {{# robots }}\n  {{>  card   title=name }}\n  {{/ robots}}\n")
+        );
+        assert_eq!("{{name | upcase}}", formatted("{{ name  |  upcase }}"));
+
+        let options = LoadOptions {
+            extended_identifiers: true,
+            ..LoadOptions::default()
+        };
+        assert_eq!("{{ $schema}}", format("{{  $schema }}", &options).unwrap());
+    }
+
+    #[test]
+    fn copies_comments_and_trimmed_tags() {
+        let source = "{{! a  note }}\n{{~ name }} {{ title ~}}";
+        assert_eq!(source, formatted(source));
+    }
+
+    #[test]
+    fn stops_at_set_delimiters() {
+        assert_eq!(
+            "{{a}}{{=<% %>=}}<% b %>{{ c }}",
+            formatted("{{ a }}{{=<% %>=}}<% b %>{{ c }}")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(format("{{#a}}", &LoadOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Front matter: a YAML block at the top of a template file, between two
+//! `---` lines, declaring metadata about the template rather than content
+//! to render.
+//!
+//! ```text
+//! ---
+//! layout: application
+//! required: [title, user]
+//! ---
+//! <h1>{{title}}</h1>
+//! ```
+
+use serde_json::{Map, Number, Value};
+use yaml_rust::{Yaml, YamlLoader};
+
+/// The metadata declared in a template's front matter.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrontMatter {
+    /// Each top-level field's value, converted to JSON.
+    pub fields: Map<String, Value>,
+}
+
+impl FrontMatter {
+    /// Parses the YAML text between the `---` lines, which must be a mapping
+    /// of fields or empty.
+    pub fn parse(text: &str) -> Result<FrontMatter, String> {
+        let documents = YamlLoader::load_from_str(text).map_err(|e| e.to_string())?;
+        match documents.first() {
+            None | Some(&Yaml::Null) => Ok(FrontMatter::default()),
+            Some(Yaml::Hash(hash)) => Ok(FrontMatter {
+                fields: hash
+                    .iter()
+                    .map(|(key, value)| (text_of(key), json(value)))
+                    .collect(),
+            }),
+            Some(_) => Err(String::from("Front matter must be a mapping of fields")),
+        }
+    }
+
+    /// Returns the name of the layout template wrapping this template's
+    /// output, declared by the `layout` field.
+    pub fn layout(&self) -> Option<&str> {
+        self.fields.get("layout").and_then(Value::as_str)
+    }
+
+    /// Returns the context variables the template requires, declared as a
+    /// list of strings by the `required` field.
+    pub fn required(&self) -> Vec<&str> {
+        match self.fields.get("required") {
+            Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Finds the front matter at the start of a template's source, returning its
+/// YAML text and the byte offset where the template's content begins. The
+/// block must open on the first line.
+pub fn split(source: &str) -> Option<(&str, usize)> {
+    let start = if source.starts_with("---\n") {
+        4
+    } else if source.starts_with("---\r\n") {
+        5
+    } else {
+        return None;
+    };
+
+    let mut offset = start;
+    for line in source[start..].split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']) == "---" {
+            return Some((&source[start..offset], offset + line.len()));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Parses a YAML document into JSON. An empty document is `null`.
+#[cfg(feature = "render")]
+pub fn yaml(text: &str) -> Result<Value, String> {
+    let documents = YamlLoader::load_from_str(text).map_err(|e| e.to_string())?;
+    Ok(documents.first().map_or(Value::Null, json))
+}
+
+/// Converts a YAML value into JSON. Aliases and invalid values are `null`.
+fn json(yaml: &Yaml) -> Value {
+    match *yaml {
+        Yaml::Real(ref text) => text
+            .parse()
+            .ok()
+            .and_then(Number::from_f64)
+            .map_or(Value::Null, Value::Number),
+        Yaml::Integer(number) => Value::from(number),
+        Yaml::String(ref text) => Value::from(text.as_str()),
+        Yaml::Boolean(value) => Value::Bool(value),
+        Yaml::Array(ref items) => Value::Array(items.iter().map(json).collect()),
+        Yaml::Hash(ref hash) => Value::Object(
+            hash.iter()
+                .map(|(key, value)| (text_of(key), json(value)))
+                .collect(),
+        ),
+        Yaml::Alias(_) | Yaml::Null | Yaml::BadValue => Value::Null,
+    }
+}
+
+/// Formats a mapping's key as a JSON object key.
+fn text_of(key: &Yaml) -> String {
+    match *key {
+        Yaml::Real(ref text) | Yaml::String(ref text) => text.clone(),
+        Yaml::Integer(number) => number.to_string(),
+        Yaml::Boolean(value) => value.to_string(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split, FrontMatter};
+
+    #[test]
+    fn splits_front_matter() {
+        let source = "---\r\nlayout: app\n---\n<h1>{{title}}</h1>";
+        assert_eq!(Some(("layout: app\n", 21)), split(source));
+        assert_eq!(Some(("", 8)), split("---\n---\n"));
+        assert_eq!(None, split("---\nlayout: app\n"));
+        assert_eq!(None, split("\n---\n---\n"));
+    }
+
+    #[test]
+    fn parses_fields() {
+        let matter =
+            FrontMatter::parse("layout: app\nrequired: [title, user]\nwidth: 2.5").unwrap();
+        assert_eq!(Some("app"), matter.layout());
+        assert_eq!(vec!["title", "user"], matter.required());
+        assert_eq!(Some(2.5), matter.fields["width"].as_f64());
+
+        assert_eq!(FrontMatter::default(), FrontMatter::parse("").unwrap());
+        assert!(FrontMatter::parse("- title").is_err());
+        assert!(FrontMatter::parse("layout: [app").is_err());
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use serde_json::{Map, Value};
+
+use super::Template;
+
+/// The partial reference graph of a template set, mapping each template name
+/// to the names of the partials it renders.
+#[derive(Debug, Default)]
+pub struct Graph {
+    edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Graph {
+    /// Builds the graph from each template's partial references.
+    pub fn build(templates: &[Template]) -> Self {
+        let edges = templates
+            .iter()
+            .map(|template| {
+                let partials = template
+                    .tree
+                    .partials()
+                    .into_iter()
+                    .map(String::from)
+                    .collect();
+                (template.name.clone(), partials)
+            })
+            .collect();
+
+        Graph { edges }
+    }
+
+    /// Iterates over each template, sorted by name, with the partials it
+    /// renders directly.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &BTreeSet<String>)> {
+        self.edges.iter()
+    }
+
+    /// Returns the partials rendered directly by the template.
+    pub fn partials(&self, name: &str) -> Vec<&String> {
+        self.edges
+            .get(name)
+            .map(|partials| partials.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns every template that includes the partial, either directly or
+    /// through another partial, sorted by name.
+    ///
+    /// Editing a shared partial changes the output of each of these
+    /// templates.
+    pub fn dependents(&self, name: &str) -> Vec<&String> {
+        let mut found = BTreeSet::new();
+        let mut pending = vec![name];
+
+        while let Some(partial) = pending.pop() {
+            for (template, partials) in &self.edges {
+                if partials.contains(partial) && found.insert(template) {
+                    pending.push(template);
+                }
+            }
+        }
+
+        found.into_iter().collect()
+    }
+
+    /// Returns the named templates and every template they render as a
+    /// partial, either directly or through another partial, sorted by name.
+    /// Names without a template of their own are left out.
+    pub fn closure(&self, roots: &[String]) -> Vec<&String> {
+        let mut found = BTreeSet::new();
+        let mut pending: Vec<_> = roots.iter().map(String::as_str).collect();
+
+        while let Some(name) = pending.pop() {
+            if let Some((template, partials)) = self.edges.get_key_value(name) {
+                if found.insert(template) {
+                    pending.extend(partials.iter().map(String::as_str));
+                }
+            }
+        }
+
+        found.into_iter().collect()
+    }
+
+    /// Returns each partial referenced by a template without a template of
+    /// its own, sorted by name.
+    pub fn unresolved(&self) -> Vec<&String> {
+        let partials: BTreeSet<_> = self.edges.values().flatten().collect();
+        partials
+            .into_iter()
+            .filter(|partial| !self.edges.contains_key(*partial))
+            .collect()
+    }
+
+    /// Writes the graph in Graphviz DOT format. Every template is a node,
+    /// including those no template renders, and unresolved partials are
+    /// drawn dashed.
+    pub fn dot(&self) -> String {
+        let mut text = String::from("digraph templates {\n");
+        for name in self.edges.keys() {
+            let _ = writeln!(text, "    {:?};", name);
+        }
+        for name in self.unresolved() {
+            let _ = writeln!(text, "    {:?} [style=dashed];", name);
+        }
+        for (template, partials) in &self.edges {
+            for partial in partials {
+                let _ = writeln!(text, "    {:?} -> {:?};", template, partial);
+            }
+        }
+        text.push_str("}\n");
+        text
+    }
+
+    /// Converts the graph to JSON, with a node for each template and
+    /// unresolved partial and an edge for each partial reference.
+    pub fn json(&self) -> Value {
+        let templates = self.edges.keys().map(|name| (name, false));
+        let unresolved = self.unresolved().into_iter().map(|name| (name, true));
+        let nodes = templates
+            .chain(unresolved)
+            .map(|(name, unresolved)| {
+                let mut map = Map::new();
+                map.insert(String::from("name"), Value::from(name.as_str()));
+                map.insert(String::from("unresolved"), Value::from(unresolved));
+                Value::Object(map)
+            })
+            .collect();
+
+        let mut edges = Vec::new();
+        for (template, partials) in &self.edges {
+            for partial in partials {
+                let mut map = Map::new();
+                map.insert(String::from("from"), Value::from(template.as_str()));
+                map.insert(String::from("to"), Value::from(partial.as_str()));
+                edges.push(Value::Object(map));
+            }
+        }
+
+        let mut map = Map::new();
+        map.insert(String::from("nodes"), Value::Array(nodes));
+        map.insert(String::from("edges"), Value::Array(edges));
+        Value::Object(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Template;
+    use super::Graph;
+
+    #[test]
+    fn finds_transitive_dependents() {
+        let templates = vec![
+            Template::from_source("page", "{{> layout}}").unwrap(),
+            Template::from_source(
+                "layout",
+                "{{> include/header}}{{#a}}{{> include/footer}}{{/a}}",
+            )
+            .unwrap(),
+            Template::from_source("include/header", "<h1>{{ title }}</h1>").unwrap(),
+            Template::from_source("include/footer", "<footer>").unwrap(),
+            Template::from_source("email", "{{> include/header}}").unwrap(),
+            Template::from_source("loop", "{{> loop}}{{> include/header}}").unwrap(),
+        ];
+        let graph = Graph::build(&templates);
+
+        assert_eq!(
+            vec!["email", "layout", "loop", "page"],
+            graph.dependents("include/header")
+        );
+        assert_eq!(vec!["layout", "page"], graph.dependents("include/footer"));
+        assert_eq!(vec!["loop"], graph.dependents("loop"));
+        assert!(graph.dependents("page").is_empty());
+    }
+
+    #[test]
+    fn finds_transitive_partials() {
+        let templates = vec![
+            Template::from_source("page", "{{> layout}}").unwrap(),
+            Template::from_source("layout", "{{> include/header}}{{> missing}}").unwrap(),
+            Template::from_source("include/header", "<h1>{{ title }}</h1>").unwrap(),
+            Template::from_source("admin", "{{> include/header}}").unwrap(),
+            Template::from_source("loop", "{{> loop}}").unwrap(),
+        ];
+        let graph = Graph::build(&templates);
+
+        let roots = vec![String::from("page"), String::from("loop")];
+        assert_eq!(
+            vec!["include/header", "layout", "loop", "page"],
+            graph.closure(&roots)
+        );
+        assert!(graph.closure(&[String::from("missing")]).is_empty());
+    }
+
+    #[test]
+    fn lists_direct_partials() {
+        let templates = vec![Template::from_source("layout", "{{> b}}{{> a}}{{> b}}").unwrap()];
+        let graph = Graph::build(&templates);
+        assert_eq!(vec!["a", "b"], graph.partials("layout"));
+        assert!(graph.partials("missing").is_empty());
+    }
+
+    #[test]
+    fn writes_dot_and_json_with_unresolved_partials() {
+        let templates = vec![
+            Template::from_source("page", "{{> layout}}{{> missing}}").unwrap(),
+            Template::from_source("layout", "").unwrap(),
+            Template::from_source("unused", "").unwrap(),
+        ];
+        let graph = Graph::build(&templates);
+        assert_eq!(vec!["missing"], graph.unresolved());
+
+        let dot = "digraph templates {
+    \"layout\";
+    \"page\";
+    \"unused\";
+    \"missing\" [style=dashed];
+    \"page\" -> \"layout\";
+    \"page\" -> \"missing\";
+}
+";
+        assert_eq!(dot, graph.dot());
+
+        let json = graph.json();
+        assert_eq!(4, json["nodes"].as_array().unwrap().len());
+        assert_eq!("missing", json["nodes"][3]["name"]);
+        assert_eq!(true, json["nodes"][3]["unresolved"]);
+        assert_eq!(false, json["nodes"][0]["unresolved"]);
+        assert_eq!("page", json["edges"][1]["from"]);
+        assert_eq!("missing", json["edges"][1]["to"]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Tests templates against fixture files, independent of any backend's host
+//! language.
+//!
+//! Each context file in the tests directory names the template it renders,
+//! and sits next to the output the template is expected to render:
+//!
+//! ```text
+//! templates/robots.mustache
+//! tests/robots.json
+//! tests/robots.expected
+//! ```
+//!
+//! Context files are JSON, or YAML when named `.yml` or `.yaml`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use super::render::{self, Renderer};
+use super::Error;
+
+/// A template rendered with a context file and compared to the output it's
+/// expected to render.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Case {
+    /// The name of the template to render, like `include/header`.
+    pub name: String,
+    /// The JSON or YAML file holding the render context.
+    pub context: PathBuf,
+    /// The file holding the expected output.
+    pub expected: PathBuf,
+}
+
+/// The result of running a test case.
+#[derive(Debug)]
+pub enum Outcome {
+    /// The template rendered the expected output.
+    Pass,
+    /// The template rendered other output than expected.
+    Fail { expected: String, actual: String },
+    /// The expected output file doesn't exist yet.
+    Missing { actual: String },
+    /// The context couldn't be read or the template couldn't be rendered.
+    Error(String),
+}
+
+impl Case {
+    /// Finds each context file in the tests directory tree, sorted by
+    /// template name.
+    pub fn discover(dir: &Path) -> Result<Vec<Case>, Error> {
+        let mut cases = Vec::new();
+        discover(dir, dir, &mut cases)?;
+        cases.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(cases)
+    }
+
+    /// Renders the case's template with its context, returning the output.
+    pub fn render(&self, renderer: &Renderer) -> Result<String, String> {
+        let text = fs::read_to_string(&self.context)
+            .map_err(|e| format!("{}: {}", self.context.display(), e))?;
+        let context = context(&self.context, &text)
+            .map_err(|e| format!("{}: {}", self.context.display(), e))?;
+        renderer
+            .render(&self.name, &context)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Renders the case's template and compares it to the expected output.
+    pub fn run(&self, renderer: &Renderer) -> Outcome {
+        let actual = match self.render(renderer) {
+            Ok(actual) => actual,
+            Err(e) => return Outcome::Error(e),
+        };
+        match fs::read_to_string(&self.expected) {
+            Ok(ref expected) if *expected == actual => Outcome::Pass,
+            Ok(expected) => Outcome::Fail { expected, actual },
+            Err(_) if !self.expected.exists() => Outcome::Missing { actual },
+            Err(e) => Outcome::Error(format!("{}: {}", self.expected.display(), e)),
+        }
+    }
+}
+
+fn discover(base: &Path, dir: &Path, cases: &mut Vec<Case>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).map_err(|e| Error::io(dir, e))? {
+        let path = entry.map_err(|e| Error::io(dir, e))?.path();
+        if path.is_dir() {
+            discover(base, &path, cases)?;
+            continue;
+        }
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") | Some("yml") | Some("yaml") => (),
+            _ => continue,
+        }
+
+        let relative = path.strip_prefix(base).unwrap_or(&path).with_extension("");
+        let name = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        cases.push(Case {
+            name,
+            expected: path.with_extension("expected"),
+            context: path,
+        });
+    }
+    Ok(())
+}
+
+/// Parses a context file's text as YAML or JSON, by its extension.
+fn context(path: &Path, text: &str) -> Result<Value, String> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yml") | Some("yaml") => render::yaml(text),
+        _ => serde_json::from_str(text).map_err(|e| e.to_string()),
+    }
+}
+
+/// Compares the expected and actual output line by line, returning the
+/// lines of each as ` ` when shared, `-` when only expected, and `+` when
+/// only rendered.
+pub fn diff(expected: &str, actual: &str) -> Vec<String> {
+    let old: Vec<_> = expected.lines().collect();
+    let new: Vec<_> = actual.lines().collect();
+
+    // The length of the longest common subsequence of each pair of suffixes.
+    let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!(" {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            lines.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Case, Outcome};
+    use render::Renderer;
+    use std::fs;
+    use tempdir::TempDir;
+    use Template;
+
+    #[test]
+    fn runs_fixture_cases() {
+        let dir = TempDir::new("stache-harness").unwrap();
+        let tests = dir.path();
+        fs::create_dir_all(tests.join("include")).unwrap();
+        fs::write(tests.join("robot.json"), r#"{"name": "hubot"}"#).unwrap();
+        fs::write(tests.join("robot.expected"), "<p>hubot</p>").unwrap();
+        fs::write(tests.join("include/header.yml"), "title: Robots\n").unwrap();
+        fs::write(tests.join("include/header.expected"), "<h1>Bots</h1>").unwrap();
+        fs::write(tests.join("page.json"), "{}").unwrap();
+        fs::write(tests.join("notes.txt"), "").unwrap();
+
+        let cases = Case::discover(tests).unwrap();
+        let names: Vec<_> = cases.iter().map(|case| case.name.as_str()).collect();
+        assert_eq!(vec!["include/header", "page", "robot"], names);
+
+        let templates = vec![
+            Template::from_source("robot", "<p>{{name}}</p>").unwrap(),
+            Template::from_source("include/header", "<h1>{{title}}</h1>").unwrap(),
+            Template::from_source("page", "page").unwrap(),
+        ];
+        let renderer = Renderer::new(&templates).unwrap();
+        match cases[0].run(&renderer) {
+            Outcome::Fail { expected, actual } => {
+                assert_eq!("<h1>Bots</h1>", expected);
+                assert_eq!("<h1>Robots</h1>", actual);
+            }
+            other => panic!("Expected a failure: {:?}", other),
+        }
+        match cases[1].run(&renderer) {
+            Outcome::Missing { actual } => assert_eq!("page", actual),
+            other => panic!("Expected missing output: {:?}", other),
+        }
+        assert!(matches!(cases[2].run(&renderer), Outcome::Pass));
+    }
+
+    #[test]
+    fn diffs_lines() {
+        assert_eq!(
+            vec![" a", "-b", "+B", " c", "+d"],
+            diff("a\nb\nc\n", "a\nB\nc\nd\n")
+        );
+        assert!(diff("", "").is_empty());
+    }
+}
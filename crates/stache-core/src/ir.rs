@@ -0,0 +1,328 @@
+//! The intermediate representation printed by code generators.
+//!
+//! Templates are lowered once into a flat list of operations per template:
+//! static content chunks, context lookups, section calls with nested
+//! operation lists, and partial calls by name or by context lookup.
+//! Comments are dropped and adjacent content is combined, so a backend only
+//! decides how to print each operation in its target language.
+
+use std::slice;
+
+use super::{Error, Path, Pragma, Statement, Template};
+
+/// A linked template set, with one exported render function per template.
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    pub functions: Vec<Function>,
+}
+
+/// A template's render function, exported under the template's name.
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub body: Vec<Op>,
+}
+
+/// One step of rendering a template into the output buffer.
+#[derive(Debug, PartialEq)]
+pub enum Op {
+    /// Appends static text.
+    Content(String),
+    /// Looks up the path in the context stack and appends its value, HTML
+    /// escaped or raw.
+    Append(Path, bool),
+    /// Appends the path's value like `Append`, but fails the render when
+    /// the value is missing, for templates with the `STRICT` pragma.
+    Require(Path, bool),
+    /// Looks up the path in the context stack, passes its value through the
+    /// named filters in order, and appends the result HTML escaped.
+    Filtered(Path, Vec<String>),
+    /// Appends the runtime translation hook's text for the key, HTML
+    /// escaped.
+    Translate(String),
+    /// Renders the body once for each item the path resolves to, with the
+    /// item pushed onto the context stack. The section's raw source text is
+    /// kept for runtimes passing it to lambdas.
+    Section(Path, Vec<Op>, String),
+    /// Renders the body once if the path resolves to a false, missing, or
+    /// empty value.
+    Inverted(Path, Vec<Op>),
+    /// Renders the named template with the current context stack, and a
+    /// frame holding each argument path's value under its key when the
+    /// partial has arguments. A standalone partial's output is indented by
+    /// its padding.
+    Partial(String, Option<String>, Vec<(String, Path)>),
+    /// Looks up the path in the context stack and renders the template it
+    /// names, or nothing when no template has that name, indented like a
+    /// partial.
+    DynamicPartial(Path, Option<String>),
+}
+
+/// Validates the template set and lowers each template into its render
+/// function.
+pub fn lower(templates: &[Template]) -> Result<Program, Error> {
+    let _span = debug_span!("lower", templates = templates.len()).entered();
+    Template::validate(templates)?;
+
+    let functions = templates
+        .iter()
+        .map(|template| {
+            Ok(Function {
+                name: template.name.clone(),
+                body: lower_tree(&template.inherit(templates)?.tree),
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(Program { functions })
+}
+
+/// Ensures no template passes a variable through filters, for backends
+/// whose runtimes have no helpers to call.
+pub fn unfiltered(templates: &[Template], backend: &str) -> Result<(), Error> {
+    let filtered = templates.iter().find(|t| !t.tree.filters().is_empty());
+    match filtered {
+        Some(template) => Err(Error::Backend(format!(
+            "Template `{}` uses filters, which are not supported by the {} target",
+            template.name, backend
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Ensures no template uses translation tags, for backends whose runtimes
+/// have no translation hook to call.
+pub fn untranslated(templates: &[Template], backend: &str) -> Result<(), Error> {
+    let translated = templates.iter().find(|t| !t.tree.translations().is_empty());
+    match translated {
+        Some(template) => Err(Error::Backend(format!(
+            "Template `{}` uses translations, which are not supported by the {} target",
+            template.name, backend
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Ensures no template reads a list item's loop metadata, like `@index`, for
+/// backends whose runtimes iterate lists without recording positions.
+pub fn unindexed(templates: &[Template], backend: &str) -> Result<(), Error> {
+    let indexed = templates.iter().find(|t| t.tree.has_metadata());
+    match indexed {
+        Some(template) => Err(Error::Backend(format!(
+            "Template `{}` uses loop metadata, which is not supported by the {} target",
+            template.name, backend
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Ensures no template enables the `STRICT` pragma, for backends whose
+/// render functions can't fail.
+pub fn lenient(templates: &[Template], backend: &str) -> Result<(), Error> {
+    let strict = templates.iter().find(|t| t.tree.has_pragma(Pragma::Strict));
+    match strict {
+        Some(template) => Err(Error::Backend(format!(
+            "Template `{}` uses the STRICT pragma, which is not supported by the {} target",
+            template.name, backend
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Determines if the operations include a standalone partial, whose output
+/// is indented by its padding.
+pub fn indented(ops: &[Op]) -> bool {
+    ops.iter().any(|op| match *op {
+        Op::Section(_, ref body, _) | Op::Inverted(_, ref body) => indented(body),
+        Op::Partial(_, ref padding, _) | Op::DynamicPartial(_, ref padding) => padding.is_some(),
+        _ => false,
+    })
+}
+
+/// Lowers a template's syntax tree into the operations rendering it. Blocks
+/// render their default contents and parent tags render the parent without
+/// overrides, unless the tree was first resolved with `Template::inherit`.
+///
+/// The tree is walked with an explicit stack of the operation lists being
+/// collected, so deeply nested sections can't overflow the call stack.
+pub fn lower_tree(node: &Statement) -> Vec<Op> {
+    let pragmas = node.pragmas();
+    let mut ops = Vec::new();
+    let mut stack = match enter(node, &pragmas, &mut ops) {
+        Some(frame) => vec![frame],
+        None => return ops,
+    };
+
+    while let Some(frame) = stack.last_mut() {
+        match frame.statements.next() {
+            Some(stmt) => {
+                if let Some(child) = enter(stmt, &pragmas, &mut frame.ops) {
+                    stack.push(child);
+                }
+            }
+            None => {
+                let frame = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => leave(frame, &mut parent.ops),
+                    None => leave(frame, &mut ops),
+                }
+            }
+        }
+    }
+
+    ops
+}
+
+/// A statement whose children are being lowered into a list of operations.
+struct Frame<'a> {
+    node: &'a Statement,
+    statements: slice::Iter<'a, Statement>,
+    ops: Vec<Op>,
+}
+
+/// Appends the operation rendering a statement without children, with the
+/// template's pragmas applied, or returns a frame to collect the operations
+/// of one that has them.
+fn enter<'a>(node: &'a Statement, pragmas: &[Pragma], ops: &mut Vec<Op>) -> Option<Frame<'a>> {
+    match *node {
+        Statement::Program(ref block)
+        | Statement::Section(_, ref block)
+        | Statement::Inverted(_, ref block)
+        | Statement::Block(_, ref block) => {
+            return Some(Frame {
+                node,
+                statements: block.statements.iter(),
+                ops: Vec::new(),
+            });
+        }
+        Statement::Partial(ref name, ref padding, ref arguments) => ops.push(Op::Partial(
+            name.clone(),
+            padding.clone(),
+            arguments.clone(),
+        )),
+        Statement::Parent(ref name, _) => ops.push(Op::Partial(name.clone(), None, Vec::new())),
+        Statement::DynamicPartial(ref path, ref padding) => {
+            ops.push(Op::DynamicPartial(path.clone(), padding.clone()))
+        }
+        Statement::Comment(_) | Statement::Pragma(_) => (),
+        Statement::Translation(ref key) => ops.push(Op::Translate(key.clone())),
+        Statement::Content(ref text) => push(ops, Op::Content(text.clone())),
+        Statement::Variable(ref path) | Statement::Html(ref path) => {
+            let escape =
+                matches!(*node, Statement::Variable(_)) && !pragmas.contains(&Pragma::Unescaped);
+            if pragmas.contains(&Pragma::Strict) {
+                ops.push(Op::Require(path.clone(), escape));
+            } else {
+                ops.push(Op::Append(path.clone(), escape));
+            }
+        }
+        Statement::Filtered(ref path, ref filters) => {
+            ops.push(Op::Filtered(path.clone(), filters.clone()))
+        }
+    }
+
+    None
+}
+
+/// Finishes a statement once its children are lowered: a section wraps them
+/// in its operation, while the operations of a block or the template itself
+/// are appended in place.
+fn leave(frame: Frame, ops: &mut Vec<Op>) {
+    match *frame.node {
+        Statement::Section(ref path, ref block) => {
+            ops.push(Op::Section(path.clone(), frame.ops, block.source.clone()))
+        }
+        Statement::Inverted(ref path, _) => ops.push(Op::Inverted(path.clone(), frame.ops)),
+        Statement::Program(_) | Statement::Block(..) => {
+            for op in frame.ops {
+                push(ops, op);
+            }
+        }
+        _ => unreachable!("only statements with children have frames"),
+    }
+}
+
+/// Appends the operation, combining content with content before it.
+fn push(ops: &mut Vec<Op>, op: Op) {
+    if let Op::Content(ref text) = op {
+        if let Some(&mut Op::Content(ref mut previous)) = ops.last_mut() {
+            previous.push_str(text);
+            return;
+        }
+    }
+    ops.push(op);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Path, Statement};
+    use super::{lower_tree, Op};
+
+    fn path(keys: &[&str]) -> Path {
+        Path::new(keys.iter().map(|key| key.to_string()).collect())
+    }
+
+    #[test]
+    fn lowers_tree_into_operations() {
+        let tree = Statement::parse(
+            "<p>{{#robots}}{{name.first}}{{> robot}}{{>*kind}}{{/robots}}{{^robots}}None{{/robots}}{{{bio}}}</p>",
+        )
+        .unwrap();
+
+        let expected = vec![
+            Op::Content(String::from("<p>")),
+            Op::Section(
+                path(&["robots"]),
+                vec![
+                    Op::Append(path(&["name", "first"]), true),
+                    Op::Partial(String::from("robot"), None, Vec::new()),
+                    Op::DynamicPartial(path(&["kind"]), None),
+                ],
+                String::from("{{name.first}}{{> robot}}{{>*kind}}"),
+            ),
+            Op::Inverted(path(&["robots"]), vec![Op::Content(String::from("None"))]),
+            Op::Append(path(&["bio"]), false),
+            Op::Content(String::from("</p>")),
+        ];
+        assert_eq!(expected, lower_tree(&tree));
+    }
+
+    #[test]
+    fn lowers_filtered_variables() {
+        let tree = Statement::parse("{{ name | upcase }}").unwrap();
+        assert_eq!(
+            vec![Op::Filtered(path(&["name"]), vec![String::from("upcase")])],
+            lower_tree(&tree)
+        );
+    }
+
+    #[test]
+    fn lowers_variables_with_pragmas() {
+        let tree = Statement::parse("{{%UNESCAPED}}{{a}}{{{b}}}").unwrap();
+        assert_eq!(
+            vec![
+                Op::Append(path(&["a"]), false),
+                Op::Append(path(&["b"]), false)
+            ],
+            lower_tree(&tree)
+        );
+
+        let tree = Statement::parse("{{a}}{{&b}}{{%STRICT}}").unwrap();
+        assert_eq!(
+            vec![
+                Op::Require(path(&["a"]), true),
+                Op::Require(path(&["b"]), false)
+            ],
+            lower_tree(&tree)
+        );
+    }
+
+    #[test]
+    fn combines_content_around_comments() {
+        let tree = Statement::parse("<b>{{! note }}</b>").unwrap();
+        assert_eq!(
+            vec![Op::Content(String::from("<b></b>"))],
+            lower_tree(&tree)
+        );
+    }
+}
@@ -0,0 +1,1545 @@
+extern crate ignore;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+extern crate serde_json;
+#[macro_use]
+extern crate tracing;
+#[cfg(test)]
+extern crate tempdir;
+extern crate yaml_rust;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use parser::Parser;
+
+pub use error::{Diagnostic, Error, ParseError};
+pub use front_matter::FrontMatter;
+pub use name::Name;
+pub use parser::Dialect;
+pub use path::Path;
+pub use pragma::Pragma;
+pub use shape::Shape;
+pub use span::Span;
+pub use template::{LoadOptions, Symlinks, Template};
+pub use visit::Visitor;
+
+pub mod ast;
+pub mod codes;
+mod delimiters;
+#[cfg(feature = "docs")]
+pub mod docs;
+mod error;
+pub mod explain;
+pub mod fixtures;
+pub mod format;
+mod front_matter;
+pub mod graph;
+#[cfg(feature = "render")]
+pub mod harness;
+pub mod ir;
+pub mod migrate;
+mod minify;
+mod name;
+mod parser;
+mod path;
+mod pragma;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "schema")]
+pub mod schema;
+mod shape;
+mod span;
+mod template;
+#[cfg(feature = "typescript")]
+pub mod typescript;
+pub mod usage;
+pub mod visit;
+pub mod warning;
+
+/// Defines the source code output behavior for compiler backends. The main
+/// compiler driver treats the result of each backend identically.
+pub trait Compile {
+    /// Writes the final translated source code to an output buffer.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()>;
+
+    /// Saves any supporting files the translated source code includes, like
+    /// a C header or shared runtime, into the output file's directory. Most
+    /// programs are a single file and write nothing.
+    fn write_support(&self, _dir: &std::path::Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Saves the translated source code to a file. The buffered output is
+    /// flushed before returning, so a failed write, like a full disk or a
+    /// file locked by another process on Windows, is reported rather than
+    /// lost when the buffer is dropped.
+    fn write<P>(&self, output: P) -> io::Result<()>
+    where
+        P: AsRef<std::path::Path>,
+        Self: Sized,
+    {
+        File::create(output)
+            .map(|file| BufWriter::new(file))
+            .and_then(|mut buf| self.emit(&mut buf).and_then(|_| buf.flush()))
+    }
+}
+
+impl<T: Compile + ?Sized> Compile for Box<T> {
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        (**self).emit(buf)
+    }
+
+    fn write_support(&self, dir: &std::path::Path) -> io::Result<()> {
+        (**self).write_support(dir)
+    }
+}
+
+/// Translates parsed templates into a program in some target language. Each
+/// of the compiler's output targets is a backend, and crates providing their
+/// own code generators implement it to be driven like the built-in targets.
+pub trait Backend {
+    /// Returns the name selecting this backend, like `ruby` in `--emit=ruby`.
+    fn name(&self) -> &str;
+
+    /// Validates the template set and translates it into a program ready to
+    /// be written to the output file.
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error>;
+}
+
+/// A sequence of statements, like a template's body or the contents of a
+/// section.
+///
+/// A parsed block keeps its raw source text, which a section's lambda
+/// receives in place of the rendered contents, along with the span of that
+/// text and of each statement, at the same index in `spans`, in the template
+/// source. Statements inlined from a parent template keep their spans in the
+/// parent's source. Blocks compare equal by their statements alone.
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub statements: Vec<Statement>,
+    pub spans: Vec<Span>,
+    pub span: Span,
+    pub source: String,
+}
+
+impl PartialEq for Block {
+    fn eq(&self, other: &Block) -> bool {
+        self.statements == other.statements
+    }
+}
+
+impl Block {
+    fn new(statements: Vec<Statement>) -> Self {
+        Block {
+            spans: vec![Span::default(); statements.len()],
+            statements: statements,
+            span: Span::default(),
+            source: String::new(),
+        }
+    }
+
+    /// Creates a block of parsed statements and their spans.
+    fn spanned(statements: Vec<(Statement, Span)>) -> Self {
+        let (statements, spans) = statements.into_iter().unzip();
+        Block {
+            statements,
+            spans,
+            span: Span::default(),
+            source: String::new(),
+        }
+    }
+
+    fn empty() -> Self {
+        Self::new(vec![])
+    }
+
+    /// Copies the block's source text and span, without its statements.
+    fn located(&self) -> Self {
+        Block {
+            source: self.source.clone(),
+            span: self.span,
+            ..Block::empty()
+        }
+    }
+
+    /// Adds the statement as the first element in the block, combining it
+    /// with a previous content statement if possible.
+    fn prepend(&mut self, mut statement: Statement, span: Span) {
+        let merged = match self.statements.get_mut(0) {
+            Some(first) => {
+                if statement.merge(first) {
+                    *first = statement;
+                    self.spans[0] = span.join(self.spans[0]);
+                    return;
+                }
+                statement
+            }
+            None => statement,
+        };
+
+        self.statements.insert(0, merged);
+        self.spans.insert(0, span);
+    }
+
+    /// Adds the statement as the final element in the block, combining it with
+    /// a previous content statement if possible.
+    fn append(&mut self, statement: Statement, span: Span) {
+        if let Some(last) = self.statements.last_mut() {
+            if last.merge(&statement) {
+                let index = self.spans.len() - 1;
+                self.spans[index] = self.spans[index].join(span);
+                return;
+            }
+        }
+        self.statements.push(statement);
+        self.spans.push(span);
+    }
+
+    /// Copies the block, resolving its parent tags and blocks.
+    fn inherit<'a>(
+        &'a self,
+        templates: &'a [Template],
+        overrides: &HashMap<&'a str, &'a Block>,
+        parents: &mut Vec<&'a str>,
+    ) -> Result<Self, String> {
+        let mut block = self.located();
+        for (stmt, &span) in self.statements.iter().zip(&self.spans) {
+            stmt.inherit(templates, overrides, parents, span, &mut block)?;
+        }
+        Ok(block)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    Program(Block),
+    Section(Path, Block),
+    Inverted(Path, Block),
+    Variable(Path),
+    /// A variable whose value is passed through the named filters, in order,
+    /// before it's escaped.
+    Filtered(Path, Vec<String>),
+    Html(Path),
+    /// A translation key, rendered as the text the runtime's translation
+    /// hook returns for it.
+    Translation(String),
+    /// A partial rendered with its standalone indentation, if any, and with
+    /// its arguments' values pushed onto the context stack by key.
+    Partial(String, Option<String>, Vec<(String, Path)>),
+    DynamicPartial(Path, Option<String>),
+    Content(String),
+    Comment(String),
+    /// A pragma tag's setting, applying to the whole template.
+    Pragma(Pragma),
+    Parent(String, Block),
+    Block(String, Block),
+}
+
+impl Statement {
+    /// Parses the Mustache text into a Statement AST.
+    pub fn parse(template: &str) -> Result<Statement, ParseError> {
+        Parser::new(template).program()
+    }
+
+    /// Parses the text in the options' dialect, failing if its sections are
+    /// nested more deeply than the options allow. The content's whitespace
+    /// is collapsed if the options minify it.
+    pub fn parse_with(template: &str, options: &LoadOptions) -> Result<Statement, ParseError> {
+        let mut tree = Parser::with(template, options).program()?;
+        if options.minify {
+            minify::minify(&mut tree);
+        }
+        Ok(tree)
+    }
+
+    /// Visits each node in the tree collecting the names of partials
+    /// referenced by the template. Dynamic partials are named by the context
+    /// at render time, so they reference no template here.
+    pub fn partials(&self) -> Vec<&str> {
+        struct Partials<'a>(Vec<&'a str>);
+
+        impl<'a> Visitor<'a> for Partials<'a> {
+            fn visit_partial(
+                &mut self,
+                name: &'a str,
+                _: Option<&'a str>,
+                _: &'a [(String, Path)],
+            ) {
+                self.0.push(name);
+            }
+
+            fn visit_parent(&mut self, name: &'a str, _: &'a Block) -> bool {
+                self.0.push(name);
+                true
+            }
+        }
+
+        let mut partials = Partials(Vec::new());
+        self.walk(&mut partials);
+        partials.0
+    }
+
+    /// Returns the names of the filters the tree's variables are passed
+    /// through, in the order they're found.
+    pub fn filters(&self) -> Vec<&String> {
+        struct Filters<'a>(Vec<&'a String>);
+
+        impl<'a> Visitor<'a> for Filters<'a> {
+            fn visit_filtered(&mut self, _: &'a Path, filters: &'a [String]) {
+                self.0.extend(filters);
+            }
+        }
+
+        let mut filters = Filters(Vec::new());
+        self.walk(&mut filters);
+        filters.0
+    }
+
+    /// Returns the paths of the tree's variable tags, escaped, unescaped,
+    /// or filtered, in the order they're found.
+    pub fn variables(&self) -> Vec<&Path> {
+        struct Variables<'a>(Vec<&'a Path>);
+
+        impl<'a> Visitor<'a> for Variables<'a> {
+            fn visit_variable(&mut self, path: &'a Path) {
+                self.0.push(path);
+            }
+
+            fn visit_filtered(&mut self, path: &'a Path, _: &'a [String]) {
+                self.0.push(path);
+            }
+
+            fn visit_html(&mut self, path: &'a Path) {
+                self.0.push(path);
+            }
+        }
+
+        let mut variables = Variables(Vec::new());
+        self.walk(&mut variables);
+        variables.0
+    }
+
+    /// Returns the keys of the tree's translation tags, in the order they're
+    /// found.
+    pub fn translations(&self) -> Vec<&str> {
+        struct Translations<'a>(Vec<&'a str>);
+
+        impl<'a> Visitor<'a> for Translations<'a> {
+            fn visit_translation(&mut self, key: &'a str) {
+                self.0.push(key);
+            }
+        }
+
+        let mut translations = Translations(Vec::new());
+        self.walk(&mut translations);
+        translations.0
+    }
+
+    /// Returns the pragmas the tree's pragma tags enable, in the order
+    /// they're found. The tree is walked with an explicit stack, so deeply
+    /// nested sections can't overflow the call stack.
+    pub fn pragmas(&self) -> Vec<Pragma> {
+        let mut pragmas = Vec::new();
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match *node {
+                Statement::Program(ref block)
+                | Statement::Section(_, ref block)
+                | Statement::Inverted(_, ref block)
+                | Statement::Parent(_, ref block)
+                | Statement::Block(_, ref block) => stack.extend(block.statements.iter().rev()),
+                Statement::Pragma(pragma) => pragmas.push(pragma),
+                _ => (),
+            }
+        }
+        pragmas
+    }
+
+    /// Returns the names of the feature flags the tree's sections test, like
+    /// `beta_ui` for `{{#flags.beta_ui}}`, in the order they're found.
+    pub fn flags(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match *node {
+                Statement::Program(ref block)
+                | Statement::Parent(_, ref block)
+                | Statement::Block(_, ref block) => stack.extend(block.statements.iter().rev()),
+                Statement::Section(ref path, ref block)
+                | Statement::Inverted(ref path, ref block) => {
+                    names.extend(path.flag());
+                    stack.extend(block.statements.iter().rev());
+                }
+                _ => (),
+            }
+        }
+        names
+    }
+
+    /// Determines if the tree enables the pragma.
+    pub fn has_pragma(&self, pragma: Pragma) -> bool {
+        self.pragmas().contains(&pragma)
+    }
+
+    /// Determines if the tree calls a partial with arguments.
+    pub fn has_arguments(&self) -> bool {
+        struct Arguments(bool);
+
+        impl<'a> Visitor<'a> for Arguments {
+            fn visit_partial(
+                &mut self,
+                _: &'a str,
+                _: Option<&'a str>,
+                arguments: &'a [(String, Path)],
+            ) {
+                self.0 |= !arguments.is_empty();
+            }
+        }
+
+        let mut arguments = Arguments(false);
+        self.walk(&mut arguments);
+        arguments.0
+    }
+
+    /// Determines if the tree reads a list item's loop metadata, like
+    /// `@index`.
+    pub fn has_metadata(&self) -> bool {
+        struct Metadata(bool);
+
+        impl<'a> Visitor<'a> for Metadata {
+            fn visit_section(&mut self, path: &'a Path, _: &'a Block) -> bool {
+                self.0 |= path.is_metadata();
+                true
+            }
+
+            fn visit_inverted(&mut self, path: &'a Path, _: &'a Block) -> bool {
+                self.0 |= path.is_metadata();
+                true
+            }
+
+            fn visit_variable(&mut self, path: &'a Path) {
+                self.0 |= path.is_metadata();
+            }
+
+            fn visit_filtered(&mut self, path: &'a Path, _: &'a [String]) {
+                self.0 |= path.is_metadata();
+            }
+
+            fn visit_html(&mut self, path: &'a Path) {
+                self.0 |= path.is_metadata();
+            }
+
+            fn visit_partial(
+                &mut self,
+                _: &'a str,
+                _: Option<&'a str>,
+                arguments: &'a [(String, Path)],
+            ) {
+                self.0 |= arguments.iter().any(|(_, path)| path.is_metadata());
+            }
+
+            fn visit_dynamic_partial(&mut self, path: &'a Path, _: Option<&'a str>) {
+                self.0 |= path.is_metadata();
+            }
+        }
+
+        let mut metadata = Metadata(false);
+        self.walk(&mut metadata);
+        metadata.0
+    }
+
+    /// Appends a copy of the statement to the block, replacing sections and
+    /// inverted sections whose paths are defined constants with their
+    /// statements when the branch is taken, or nothing when it isn't.
+    ///
+    /// A section over a feature flag, like `flags.beta_ui`, is always
+    /// replaced: the flag takes the value defined for its name, like
+    /// `beta_ui`, and is off when it's not defined.
+    ///
+    /// Content inlined from a taken branch is combined with the content
+    /// around the section.
+    ///
+    /// The tree is walked with an explicit stack rather than recursion, so
+    /// deeply nested sections can't overflow the call stack.
+    fn define(&self, defines: &HashMap<String, bool>, span: Span, out: &mut Block) {
+        enum Step<'a> {
+            Copy(&'a Statement, Span),
+            Close(&'a Statement, Span),
+        }
+
+        // The copies of the blocks being defined, innermost last, appended
+        // to `out` or the enclosing copy when their statement is closed.
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut steps = vec![Step::Copy(self, span)];
+        while let Some(step) = steps.pop() {
+            let (stmt, span) = match step {
+                Step::Copy(stmt, span) => (stmt, span),
+                Step::Close(stmt, span) => {
+                    let block = blocks.pop().expect("open block");
+                    let stmt = match *stmt {
+                        Statement::Program(_) => Statement::Program(block),
+                        Statement::Section(ref path, _) => Statement::Section(path.clone(), block),
+                        Statement::Inverted(ref path, _) => {
+                            Statement::Inverted(path.clone(), block)
+                        }
+                        Statement::Parent(ref name, _) => Statement::Parent(name.clone(), block),
+                        Statement::Block(ref name, _) => Statement::Block(name.clone(), block),
+                        _ => unreachable!("only statements with blocks are closed"),
+                    };
+                    blocks.last_mut().unwrap_or(&mut *out).append(stmt, span);
+                    continue;
+                }
+            };
+
+            let block = match *stmt {
+                Statement::Program(ref block)
+                | Statement::Parent(_, ref block)
+                | Statement::Block(_, ref block) => block,
+                Statement::Section(ref path, ref block)
+                | Statement::Inverted(ref path, ref block) => {
+                    let inverted = matches!(*stmt, Statement::Inverted(..));
+                    let value = defines.get(&path.to_string()).cloned().or_else(|| {
+                        path.flag()
+                            .map(|name| defines.get(name).cloned().unwrap_or(false))
+                    });
+                    match value {
+                        Some(value) if value != inverted => {
+                            let statements = block.statements.iter().zip(&block.spans).rev();
+                            steps.extend(statements.map(|(stmt, &span)| Step::Copy(stmt, span)));
+                            continue;
+                        }
+                        Some(_) => continue,
+                        None => block,
+                    }
+                }
+                _ => {
+                    blocks
+                        .last_mut()
+                        .unwrap_or(&mut *out)
+                        .append(stmt.clone(), span);
+                    continue;
+                }
+            };
+
+            blocks.push(block.located());
+            steps.push(Step::Close(stmt, span));
+            let statements = block.statements.iter().zip(&block.spans).rev();
+            steps.extend(statements.map(|(stmt, &span)| Step::Copy(stmt, span)));
+        }
+    }
+
+    /// Appends a copy of the statement to the block, replacing parent tags
+    /// with the statements of the parent template, and blocks with their
+    /// overrides or, lacking one, their default contents.
+    ///
+    /// Overrides given by a child take precedence over those its parent
+    /// passes on to a grandparent. Fails with the name of a parent template
+    /// that inherits from itself.
+    fn inherit<'a>(
+        &'a self,
+        templates: &'a [Template],
+        overrides: &HashMap<&'a str, &'a Block>,
+        parents: &mut Vec<&'a str>,
+        span: Span,
+        out: &mut Block,
+    ) -> Result<(), String> {
+        match *self {
+            Statement::Program(ref block) => out.append(
+                Statement::Program(block.inherit(templates, overrides, parents)?),
+                span,
+            ),
+            Statement::Section(ref path, ref block) => out.append(
+                Statement::Section(path.clone(), block.inherit(templates, overrides, parents)?),
+                span,
+            ),
+            Statement::Inverted(ref path, ref block) => out.append(
+                Statement::Inverted(path.clone(), block.inherit(templates, overrides, parents)?),
+                span,
+            ),
+            Statement::Block(ref name, ref block) => {
+                let block = overrides.get(name.as_str()).cloned().unwrap_or(block);
+                for (stmt, &span) in block.statements.iter().zip(&block.spans) {
+                    stmt.inherit(templates, overrides, parents, span, out)?;
+                }
+            }
+            Statement::Parent(ref name, ref block) => {
+                if parents.contains(&name.as_str()) {
+                    return Err(name.clone());
+                }
+
+                let mut inherited: HashMap<&str, &Block> = block
+                    .statements
+                    .iter()
+                    .filter_map(|stmt| match *stmt {
+                        Statement::Block(ref name, ref block) => Some((name.as_str(), block)),
+                        _ => None,
+                    })
+                    .collect();
+                inherited.extend(overrides.iter().map(|(&name, &block)| (name, block)));
+
+                let parent = match templates.iter().find(|t| &t.name == name) {
+                    Some(parent) => parent,
+                    None => return Ok(()),
+                };
+                let (statements, spans) = match parent.tree {
+                    Statement::Program(ref block) => (&block.statements[..], &block.spans[..]),
+                    ref tree => (std::slice::from_ref(tree), &[span][..]),
+                };
+
+                parents.push(name);
+                for (stmt, &span) in statements.iter().zip(spans) {
+                    stmt.inherit(templates, &inherited, parents, span, out)?;
+                }
+                parents.pop();
+            }
+            _ => out.append(self.clone(), span),
+        }
+        Ok(())
+    }
+
+    /// Combines adjacent content statements into a single statement.
+    ///
+    /// Returns true if the statements were merged.
+    fn merge(&mut self, statement: &Statement) -> bool {
+        match *self {
+            Statement::Content(ref mut left) => match *statement {
+                Statement::Content(ref right) => {
+                    left.push_str(right);
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn define() {
+        let source = "a{{#beta}}b{{/beta}}{{^beta}}c{{/beta}}\
+                      {{#debug}}d{{/debug}}{{^debug}}e{{/debug}}\
+                      {{#robots}}{{#beta}}f{{/beta}}{{/robots}}";
+        let tree = Statement::parse(source).unwrap();
+
+        let mut defines = HashMap::new();
+        defines.insert(String::from("beta"), true);
+        defines.insert(String::from("debug"), false);
+
+        let mut block = Block::empty();
+        tree.define(&defines, Span::default(), &mut block);
+        let expected = Block::new(vec![Statement::Program(Block::new(vec![
+            Statement::Content("abe".into()),
+            Statement::Section(
+                Path::new(vec!["robots".into()]),
+                Block::new(vec![Statement::Content("f".into())]),
+            ),
+        ]))]);
+        assert_eq!(expected, block);
+    }
+
+    #[test]
+    fn define_flags() {
+        let source = "{{#flags.beta}}a{{/flags.beta}}{{^flags.beta}}b{{/flags.beta}}\
+                      {{#flags.debug}}c{{/flags.debug}}{{^flags.debug}}d{{/flags.debug}}\
+                      {{#flags.a.b}}e{{/flags.a.b}}";
+        let tree = Statement::parse(source).unwrap();
+        assert_eq!(vec!["beta", "beta", "debug", "debug"], tree.flags());
+
+        let mut defines = HashMap::new();
+        defines.insert(String::from("beta"), true);
+
+        let mut block = Block::empty();
+        tree.define(&defines, Span::default(), &mut block);
+        let expected = Block::new(vec![Statement::Program(Block::new(vec![
+            Statement::Content("ad".into()),
+            Statement::Section(
+                Path::new(vec!["flags".into(), "a".into(), "b".into()]),
+                Block::new(vec![Statement::Content("e".into())]),
+            ),
+        ]))]);
+        assert_eq!(expected, block);
+    }
+
+    #[test]
+    fn define_deeply_nested_sections() {
+        let depth = 2500;
+        let source = format!(
+            "{}x{}",
+            "{{#a}}{{#b}}".repeat(depth),
+            "{{/b}}{{/a}}".repeat(depth)
+        );
+        let options = LoadOptions {
+            max_depth: depth * 2,
+            ..LoadOptions::default()
+        };
+        let tree = Statement::parse_with(&source, &options).unwrap();
+        assert!(tree.flags().is_empty());
+
+        let mut defines = HashMap::new();
+        defines.insert(String::from("b"), true);
+
+        let mut block = Block::empty();
+        tree.define(&defines, Span::default(), &mut block);
+        let mut depth_found = 0;
+        let mut node = &block.statements[0];
+        while let Statement::Program(ref block) | Statement::Section(_, ref block) = *node {
+            depth_found += matches!(*node, Statement::Section(..)) as usize;
+            node = &block.statements[0];
+        }
+        assert_eq!(depth, depth_found);
+        assert_eq!(Statement::Content("x".into()), *node);
+    }
+
+    #[test]
+    fn append() {
+        let mut block = Block::new(vec![Statement::Comment("a".into())]);
+        block.append(Statement::Content("b".into()), Span::default());
+        let expected = Block::new(vec![
+            Statement::Comment("a".into()),
+            Statement::Content("b".into()),
+        ]);
+        assert_eq!(expected, block);
+    }
+
+    #[test]
+    fn append_and_merge() {
+        let mut block = Block::new(vec![Statement::Content("a".into())]);
+        block.append(Statement::Content("b".into()), Span::default());
+        assert_eq!(Block::new(vec![Statement::Content("ab".into())]), block);
+    }
+
+    #[test]
+    fn prepend() {
+        let mut block = Block::new(vec![Statement::Comment("a".into())]);
+        block.prepend(Statement::Content("b".into()), Span::default());
+        let expected = Block::new(vec![
+            Statement::Content("b".into()),
+            Statement::Comment("a".into()),
+        ]);
+        assert_eq!(expected, block);
+    }
+
+    #[test]
+    fn prepend_and_merge() {
+        let mut block = Block::new(vec![Statement::Content("a".into())]);
+        block.prepend(Statement::Content("b".into()), Span::default());
+        assert_eq!(Block::new(vec![Statement::Content("ba".into())]), block);
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = Statement::Content("a".into());
+        let b = Statement::Content("b".into());
+        assert!(a.merge(&b));
+        assert_eq!(Statement::Content("ab".into()), a);
+    }
+
+    #[test]
+    fn inline_section() {
+        let tree = Statement::parse("a{{#b}}c{{/b}}d").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("c".into())]),
+            ),
+            Statement::Content("d".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inverted_section() {
+        let tree = Statement::parse("a{{^b}}c{{/b}}d").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Inverted(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("c".into())]),
+            ),
+            Statement::Content("d".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn empty_standalone_section() {
+        let tree = Statement::parse("\r\n{{^boolean}}\r\n{{/boolean}}\r\n").unwrap();
+
+        let program = vec![
+            Statement::Content("\r\n".into()),
+            Statement::Inverted(Path::new(vec!["boolean".into()]), Block::new(vec![])),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn empty_inline_section() {
+        let tree = Statement::parse("{{^boolean}}{{/boolean}}").unwrap();
+
+        let program = vec![Statement::Inverted(
+            Path::new(vec!["boolean".into()]),
+            Block::new(vec![]),
+        )];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_section_on_standalone_line() {
+        let tree = Statement::parse("a\r\n{{#b}}c{{/b}}\nd").unwrap();
+
+        let program = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("c".into())]),
+            ),
+            Statement::Content("\n".into()),
+            Statement::Content("d".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn standalone_section_open_and_close_tags() {
+        let tree = Statement::parse("a\n{{#b}}\nc\n{{/b}}\r\nd").unwrap();
+
+        let program = vec![
+            Statement::Content("a\n".into()),
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("c\n".into())]),
+            ),
+            Statement::Content("d".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn indented_standalone_section_open_and_close_tags() {
+        let tree = Statement::parse("a\n  {{#b}}\n    c\n  {{/b}}\r\nd").unwrap();
+
+        let program = vec![
+            Statement::Content("a\n".into()),
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("    c\n".into())]),
+            ),
+            Statement::Content("d".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn standalone_section_open_and_close_tags_at_eoi() {
+        let tree = Statement::parse("{{#b}}\nc\n{{/b}}").unwrap();
+
+        let program = vec![Statement::Section(
+            Path::new(vec!["b".into()]),
+            Block::new(vec![Statement::Content("c\n".into())]),
+        )];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_section_at_input_boundaries() {
+        let tree = Statement::parse("{{#b}}c{{/b}}").unwrap();
+
+        let program = vec![Statement::Section(
+            Path::new(vec!["b".into()]),
+            Block::new(vec![Statement::Content("c".into())]),
+        )];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_open_indented_standalone_close_at_eoi() {
+        let tree = Statement::parse("{{#b}}c\n  {{/b}}").unwrap();
+
+        let program = vec![Statement::Section(
+            Path::new(vec!["b".into()]),
+            Block::new(vec![Statement::Content("c\n".into())]),
+        )];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_open_indented_standalone_close_at_eoi_with_leading_content() {
+        let tree = Statement::parse("a{{#b}}\nc\n  {{/b}}").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("\nc\n".into())]),
+            ),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_open_indented_inline_close() {
+        let tree = Statement::parse("{{#b}}c\n  {{/b}} a").unwrap();
+
+        let program = vec![
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("c\n  ".into())]),
+            ),
+            Statement::Content(" a".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_open_indented_inline_close_with_trailing_newline() {
+        let tree = Statement::parse("{{#b}}c\n d {{/b}}\na").unwrap();
+
+        let program = vec![
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("c\n d ".into())]),
+            ),
+            Statement::Content("\n".into()),
+            Statement::Content("a".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_partial() {
+        let tree = Statement::parse("a {{> b }} c").unwrap();
+
+        let program = vec![
+            Statement::Content("a ".into()),
+            Statement::Partial("b".into(), None, Vec::new()),
+            Statement::Content(" c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_partial_at_eoi() {
+        let tree = Statement::parse("a {{> b }}").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Content(" ".into()),
+            Statement::Partial("b".into(), None, Vec::new()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_partial_at_eol() {
+        let tree = Statement::parse("a {{> b }}\nc").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Content(" ".into()),
+            Statement::Partial("b".into(), None, Vec::new()),
+            Statement::Content("\n".into()),
+            Statement::Content("c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn standalone_partial() {
+        let tree = Statement::parse("a\r\n{{> b }}\nc").unwrap();
+
+        let program = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Partial("b".into(), None, Vec::new()),
+            Statement::Content("c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn indented_standalone_partial() {
+        let tree = Statement::parse("a\r\n  {{> b }}\nc").unwrap();
+
+        let program = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Partial("b".into(), Some("  ".into()), Vec::new()),
+            Statement::Content("c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn standalone_partial_with_trailing_content() {
+        let tree = Statement::parse("a\r\n{{> b }}c").unwrap();
+
+        let program = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Partial("b".into(), None, Vec::new()),
+            Statement::Content("c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn standalone_partial_at_eoi() {
+        let tree = Statement::parse("a\r\n  {{> b }}").unwrap();
+
+        let program = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Partial("b".into(), Some("  ".into()), Vec::new()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn partial_arguments() {
+        let tree = Statement::parse("{{> card title=user.name body=. }}").unwrap();
+
+        let arguments = vec![
+            (
+                "title".into(),
+                Path::new(vec!["user".into(), "name".into()]),
+            ),
+            ("body".into(), Path::new(vec![".".into()])),
+        ];
+        let program = vec![Statement::Partial("card".into(), None, arguments)];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+
+        assert!(Statement::parse("{{> card title }}").is_err());
+        assert!(Statement::parse("{{> card title= }}").is_err());
+        assert!(Statement::parse("{{>*card title=name }}").is_err());
+    }
+
+    #[test]
+    fn loop_metadata() {
+        let tree = Statement::parse("{{#a}}{{@index}}{{#@last}}.{{/@last}}{{/a}}").unwrap();
+
+        let last = vec![Statement::Content(".".into())];
+        let section = vec![
+            Statement::Variable(Path::new(vec!["@index".into()])),
+            Statement::Section(Path::new(vec!["@last".into()]), Block::new(last)),
+        ];
+        let program = vec![Statement::Section(
+            Path::new(vec!["a".into()]),
+            Block::new(section),
+        )];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+        assert!(tree.has_metadata());
+        assert!(!Statement::parse("{{#a}}{{b}}{{/a}}")
+            .unwrap()
+            .has_metadata());
+        assert!(Statement::parse("{{@}}").is_err());
+    }
+
+    #[test]
+    fn strips_bom_and_normalizes_newlines() {
+        let source = "\u{feff}a\r\n{{#b}}\r\nc\r\n{{/b}}\r\n";
+        let tree = Statement::parse(source).unwrap();
+        let expected = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("c\r\n".into())]),
+            ),
+        ];
+        assert_eq!(Statement::Program(Block::new(expected)), tree);
+
+        let options = LoadOptions {
+            normalize_newlines: true,
+            ..LoadOptions::default()
+        };
+        let tree = Statement::parse_with(source, &options).unwrap();
+        let expected = vec![
+            Statement::Content("a\n".into()),
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("c\n".into())]),
+            ),
+        ];
+        assert_eq!(Statement::Program(Block::new(expected)), tree);
+
+        let options = LoadOptions {
+            strip_bom: false,
+            ..LoadOptions::default()
+        };
+        let tree = Statement::parse_with("\u{feff}a", &options).unwrap();
+        let expected = vec![Statement::Content("\u{feff}a".into())];
+        assert_eq!(Statement::Program(Block::new(expected)), tree);
+    }
+
+    #[test]
+    fn pragmas() {
+        let tree = Statement::parse("{{%STRICT}}\n<p>{{% UNESCAPED }}{{name}}</p>").unwrap();
+        let program = vec![
+            Statement::Pragma(Pragma::Strict),
+            Statement::Content("<p>".into()),
+            Statement::Pragma(Pragma::Unescaped),
+            Statement::Variable(Path::new(vec!["name".into()])),
+            Statement::Content("</p>".into()),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+        assert_eq!(vec![Pragma::Strict, Pragma::Unescaped], tree.pragmas());
+        assert!(tree.has_pragma(Pragma::Strict));
+
+        match Statement::parse("a{{%IMPLICIT-ITERATOR}}") {
+            Err(ParseError::UnknownPragma { ref name, position }) => {
+                assert_eq!("IMPLICIT-ITERATOR", name);
+                assert_eq!(4, position);
+            }
+            other => panic!("unexpected parse: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dynamic_partial() {
+        let tree = Statement::parse("a {{>*b.c}}\n  {{> *d }}\n").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Content(" ".into()),
+            Statement::DynamicPartial(Path::new(vec!["b".into(), "c".into()]), None),
+            Statement::Content("\n".into()),
+            Statement::DynamicPartial(Path::new(vec!["d".into()]), Some("  ".into())),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+        assert!(tree.partials().is_empty());
+    }
+
+    #[test]
+    fn inline_comment() {
+        let tree = Statement::parse("a {{! b }} c").unwrap();
+
+        let program = vec![
+            Statement::Content("a ".into()),
+            Statement::Comment("b".into()),
+            Statement::Content(" c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_comment_at_eoi() {
+        let tree = Statement::parse("a {{! b }}").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Content(" ".into()),
+            Statement::Comment("b".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn inline_comment_at_eol() {
+        let tree = Statement::parse("a {{! b }}\nc").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Content(" ".into()),
+            Statement::Comment("b".into()),
+            Statement::Content("\n".into()),
+            Statement::Content("c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn standalone_comment() {
+        let tree = Statement::parse("a\r\n{{! b }}\nc").unwrap();
+
+        let program = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Comment("b".into()),
+            Statement::Content("c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn indented_standalone_comment() {
+        let tree = Statement::parse("a\r\n  {{! b }}\nc").unwrap();
+
+        let program = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Comment("b".into()),
+            Statement::Content("c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn standalone_comment_with_trailing_content() {
+        let tree = Statement::parse("a\r\n{{! b }}c").unwrap();
+
+        let program = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Comment("b".into()),
+            Statement::Content("c".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn standalone_comment_at_eoi() {
+        let tree = Statement::parse("a\r\n  {{! b }}").unwrap();
+
+        let program = vec![
+            Statement::Content("a\r\n".into()),
+            Statement::Comment("b".into()),
+        ];
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn set_delimiters() {
+        let tree = Statement::parse("{{=<% %>=}}var a = {{b}};<% c %><%={{ }}=%>{{ d }}").unwrap();
+
+        let program = vec![
+            Statement::Content("var a = {{b}};".into()),
+            Statement::Variable(Path::new(vec!["c".into()])),
+            Statement::Variable(Path::new(vec!["d".into()])),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    #[test]
+    fn standalone_set_delimiters() {
+        let tree = Statement::parse("a\n  {{= | | =}}\n|#b|\nc\n|/b|\n").unwrap();
+
+        let program = vec![
+            Statement::Content("a\n".into()),
+            Statement::Section(
+                Path::new(vec!["b".into()]),
+                Block::new(vec![Statement::Content("c\n".into())]),
+            ),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    #[test]
+    fn inline_set_delimiters() {
+        let tree = Statement::parse("a {{=<% %>=}}\n<%b%>").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Content(" ".into()),
+            Statement::Content("\n".into()),
+            Statement::Variable(Path::new(vec!["b".into()])),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    #[test]
+    fn trimmed_variable() {
+        let tree = Statement::parse("<p>\n  {{~ name ~}}  \n</p>{{a~}} b {{~b}}").unwrap();
+
+        let program = vec![
+            Statement::Content("<p>".into()),
+            Statement::Variable(Path::new(vec!["name".into()])),
+            Statement::Content("</p>".into()),
+            Statement::Variable(Path::new(vec!["a".into()])),
+            Statement::Content("b".into()),
+            Statement::Variable(Path::new(vec!["b".into()])),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    #[test]
+    fn trimmed_section() {
+        let tree = Statement::parse(
+            "<ul>\n  {{~#items~}}\n    <li>{{.}}</li>\n  {{~/items~}}\n</ul>\n{{! done ~}}\n",
+        )
+        .unwrap();
+
+        let items = vec![
+            Statement::Content("<li>".into()),
+            Statement::Variable(Path::new(vec![".".into()])),
+            Statement::Content("</li>".into()),
+        ];
+        let program = vec![
+            Statement::Content("<ul>".into()),
+            Statement::Section(Path::new(vec!["items".into()]), Block::new(items)),
+            Statement::Content("</ul>\n".into()),
+            Statement::Comment("done".into()),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    #[test]
+    fn trimmed_set_delimiters() {
+        let tree = Statement::parse("a {{~=<% %>=~}} <%b%>").unwrap();
+
+        let program = vec![
+            Statement::Content("a".into()),
+            Statement::Variable(Path::new(vec!["b".into()])),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    fn handlebars(template: &str) -> Result<Statement, ParseError> {
+        let options = LoadOptions {
+            dialect: Dialect::Handlebars,
+            ..LoadOptions::default()
+        };
+        Statement::parse_with(template, &options)
+    }
+
+    #[test]
+    fn variables() {
+        let tree = Statement::parse(
+            "{{a}} {{#b}}{{{c.d}}}{{/b}} {{^e}}{{&f}}{{/e}} {{g | upcase}} {{>h}}",
+        )
+        .unwrap();
+        let names: Vec<_> = tree.variables().iter().map(|p| p.to_string()).collect();
+        assert_eq!(vec!["a", "c.d", "f", "g"], names);
+    }
+
+    #[test]
+    fn translation() {
+        let options = LoadOptions {
+            translations: true,
+            ..LoadOptions::default()
+        };
+        let tree = Statement::parse_with("{{t \"checkout.title\"}} {{ t }}", &options).unwrap();
+        let program = vec![
+            Statement::Translation("checkout.title".into()),
+            Statement::Content(" ".into()),
+            Statement::Variable(Path::new(vec!["t".into()])),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+
+        assert!(Statement::parse_with("{{t \"\"}}", &options).is_err());
+        assert!(Statement::parse_with("{{t \"a\nb\"}}", &options).is_err());
+        assert!(Statement::parse("{{t \"checkout.title\"}}").is_err());
+    }
+
+    #[test]
+    fn else_section() {
+        let tree =
+            Statement::parse("{{#a}}\n  b\n{{else}}\n  c\n{{/a}}\n{{^d}}e {{else}}\nf{{/d}}")
+                .unwrap();
+
+        let a = Path::new(vec!["a".into()]);
+        let d = Path::new(vec!["d".into()]);
+        let program = vec![
+            Statement::Section(
+                a.clone(),
+                Block::new(vec![Statement::Content("  b\n".into())]),
+            ),
+            Statement::Inverted(a, Block::new(vec![Statement::Content("  c\n".into())])),
+            Statement::Inverted(d.clone(), Block::new(vec![Statement::Content("e ".into())])),
+            Statement::Section(d, Block::new(vec![Statement::Content("\nf".into())])),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    #[test]
+    fn else_outside_section() {
+        assert!(handlebars("a{{else}}b").is_err());
+        assert!(handlebars("  {{else}}\n").is_err());
+        assert!(handlebars("{{$a}}b{{else}}c{{/a}}").is_err());
+
+        let tree = Statement::parse("  {{else}}\n{{$a}}{{else}}{{/a}}").unwrap();
+        let program = vec![
+            Statement::Content("  ".into()),
+            Statement::Variable(Path::new(vec!["else".into()])),
+            Statement::Content("\n".into()),
+            Statement::Block(
+                "a".into(),
+                Block::new(vec![Statement::Variable(Path::new(vec!["else".into()]))]),
+            ),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    #[test]
+    fn handlebars_comments_and_this() {
+        let tree =
+            handlebars("{{!-- {{a}} --}}\n{{! b }}{{this}}{{this.c}}{{#this}}{{/this}}").unwrap();
+
+        let program = vec![
+            Statement::Comment("{{a}}".into()),
+            Statement::Comment("b".into()),
+            Statement::Variable(Path::new(vec![".".into()])),
+            Statement::Variable(Path::new(vec!["c".into()])),
+            Statement::Section(Path::new(vec![".".into()]), Block::new(vec![])),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+
+        let tree = Statement::parse("{{!-- a --}}{{this}}").unwrap();
+        let program = vec![
+            Statement::Comment("-- a --".into()),
+            Statement::Variable(Path::new(vec!["this".into()])),
+        ];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    #[test]
+    fn parent_and_blocks() {
+        let tree = Statement::parse(
+            "{{<includes/layout}}\n  {{$title}}\n  Robots\n  {{/title}}\n{{/includes/layout}}\n",
+        )
+        .unwrap();
+
+        let title = Statement::Block(
+            "title".into(),
+            Block::new(vec![Statement::Content("  Robots\n".into())]),
+        );
+        let program = vec![Statement::Parent(
+            "includes/layout".into(),
+            Block::new(vec![title]),
+        )];
+        assert_eq!(Statement::Program(Block::new(program)), tree);
+    }
+
+    #[test]
+    fn section_source() {
+        let tree = Statement::parse("a{{#b}}<i>{{c}}</i>{{/b}}").unwrap();
+        match tree {
+            Statement::Program(ref block) => match block.statements[1] {
+                Statement::Section(_, ref section) => assert_eq!("<i>{{c}}</i>", section.source),
+                _ => panic!("Expected section"),
+            },
+            _ => panic!("Expected program"),
+        }
+    }
+
+    #[test]
+    fn tree() {
+        let tree = Statement::parse(
+            "
+            {{> includes/header }}
+            <ul>
+                {{# robots}}
+                    <li>{{ name.first }}</li>
+                {{/ robots}}
+                {{^ robots}}
+                    {{! else clause }}
+                    No robots
+                {{/ robots}}
+            </ul>
+            {{> includes/footer }}
+            {{{ unescaped.html }}}
+        ",
+        )
+        .unwrap();
+
+        let program = vec![
+            Statement::Content("\n".into()),
+            Statement::Partial(
+                "includes/header".into(),
+                Some("            ".into()),
+                Vec::new(),
+            ),
+            Statement::Content("            <ul>\n".into()),
+            Statement::Section(
+                Path::new(vec!["robots".into()]),
+                Block::new(vec![
+                    Statement::Content("                    <li>".into()),
+                    Statement::Variable(Path::new(vec!["name".into(), "first".into()])),
+                    Statement::Content("</li>\n".into()),
+                ]),
+            ),
+            Statement::Inverted(
+                Path::new(vec!["robots".into()]),
+                Block::new(vec![
+                    Statement::Comment("else clause".into()),
+                    Statement::Content("                    No robots\n".into()),
+                ]),
+            ),
+            Statement::Content("            </ul>\n".into()),
+            Statement::Partial(
+                "includes/footer".into(),
+                Some("            ".into()),
+                Vec::new(),
+            ),
+            Statement::Content("            ".into()),
+            Statement::Html(Path::new(vec!["unescaped".into(), "html".into()])),
+            Statement::Content("\n        ".into()),
+        ];
+
+        let expected = Statement::Program(Block::new(program));
+        assert_eq!(expected, tree);
+    }
+
+    #[test]
+    fn spans_statements() {
+        let source = "a {{name}}\n{{#items}}\n  {{> item}}\n b {{#x}}\ny{{/x}}\n{{/items}}";
+        let block = match Statement::parse(source).unwrap() {
+            Statement::Program(block) => block,
+            other => panic!("Expected a program: {:?}", other),
+        };
+        let text = |block: &Block| -> Vec<_> {
+            block
+                .spans
+                .iter()
+                .map(|span| &source[span.start..span.end])
+                .collect()
+        };
+
+        assert_eq!(source, &source[block.span.start..block.span.end]);
+        assert_eq!(
+            vec![
+                "a ",
+                "{{name}}",
+                "\n",
+                "{{#items}}\n  {{> item}}\n b {{#x}}\ny{{/x}}\n{{/items}}"
+            ],
+            text(&block)
+        );
+
+        let items = match block.statements[3] {
+            Statement::Section(_, ref block) => block,
+            ref other => panic!("Expected a section: {:?}", other),
+        };
+        assert_eq!(
+            vec!["  {{> item}}\n", " b", " ", "{{#x}}\ny{{/x}}", "\n"],
+            text(items)
+        );
+        assert_eq!((3, 1), (items.span.line, items.span.column));
+
+        let x = match items.statements[3] {
+            Statement::Section(_, ref block) => block,
+            ref other => panic!("Expected a section: {:?}", other),
+        };
+        assert_eq!(vec!["\ny"], text(x));
+    }
+}
@@ -0,0 +1,447 @@
+//! Rewrites templates written in a subset of Handlebars into Mustache.
+//!
+//! The `if`, `unless`, `each`, and `with` block helpers become sections and
+//! inverted sections, `{{else}}` closes one and opens the other, and path
+//! expressions like `this.name` or `user/name` become dotted Mustache paths.
+//! Constructs with no Mustache equivalent, like helper calls, parent scope
+//! paths, and `@index`, are copied unchanged and reported, so they can be
+//! rewritten by hand. Blocks whose sections may render differently than the
+//! helpers they replace, like `{{#if list}}`, are translated with a note.
+
+use std::fmt;
+
+use super::Span;
+
+/// The Mustache text translated from a Handlebars template, along with each
+/// construct that couldn't be translated, and each translated one that may
+/// change meaning.
+#[derive(Debug)]
+pub struct Migration {
+    pub text: String,
+    pub issues: Vec<Issue>,
+    pub notes: Vec<Issue>,
+}
+
+/// A Handlebars tag that was copied into the output unchanged, only
+/// partially translated, or translated into a section that may render
+/// differently.
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    pub tag: String,
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} {}: {}",
+            self.span.line, self.span.column, self.tag, self.message
+        )
+    }
+}
+
+/// An open block tag, tracking the section its `{{else}}` and closing tags
+/// translate to.
+struct Frame {
+    /// The helper or path named by the closing tag, like `if` or `robots`.
+    name: String,
+    /// The Mustache path of the section, or none if the block couldn't be
+    /// translated and is copied unchanged.
+    path: Option<String>,
+    /// Whether the Mustache section currently open is inverted.
+    inverted: bool,
+}
+
+/// Translates the Handlebars template text into Mustache.
+pub fn migrate(source: &str) -> Migration {
+    let mut migrator = Migrator {
+        source,
+        text: String::with_capacity(source.len()),
+        issues: Vec::new(),
+        notes: Vec::new(),
+        frames: Vec::new(),
+    };
+    migrator.run();
+    Migration {
+        text: migrator.text,
+        issues: migrator.issues,
+        notes: migrator.notes,
+    }
+}
+
+struct Migrator<'a> {
+    source: &'a str,
+    text: String,
+    issues: Vec<Issue>,
+    notes: Vec<Issue>,
+    frames: Vec<Frame>,
+}
+
+impl<'a> Migrator<'a> {
+    fn run(&mut self) {
+        let source = self.source;
+        let mut offset = 0;
+        while let Some(found) = source[offset..].find("{{") {
+            let start = offset + found;
+            self.text.push_str(&source[offset..start]);
+
+            let (close, skip) = if source[start..].starts_with("{{{") {
+                ("}}}", 3)
+            } else if source[start..].starts_with("{{!--") {
+                ("--}}", 5)
+            } else {
+                ("}}", 2)
+            };
+
+            match source[start + skip..].find(close) {
+                Some(length) => {
+                    let end = start + skip + length + close.len();
+                    self.tag(start, end, skip, close.len());
+                    offset = end;
+                }
+                None => {
+                    self.issue(start, source.len(), "unterminated tag");
+                    self.text.push_str(&source[start..]);
+                    offset = source.len();
+                }
+            }
+        }
+        self.text.push_str(&source[offset..]);
+
+        while let Some(frame) = self.frames.pop() {
+            let tag = format!("{{{{#{}}}}}", frame.name);
+            self.issues.push(Issue {
+                tag,
+                span: self.span(source.len(), source.len()),
+                message: String::from("block is never closed"),
+            });
+        }
+    }
+
+    /// Translates the tag between the byte offsets, whose opening and
+    /// closing delimiters have the given lengths.
+    fn tag(&mut self, start: usize, end: usize, open: usize, close: usize) {
+        let tag = &self.source[start..end];
+        let mut inner = &self.source[start + open..end - close];
+
+        if inner.starts_with('~') || inner.ends_with('~') {
+            self.issue(
+                start,
+                end,
+                "whitespace control isn't supported; surrounding whitespace is kept",
+            );
+            inner = inner.trim_start_matches('~').trim_end_matches('~');
+        }
+
+        if open == 3 {
+            return self.variable(start, end, "{{{", inner.trim(), "}}}");
+        }
+        if open == 5 {
+            return self.comment(start, end, inner);
+        }
+
+        let inner = inner.trim();
+        let (sigil, rest) = match inner.chars().next() {
+            Some(c @ '!') | Some(c @ '#') | Some(c @ '^') | Some(c @ '/') | Some(c @ '>')
+            | Some(c @ '&') => (Some(c), inner[1..].trim()),
+            _ => (None, inner),
+        };
+
+        match sigil {
+            Some('!') => self.comment(start, end, &inner[1..]),
+            Some('#') => self.open(start, end, rest, false),
+            Some('^') => self.open(start, end, rest, true),
+            Some('/') => self.close(start, end, rest),
+            Some('>') => self.partial(start, end, rest),
+            Some('&') => self.variable(start, end, "{{&", rest, "}}"),
+            _ if words(inner) == ["else"] => self.otherwise(start, end),
+            _ if inner.starts_with("else ") => {
+                self.issue(start, end, "chained else blocks aren't supported");
+                self.text.push_str(tag);
+            }
+            _ => self.variable(start, end, "{{", inner, "}}"),
+        }
+    }
+
+    fn comment(&mut self, start: usize, end: usize, text: &str) {
+        if text.contains("}}") {
+            self.issue(start, end, "comments can't contain `}}` in Mustache");
+            self.text.push_str(&self.source[start..end]);
+        } else {
+            self.text.push_str("{{!");
+            self.text.push_str(text);
+            self.text.push_str("}}");
+        }
+    }
+
+    fn variable(&mut self, start: usize, end: usize, open: &str, expr: &str, close: &str) {
+        if words(expr).len() > 1 {
+            self.issue(start, end, "helper calls aren't supported");
+            self.text.push_str(&self.source[start..end]);
+            return;
+        }
+
+        match path(expr) {
+            Ok(path) => {
+                self.text.push_str(open);
+                self.text.push_str(&path);
+                self.text.push_str(close);
+            }
+            Err(message) => {
+                self.issue(start, end, message);
+                self.text.push_str(&self.source[start..end]);
+            }
+        }
+    }
+
+    fn partial(&mut self, start: usize, end: usize, expr: &str) {
+        match words(expr).as_slice() {
+            [name] => {
+                self.text.push_str("{{> ");
+                self.text.push_str(name);
+                self.text.push_str("}}");
+            }
+            _ => {
+                self.issue(
+                    start,
+                    end,
+                    "partial contexts and parameters aren't supported",
+                );
+                self.text.push_str(&self.source[start..end]);
+            }
+        }
+    }
+
+    /// Opens a section for a block helper or a Mustache-style section.
+    fn open(&mut self, start: usize, end: usize, expr: &str, inverted: bool) {
+        let words = words(expr);
+        let (name, inverted, result) = match words.as_slice() {
+            [helper, expr] if !inverted => match *helper {
+                "if" | "each" | "with" => (*helper, false, path(expr)),
+                "unless" => (*helper, true, path(expr)),
+                _ => (*helper, false, Err("custom block helpers aren't supported")),
+            },
+            [expr] => (*expr, inverted, path(expr)),
+            [helper, ..] => (*helper, inverted, Err("block parameters aren't supported")),
+            [] => ("", inverted, Err("block tag has no name")),
+        };
+
+        // A Mustache section repeats for each item of a list and renders an
+        // object once as the context, where `if` renders once for any truthy
+        // value and `each` iterates an object's values.
+        let note = match words.as_slice() {
+            ["if", _] => {
+                Some("a list value repeats this section for each item, where `if` rendered once")
+            }
+            ["each", _] => {
+                Some("an object value renders this section once, where `each` iterated its values")
+            }
+            _ => None,
+        };
+
+        let path = match result {
+            Ok(path) => {
+                if let Some(message) = note {
+                    let issue = self.locate(start, end, message);
+                    self.notes.push(issue);
+                }
+                self.text.push_str(if inverted { "{{^" } else { "{{#" });
+                self.text.push_str(&path);
+                self.text.push_str("}}");
+                Some(path)
+            }
+            Err(message) => {
+                self.issue(start, end, message);
+                self.text.push_str(&self.source[start..end]);
+                None
+            }
+        };
+
+        self.frames.push(Frame {
+            name: name.to_string(),
+            path,
+            inverted,
+        });
+    }
+
+    /// Closes the open section and opens its inverse.
+    fn otherwise(&mut self, start: usize, end: usize) {
+        let tag = &self.source[start..end];
+        match self.frames.last_mut() {
+            Some(&mut Frame {
+                path: Some(ref path),
+                ref mut inverted,
+                ..
+            }) => {
+                *inverted = !*inverted;
+                let open = if *inverted { "^" } else { "#" };
+                self.text
+                    .push_str(&format!("{{{{/{}}}}}{{{{{}{}}}}}", path, open, path));
+            }
+            Some(_) => self.text.push_str(tag),
+            None => {
+                self.issue(start, end, "else outside of a block");
+                self.text.push_str(tag);
+            }
+        }
+    }
+
+    fn close(&mut self, start: usize, end: usize, name: &str) {
+        let tag = &self.source[start..end];
+        if !matches!(self.frames.last(), Some(frame) if frame.name == name) {
+            self.issue(start, end, "closing tag doesn't match the open block");
+            self.text.push_str(tag);
+            return;
+        }
+
+        match self.frames.pop().and_then(|frame| frame.path) {
+            Some(path) => {
+                self.text.push_str("{{/");
+                self.text.push_str(&path);
+                self.text.push_str("}}");
+            }
+            None => self.text.push_str(tag),
+        }
+    }
+
+    fn issue(&mut self, start: usize, end: usize, message: &str) {
+        let issue = self.locate(start, end, message);
+        self.issues.push(issue);
+    }
+
+    /// Describes the tag between the byte offsets with the message.
+    fn locate(&self, start: usize, end: usize, message: &str) -> Issue {
+        Issue {
+            tag: self.source[start..end].to_string(),
+            span: self.span(start, end),
+            message: message.to_string(),
+        }
+    }
+
+    /// Locates the byte range in the source by one-based line and column.
+    fn span(&self, start: usize, end: usize) -> Span {
+        let before = &self.source[..start];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rfind('\n')
+            .map_or(before, |i| &before[i + 1..])
+            .chars()
+            .count()
+            + 1;
+        Span::new(start, end, line, column)
+    }
+}
+
+/// Splits a tag's expression into its whitespace separated words.
+fn words(expr: &str) -> Vec<&str> {
+    expr.split_whitespace().collect()
+}
+
+/// Translates a Handlebars path expression into a dotted Mustache path.
+fn path(expr: &str) -> Result<String, &'static str> {
+    if expr == "this" || expr == "." {
+        return Ok(String::from("."));
+    }
+    if expr.starts_with("../") {
+        return Err("parent scope paths aren't supported");
+    }
+    if expr.starts_with('@') {
+        return Err("data variables like @index aren't supported");
+    }
+
+    let expr = ["this.", "this/", "./"]
+        .iter()
+        .find(|prefix| expr.starts_with(*prefix))
+        .map_or(expr, |prefix| &expr[prefix.len()..]);
+
+    let keys: Vec<_> = expr.split(&['.', '/'][..]).collect();
+    let valid = keys.iter().all(|key| {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_?!".contains(c))
+    });
+
+    if valid {
+        Ok(keys.join("."))
+    } else {
+        Err("path isn't a valid Mustache path")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Statement;
+    use super::{migrate, path};
+
+    #[test]
+    fn translates_block_helpers() {
+        let source = "{{#if user}}Hi {{user/name}}{{else}}Sign in{{/if}}\n\
+                      {{#each robots}}<li>{{this.name}} {{{bio}}}</li>{{else}}None{{/each}}\n\
+                      {{#unless admin}}{{> footer}}{{/unless}}{{!-- note --}}";
+        let expected = "{{#user}}Hi {{user.name}}{{/user}}{{^user}}Sign in{{/user}}\n\
+                        {{#robots}}<li>{{name}} {{{bio}}}</li>{{/robots}}{{^robots}}None{{/robots}}\n\
+                        {{^admin}}{{> footer}}{{/admin}}{{! note }}";
+
+        let migration = migrate(source);
+        assert_eq!(expected, migration.text);
+        assert!(migration.issues.is_empty());
+        assert!(Statement::parse(&migration.text).is_ok());
+
+        let notes: Vec<_> = migration.notes.iter().map(|n| n.to_string()).collect();
+        assert_eq!(
+            vec![
+                "1:1 {{#if user}}: a list value repeats this section for each item, where `if` rendered once",
+                "2:1 {{#each robots}}: an object value renders this section once, where `each` iterated its values",
+            ],
+            notes
+        );
+    }
+
+    #[test]
+    fn reports_untranslatable_constructs() {
+        let source =
+            "{{#each items as |item|}}\n  {{@index}} {{format date}}{{/each}}\n{{../title}}";
+        let migration = migrate(source);
+        assert_eq!(source, migration.text);
+
+        let issues: Vec<_> = migration.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            vec![
+                "1:1 {{#each items as |item|}}: block parameters aren't supported",
+                "2:3 {{@index}}: data variables like @index aren't supported",
+                "2:14 {{format date}}: helper calls aren't supported",
+                "3:1 {{../title}}: parent scope paths aren't supported",
+            ],
+            issues
+        );
+    }
+
+    #[test]
+    fn reports_unbalanced_blocks() {
+        let migration = migrate("{{#if a}}{{/each}}{{else}}");
+        let messages: Vec<_> = migration
+            .issues
+            .iter()
+            .map(|i| i.message.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "closing tag doesn't match the open block",
+                "block is never closed",
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn translates_paths() {
+        assert_eq!(Ok(String::from(".")), path("this"));
+        assert_eq!(Ok(String::from("name")), path("./name"));
+        assert_eq!(Ok(String::from("a.b.c")), path("a/b.c"));
+        assert!(path("[first name]").is_err());
+        assert!(path("\"text\"").is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! Collapses insignificant whitespace in HTML template content, shrinking
+//! both the compiled templates' static strings and their rendered output.
+//!
+//! The pass is conservative: each run of whitespace becomes a single newline
+//! if it spans lines, or a single space otherwise, as a browser would render
+//! it. Whitespace is never removed entirely, so text on either side of an
+//! inline element stays separated. Tags, with their attribute values, and
+//! the contents of `<pre>`, `<textarea>`, `<script>`, and `<style>` elements
+//! are copied unchanged.
+
+use super::Statement;
+
+/// The elements whose text is rendered or executed as written.
+const PRESERVED: &[&str] = &["pre", "textarea", "script", "style"];
+
+/// Where collapsing stands relative to an HTML tag, which may span several
+/// content statements, like `<input value="{{a}}  b">`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tag {
+    /// Between tags, where whitespace is collapsed.
+    Outside,
+    /// Within a tag's name and attributes.
+    Inside,
+    /// Within an attribute value quoted by the character.
+    Quoted(char),
+}
+
+/// Collapses the whitespace in each content statement of the tree, in
+/// source order, tracking the tags and preserved elements content opens and
+/// closes. The tree is walked with an explicit stack, like
+/// `Statement::pragmas`, as the visitor only reads statements.
+pub fn minify(tree: &mut Statement) {
+    let mut open = None;
+    let mut tag = Tag::Outside;
+    let mut stack = vec![tree];
+    while let Some(node) = stack.pop() {
+        match *node {
+            Statement::Program(ref mut block)
+            | Statement::Section(_, ref mut block)
+            | Statement::Inverted(_, ref mut block)
+            | Statement::Parent(_, ref mut block)
+            | Statement::Block(_, ref mut block) => stack.extend(block.statements.iter_mut().rev()),
+            Statement::Content(ref mut text) => *text = collapse(text, &mut open, &mut tag),
+            _ => (),
+        }
+    }
+}
+
+/// Collapses the text's whitespace outside tags and preserved elements. The
+/// tag or element left open at the end of the text, if any, is kept open for
+/// the content following it.
+fn collapse(text: &str, open: &mut Option<&'static str>, tag: &mut Tag) -> String {
+    // Lowercasing ASCII keeps byte offsets the same as the text's.
+    let lower = text.to_ascii_lowercase();
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    loop {
+        match *open {
+            Some(name) => match find_tag(&lower, pos, &format!("</{}", name)) {
+                Some(end) => {
+                    out.push_str(&text[pos..end]);
+                    pos = end;
+                    *open = None;
+                }
+                None => {
+                    out.push_str(&text[pos..]);
+                    return out;
+                }
+            },
+            None => {
+                let next = PRESERVED
+                    .iter()
+                    .filter_map(|&name| {
+                        find_tag(&lower, pos, &format!("<{}", name)).map(|end| (end, name))
+                    })
+                    .min();
+                match next {
+                    Some((end, name)) => {
+                        let start = end - name.len() - 1;
+                        whitespace(&text[pos..start], tag, &mut out);
+                        out.push_str(&text[start..end]);
+                        pos = end;
+                        *open = Some(name);
+                    }
+                    None => {
+                        whitespace(&text[pos..], tag, &mut out);
+                        return out;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Finds the tag prefix, like `<pre` or `</pre`, in the lowercase text,
+/// returning the offset just past it. The prefix must end the element's
+/// name, so `<pre` doesn't match `<preview>`.
+fn find_tag(lower: &str, from: usize, prefix: &str) -> Option<usize> {
+    let mut pos = from;
+    while let Some(i) = lower[pos..].find(prefix) {
+        let end = pos + i + prefix.len();
+        match lower[end..].chars().next() {
+            None | Some('>') | Some('/') => return Some(end),
+            Some(c) if c.is_whitespace() => return Some(end),
+            _ => pos = end,
+        }
+    }
+    None
+}
+
+/// Appends the text with each run of whitespace between tags replaced by a
+/// newline, if the run contains one, or a space. Text within a tag is
+/// appended as is.
+fn whitespace(text: &str, tag: &mut Tag, out: &mut String) {
+    let mut run: Option<char> = None;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (*tag, c) {
+            (Tag::Outside, _) => (),
+            (Tag::Inside, '>') => *tag = Tag::Outside,
+            (Tag::Inside, '"') | (Tag::Inside, '\'') => *tag = Tag::Quoted(c),
+            (Tag::Quoted(quote), c) if c == quote => *tag = Tag::Inside,
+            _ => (),
+        }
+        if *tag != Tag::Outside {
+            out.push(c);
+            continue;
+        }
+
+        if c.is_whitespace() {
+            run = match run {
+                Some('\n') => Some('\n'),
+                _ if c == '\n' => Some('\n'),
+                _ => Some(' '),
+            };
+            continue;
+        }
+        if let Some(space) = run.take() {
+            out.push(space);
+        }
+        out.push(c);
+
+        // A `<` begins a tag when followed by its name, a closing slash, or
+        // the `!` of a comment or doctype.
+        if c == '<'
+            && chars
+                .peek()
+                .is_some_and(|&c| c.is_ascii_alphabetic() || c == '/' || c == '!')
+        {
+            *tag = Tag::Inside;
+        }
+    }
+    if let Some(space) = run {
+        out.push(space);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{LoadOptions, Statement};
+
+    fn minified(source: &str) -> Statement {
+        let options = LoadOptions {
+            minify: true,
+            ..LoadOptions::default()
+        };
+        Statement::parse_with(source, &options).unwrap()
+    }
+
+    #[test]
+    fn collapses_whitespace_runs() {
+        assert_eq!(
+            Statement::parse("<ul>\n<li>{{a}} <b>b</b></li>\n</ul>\n").unwrap(),
+            minified("<ul>\n    <li>{{a}}   <b>b</b></li>\n\n  </ul>\n")
+        );
+    }
+
+    #[test]
+    fn preserves_preformatted_elements() {
+        let source = "<pre class=\"code\">\n  {{a}}\n    b\n</pre>\n  <p>  c  </p>";
+        assert_eq!(
+            Statement::parse("<pre class=\"code\">\n  {{a}}\n    b\n</pre>\n<p> c </p>").unwrap(),
+            minified(source)
+        );
+
+        let source = "<SCRIPT>\n  let a  = 1;\n</SCRIPT>  <preview>  a  </preview>";
+        assert_eq!(
+            Statement::parse("<SCRIPT>\n  let a  = 1;\n</SCRIPT> <preview> a </preview>").unwrap(),
+            minified(source)
+        );
+    }
+
+    #[test]
+    fn preserves_tags() {
+        let source = "<input  value=\"a  b\"\n  title='{{t}}  x > y'>  c  <a  href=\"/\">\n\n  d</a>  1 <  2";
+        assert_eq!(
+            Statement::parse(
+                "<input  value=\"a  b\"\n  title='{{t}}  x > y'> c <a  href=\"/\">\nd</a> 1 < 2"
+            )
+            .unwrap(),
+            minified(source)
+        );
+    }
+}
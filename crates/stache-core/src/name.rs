@@ -0,0 +1,302 @@
+use std::fmt;
+
+/// An identifier name generator.
+#[derive(Debug)]
+pub struct Name {
+    pub base: String,
+    id: String,
+    next: usize,
+}
+
+impl Name {
+    /// Initialize a name generator with a base name. A unique identifier may
+    /// then be generated with the `next` and `to_string` functions.
+    pub fn new(base: &str) -> Self {
+        Name {
+            base: String::from(base),
+            id: identifier(base),
+            next: 0,
+        }
+    }
+
+    /// Initialize a name generator whose identifier is a short hash of the
+    /// base name, `t_<hash>`, rather than the base name itself. Deeply nested
+    /// template paths otherwise produce very long symbol names.
+    pub fn short(base: &str) -> Self {
+        Name {
+            base: String::from(base),
+            id: format!("{}{:016x}", MANGLE, long_hash(base)),
+            next: 0,
+        }
+    }
+
+    /// Advances the generator to the next unique identifier. When passing
+    /// a `Name` through recursive function calls, this can be called before
+    /// the next recursion to increment the depth of the generated
+    /// identifiers.
+    pub fn next(&mut self) -> &mut Self {
+        self.next = self.next + 1;
+        self
+    }
+}
+
+impl Name {
+    /// Returns a valid identifier created from the template's short name to
+    /// be used in function or variable names generated from this template
+    /// file: `include/header -> include_header`.
+    ///
+    /// Distinct names always produce distinct identifiers. A name containing
+    /// characters other than letters, digits, and path separators, which
+    /// can't be recovered from the identifier, is suffixed with a hash of the
+    /// full name: `include/page-header -> include_page_header__d7111353`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl fmt::Display for Name {
+    /// Creates a unique identifier to be used as a variable or function name.
+    /// The counter is separated from the identifier so `a1` at step 2 and
+    /// `a` at step 12 remain distinct.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}_{}", self.id, self.next)
+    }
+}
+
+/// C keywords and symbols defined by the generated runtime, which template
+/// identifiers must never shadow.
+const RESERVED: &[&str] = &[
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "append_content",
+    "append_value",
+    "append_escaped",
+    "append_string",
+    "begin_line",
+    "buffer",
+    "buffer_append",
+    "buffer_clear",
+    "buffer_data_type",
+    "buffer_destroy",
+    "buffer_finish",
+    "buffer_free",
+    "buffer_init",
+    "buffer_length",
+    "buffer_mark",
+    "buffer_memsize",
+    "buffer_reserve",
+    "buffer_resize",
+    "buffer_result",
+    "buffer_slice",
+    "buffer_string",
+    "buffer_trim",
+    "context_fetch",
+    "dynamic_partial",
+    "escape_html",
+    "expand",
+    "expand_block",
+    "expand_text",
+    "falsy",
+    "fetch",
+    "fetch_path",
+    "find_text",
+    "format_integer",
+    "html_escaped_cat",
+    "id_buf",
+    "id_call",
+    "id_miss",
+    "id_to_s",
+    "indent",
+    "indent_pop",
+    "indent_push",
+    "init_templates",
+    "inverted",
+    "lambda",
+    "memoize",
+    "missing_value",
+    "next_tag",
+    "optimized_escape_html",
+    "partial_name",
+    "path",
+    "path_name",
+    "render",
+    "section",
+    "section_end",
+    "stack",
+    "string_copy",
+    "string_value",
+    "tag",
+    "tag_path",
+    "templates_clear_cache",
+    "templates_get_buf",
+    "templates_init",
+    "template_not_found",
+    "templates_render_trace",
+    "trace_lookup",
+    "trace_render",
+    "trace_stop",
+    "type_of",
+    "value_text",
+    "write_indent",
+    "Buffer",
+    "DOT",
+];
+
+/// The prefix marking a mangled identifier.
+const MANGLE: &str = "t_";
+
+/// Replaces each non-word character in the name with an underscore. The
+/// identifier is computed once per generator rather than on each unique name
+/// it formats.
+///
+/// Names that are reserved, begin with a digit, or already begin with the
+/// mangling prefix are prefixed with `t_`, so `static -> t_static` can't
+/// collide with a template named `t/static -> t_t_static`.
+fn identifier(base: &str) -> String {
+    let mut id: String = base
+        .chars()
+        .map(|c| {
+            if c == '_' || c.is_alphanumeric() {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    // Only letters and separators between non-empty path segments map
+    // directly, so a plain identifier never contains `__`.
+    let plain = base
+        .split('/')
+        .all(|segment| !segment.is_empty() && segment.chars().all(char::is_alphanumeric));
+    if !plain {
+        id.push_str(&format!("__{:08x}", hash(base)));
+    }
+
+    let mangle = RESERVED.contains(&id.as_str())
+        || id.starts_with(MANGLE)
+        || id.is_empty()
+        || id.starts_with(char::is_numeric);
+
+    if mangle {
+        format!("{}{}", MANGLE, id)
+    } else {
+        id
+    }
+}
+
+/// Computes the 32-bit FNV-1a hash of the name, which is stable across
+/// compiler versions and platforms so generated symbols are reproducible.
+fn hash(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Computes the 64-bit FNV-1a hash of the name, for short names that stand
+/// in for the whole name rather than suffixing it.
+fn long_hash(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Name;
+
+    #[test]
+    fn id() {
+        let name = Name::new("include/header");
+        assert_eq!("include_header", name.id());
+    }
+
+    #[test]
+    fn mangles_reserved_words() {
+        assert_eq!("t_static", Name::new("static").id());
+        assert_eq!("t_render", Name::new("render").id());
+        assert_eq!("t_t_static", Name::new("t/static").id());
+        assert_eq!("t_404", Name::new("404").id());
+        assert_eq!("statics", Name::new("statics").id());
+    }
+
+    #[test]
+    fn distinguishes_lossy_names() {
+        let names = ["a/b", "a_b", "a-b", "a.b", "a//b", "a/b/", "", "_"];
+        let ids: Vec<_> = names
+            .iter()
+            .map(|name| Name::new(name).id().to_string())
+            .collect();
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(
+                1,
+                ids.iter().filter(|other| *other == id).count(),
+                "{}",
+                names[i]
+            );
+        }
+
+        assert_eq!("a_b", ids[0]);
+        assert!(ids[1].starts_with("a_b__"));
+        assert_eq!(13, ids[1].len());
+        assert_eq!(
+            "include_page_header__d7111353",
+            Name::new("include/page-header").id()
+        );
+    }
+
+    #[test]
+    fn short() {
+        let mut name = Name::short("app/views/admin/settings/billing/invoices/line_item");
+        assert_eq!(18, name.id().len());
+        assert!(name.id().starts_with("t_"));
+        assert_ne!(Name::short("a/b").id(), Name::short("a_b").id());
+
+        name.next();
+        assert_eq!(format!("{}_1", name.id()), name.to_string());
+    }
+
+    #[test]
+    fn next() {
+        let mut name = Name::new("include/header");
+        assert_eq!("include_header_0", name.to_string());
+
+        name.next();
+        assert_eq!("include_header_1", name.to_string());
+
+        name.next();
+        assert_eq!("include_header_2", name.to_string());
+    }
+}
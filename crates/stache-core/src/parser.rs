@@ -0,0 +1,1322 @@
+//! The Mustache template parser.
+//!
+//! Templates are parsed in one pass over the source with this grammar, in
+//! which each alternative is tried in order:
+//!
+//! ```text
+//! program     = statement* EOI
+//! statement   = content | comment | delimiters | pragma | section | variable | partial | html
+//! content     = (!(open | standalone) ANY)+
+//! variable    = open path ("|" identifier)* close
+//! html        = "{{{" path "}}}" | "{{&" path close
+//! comment     = line(comment_tag) | comment_tag
+//! delimiters  = line(delimiters_tag) | delimiters_tag
+//! pragma      = line(pragma_tag) | pragma_tag
+//! partial     = line(partial_tag) | partial_tag
+//! section     = (indent open_tag terminator | open_tag) statement* (otherwise statement*)?
+//!               (line(close_tag) | close_tag)
+//! otherwise   = line(else_tag) | else_tag
+//!
+//! comment_tag     = "{{!" (!close ANY)* close
+//! delimiters_tag  = "{{=" delimiter delimiter "=}}"
+//! pragma_tag      = "{{%" identifier close
+//! partial_tag     = "{{>" ("*" path | name argument*) close
+//! open_tag        = ("{{#" | "{{^" | "{{$") path close | "{{<" name close
+//! close_tag       = "{{/" <the open tag's path or name> close
+//! else_tag        = "{{" "else" close
+//! standalone      = line(open_tag | "{{/" path close | partial_tag | comment_tag | delimiters_tag
+//!                        | pragma_tag | else_tag)
+//!
+//! line(tag)   = indent tag (terminator | EOI)
+//! indent      = (" " | "\t")*
+//! terminator  = "\r"? "\n"
+//! path        = "." | "@"? identifier ("." identifier)*
+//! identifier  = ([a-zA-Z0-9] | "-" | "_" | "?" | "!")+
+//! name        = ([a-zA-Z0-9] | "-" | "_" | "/")+
+//! argument    = identifier "=" path
+//! delimiter   = (!(whitespace | "=") ANY)+
+//! whitespace  = " " | "\t" | "\r" | "\n"
+//! ```
+//!
+//! Literals beginning with `{{` or ending with `}}` are read as the
+//! delimiters active at their position. Whitespace may separate the parts
+//! of a tag, but not the characters of a path or name.
+//!
+//! A `~` just inside a tag's open or close delimiter, as in `{{~name}}` or
+//! `{{/items~}}`, trims all whitespace from the content before or after the
+//! tag, including line terminators.
+//!
+//! An `{{else}}` tag ends a section's block and begins an inverted section
+//! on the same path, or the reverse in an inverted section. Outside of one,
+//! it's a variable named `else`.
+//!
+//! The Handlebars dialect adds a few of its own syntaxes, mapped onto the
+//! same statements:
+//!
+//! ```text
+//! comment_tag = "{{!--" (!("--" close) ANY)* "--" close | ...
+//! path        = "this" ("." identifier)* | ...
+//! ```
+//!
+//! A `{{!-- --}}` comment may contain `}}`. A `this` path names the current
+//! context like `.`, and `this.name` looks up `name`. An `{{else}}` tag
+//! outside of a section is an error.
+//!
+//! Translation tags are parsed when the load options enable them, and are
+//! tried before variables:
+//!
+//! ```text
+//! translation = open "t" whitespace+ "\"" (!("\"" | terminator) ANY)+ "\"" close
+//! ```
+//!
+//! With the extended identifiers option, identifiers and names may also
+//! contain `$`, `@`, and non-ASCII letters. With the front matter option, a
+//! leading YAML block between `---` lines is skipped rather than parsed.
+//!
+//! A UTF-8 byte order mark beginning the source is skipped unless the
+//! options keep it, and with the normalize newlines option, `\r\n` line
+//! terminators in content and section source are read as `\n`.
+//!
+//! A `line` tag whose indentation begins a line stands alone: its
+//! indentation and terminator are removed from the output. Elsewhere, they
+//! are kept as content around the tag.
+
+use std::collections::HashSet;
+use std::mem;
+
+use delimiters::Delimiters;
+use front_matter;
+
+use super::{Block, LoadOptions, ParseError, Path, Pragma, Span, Statement};
+
+/// The deepest nesting of sections a template may have unless the parser is
+/// given its own limit.
+///
+/// The parser, the IR lowering, and the C backends walk templates with
+/// explicit stacks, but the renderer and the other backends recurse once for
+/// each nested section, so this limit also bounds their stack use. A limit
+/// raised far beyond it may overflow the stack of those targets.
+pub const MAX_DEPTH: usize = 1000;
+
+/// The template syntax a parser accepts.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dialect {
+    #[default]
+    Mustache,
+    /// Mustache with the `{{!-- --}}` comments and `{{this}}` paths of the
+    /// Handlebars templates it's compatible with. `{{else}}` is reserved
+    /// for sections.
+    Handlebars,
+}
+
+/// A tag that may stand alone on its line, located by byte offsets: its
+/// indentation begins at `start`, the tag itself at `tag`, and its line
+/// terminator, if it has one, at `terminator`, running through `end`.
+#[derive(Clone, Copy, Debug)]
+pub struct Candidate {
+    pub start: usize,
+    pub tag: usize,
+    pub terminator: usize,
+    pub end: usize,
+}
+
+/// The name of the template a partial tag renders: written in the tag, or
+/// looked up in the context at render time with `{{>*name}}`.
+enum PartialName {
+    Static(String, Vec<(String, Path)>),
+    Dynamic(Path),
+}
+
+impl PartialName {
+    fn statement(self, padding: Option<String>) -> Statement {
+        match self {
+            PartialName::Static(name, arguments) => Statement::Partial(name, padding, arguments),
+            PartialName::Dynamic(path) => Statement::DynamicPartial(path, padding),
+        }
+    }
+}
+
+/// The statement a section's open tag begins.
+#[derive(Clone, Copy)]
+enum Kind {
+    Section,
+    Inverted,
+    Block,
+    Parent,
+}
+
+/// Whitespace around a tag kept as content, and its span.
+type Text = (String, Span);
+
+/// A section whose open tag has been parsed, awaiting the close tag that
+/// ends its block.
+struct Frame<'a> {
+    kind: Kind,
+    name: &'a str,
+    path: Path,
+    start: usize,
+    body: usize,
+    leading: Option<Text>,
+    terminator: Option<Text>,
+}
+
+/// The tree built from a template's source, along with the tags that may
+/// stand alone on their lines.
+pub struct Parser<'a> {
+    source: &'a str,
+    dialect: Dialect,
+    max_depth: usize,
+    delimiters: Delimiters,
+    lines: Vec<usize>,
+    furthest: usize,
+    candidates: Vec<Candidate>,
+    inverse: bool,
+    trims: HashSet<usize>,
+    translations: bool,
+    extended: bool,
+    newlines: bool,
+    start: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Parser::with(source, &LoadOptions::default())
+    }
+
+    /// Creates a parser for the options' dialect, failing on templates with
+    /// sections nested more deeply than the options allow.
+    pub fn with(source: &'a str, options: &LoadOptions) -> Self {
+        let source = strip_bom(source, options);
+        Parser {
+            source,
+            dialect: options.dialect,
+            max_depth: options.max_depth,
+            delimiters: Delimiters::new(source),
+            lines: lines(source),
+            furthest: 0,
+            candidates: Vec::new(),
+            inverse: false,
+            trims: HashSet::new(),
+            translations: options.translations,
+            extended: options.extended_identifiers,
+            newlines: options.normalize_newlines,
+            start: if options.front_matter {
+                front_matter::split(source).map_or(0, |(_, end)| end)
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Returns the tags parsed as standalone line candidates, in source
+    /// order.
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+
+    /// Parses the template source into a program statement.
+    ///
+    /// Open sections are kept on a stack rather than parsed recursively, so
+    /// deeply nested templates can't overflow the call stack; nesting beyond
+    /// the parser's depth limit fails with `ParseError::TooDeep`. A section
+    /// closed by another's tag, or left open, reports where it was opened.
+    /// Otherwise, a syntax error reports the furthest position the parser
+    /// reached.
+    pub fn program(&mut self) -> Result<Statement, ParseError> {
+        let mut stack: Vec<(Frame<'a>, Vec<(Statement, Span)>)> = Vec::new();
+        let mut statements = Vec::new();
+        let mut pos = self.start;
+
+        loop {
+            self.track(pos);
+
+            // Only a section's else tag begins an inverse; elsewhere, it's a
+            // variable in Mustache and an error in Handlebars.
+            let section = stack.last().map(|(frame, _)| frame.kind);
+            self.inverse = self.dialect == Dialect::Handlebars
+                || matches!(section, Some(Kind::Section) | Some(Kind::Inverted));
+            if let Some(end) = self.content(pos, &mut statements) {
+                pos = end;
+            } else if let Some(end) = self.comment(pos, &mut statements) {
+                pos = end;
+            } else if let Some(end) = self.set_delimiters(pos, &mut statements) {
+                pos = end;
+            } else if let Some(end) = self.pragma(pos, &mut statements) {
+                pos = end?;
+            } else if let Some((frame, end)) = self.section(pos) {
+                if stack.len() == self.max_depth {
+                    return Err(ParseError::TooDeep {
+                        position: frame.path.span.start,
+                        limit: self.max_depth,
+                    });
+                }
+                stack.push((frame, mem::take(&mut statements)));
+                pos = end;
+            } else if self.otherwise(pos) {
+                let (frame, outer) = match stack.pop() {
+                    Some((frame, outer)) => (frame, outer),
+                    None => return Err(ParseError::UnexpectedToken(self.indent(pos))),
+                };
+                let inner = mem::replace(&mut statements, outer);
+                let (frame, end) = self
+                    .inverse(pos, frame, inner, &mut statements)
+                    .ok_or(ParseError::UnexpectedToken(self.indent(pos)))?;
+                stack.push((frame, mem::take(&mut statements)));
+                pos = end;
+            } else if let Some((key, end)) = self.translation(pos) {
+                statements.push((Statement::Translation(key), self.locate(pos, end)));
+                pos = end;
+            } else if let Some((variable, end)) = self.variable(pos) {
+                statements.push((variable, self.locate(pos, end)));
+                pos = end;
+            } else if let Some(end) = self.partial(pos, &mut statements) {
+                pos = end;
+            } else if let Some((path, end)) = self.html(pos) {
+                statements.push((Statement::Html(path), self.locate(pos, end)));
+                pos = end;
+            } else if let Some((frame, outer)) = stack.pop() {
+                let inner = mem::replace(&mut statements, outer);
+                let (name, opened) = (frame.name, frame.path.span);
+                pos = match self.finish(pos, frame, inner, &mut statements) {
+                    Some(end) => end,
+                    None => return Err(self.unclosed(pos, name, opened)),
+                };
+            } else if pos == self.source.len() {
+                break;
+            } else {
+                return Err(ParseError::UnexpectedToken(self.furthest));
+            }
+        }
+
+        let mut block = Block::spanned(statements);
+        self.trim(&mut block);
+        block.source = self.source.into();
+        block.span = self.locate(0, self.source.len());
+        Ok(Statement::Program(block))
+    }
+
+    /// Locates the byte range in the template source. Lines end at `\n`,
+    /// `\r\n`, or a lone `\r`, and columns count characters.
+    pub fn locate(&self, start: usize, end: usize) -> Span {
+        let line = match self.lines.binary_search(&start) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+
+        // Between a `\r` and its `\n` is the start of the next line.
+        let bytes = self.source.as_bytes();
+        if start > 0 && bytes[start - 1] == b'\r' && bytes.get(start) == Some(&b'\n') {
+            return Span::new(start, end, line + 2, 1);
+        }
+
+        let column = self.source[self.lines[line]..start].chars().count() + 1;
+        Span::new(start, end, line + 1, column)
+    }
+
+    /// Records a failed match at the byte offset, to report the furthest
+    /// point the parser reached if the template has a syntax error.
+    fn track(&mut self, pos: usize) {
+        self.furthest = self.furthest.max(pos);
+    }
+
+    fn fail<T>(&mut self, pos: usize) -> Option<T> {
+        self.track(pos);
+        None
+    }
+
+    /// Parses text up to the next tag, or up to the indentation of a tag
+    /// that may stand alone on its line.
+    fn content(&mut self, pos: usize, out: &mut Vec<(Statement, Span)>) -> Option<usize> {
+        let mut end = pos;
+        while let Some(c) = self.source[end..].chars().next() {
+            if self.open(end, "").is_some() {
+                break;
+            }
+            if c == ' ' || c == '\t' {
+                let tag = self.indent(end);
+                if self.standalone(tag) {
+                    break;
+                }
+                end = tag;
+            } else {
+                end += c.len_utf8();
+            }
+        }
+        self.track(end);
+
+        match end - pos {
+            0 => None,
+            _ => {
+                let text = self.slice(pos, end);
+                out.push((Statement::Content(text), self.locate(pos, end)));
+                Some(end)
+            }
+        }
+    }
+
+    /// Tests whether a tag that may stand alone begins at the byte offset
+    /// and ends its line.
+    fn standalone(&mut self, pos: usize) -> bool {
+        let tag = self
+            .open_tag(pos)
+            .map(|(_, end)| end)
+            .or_else(|| self.close_tag(pos))
+            .or_else(|| self.partial_tag(pos).map(|(_, end)| end))
+            .or_else(|| self.comment_tag(pos).map(|(_, end)| end))
+            .or_else(|| self.delimiters_tag(pos))
+            .or_else(|| self.pragma_tag(pos).map(|(_, end)| end))
+            .or_else(|| {
+                if self.inverse {
+                    self.else_tag(pos)
+                } else {
+                    None
+                }
+            });
+        tag.and_then(|end| self.line_end(end)).is_some()
+    }
+
+    fn comment(&mut self, pos: usize, out: &mut Vec<(Statement, Span)>) -> Option<usize> {
+        if let Some((text, line)) = self.line(pos, true, Parser::comment_tag) {
+            self.candidates.push(line);
+            self.stand(line, Some(Statement::Comment(text)), out);
+            return Some(line.end);
+        }
+
+        let (text, end) = self.comment_tag(pos)?;
+        out.push((Statement::Comment(text), self.locate(pos, end)));
+        Some(end)
+    }
+
+    /// Parses a Set Delimiter tag, which adds no statement of its own. The
+    /// delimiters it sets were found before parsing began.
+    fn set_delimiters(&mut self, pos: usize, out: &mut Vec<(Statement, Span)>) -> Option<usize> {
+        let tag = |parser: &mut Self, pos| parser.delimiters_tag(pos).map(|end| ((), end));
+        if let Some(((), line)) = self.line(pos, true, tag) {
+            self.stand(line, None, out);
+            return Some(line.end);
+        }
+
+        self.delimiters_tag(pos)
+    }
+
+    /// Parses a pragma tag, failing with an error rather than a mismatch when
+    /// the tag names a pragma that isn't recognized.
+    fn pragma(
+        &mut self,
+        pos: usize,
+        out: &mut Vec<(Statement, Span)>,
+    ) -> Option<Result<usize, ParseError>> {
+        if let Some(((name, at), line)) = self.line(pos, true, Parser::pragma_tag) {
+            let pragma = match Pragma::parse(&name) {
+                Some(pragma) => pragma,
+                None => return Some(Err(ParseError::UnknownPragma { name, position: at })),
+            };
+            self.candidates.push(line);
+            self.stand(line, Some(Statement::Pragma(pragma)), out);
+            return Some(Ok(line.end));
+        }
+
+        let ((name, at), end) = self.pragma_tag(pos)?;
+        match Pragma::parse(&name) {
+            Some(pragma) => {
+                out.push((Statement::Pragma(pragma), self.locate(pos, end)));
+                Some(Ok(end))
+            }
+            None => Some(Err(ParseError::UnknownPragma { name, position: at })),
+        }
+    }
+
+    fn partial(&mut self, pos: usize, out: &mut Vec<(Statement, Span)>) -> Option<usize> {
+        if let Some((name, line)) = self.line(pos, true, Parser::partial_tag) {
+            self.candidates.push(line);
+
+            // Standalone partial indents its lines by the leading whitespace.
+            let standalone = self.locate(line.start, line.start).column == 1;
+            let indent = if standalone && line.tag > line.start {
+                Some(self.source[line.start..line.tag].into())
+            } else {
+                None
+            };
+            self.stand(line, Some(name.statement(indent)), out);
+            return Some(line.end);
+        }
+
+        let (name, end) = self.partial_tag(pos)?;
+        out.push((name.statement(None), self.locate(pos, end)));
+        Some(end)
+    }
+
+    /// Parses a section's open tag, beginning its block.
+    fn section(&mut self, pos: usize) -> Option<(Frame<'a>, usize)> {
+        if let Some(((kind, path, name), line)) = self.line(pos, false, Parser::open_tag) {
+            self.candidates.push(line);
+            let mut frame = Frame {
+                kind,
+                name,
+                path,
+                start: pos,
+                body: line.end,
+                leading: None,
+                terminator: None,
+            };
+
+            // Inline open tag emits leading whitespace and line terminator.
+            if self.locate(pos, pos).column != 1 {
+                frame.start = line.tag;
+                frame.leading = self.text(pos, line.tag);
+                frame.terminator = self.text(line.terminator, line.end);
+            }
+            return Some((frame, line.end));
+        }
+
+        let ((kind, path, name), end) = self.open_tag(pos)?;
+        let frame = Frame {
+            kind,
+            name,
+            path,
+            start: pos,
+            body: end,
+            leading: None,
+            terminator: None,
+        };
+        Some((frame, end))
+    }
+
+    /// Parses the close tag of the innermost open section, adding the
+    /// fully formed section, along with any whitespace its inline tags
+    /// emit, to the enclosing block.
+    fn finish(
+        &mut self,
+        pos: usize,
+        frame: Frame<'a>,
+        statements: Vec<(Statement, Span)>,
+        out: &mut Vec<(Statement, Span)>,
+    ) -> Option<usize> {
+        let name = frame.name;
+        let tag = |parser: &mut Self, pos| parser.section_close(pos, name);
+        let (_, end, trailing) = self.end_block(pos, frame, statements, tag, out)?;
+        let content = |(text, span)| (Statement::Content(text), span);
+        out.extend(trailing.map(content));
+        Some(end)
+    }
+
+    /// Parses an `{{else}}` tag in the innermost open section, adding the
+    /// section to the enclosing block and returning the frame of the
+    /// section it begins: an inverted section on the same path, or the
+    /// reverse. The else tag's line terminator, if it's inline, begins the
+    /// new section's block.
+    fn inverse(
+        &mut self,
+        pos: usize,
+        frame: Frame<'a>,
+        statements: Vec<(Statement, Span)>,
+        out: &mut Vec<(Statement, Span)>,
+    ) -> Option<(Frame<'a>, usize)> {
+        let kind = match frame.kind {
+            Kind::Section => Kind::Inverted,
+            Kind::Inverted => Kind::Section,
+            Kind::Block | Kind::Parent => return None,
+        };
+        let (name, path) = (frame.name, frame.path.clone());
+        let tag = |parser: &mut Self, pos| parser.else_tag(pos);
+        let (start, end, terminator) = self.end_block(pos, frame, statements, tag, out)?;
+        let frame = Frame {
+            kind,
+            name,
+            path,
+            start,
+            body: end,
+            leading: None,
+            terminator,
+        };
+        Some((frame, end))
+    }
+
+    /// Parses the tag ending the innermost open section's block, standing
+    /// alone on its line or inline, and adds the fully formed section to the
+    /// enclosing block. Returns the tag's start and end, along with the line
+    /// terminator an inline tag emits after itself.
+    fn end_block<F>(
+        &mut self,
+        pos: usize,
+        frame: Frame<'a>,
+        statements: Vec<(Statement, Span)>,
+        tag: F,
+        out: &mut Vec<(Statement, Span)>,
+    ) -> Option<(usize, usize, Option<Text>)>
+    where
+        F: Fn(&mut Self, usize) -> Option<usize> + Copy,
+    {
+        let line = self.line(pos, true, |parser, pos| {
+            tag(parser, pos).map(|end| ((), end))
+        });
+        let (start, end, inner, trailing) = match line {
+            Some(((), line)) => {
+                self.candidates.push(line);
+                if self.locate(pos, pos).column == 1 {
+                    (pos, line.end, None, None)
+                } else {
+                    let inner = self.text(pos, line.tag);
+                    let trailing = self.text(line.terminator, line.end);
+                    (line.tag, line.end, inner, trailing)
+                }
+            }
+            None => (pos, tag(self, pos)?, None, None),
+        };
+
+        let mut block = Block::spanned(statements);
+        block.source = self.slice(frame.body, pos);
+        block.span = self.locate(frame.body, pos);
+
+        // Inline open tag emits line terminator.
+        if let Some((text, span)) = frame.terminator {
+            block.prepend(Statement::Content(text), span);
+        }
+
+        // Inline close tag emits leading whitespace.
+        if let Some((text, span)) = inner {
+            block.append(Statement::Content(text), span);
+        }
+        self.trim(&mut block);
+
+        let section = match frame.kind {
+            Kind::Inverted => Statement::Inverted(frame.path, block),
+            Kind::Section => Statement::Section(frame.path, block),
+            Kind::Block => Statement::Block(frame.path.to_string(), block),
+            Kind::Parent => Statement::Parent(frame.path.to_string(), block),
+        };
+
+        // The section's span excludes whitespace an inline tag emits.
+        let close = trailing.as_ref().map_or(end, |&(_, span)| span.start);
+        let content = |(text, span)| (Statement::Content(text), span);
+        out.extend(frame.leading.map(content));
+        out.push((section, self.locate(frame.start, close)));
+        Some((start, end, trailing))
+    }
+
+    /// Explains why the innermost open section couldn't be closed at the
+    /// byte offset: another section's close tag is in its place, the
+    /// template ended, or the template has a syntax error.
+    fn unclosed(&mut self, pos: usize, name: &str, opened: Span) -> ParseError {
+        let furthest = self.furthest;
+        let tag = self
+            .open(self.indent(pos), "/")
+            .and_then(|end| self.expect_path(end))
+            .and_then(|(path, end)| self.expect_close(end, "").map(|_| path.span));
+
+        if let Some(closed) = tag {
+            ParseError::Mismatched {
+                name: name.into(),
+                opened,
+                found: self.source[closed.start..closed.end].into(),
+                closed,
+            }
+        } else if pos == self.source.len() {
+            ParseError::Unclosed {
+                name: name.into(),
+                opened,
+            }
+        } else {
+            ParseError::UnexpectedToken(furthest)
+        }
+    }
+
+    /// Parses a tag with optional indentation, ending its line. At the end
+    /// of input, `eoi` allows the tag to stand alone without a terminator.
+    fn line<T, F>(&mut self, pos: usize, eoi: bool, tag: F) -> Option<(T, Candidate)>
+    where
+        F: FnOnce(&mut Self, usize) -> Option<(T, usize)>,
+    {
+        let start = self.indent(pos);
+        let (value, close) = tag(self, start)?;
+        let end = match self.terminator(close) {
+            Some(end) => end,
+            None if eoi && close == self.source.len() => close,
+            None => return None,
+        };
+        let line = Candidate {
+            start: pos,
+            tag: start,
+            terminator: close,
+            end,
+        };
+        Some((value, line))
+    }
+
+    /// Adds the statements of a tag that may stand alone on its line. A
+    /// standalone tag consumes its leading whitespace and line terminator,
+    /// while an inline tag emits them as content around its statement.
+    fn stand(&self, line: Candidate, tag: Option<Statement>, out: &mut Vec<(Statement, Span)>) {
+        if self.locate(line.start, line.start).column == 1 {
+            out.extend(tag.map(|tag| (tag, self.locate(line.start, line.end))));
+            return;
+        }
+
+        let content = |(text, span)| (Statement::Content(text), span);
+        out.extend(self.text(line.start, line.tag).map(content));
+        out.extend(tag.map(|tag| (tag, self.locate(line.tag, line.terminator))));
+        out.extend(self.text(line.terminator, line.end).map(content));
+    }
+
+    /// Strips the whitespace that `~` markers in the tags around the block's
+    /// content trim, dropping content left empty.
+    fn trim(&self, block: &mut Block) {
+        let mut index = 0;
+        while index < block.statements.len() {
+            let span = block.spans[index];
+            let (start, end) = self.trimmed(span.start, span.end);
+            let content = matches!(block.statements[index], Statement::Content(_));
+            if !content || (start, end) == (span.start, span.end) {
+                index += 1;
+                continue;
+            }
+
+            match self.text(start, end) {
+                Some((text, span)) => {
+                    block.statements[index] = Statement::Content(text);
+                    block.spans[index] = span;
+                    index += 1;
+                }
+                None => {
+                    block.statements.remove(index);
+                    block.spans.remove(index);
+                }
+            }
+        }
+    }
+
+    /// Narrows the byte range of content to exclude the whitespace trimmed
+    /// by the tags around it.
+    fn trimmed(&self, start: usize, end: usize) -> (usize, usize) {
+        let text = &self.source[start..end];
+        let mut range = (start, end);
+
+        // A tag ending with `~` before the content trims its start.
+        let before = self.source[..start].trim_end_matches(WHITESPACE).len();
+        if self.trims.contains(&before) {
+            range.0 = end - text.trim_start_matches(WHITESPACE).len();
+        }
+
+        // A tag beginning with `~` after the content trims its end.
+        let after = self.skip(end);
+        let (open, _) = self.delimiters.at(after);
+        if self.source[after..].starts_with(open)
+            && self.source[after + open.len()..].starts_with('~')
+        {
+            range.1 = range.0.max(start + text.trim_end_matches(WHITESPACE).len());
+        }
+
+        range
+    }
+
+    /// Returns the source text in the byte range and its span, if the range
+    /// isn't empty.
+    fn text(&self, start: usize, end: usize) -> Option<Text> {
+        match end - start {
+            0 => None,
+            _ => Some((self.slice(start, end), self.locate(start, end))),
+        }
+    }
+
+    /// Copies the source between the byte offsets, with `\r\n` line
+    /// terminators normalized to `\n` when the options enable it.
+    fn slice(&self, start: usize, end: usize) -> String {
+        let text = &self.source[start..end];
+        if self.newlines {
+            text.replace("\r\n", "\n")
+        } else {
+            text.into()
+        }
+    }
+
+    /// Parses a variable tag, whose path may be followed by the names of
+    /// filters its value is passed through in order, like `{{ a | upcase }}`.
+    fn variable(&mut self, pos: usize) -> Option<(Statement, usize)> {
+        let tag = self
+            .open(pos, "")
+            .and_then(|end| self.expect_path(end))
+            .and_then(|(path, mut end)| {
+                let mut filters = Vec::new();
+                while let Some((filter, next)) = self.filter(end) {
+                    filters.push(filter);
+                    end = next;
+                }
+                let variable = if filters.is_empty() {
+                    Statement::Variable(path)
+                } else {
+                    Statement::Filtered(path, filters)
+                };
+                self.expect_close(end, "").map(|end| (variable, end))
+            });
+        tag.or_else(|| self.fail(pos))
+    }
+
+    /// Parses a translation tag, like `{{t "checkout.title"}}`, returning its
+    /// key. A `t` not followed by a quoted key is left to parse as a variable.
+    fn translation(&mut self, pos: usize) -> Option<(String, usize)> {
+        if !self.translations {
+            return None;
+        }
+
+        let start = self.skip(self.open(pos, "")?);
+        if !self.source[start..].starts_with('t') {
+            return None;
+        }
+        let quote = self.skip(start + 1);
+        if quote == start + 1 || !self.source[quote..].starts_with('"') {
+            return None;
+        }
+
+        let key = quote + 1;
+        let end = match self.source[key..].find(['"', '\r', '\n']) {
+            Some(0) | None => return self.fail(key),
+            Some(len) => key + len,
+        };
+        if !self.source[end..].starts_with('"') {
+            return self.fail(end);
+        }
+
+        let tag = self
+            .expect_close(end + 1, "")
+            .map(|close| (self.source[key..end].into(), close));
+        tag.or_else(|| self.fail(pos))
+    }
+
+    fn filter(&mut self, pos: usize) -> Option<(String, usize)> {
+        let start = self.skip(pos);
+        if !self.source[start..].starts_with('|') {
+            return None;
+        }
+
+        let start = self.skip(start + 1);
+        match self.identifier(start) {
+            Some(end) => Some((self.source[start..end].into(), end)),
+            None => self.fail(start),
+        }
+    }
+
+    fn html(&mut self, pos: usize) -> Option<(Path, usize)> {
+        let triple = self
+            .open(pos, "{")
+            .and_then(|end| self.expect_path(end))
+            .and_then(|(path, end)| self.expect_close(end, "}").map(|end| (path, end)));
+        let tag = triple.or_else(|| {
+            self.open(pos, "&")
+                .and_then(|end| self.expect_path(end))
+                .and_then(|(path, end)| self.expect_close(end, "").map(|end| (path, end)))
+        });
+        tag.or_else(|| self.fail(pos))
+    }
+
+    /// Parses a comment tag, which in the Handlebars dialect may be written
+    /// `{{!-- --}}` to contain the close delimiter.
+    fn comment_tag(&mut self, pos: usize) -> Option<(String, usize)> {
+        let block = match self.dialect {
+            Dialect::Handlebars => self.open(pos, "!--"),
+            Dialect::Mustache => None,
+        };
+        let (start, sigil) = match block.or_else(|| self.open(pos, "!")) {
+            Some(end) if block.is_some() => (self.skip(end), "--"),
+            Some(end) => (self.skip(end), ""),
+            None => return self.fail(pos),
+        };
+
+        let mut end = start;
+        loop {
+            if let Some(close) = self.close(end, sigil) {
+                let text = self.source[start..end].trim_matches(WHITESPACE);
+                return Some((text.into(), close));
+            }
+            match self.source[end..].chars().next() {
+                Some(c) => end += c.len_utf8(),
+                None => {
+                    self.track(end);
+                    return self.fail(pos);
+                }
+            }
+        }
+    }
+
+    fn delimiters_tag(&mut self, pos: usize) -> Option<usize> {
+        let tag = self
+            .open(pos, "=")
+            .and_then(|end| self.expect_delimiter(end))
+            .and_then(|end| self.expect_delimiter(end))
+            .and_then(|end| self.expect_close(end, "="));
+        tag.or_else(|| self.fail(pos))
+    }
+
+    /// Parses a pragma tag into the pragma's name and its byte offset.
+    fn pragma_tag(&mut self, pos: usize) -> Option<((String, usize), usize)> {
+        let start = match self.open(pos, "%") {
+            Some(end) => self.skip(end),
+            None => return self.fail(pos),
+        };
+        let tag = match self.identifier(start) {
+            Some(end) => self
+                .expect_close(end, "")
+                .map(|close| ((self.source[start..end].to_string(), start), close)),
+            None => self.fail(start),
+        };
+        tag.or_else(|| self.fail(pos))
+    }
+
+    fn partial_tag(&mut self, pos: usize) -> Option<(PartialName, usize)> {
+        let start = match self.open(pos, ">") {
+            Some(end) => self.skip(end),
+            None => return self.fail(pos),
+        };
+
+        let dynamic = if self.source[start..].starts_with('*') {
+            self.expect_path(start + 1)
+                .map(|(path, end)| (PartialName::Dynamic(path), end))
+        } else {
+            self.fail(start)
+        };
+        let name = dynamic.or_else(|| {
+            let mut end = self.expect_name(start)?;
+            let name = self.source[start..end].into();
+            let mut arguments = Vec::new();
+            while let Some((argument, next)) = self.argument(end) {
+                arguments.push(argument);
+                end = next;
+            }
+            Some((PartialName::Static(name, arguments), end))
+        });
+
+        let tag = name.and_then(|(name, end)| self.expect_close(end, "").map(|end| (name, end)));
+        tag.or_else(|| self.fail(pos))
+    }
+
+    /// Parses a partial's `key=path` argument, whose value is looked up in
+    /// the caller's context and pushed for the partial under the key.
+    fn argument(&mut self, pos: usize) -> Option<((String, Path), usize)> {
+        let start = self.skip(pos);
+        let end = self.identifier(start)?;
+        if !self.source[end..].starts_with('=') {
+            return None;
+        }
+
+        let key = self.source[start..end].into();
+        self.expect_path(end + 1)
+            .map(|(path, end)| ((key, path), end))
+    }
+
+    /// Parses a section open tag, returning the kind of section it begins,
+    /// its path, and the name its close tag must repeat.
+    fn open_tag(&mut self, pos: usize) -> Option<((Kind, Path, &'a str), usize)> {
+        let kinds = [
+            ("#", Kind::Section),
+            ("^", Kind::Inverted),
+            ("$", Kind::Block),
+        ];
+        let path = kinds
+            .iter()
+            .filter_map(|&(sigil, kind)| self.open(pos, sigil).map(|end| (kind, end)))
+            .next()
+            .and_then(|(kind, end)| self.expect_path(end).map(|(path, end)| (kind, path, end)));
+
+        let tag = path
+            .or_else(|| {
+                let start = self.skip(self.open(pos, "<")?);
+                let end = self.expect_name(start)?;
+                let name = vec![self.source[start..end].to_string()];
+                Some((Kind::Parent, Path::at(name, self.locate(start, end)), end))
+            })
+            .and_then(|(kind, path, end)| self.expect_close(end, "").map(|end| (kind, path, end)));
+
+        match tag {
+            Some((kind, path, end)) => {
+                let name = &self.source[path.span.start..path.span.end];
+                Some(((kind, path, name), end))
+            }
+            None => self.fail(pos),
+        }
+    }
+
+    /// Parses any section close tag.
+    fn close_tag(&mut self, pos: usize) -> Option<usize> {
+        let tag = self
+            .open(pos, "/")
+            .and_then(|end| self.expect_path(end))
+            .and_then(|(_, end)| self.expect_close(end, ""));
+        tag.or_else(|| self.fail(pos))
+    }
+
+    /// Tests whether an `{{else}}` tag begins at the byte offset or after
+    /// its indentation, where it may end a section.
+    fn otherwise(&mut self, pos: usize) -> bool {
+        self.inverse && self.else_tag(self.indent(pos)).is_some()
+    }
+
+    fn else_tag(&mut self, pos: usize) -> Option<usize> {
+        let tag = self
+            .open(pos, "")
+            .map(|end| self.skip(end))
+            .filter(|&start| self.source[start..].starts_with("else"))
+            .and_then(|start| self.expect_close(start + 4, ""));
+        tag.or_else(|| self.fail(pos))
+    }
+
+    /// Parses the close tag naming the open section.
+    fn section_close(&mut self, pos: usize, name: &str) -> Option<usize> {
+        let tag = self
+            .open(pos, "/")
+            .map(|end| self.skip(end))
+            .filter(|&start| self.source[start..].starts_with(name))
+            .and_then(|start| self.expect_close(start + name.len(), ""));
+        tag.or_else(|| self.fail(pos))
+    }
+
+    /// Matches the open delimiter active at the byte offset, followed by an
+    /// optional `~` and the sigil, returning the offset following them.
+    fn open(&self, pos: usize, sigil: &str) -> Option<usize> {
+        let (open, _) = self.delimiters.at(pos);
+        let rest = self.source[pos..].strip_prefix(open)?;
+        let rest = rest.strip_prefix('~').unwrap_or(rest);
+        if rest.starts_with(sigil) {
+            Some(self.source.len() - rest.len() + sigil.len())
+        } else {
+            None
+        }
+    }
+
+    /// Matches the sigil followed by an optional `~` and the close delimiter
+    /// active at the byte offset, returning the offset following them. The
+    /// offset is recorded to trim the content after a tag ending with `~`.
+    fn close(&mut self, pos: usize, sigil: &str) -> Option<usize> {
+        let (_, close) = self.delimiters.at(pos);
+        let rest = self.source[pos..].strip_prefix(sigil)?;
+        let (trim, rest) = match rest.strip_prefix('~') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        if !rest.starts_with(close) {
+            return None;
+        }
+
+        let end = self.source.len() - rest.len() + close.len();
+        if trim {
+            self.trims.insert(end);
+        }
+        Some(end)
+    }
+
+    fn expect_close(&mut self, pos: usize, sigil: &str) -> Option<usize> {
+        self.close(self.skip(pos), sigil)
+    }
+
+    fn expect_path(&mut self, pos: usize) -> Option<(Path, usize)> {
+        let start = self.skip(pos);
+        match self.path(start) {
+            Some(end) => {
+                let mut keys: Vec<String> = match &self.source[start..end] {
+                    "." => vec![String::from(".")],
+                    path => path.split('.').map(String::from).collect(),
+                };
+                if self.dialect == Dialect::Handlebars && keys[0] == "this" {
+                    match keys.len() {
+                        1 => keys[0] = String::from("."),
+                        _ => {
+                            keys.remove(0);
+                        }
+                    }
+                }
+                Some((Path::at(keys, self.locate(start, end)), end))
+            }
+            None => self.fail(start),
+        }
+    }
+
+    fn expect_name(&mut self, pos: usize) -> Option<usize> {
+        let start = self.skip(pos);
+        match self.run(start, |c| {
+            c.is_ascii_alphanumeric() || "-_/".contains(c) || self.extension(c)
+        }) {
+            0 => self.fail(start),
+            len => Some(start + len),
+        }
+    }
+
+    fn expect_delimiter(&mut self, pos: usize) -> Option<usize> {
+        let start = self.skip(pos);
+        match self.run(start, |c| !WHITESPACE.contains(&c) && c != '=') {
+            0 => self.fail(start),
+            len => Some(start + len),
+        }
+    }
+
+    fn path(&self, pos: usize) -> Option<usize> {
+        if self.source[pos..].starts_with('.') {
+            return Some(pos + 1);
+        }
+
+        let start = if self.source[pos..].starts_with('@') {
+            pos + 1
+        } else {
+            pos
+        };
+        let mut end = self.identifier(start)?;
+        while self.source[end..].starts_with('.') {
+            match self.identifier(end + 1) {
+                Some(next) => end = next,
+                None => break,
+            }
+        }
+        Some(end)
+    }
+
+    fn identifier(&self, pos: usize) -> Option<usize> {
+        match self.run(pos, |c| {
+            c.is_ascii_alphanumeric() || "-_?!".contains(c) || self.extension(c)
+        }) {
+            0 => None,
+            len => Some(pos + len),
+        }
+    }
+
+    /// Determines if the character is allowed in identifiers and names only
+    /// with the extended identifiers option.
+    fn extension(&self, c: char) -> bool {
+        self.extended && (c == '$' || c == '@' || (!c.is_ascii() && c.is_alphabetic()))
+    }
+
+    fn terminator(&self, pos: usize) -> Option<usize> {
+        let rest = &self.source[pos..];
+        if rest.starts_with("\r\n") {
+            Some(pos + 2)
+        } else if rest.starts_with('\n') {
+            Some(pos + 1)
+        } else {
+            None
+        }
+    }
+
+    /// Matches a line terminator or the end of input.
+    fn line_end(&self, pos: usize) -> Option<usize> {
+        if pos == self.source.len() {
+            Some(pos)
+        } else {
+            self.terminator(pos)
+        }
+    }
+
+    fn indent(&self, pos: usize) -> usize {
+        pos + self.run(pos, |c| c == ' ' || c == '\t')
+    }
+
+    fn skip(&self, pos: usize) -> usize {
+        pos + self.run(pos, |c| WHITESPACE.contains(&c))
+    }
+
+    /// Returns the byte length of the characters at the offset matching the
+    /// predicate.
+    fn run<F>(&self, pos: usize, matches: F) -> usize
+    where
+        F: Fn(char) -> bool,
+    {
+        self.source[pos..]
+            .char_indices()
+            .find(|&(_, c)| !matches(c))
+            .map_or(self.source.len() - pos, |(i, _)| i)
+    }
+}
+
+/// The whitespace allowed between the parts of a tag.
+const WHITESPACE: &[char] = &[' ', '\t', '\r', '\n'];
+
+/// Removes a leading UTF-8 byte order mark from the source when the options
+/// enable it. Byte offsets in the parsed tree and its errors are relative to
+/// the remaining text.
+pub fn strip_bom<'a>(source: &'a str, options: &LoadOptions) -> &'a str {
+    match source.strip_prefix('\u{feff}') {
+        Some(rest) if options.strip_bom => rest,
+        _ => source,
+    }
+}
+
+/// Finds the byte offset beginning each line of the source.
+fn lines(source: &str) -> Vec<usize> {
+    let bytes = source.as_bytes();
+    let mut lines = vec![0];
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'\n' || (b == b'\r' && bytes.get(i + 1) != Some(&b'\n')) {
+            lines.push(i + 1);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{LoadOptions, ParseError, Path, Span, Statement};
+    use super::Parser;
+
+    #[test]
+    fn identifier() {
+        assert_eq!(Some(4), Parser::new("abc?").identifier(0));
+        assert_eq!(None, Parser::new(".abc").identifier(0));
+    }
+
+    #[test]
+    fn path() {
+        let (path, end) = Parser::new("a.b.c!").expect_path(0).unwrap();
+        assert_eq!(Path::new(vec!["a".into(), "b".into(), "c!".into()]), path);
+        assert_eq!(Span::new(0, 6, 1, 1), path.span);
+        assert_eq!(6, end);
+    }
+
+    #[test]
+    fn metadata_path() {
+        let (path, end) = Parser::new("@index").expect_path(0).unwrap();
+        assert_eq!(Path::new(vec!["@index".into()]), path);
+        assert_eq!(6, end);
+        assert_eq!(1, Parser::new("a.@b").expect_path(0).unwrap().1);
+    }
+
+    #[test]
+    fn extended_identifiers() {
+        assert!(Parser::new("$schema").expect_path(0).is_none());
+        assert_eq!(3, Parser::new("café").expect_path(0).unwrap().1);
+
+        let options = LoadOptions {
+            extended_identifiers: true,
+            ..LoadOptions::default()
+        };
+        let (path, end) = Parser::with("$schema.café", &options)
+            .expect_path(0)
+            .unwrap();
+        assert_eq!(Path::new(vec!["$schema".into(), "café".into()]), path);
+        assert_eq!(13, end);
+        assert_eq!(Some(10), Parser::with("prix/été", &options).expect_name(0));
+    }
+
+    #[test]
+    fn name() {
+        assert_eq!(Some(5), Parser::new("a/b/c").expect_name(0));
+    }
+
+    #[test]
+    fn invalid_section() {
+        assert!(Parser::new("{{#one}}test{{/two}}").program().is_err());
+    }
+
+    #[test]
+    fn mismatched_section() {
+        let template = "{{#one}}\n  {{#two}}\n  {{/one}}\n{{/two}}";
+        let err = Parser::new(template).program().unwrap_err();
+        assert_eq!(25, err.position());
+        assert_eq!(
+            "Section 'two' opened at line 2 closed as 'one' at line 3",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn unclosed_section() {
+        let err = Parser::new("{{#one}}\n{{#two}}{{/two}}\n")
+            .program()
+            .unwrap_err();
+        assert_eq!(3, err.position());
+        assert_eq!(
+            "Section 'one' opened at line 1 is never closed",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn unexpected_token_in_section() {
+        match Parser::new("{{#one}}{{/one").program() {
+            Err(ParseError::UnexpectedToken(position)) => assert_eq!(10, position),
+            other => panic!("Expected unexpected token error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn variable() {
+        match Parser::new("{{ a }}").variable(0).unwrap() {
+            (Statement::Variable(path), end) => {
+                assert_eq!(Path::new(vec!["a".into()]), path);
+                assert_eq!(Span::new(3, 4, 1, 4), path.span);
+                assert_eq!(7, end);
+            }
+            other => panic!("Expected variable, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dot() {
+        match Parser::new("{{ . }}").variable(0).unwrap() {
+            (Statement::Variable(path), end) => {
+                assert_eq!(Path::new(vec![".".into()]), path);
+                assert_eq!(Span::new(3, 4, 1, 4), path.span);
+                assert_eq!(7, end);
+            }
+            other => panic!("Expected variable, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn filtered() {
+        let (filtered, end) = Parser::new("{{ a.b | upcase|truncate }}")
+            .variable(0)
+            .unwrap();
+        let filters = vec![String::from("upcase"), String::from("truncate")];
+        assert_eq!(
+            Statement::Filtered(Path::new(vec!["a".into(), "b".into()]), filters),
+            filtered
+        );
+        assert_eq!(27, end);
+
+        assert!(Parser::new("{{ a | }}").variable(0).is_none());
+        assert!(Parser::new("{{ a | b c }}").variable(0).is_none());
+    }
+
+    #[test]
+    fn html() {
+        let (path, end) = Parser::new("{{{ a }}}").html(0).unwrap();
+        assert_eq!(Path::new(vec!["a".into()]), path);
+        assert_eq!(Span::new(4, 5, 1, 5), path.span);
+        assert_eq!(9, end);
+    }
+
+    #[test]
+    fn ampersand() {
+        let (path, end) = Parser::new("{{& a }}").html(0).unwrap();
+        assert_eq!(Path::new(vec!["a".into()]), path);
+        assert_eq!(8, end);
+    }
+
+    #[test]
+    fn locate() {
+        let parser = Parser::new("a\rb\nc\r\nd嗨");
+        assert_eq!(Span::new(0, 1, 1, 1), parser.locate(0, 1));
+        assert_eq!(Span::new(2, 3, 2, 1), parser.locate(2, 3));
+        assert_eq!(Span::new(6, 7, 4, 1), parser.locate(6, 7));
+        assert_eq!(Span::new(11, 11, 4, 3), parser.locate(11, 11));
+    }
+
+    #[test]
+    fn deeply_nested_sections() {
+        let template = format!("{}{}", "{{#a}}".repeat(5000), "{{/a}}".repeat(5000));
+        let mut options = LoadOptions {
+            max_depth: 5000,
+            ..LoadOptions::default()
+        };
+        assert!(Parser::with(&template, &options).program().is_ok());
+
+        options.max_depth = 100;
+        match Parser::with(&template, &options).program() {
+            Err(ParseError::TooDeep { position, limit }) => {
+                assert_eq!(603, position);
+                assert_eq!(100, limit);
+            }
+            other => panic!("Expected too deep error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty_comment() {
+        assert!(Parser::new("{{!}}{{! }}").program().is_ok());
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+use super::Span;
+
+/// The reserved namespace of compile-time feature flags, like
+/// `flags.beta_ui`.
+pub const FLAGS: &str = "flags";
+
+/// A dotted context key path, like `name.first`, referenced by a variable or
+/// section tag.
+///
+/// Paths compare equal by their keys alone; the source span records where
+/// the path appeared in its template and does not affect equality.
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub keys: Vec<String>,
+    pub span: Span,
+}
+
+impl Path {
+    pub fn new(keys: Vec<String>) -> Self {
+        Path {
+            keys,
+            span: Span::default(),
+        }
+    }
+
+    /// Creates a path parsed from the template source at the span.
+    pub fn at(keys: Vec<String>, span: Span) -> Self {
+        Path { keys, span }
+    }
+
+    /// Determines if the path names a list item's loop metadata, like
+    /// `@index`, rather than a context value.
+    pub fn is_metadata(&self) -> bool {
+        self.keys.first().is_some_and(|key| key.starts_with('@'))
+    }
+
+    /// Returns the name of the feature flag the path tests, like `beta_ui`
+    /// for `flags.beta_ui`, if it's in the reserved flags namespace.
+    pub fn flag(&self) -> Option<&str> {
+        match self.keys.as_slice() {
+            [namespace, name] if namespace == FLAGS => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Path {
+    fn eq(&self, other: &Path) -> bool {
+        self.keys == other.keys
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.keys.join("."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Span;
+    use super::Path;
+
+    #[test]
+    fn to_string() {
+        let path = Path::new(vec![String::from("one"), String::from("two")]);
+        assert_eq!("one.two", path.to_string());
+    }
+
+    #[test]
+    fn equality_ignores_span() {
+        let keys = vec![String::from("one")];
+        let path = Path::at(keys.clone(), Span::new(3, 6, 1, 4));
+        assert_eq!(Path::new(keys), path);
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt;
+
+/// A setting enabled by a `{{%NAME}}` pragma tag, applying to the whole
+/// template file the tag is in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pragma {
+    /// `{{%UNESCAPED}}`: variables render their values without HTML
+    /// escaping, as triple mustaches do.
+    Unescaped,
+
+    /// `{{%STRICT}}`: a variable whose value is missing fails the render,
+    /// rather than rendering nothing.
+    Strict,
+}
+
+impl Pragma {
+    /// Every pragma the compiler recognizes.
+    pub const ALL: &'static [Pragma] = &[Pragma::Unescaped, Pragma::Strict];
+
+    /// Finds the pragma a tag names.
+    pub fn parse(name: &str) -> Option<Pragma> {
+        Pragma::ALL.iter().find(|p| p.name() == name).cloned()
+    }
+
+    /// Returns the name pragma tags spell the pragma with.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Pragma::Unescaped => "UNESCAPED",
+            Pragma::Strict => "STRICT",
+        }
+    }
+}
+
+impl fmt::Display for Pragma {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
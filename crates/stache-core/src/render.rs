@@ -0,0 +1,670 @@
+//! Renders templates directly in Rust, interpreting their syntax trees
+//! against a JSON context, for applications using stache as a templating
+//! library rather than a compiler.
+//!
+//! Rendering follows the compiled backends: a path's first key is looked up
+//! through the context stack, from the innermost frame out, and its
+//! remaining keys within the value found. Sections repeat for each item of a
+//! list, render once for `true` or any other present value, and are skipped
+//! for `false`, `null`, missing values, and empty lists. Each item of a list
+//! is rendered with a frame holding its `@index`, `@first`, and `@last`
+//! metadata beneath it.
+//!
+//! Filtered variables pass the value found through each filter registered
+//! with `Renderer::filter`, in order, before escaping it. A missing value is
+//! passed as `null`.
+//!
+//! A standalone partial's indentation is added to each line of the partial's
+//! template content, as the spec indents the partial's text before rendering
+//! it, so lines within an interpolated value aren't indented.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use super::{Error, Path, Pragma, Statement, Template};
+use front_matter;
+
+/// The number of partials that may be rendered within one another, so a
+/// partial rendering itself without end fails rather than overflowing the
+/// stack.
+pub const MAX_PARTIAL_DEPTH: usize = 256;
+
+/// A function transforming a variable's value before it's rendered.
+pub type Filter = fn(&Value) -> Value;
+
+/// A function returning the translated text for a translation tag's key.
+pub type Translator = fn(&str) -> String;
+
+/// Renders a linked set of templates by name.
+#[derive(Debug)]
+pub struct Renderer<'a> {
+    templates: HashMap<&'a str, Statement>,
+    pragmas: HashMap<&'a str, Vec<Pragma>>,
+    filters: HashMap<String, Filter>,
+    translator: Option<Translator>,
+}
+
+impl<'a> Renderer<'a> {
+    /// Links the templates for rendering, checking that every partial they
+    /// call is provided by one of them. Sections over feature flags are
+    /// resolved as off, as when compiling without defines.
+    pub fn new(templates: &'a [Template]) -> Result<Self, Error> {
+        Renderer::with_defines(templates, &HashMap::new())
+    }
+
+    /// Links the templates for rendering, first resolving sections over the
+    /// defined names and feature flags with `Template::define_all`, so the
+    /// output matches a build compiled with the same defines.
+    pub fn with_defines(
+        templates: &'a [Template],
+        defines: &HashMap<String, bool>,
+    ) -> Result<Self, Error> {
+        Template::validate(templates)?;
+        let pragmas = templates
+            .iter()
+            .map(|template| (template.name.as_str(), template.tree.pragmas()))
+            .collect();
+        let defined = Template::define_all(templates, defines);
+        let linked = defined.as_deref().unwrap_or(templates);
+        let templates = templates
+            .iter()
+            .zip(linked)
+            .map(|(template, linked_template)| {
+                Ok((
+                    template.name.as_str(),
+                    linked_template.inherit(linked)?.tree,
+                ))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Renderer {
+            templates,
+            pragmas,
+            filters: HashMap::new(),
+            translator: None,
+        })
+    }
+
+    /// Registers the function called for the filter name in the templates'
+    /// variable tags, replacing any previously registered with that name.
+    pub fn filter(&mut self, name: &str, filter: Filter) {
+        self.filters.insert(name.to_string(), filter);
+    }
+
+    /// Sets the function called for the text of each translation tag. Without
+    /// one, a translation tag renders its key.
+    pub fn translator(&mut self, translator: Translator) {
+        self.translator = Some(translator);
+    }
+
+    /// Renders the named template with the context data.
+    pub fn render(&self, name: &str, context: &Value) -> Result<String, Error> {
+        let tree = self
+            .templates
+            .get(name)
+            .ok_or_else(|| Error::TemplateNotFound {
+                name: name.to_string(),
+            })?;
+
+        // Partials may render any linked template, so every filter must be
+        // known before rendering starts.
+        let mut filters: Vec<_> = self
+            .templates
+            .values()
+            .flat_map(|tree| tree.filters())
+            .filter(|name| !self.filters.contains_key(name.as_str()))
+            .collect();
+        filters.sort();
+        if let Some(name) = filters.first() {
+            return Err(Error::UnknownFilter {
+                name: name.to_string(),
+            });
+        }
+
+        let mut out = Output::default();
+        out.pragmas(&self.pragmas[name]);
+        self.node(tree, &mut vec![context], &mut out);
+        if let Some(name) = out.too_deep {
+            return Err(Error::PartialDepth {
+                name,
+                limit: MAX_PARTIAL_DEPTH,
+            });
+        }
+        match out.missing {
+            Some(path) => Err(Error::MissingValue { path }),
+            None => Ok(out.text),
+        }
+    }
+
+    /// Renders the statement, recursing into the blocks of sections and
+    /// partials. Section nesting within a template is bounded by the parser's
+    /// depth limit.
+    fn node(&self, node: &Statement, stack: &mut Vec<&Value>, out: &mut Output) {
+        match *node {
+            Statement::Program(ref block) => {
+                for stmt in &block.statements {
+                    self.node(stmt, stack, out);
+                }
+            }
+            Statement::Section(ref path, ref block) => match lookup(stack, path) {
+                Some(Value::Array(items)) => {
+                    for (index, item) in items.iter().enumerate() {
+                        let meta = metadata(index, items.len());
+                        let mut frames = stack.clone();
+                        frames.push(&meta);
+                        frames.push(item);
+                        for stmt in &block.statements {
+                            self.node(stmt, &mut frames, out);
+                        }
+                    }
+                }
+                None | Some(&Value::Null) | Some(&Value::Bool(false)) => (),
+                Some(&Value::Bool(true)) => {
+                    for stmt in &block.statements {
+                        self.node(stmt, stack, out);
+                    }
+                }
+                Some(value) => {
+                    stack.push(value);
+                    for stmt in &block.statements {
+                        self.node(stmt, stack, out);
+                    }
+                    stack.pop();
+                }
+            },
+            Statement::Inverted(ref path, ref block) => {
+                let falsy = match lookup(stack, path) {
+                    Some(Value::Array(items)) => items.is_empty(),
+                    None | Some(&Value::Null) | Some(&Value::Bool(false)) => true,
+                    Some(_) => false,
+                };
+                if falsy {
+                    for stmt in &block.statements {
+                        self.node(stmt, stack, out);
+                    }
+                }
+            }
+            Statement::Variable(ref path) | Statement::Html(ref path) => {
+                out.begin_line();
+                match lookup(stack, path) {
+                    Some(value) if !value.is_null() => {
+                        if matches!(*node, Statement::Variable(_)) && !out.unescaped {
+                            escape(&text(value), &mut out.text);
+                        } else {
+                            out.text.push_str(&text(value));
+                        }
+                    }
+                    _ if out.strict && out.missing.is_none() => {
+                        out.missing = Some(path.to_string());
+                    }
+                    _ => (),
+                }
+            }
+            Statement::Filtered(ref path, ref filters) => {
+                out.begin_line();
+                let value = lookup(stack, path).cloned().unwrap_or(Value::Null);
+                let value = filters
+                    .iter()
+                    .fold(value, |value, name| self.filters[name.as_str()](&value));
+                escape(&text(&value), &mut out.text);
+            }
+            Statement::Partial(ref name, ref padding, ref arguments) => {
+                if arguments.is_empty() {
+                    self.partial(name, padding, stack, out);
+                } else {
+                    let frame = Value::Object(
+                        arguments
+                            .iter()
+                            .map(|(key, path)| {
+                                let value = lookup(stack, path).cloned().unwrap_or(Value::Null);
+                                (key.clone(), value)
+                            })
+                            .collect(),
+                    );
+                    let mut frames = stack.clone();
+                    frames.push(&frame);
+                    self.partial(name, padding, &mut frames, out);
+                }
+            }
+            Statement::DynamicPartial(ref path, ref padding) => {
+                let name = lookup(stack, path).map(text).unwrap_or_default();
+                if self.templates.contains_key(name.as_str()) {
+                    self.partial(&name, padding, stack, out);
+                }
+            }
+            Statement::Content(ref text) => out.content(text),
+            Statement::Comment(_) | Statement::Pragma(_) => (),
+            Statement::Translation(ref key) => {
+                out.begin_line();
+                let text = match self.translator {
+                    Some(translator) => translator(key),
+                    None => key.clone(),
+                };
+                escape(&text, &mut out.text);
+            }
+            Statement::Parent(ref name, _) => self.partial(name, &None, stack, out),
+            Statement::Block(_, ref block) => {
+                for stmt in &block.statements {
+                    self.node(stmt, stack, out);
+                }
+            }
+        }
+    }
+
+    /// Renders the named template as a partial with its own pragmas,
+    /// indenting each line of its content when the partial tag stands alone
+    /// on its line. Past the depth limit, the partial is recorded as too
+    /// deep and no more partials are rendered.
+    fn partial(
+        &self,
+        name: &str,
+        padding: &Option<String>,
+        stack: &mut Vec<&Value>,
+        out: &mut Output,
+    ) {
+        if out.too_deep.is_some() {
+            return;
+        }
+        if out.depth == MAX_PARTIAL_DEPTH {
+            out.too_deep = Some(name.to_string());
+            return;
+        }
+
+        let tree = &self.templates[name];
+        out.depth += 1;
+        let outer = (out.unescaped, out.strict);
+        out.pragmas(&self.pragmas[name]);
+        match *padding {
+            Some(ref indent) => {
+                let length = out.indent.len();
+                out.indent.push_str(indent);
+                out.line_start = true;
+                self.node(tree, stack, out);
+                out.indent.truncate(length);
+                out.line_start = true;
+            }
+            None => self.node(tree, stack, out),
+        }
+        out.unescaped = outer.0;
+        out.strict = outer.1;
+        out.depth -= 1;
+    }
+}
+
+/// The rendered text, along with the indentation of the standalone partials
+/// being rendered, the pragmas of the template being rendered, the first
+/// value missing from a strict template, and the depth of the partials
+/// being rendered with the first partial past the limit.
+#[derive(Debug, Default)]
+struct Output {
+    text: String,
+    indent: String,
+    line_start: bool,
+    unescaped: bool,
+    strict: bool,
+    missing: Option<String>,
+    depth: usize,
+    too_deep: Option<String>,
+}
+
+impl Output {
+    /// Applies a template's pragmas to the variables rendered next.
+    fn pragmas(&mut self, pragmas: &[Pragma]) {
+        self.unescaped = pragmas.contains(&Pragma::Unescaped);
+        self.strict = pragmas.contains(&Pragma::Strict);
+    }
+
+    /// Adds the indentation when output begins a new line of a partial's
+    /// template.
+    fn begin_line(&mut self) {
+        if self.line_start {
+            self.line_start = false;
+            self.text.push_str(&self.indent);
+        }
+    }
+
+    /// Appends template content, indenting each of its lines.
+    fn content(&mut self, content: &str) {
+        for line in content.split_inclusive('\n') {
+            self.begin_line();
+            self.text.push_str(line);
+            self.line_start = line.ends_with('\n');
+        }
+    }
+}
+
+/// Resolves the path's first key through the context stack, from the
+/// innermost frame out, then each remaining key within the value found.
+fn lookup<'a>(stack: &[&'a Value], path: &Path) -> Option<&'a Value> {
+    let mut keys = path.keys.iter();
+    let first = keys.next()?;
+    let value = stack
+        .iter()
+        .rev()
+        .filter_map(|frame| fetch(frame, first))
+        .next()?;
+    keys.try_fold(value, |value, key| fetch(value, key))
+}
+
+/// Builds the frame of loop metadata for the list item at the index.
+fn metadata(index: usize, len: usize) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert("@index".into(), Value::from(index));
+    map.insert("@first".into(), Value::Bool(index == 0));
+    map.insert("@last".into(), Value::Bool(index + 1 == len));
+    Value::Object(map)
+}
+
+/// Finds the key's value within the context value. A key made only of
+/// digits indexes a list.
+fn fetch<'a>(context: &'a Value, key: &str) -> Option<&'a Value> {
+    if key == "." {
+        return Some(context);
+    }
+    match *context {
+        Value::Object(ref map) => map.get(key),
+        Value::Array(ref items) if key.bytes().all(|b| b.is_ascii_digit()) => {
+            key.parse().ok().and_then(|index: usize| items.get(index))
+        }
+        _ => None,
+    }
+}
+
+/// Parses a YAML document into a context to render templates with, for
+/// contexts written by hand rather than serialized as JSON.
+pub fn yaml(text: &str) -> Result<Value, String> {
+    front_matter::yaml(text)
+}
+
+/// Formats an interpolated value: strings as is, `null` as nothing, and
+/// other values as JSON.
+fn text(value: &Value) -> String {
+    match *value {
+        Value::String(ref text) => text.clone(),
+        Value::Null => String::new(),
+        _ => value.to_string(),
+    }
+}
+
+/// Appends the text with HTML special characters escaped, as the compiled
+/// runtimes escape them.
+pub fn escape(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{yaml, Renderer, MAX_PARTIAL_DEPTH};
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use {Error, LoadOptions, Statement, Template};
+
+    fn json(text: &str) -> Value {
+        serde_json::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn renders_filtered_variables() {
+        let templates = vec![Template::from_source(
+            "robot",
+            "{{ name | upcase }} {{ name | upcase | quote }} {{ missing | quote }}",
+        )
+        .unwrap()];
+        let mut renderer = Renderer::new(&templates).unwrap();
+        renderer.filter("quote", |value| match *value {
+            Value::String(ref text) => Value::from(format!("\"{}\"", text)),
+            _ => Value::from("none"),
+        });
+
+        let context = json(r#"{"name": "hubot"}"#);
+        match renderer.render("robot", &context) {
+            Err(Error::UnknownFilter { ref name }) => assert_eq!("upcase", name),
+            other => panic!("Expected unknown filter, got {:?}", other),
+        }
+
+        renderer.filter("upcase", |value| match *value {
+            Value::String(ref text) => Value::from(text.to_uppercase()),
+            _ => value.clone(),
+        });
+        assert_eq!(
+            "HUBOT &quot;HUBOT&quot; none",
+            renderer.render("robot", &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn renders_partial_arguments() {
+        let templates = vec![
+            Template::from_source("robot", "{{> card title=user.name subtitle=missing }}").unwrap(),
+            Template::from_source("card", "{{ title }}: {{ subtitle }} {{ footer }}").unwrap(),
+        ];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context = json(r#"{"user": {"name": "Hubot"}, "subtitle": "hidden", "footer": "ok"}"#);
+        assert_eq!("Hubot:  ok", renderer.render("robot", &context).unwrap());
+    }
+
+    #[test]
+    fn renders_translations() {
+        let options = LoadOptions {
+            translations: true,
+            ..LoadOptions::default()
+        };
+        let tree = Statement::parse_with("{{t \"greeting\"}}", &options).unwrap();
+        let base = PathBuf::from("app/templates");
+        let templates = vec![Template::new(&base, base.join("robot.mustache"), tree)];
+        let mut renderer = Renderer::new(&templates).unwrap();
+        assert_eq!("greeting", renderer.render("robot", &json("{}")).unwrap());
+
+        renderer.translator(|key| format!("<{}>", key));
+        assert_eq!(
+            "&lt;greeting&gt;",
+            renderer.render("robot", &json("{}")).unwrap()
+        );
+    }
+
+    #[test]
+    fn renders_variables() {
+        let templates = vec![Template::from_source(
+            "robot",
+            "{{name.login}} {{{bio}}} {{bio}} {{age}} {{admin}} {{missing}}{{empty}}",
+        )
+        .unwrap()];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context = json(
+            r#"{"name": {"login": "hubot"}, "bio": "<b>'&'</b>", "age": 7, "admin": false, "empty": null}"#,
+        );
+        assert_eq!(
+            "hubot <b>'&'</b> &lt;b&gt;&#39;&amp;&#39;&lt;/b&gt; 7 false ",
+            renderer.render("robot", &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn renders_sections() {
+        let source = "{{#robots}}{{name}}{{#admin}}*{{/admin}}{{title}},{{/robots}}\
+                      {{^robots}}None{{/robots}}{{#visible}}{{title}}{{/visible}}\
+                      {{#missing}}x{{/missing}}{{^missing}}!{{/missing}}{{#count}}{{.}}{{/count}}";
+        let templates = vec![Template::from_source("robots", source).unwrap()];
+        let renderer = Renderer::new(&templates).unwrap();
+
+        let context = json(
+            r#"{"title": "T", "visible": true, "count": 3,
+                "robots": [{"name": "a", "admin": true}, {"name": "b", "title": "B"}]}"#,
+        );
+        assert_eq!("a*T,bB,T!3", renderer.render("robots", &context).unwrap());
+
+        let context = json(r#"{"robots": [], "visible": false}"#);
+        assert_eq!("None!", renderer.render("robots", &context).unwrap());
+    }
+
+    #[test]
+    fn resolves_dotted_names_in_found_frame() {
+        let templates = vec![Template::from_source("robot", "{{#a}}{{b.c}}{{/a}}").unwrap()];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context = json(r#"{"b": {"c": "outer"}, "a": {"b": {}}}"#);
+        assert_eq!("", renderer.render("robot", &context).unwrap());
+    }
+
+    #[test]
+    fn renders_loop_metadata() {
+        let source = "{{#robots}}{{@index}}.{{name}}{{#@first}}!{{/@first}}\
+                      {{^@last}},{{/@last}}{{#tags}}{{@index}}{{/tags}}{{/robots}}{{@index}}";
+        let templates = vec![Template::from_source("robots", source).unwrap()];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context =
+            json(r#"{"robots": [{"name": "a", "tags": [1, 2]}, {"name": "b"}, {"name": "c"}]}"#);
+        assert_eq!(
+            "0.a!,011.b,2.c",
+            renderer.render("robots", &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn renders_yaml_contexts() {
+        let templates =
+            vec![
+                Template::from_source("robots", "{{#robots}}{{name}} {{/robots}}{{count}}")
+                    .unwrap(),
+            ];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context = yaml("robots:\n  - name: hubot\n  - name: bender\ncount: 2\n").unwrap();
+        assert_eq!(
+            "hubot bender 2",
+            renderer.render("robots", &context).unwrap()
+        );
+        assert_eq!(Value::Null, yaml("").unwrap());
+        assert!(yaml("robots: [").is_err());
+    }
+
+    #[test]
+    fn renders_with_pragmas() {
+        let templates = vec![
+            Template::from_source("page", "{{%UNESCAPED}}{{bio}}|{{> card}}").unwrap(),
+            Template::from_source("card", "{{%STRICT}}{{bio}}{{title}}").unwrap(),
+        ];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context = json(r#"{"bio": "<b>", "title": "Dr."}"#);
+        assert_eq!(
+            "<b>|&lt;b&gt;Dr.",
+            renderer.render("page", &context).unwrap()
+        );
+
+        match renderer.render("page", &json(r#"{"bio": "<b>"}"#)) {
+            Err(Error::MissingValue { ref path }) => assert_eq!("title", path),
+            other => panic!("unexpected render: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn renders_list_indexes() {
+        let templates =
+            vec![
+                Template::from_source("robots", "{{robots.0.name}} {{robots.1}}{{robots.2}}")
+                    .unwrap(),
+            ];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context = json(r#"{"robots": [{"name": "a"}, "b"]}"#);
+        assert_eq!("a b", renderer.render("robots", &context).unwrap());
+    }
+
+    #[test]
+    fn renders_indented_partials() {
+        let templates = vec![
+            Template::from_source("list", "<ul>\n  {{> item}}\n</ul>\n").unwrap(),
+            Template::from_source("item", "<li>\n{{name}}\n</li>\n").unwrap(),
+        ];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context = json(r#"{"name": "hubot"}"#);
+        assert_eq!(
+            "<ul>\n  <li>\n  hubot\n  </li>\n</ul>\n",
+            renderer.render("list", &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn indents_partial_content_not_values() {
+        let templates = vec![
+            Template::from_source("list", "<ul>\n  {{> item}}\n</ul>\n").unwrap(),
+            Template::from_source("item", "<li>{{{name}}}</li>\n").unwrap(),
+        ];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context = json(r#"{"name": "hu\nbot"}"#);
+        assert_eq!(
+            "<ul>\n  <li>hu\nbot</li>\n</ul>\n",
+            renderer.render("list", &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn renders_dynamic_partials() {
+        let templates = vec![
+            Template::from_source("page", "{{#items}}{{>*kind}}{{/items}}").unwrap(),
+            Template::from_source("robot", "[{{name}}]").unwrap(),
+        ];
+        let renderer = Renderer::new(&templates).unwrap();
+        let context =
+            json(r#"{"items": [{"kind": "robot", "name": "hubot"}, {"kind": "missing"}, {}]}"#);
+        assert_eq!("[hubot]", renderer.render("page", &context).unwrap());
+    }
+
+    #[test]
+    fn resolves_feature_flags() {
+        let templates = vec![Template::from_source(
+            "page",
+            "{{#flags.beta}}beta{{/flags.beta}}{{^flags.beta}}stable{{/flags.beta}}",
+        )
+        .unwrap()];
+        let context = json(r#"{"flags": {"beta": true}}"#);
+
+        let renderer = Renderer::new(&templates).unwrap();
+        assert_eq!("stable", renderer.render("page", &context).unwrap());
+
+        let mut defines = HashMap::new();
+        defines.insert(String::from("beta"), true);
+        let renderer = Renderer::with_defines(&templates, &defines).unwrap();
+        assert_eq!("beta", renderer.render("page", &Value::Null).unwrap());
+    }
+
+    #[test]
+    fn bounds_recursive_partials() {
+        let templates = vec![
+            Template::from_source("loop", "a{{> loop}}").unwrap(),
+            Template::from_source("tree", "{{name}}{{#children}}({{> tree}}){{/children}}")
+                .unwrap(),
+        ];
+        let renderer = Renderer::new(&templates).unwrap();
+        match renderer.render("loop", &Value::Null) {
+            Err(Error::PartialDepth { ref name, limit }) => {
+                assert_eq!("loop", name);
+                assert_eq!(MAX_PARTIAL_DEPTH, limit);
+            }
+            other => panic!("expected partial depth error: {:?}", other),
+        }
+
+        let context = json(r#"{"name": "a", "children": [{"name": "b", "children": []}]}"#);
+        assert_eq!("a(b)", renderer.render("tree", &context).unwrap());
+    }
+
+    #[test]
+    fn reports_unknown_templates() {
+        let templates = vec![Template::from_source("robot", "{{> missing}}").unwrap()];
+        assert!(Renderer::new(&templates).is_err());
+
+        let templates = vec![Template::from_source("robot", "hubot").unwrap()];
+        let renderer = Renderer::new(&templates).unwrap();
+        match renderer.render("robots", &Value::Null) {
+            Err(Error::TemplateNotFound { ref name }) => assert_eq!("robots", name),
+            other => panic!("expected template not found: {:?}", other),
+        }
+    }
+}
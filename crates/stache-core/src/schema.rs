@@ -0,0 +1,157 @@
+extern crate serde_json;
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use super::{Backend, Compile, Error, Shape, Template};
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// A set of JSON Schema documents, one per template, describing the context
+/// data each template expects to be rendered with.
+///
+/// Mustache renders missing values as empty text, so no property is marked
+/// as required. The schemas constrain the types of the values a template
+/// reads, allowing callers to validate data before rendering.
+#[derive(Debug)]
+pub struct Schemas {
+    documents: Map<String, Value>,
+}
+
+impl Compile for Schemas {
+    /// Writes a JSON object mapping each template name to its schema.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *buf, &self.documents)?;
+        writeln!(buf)
+    }
+}
+
+/// The `schema` backend, generating the JSON Schema documents.
+#[derive(Debug)]
+pub struct Generator;
+
+impl Backend for Generator {
+    fn name(&self) -> &str {
+        "schema"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(generate(templates)))
+    }
+}
+
+/// Infers the context shape of each template and translates it into a JSON
+/// Schema document. Feature flags are resolved at compile time, so they're
+/// not described as context.
+pub fn generate(templates: &[Template]) -> Schemas {
+    let defined = Template::define_all(templates, &HashMap::new());
+    let templates = defined.as_deref().unwrap_or(templates);
+    let documents = templates
+        .iter()
+        .map(|template| {
+            let shape = Shape::infer(template, templates);
+            (template.name.clone(), document(&template.name, &shape))
+        })
+        .collect();
+
+    Schemas { documents }
+}
+
+/// Builds the root schema document for a template's context.
+fn document(name: &str, shape: &Shape) -> Value {
+    let mut root = Map::new();
+    root.insert("$schema".into(), Value::from(DRAFT));
+    root.insert("title".into(), Value::from(name));
+    root.insert("type".into(), Value::from("object"));
+    root.insert("properties".into(), properties(shape));
+    Value::Object(root)
+}
+
+/// Translates a context value's shape into its schema.
+///
+/// Interpolated values are text-like scalars. Values with nested keys are
+/// objects. Section values may be a list of items, a single item pushed onto
+/// the context stack, or a boolean toggle, so their schema permits each of
+/// those types.
+fn schema(shape: &Shape) -> Value {
+    let mut keywords = Map::new();
+
+    if let Some(item) = shape.item() {
+        let types = vec!["array", "object", "boolean", "null"];
+        keywords.insert("type".into(), Value::from(types));
+        keywords.insert("items".into(), schema(&item));
+        if !item.properties.is_empty() {
+            keywords.insert("properties".into(), properties(&item));
+        }
+    } else if !shape.properties.is_empty() {
+        keywords.insert("type".into(), Value::from("object"));
+        keywords.insert("properties".into(), properties(shape));
+    } else if shape.interpolated {
+        let types = vec!["string", "number", "boolean", "null"];
+        keywords.insert("type".into(), Value::from(types));
+    }
+
+    Value::Object(keywords)
+}
+
+/// Builds the `properties` keyword for an object shape.
+fn properties(shape: &Shape) -> Value {
+    let properties = shape
+        .properties
+        .iter()
+        .map(|(key, value)| (key.clone(), schema(value)))
+        .collect();
+    Value::Object(properties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Compile, Statement, Template};
+    use super::generate;
+    use serde_json::{self, Value};
+    use std::path::PathBuf;
+
+    #[test]
+    fn generates_schema_per_template() {
+        let base = PathBuf::from("app/templates");
+        let path = base.join("robots.mustache");
+        let text = "{{ title }}{{#robots}}<li>{{ name.first }}</li>{{/robots}}";
+        let template = Template::new(&base, path, Statement::parse(text).unwrap());
+
+        let mut buf = Vec::new();
+        generate(&[template]).emit(&mut buf).unwrap();
+        let json: Value = serde_json::from_slice(&buf).unwrap();
+
+        let schema = &json["robots"];
+        assert_eq!("robots", schema["title"]);
+        assert_eq!("object", schema["type"]);
+
+        let title = &schema["properties"]["title"];
+        let types = vec!["string", "number", "boolean", "null"];
+        assert_eq!(Value::from(types), title["type"]);
+
+        let robots = &schema["properties"]["robots"];
+        let types = vec!["array", "object", "boolean", "null"];
+        assert_eq!(Value::from(types), robots["type"]);
+
+        let name = &robots["items"]["properties"]["name"];
+        assert_eq!("object", name["type"]);
+        assert!(name["properties"]["first"].is_object());
+    }
+
+    #[test]
+    fn omits_feature_flags() {
+        let base = PathBuf::from("app/templates");
+        let path = base.join("page.mustache");
+        let text = "{{#flags.beta}}{{ name }}{{/flags.beta}}{{ title }}";
+        let template = Template::new(&base, path, Statement::parse(text).unwrap());
+
+        let mut buf = Vec::new();
+        generate(&[template]).emit(&mut buf).unwrap();
+        let json: Value = serde_json::from_slice(&buf).unwrap();
+
+        let properties = json["page"]["properties"].as_object().unwrap();
+        assert_eq!(vec!["title"], properties.keys().collect::<Vec<_>>());
+    }
+}
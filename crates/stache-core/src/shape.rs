@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use super::{Path, Statement, Template};
+
+/// The expected structure of a template's context data, inferred from the
+/// variable and section paths referenced by the template.
+///
+/// Each shape describes one value in the context: the keys read from it
+/// with dotted names, whether it is interpolated as text, whether it opens a
+/// section, and whether it is only tested for truthiness by an inverted
+/// section.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Shape {
+    pub properties: BTreeMap<String, Shape>,
+    pub interpolated: bool,
+    pub section: Option<Box<Shape>>,
+    pub tested: bool,
+}
+
+impl Shape {
+    /// Infers the context shape of a template, including the shapes of any
+    /// partials it renders. Partials are resolved by name from the full
+    /// template set; references to unknown partials are ignored.
+    pub fn infer(template: &Template, templates: &[Template]) -> Self {
+        let mut shape = Shape::default();
+        let mut visiting = vec![&template.name];
+        shape.collect(&template.tree, templates, &mut visiting);
+        shape
+    }
+
+    /// Returns true if the value is only read as text, with no nested keys
+    /// or section blocks.
+    pub fn is_scalar(&self) -> bool {
+        self.properties.is_empty() && self.section.is_none()
+    }
+
+    /// Returns the shape of the context frame a section value pushes onto the
+    /// stack: the section block's item shape combined with any keys read from
+    /// the value directly by dotted names elsewhere in the template.
+    pub fn item(&self) -> Option<Shape> {
+        self.section.as_ref().map(|item| {
+            let mut frame = Shape {
+                properties: item.properties.clone(),
+                interpolated: item.interpolated,
+                ..Shape::default()
+            };
+            for (key, value) in &self.properties {
+                frame
+                    .properties
+                    .entry(key.clone())
+                    .or_default()
+                    .merge(value.clone());
+            }
+            frame
+        })
+    }
+
+    /// Combines the expectations of another shape describing the same value.
+    pub fn merge(&mut self, other: Shape) {
+        self.interpolated |= other.interpolated;
+        self.tested |= other.tested;
+
+        for (key, shape) in other.properties {
+            self.properties.entry(key).or_default().merge(shape);
+        }
+
+        if let Some(item) = other.section {
+            match self.section {
+                Some(ref mut existing) => existing.merge(*item),
+                None => self.section = Some(item),
+            }
+        }
+    }
+
+    /// Walks the statement, recording each path relative to this shape's
+    /// context frame. Sections descend into the item shape of their path.
+    fn collect<'a>(
+        &mut self,
+        node: &'a Statement,
+        templates: &'a [Template],
+        visiting: &mut Vec<&'a String>,
+    ) {
+        match *node {
+            Statement::Program(ref block) => {
+                for stmt in &block.statements {
+                    self.collect(stmt, templates, visiting);
+                }
+            }
+            Statement::Section(ref path, ref block) => {
+                let mut item = Shape::default();
+                for stmt in &block.statements {
+                    item.collect(stmt, templates, visiting);
+                }
+
+                let shape = Shape {
+                    section: Some(Box::new(item)),
+                    ..Shape::default()
+                };
+                self.insert(path, shape);
+            }
+            Statement::Inverted(ref path, ref block) => {
+                for stmt in &block.statements {
+                    self.collect(stmt, templates, visiting);
+                }
+
+                let shape = Shape {
+                    tested: true,
+                    ..Shape::default()
+                };
+                self.insert(path, shape);
+            }
+            Statement::Variable(ref path)
+            | Statement::Filtered(ref path, _)
+            | Statement::Html(ref path)
+            | Statement::DynamicPartial(ref path, _) => {
+                let shape = Shape {
+                    interpolated: true,
+                    ..Shape::default()
+                };
+                self.insert(path, shape);
+            }
+            Statement::Partial(ref name, _, ref arguments) => {
+                // Keys the partial reads from its arguments frame describe the
+                // argument values; the rest resolve through the caller's stack.
+                let mut frame = Shape::default();
+                if !visiting.contains(&name) {
+                    if let Some(partial) = templates.iter().find(|t| &t.name == name) {
+                        visiting.push(name);
+                        frame.collect(&partial.tree, templates, visiting);
+                        visiting.pop();
+                    }
+                }
+
+                for (key, path) in arguments {
+                    let shape = frame.properties.remove(key).unwrap_or_default();
+                    self.insert(path, shape);
+                }
+                self.merge(frame);
+            }
+            Statement::Parent(ref name, ref block) => {
+                for stmt in &block.statements {
+                    self.collect(stmt, templates, visiting);
+                }
+
+                if visiting.contains(&name) {
+                    return;
+                }
+
+                if let Some(parent) = templates.iter().find(|t| &t.name == name) {
+                    visiting.push(name);
+                    self.collect(&parent.tree, templates, visiting);
+                    visiting.pop();
+                }
+            }
+            Statement::Block(_, ref block) => {
+                for stmt in &block.statements {
+                    self.collect(stmt, templates, visiting);
+                }
+            }
+            Statement::Content(_)
+            | Statement::Comment(_)
+            | Statement::Translation(_)
+            | Statement::Pragma(_) => (),
+        }
+    }
+
+    /// Records the shape found at the end of a dotted path. The implicit
+    /// iterator `.` describes this shape itself.
+    fn insert(&mut self, path: &Path, shape: Shape) {
+        let mut target = self;
+        for key in &path.keys {
+            if key == "." {
+                continue;
+            }
+            target = target.properties.entry(key.clone()).or_default();
+        }
+        target.merge(shape);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Template;
+    use super::Shape;
+
+    #[test]
+    fn infers_dotted_paths() {
+        let templates =
+            vec![Template::from_source("robot", "{{ name.first }} {{{ name.last }}}").unwrap()];
+        let shape = Shape::infer(&templates[0], &templates);
+
+        let name = &shape.properties["name"];
+        assert!(!name.interpolated);
+        assert!(name.properties["first"].interpolated);
+        assert!(name.properties["last"].interpolated);
+    }
+
+    #[test]
+    fn infers_section_items() {
+        let text = "{{#robots}}{{ name }}{{/robots}}{{^robots}}none{{/robots}}";
+        let templates = vec![Template::from_source("robots", text).unwrap()];
+        let shape = Shape::infer(&templates[0], &templates);
+
+        let robots = &shape.properties["robots"];
+        assert!(robots.tested);
+
+        let item = robots.section.as_ref().unwrap();
+        assert!(item.properties["name"].interpolated);
+    }
+
+    #[test]
+    fn infers_implicit_iterator() {
+        let templates = vec![Template::from_source("tags", "{{#tags}}{{.}}{{/tags}}").unwrap()];
+        let shape = Shape::infer(&templates[0], &templates);
+
+        let item = shape.properties["tags"].section.as_ref().unwrap();
+        assert!(item.interpolated);
+        assert!(item.is_scalar());
+    }
+
+    #[test]
+    fn merges_partial_shapes() {
+        let templates = vec![
+            Template::from_source("robots", "{{#robots}}{{> robot}}{{/robots}}").unwrap(),
+            Template::from_source("robot", "{{ name }}{{> robots}}").unwrap(),
+        ];
+        let shape = Shape::infer(&templates[0], &templates);
+
+        let item = shape.properties["robots"].section.as_ref().unwrap();
+        assert!(item.properties["name"].interpolated);
+        assert!(!item.properties.contains_key("robots"));
+    }
+
+    #[test]
+    fn infers_partial_arguments() {
+        let templates = vec![
+            Template::from_source("robot", "{{> card title=user.name }}").unwrap(),
+            Template::from_source("card", "{{ title.first }} {{ footer }}").unwrap(),
+        ];
+        let shape = Shape::infer(&templates[0], &templates);
+
+        let name = &shape.properties["user"].properties["name"];
+        assert!(name.properties["first"].interpolated);
+        assert!(shape.properties["footer"].interpolated);
+        assert!(!shape.properties.contains_key("title"));
+    }
+}
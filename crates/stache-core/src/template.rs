@@ -0,0 +1,1171 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use super::front_matter;
+use super::graph::Graph;
+use super::parser::{strip_bom, Dialect, MAX_DEPTH};
+use super::{Block, Diagnostic, Error, FrontMatter, Name, ParseError, Span, Statement};
+
+// A binding of template source file information and the parsed AST.
+#[derive(Debug)]
+pub struct Template {
+    pub tree: Statement,
+    pub path: PathBuf,
+    pub name: String,
+    pub front_matter: Option<FrontMatter>,
+}
+
+/// How symbolic links found in the template directory tree are treated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Symlinks {
+    /// Parses linked files and descends into linked directories. A file or
+    /// directory reached by more than one path is an error.
+    Follow,
+    /// Skips linked files and directories.
+    Ignore,
+}
+
+/// Controls how template files are found and read from a directory tree.
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    /// Whether symbolic links are followed or skipped.
+    pub symlinks: Symlinks,
+
+    /// The largest template file, in bytes, that may be loaded. Larger files
+    /// are usually generated or misplaced assets rather than templates.
+    pub max_size: u64,
+
+    /// The deepest nesting of sections a template may have. Templates are
+    /// parsed without recursion, but each nested section deepens the code
+    /// generated from it.
+    pub max_depth: usize,
+
+    /// The template syntax to parse.
+    pub dialect: Dialect,
+
+    /// Whether `{{t "key"}}` tags are parsed as translations rather than
+    /// as variables named `t`.
+    pub translations: bool,
+
+    /// Whether identifiers and partial names may also contain `$`, `@`, and
+    /// non-ASCII letters, for context keys like `$schema`.
+    pub extended_identifiers: bool,
+
+    /// Whether a leading YAML block between `---` lines is read as the
+    /// template's front matter rather than parsed as content.
+    pub front_matter: bool,
+
+    /// Whether a leading UTF-8 byte order mark is removed rather than
+    /// parsed as content.
+    pub strip_bom: bool,
+
+    /// Whether `\r\n` line terminators in content are normalized to `\n`,
+    /// so templates checked out with Windows line endings compile to the
+    /// same output.
+    pub normalize_newlines: bool,
+
+    /// Whether runs of whitespace in content are collapsed, outside of
+    /// elements like `<pre>` whose text is rendered as written, to shrink
+    /// HTML output.
+    pub minify: bool,
+
+    /// Gitignore-style patterns, relative to the template directory, that a
+    /// file must match to be loaded. All files are loaded when empty.
+    pub include: Vec<String>,
+
+    /// Gitignore-style patterns, relative to the template directory, of
+    /// files and directories to skip, in addition to those in its
+    /// `.stacheignore` file.
+    pub exclude: Vec<String>,
+
+    /// The names of the entry point templates to keep once the files are
+    /// loaded, along with every partial they render, directly or through
+    /// other partials. All templates are kept when empty.
+    pub roots: Vec<String>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            symlinks: Symlinks::Follow,
+            max_size: 1024 * 1024,
+            max_depth: MAX_DEPTH,
+            dialect: Dialect::default(),
+            translations: false,
+            extended_identifiers: false,
+            front_matter: false,
+            strip_bom: true,
+            normalize_newlines: false,
+            minify: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+}
+
+impl Template {
+    /// Parses each template file in the directory tree.
+    pub fn parse<P>(directory: P) -> Result<Vec<Template>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Template::parse_with(directory, &LoadOptions::default())
+    }
+
+    /// Parses each template file in the directory tree with the options.
+    ///
+    /// Files and directories matching the gitignore-style patterns of a
+    /// `.stacheignore` file in the root directory are skipped. Every
+    /// template with a syntax error is reported, not just the first.
+    ///
+    /// The files are parsed in parallel with the `parallel` feature, but the
+    /// templates are always returned in the order the directory was walked.
+    pub fn parse_with<P>(directory: P, options: &LoadOptions) -> Result<Vec<Template>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let base = directory.as_ref();
+        let _span = info_span!("load", directory = %base.display()).entered();
+
+        let mut walk = Walk {
+            options: options.clone(),
+            ignore: read_ignore(base, &options.exclude)?,
+            include: patterns(base, &options.include)?,
+            visited: HashMap::new(),
+        };
+        let paths = walk.files(base, base)?;
+
+        let load = |path: &PathBuf| parse(path, options);
+        #[cfg(feature = "parallel")]
+        let parsed: Vec<_> = paths.par_iter().map(load).collect();
+        #[cfg(not(feature = "parallel"))]
+        let parsed: Vec<_> = paths.iter().map(load).collect();
+
+        let mut templates = Vec::new();
+        let mut diagnostics = Vec::new();
+        for (path, result) in paths.into_iter().zip(parsed) {
+            match result {
+                Ok((tree, front_matter)) => {
+                    let mut template = Template::try_new(base, path, tree)?;
+                    template.front_matter = front_matter;
+                    templates.push(template);
+                }
+                Err(Error::Parse(diagnostic)) => diagnostics.push(*diagnostic),
+                Err(e) => return Err(e),
+            }
+        }
+
+        diagnostics.sort_by(|a, b| a.path.cmp(&b.path));
+        Error::all(
+            diagnostics
+                .into_iter()
+                .map(Box::new)
+                .map(Error::Parse)
+                .collect(),
+        )?;
+        let templates = select(templates, &options.roots)?;
+        info!(templates = templates.len(), "loaded templates");
+        Ok(templates)
+    }
+
+    /// Parses each template file in the directory trees with the options,
+    /// merging them into one set. A template in a later directory replaces
+    /// any of the same name from earlier ones, so an application's templates
+    /// may be overridden by a theme's.
+    ///
+    /// Syntax errors are reported from every directory. Templates sharing a
+    /// name within one directory are kept for `validate` to report. The
+    /// options' root templates may render partials from any directory.
+    pub fn parse_dirs<P>(directories: &[P], options: &LoadOptions) -> Result<Vec<Template>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let layer_options = LoadOptions {
+            roots: Vec::new(),
+            ..options.clone()
+        };
+        let mut templates: Vec<Template> = Vec::new();
+        let mut errors = Vec::new();
+        for directory in directories {
+            match Template::parse_with(directory, &layer_options) {
+                Ok(layer) => {
+                    let names: HashSet<&str> = layer.iter().map(|t| t.name.as_str()).collect();
+                    templates.retain(|t| !names.contains(t.name.as_str()));
+                    templates.extend(layer);
+                }
+                Err(Error::Multiple(all)) => errors.extend(all),
+                Err(e) => errors.push(e),
+            }
+        }
+        Error::all(errors)?;
+        select(templates, &options.roots)
+    }
+
+    /// Ensures all templates may be linked together into an executable.
+    ///
+    /// This method checks that all partial template paths are provided by
+    /// another template. For example, a `{{>include/header}}` partial
+    /// invocation must be provided by an `include/header.mustache` template
+    /// file. Every duplicate template name and undefined partial is reported,
+    /// not just the first.
+    pub fn validate(templates: &[Template]) -> Result<(), Error> {
+        let mut errors = Vec::new();
+        let mut all = HashMap::new();
+        for template in templates {
+            if let Some(first) = all.insert(template.name.as_str(), &template.path) {
+                errors.push(Error::DuplicateTemplate {
+                    name: template.name.clone(),
+                    first: first.clone(),
+                    second: template.path.clone(),
+                });
+            }
+        }
+
+        for template in templates {
+            let mut missing: Vec<_> = template
+                .tree
+                .partials()
+                .into_iter()
+                .filter(|name| !all.contains_key(name))
+                .collect();
+            missing.sort();
+            missing.dedup();
+
+            for name in missing {
+                errors.push(Error::UnknownPartial {
+                    name: name.to_string(),
+                    path: template.path.clone(),
+                });
+            }
+        }
+
+        Error::all(errors)
+    }
+
+    /// Ensures the templates may be linked by any backend, without
+    /// generating code for one. Along with `validate`'s checks, each
+    /// template's parent tags must resolve without recursion. Every
+    /// template that fails to resolve is reported.
+    pub fn check(templates: &[Template]) -> Result<(), Error> {
+        Template::validate(templates)?;
+        let errors = templates
+            .iter()
+            .filter_map(|t| t.inherit(templates).err())
+            .collect();
+        Error::all(errors)
+    }
+
+    /// Creates a template from file name and root AST node.
+    ///
+    /// The file name is used as an identifier in compiled function names
+    /// to ensure uniqueness when linked with other templates. It provides
+    /// a stable name to be referenced as a partial in other templates.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path isn't inside the base directory. Use `try_new` for
+    /// paths that aren't known to be.
+    pub fn new(base: &Path, path: PathBuf, tree: Statement) -> Self {
+        match Template::try_new(base, path, tree) {
+            Ok(template) => template,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Creates a template from file name and root AST node, failing if the
+    /// path isn't inside the base directory its name is relative to.
+    pub fn try_new(base: &Path, path: PathBuf, tree: Statement) -> Result<Self, Error> {
+        let name = name(base, &path)?;
+
+        Ok(Template {
+            tree: tree,
+            path: path,
+            name: name,
+            front_matter: None,
+        })
+    }
+
+    /// Parses a template's source text as if it had been loaded from
+    /// `app/templates/{name}.mustache`. Useful for compiling templates that
+    /// don't live on disk, such as in tests.
+    pub fn from_source(name: &str, source: &str) -> Result<Self, ParseError> {
+        let base = PathBuf::from("app/templates");
+        let path = base.join(format!("{}.mustache", name));
+        Ok(Template::new(&base, path, Statement::parse(source)?))
+    }
+
+    pub fn name(&self) -> Name {
+        Name::new(&self.name)
+    }
+
+    /// Copies the template, evaluating sections whose paths are defined
+    /// constants at compile time. A taken branch is replaced by its contents
+    /// and an untaken branch is removed, so a build for each set of defines
+    /// has no runtime cost for them.
+    pub fn define(&self, defines: &HashMap<String, bool>) -> Template {
+        let mut block = Block::empty();
+        self.tree.define(defines, Span::default(), &mut block);
+        let tree = match block.statements.len() {
+            1 => block.statements.remove(0),
+            _ => Statement::Program(block),
+        };
+
+        Template {
+            tree,
+            path: self.path.clone(),
+            name: self.name.clone(),
+            front_matter: self.front_matter.clone(),
+        }
+    }
+
+    /// Copies each template with `Template::define`, or returns `None` when
+    /// nothing is defined and no template tests a feature flag, so the
+    /// templates may be used as they are.
+    pub fn define_all(
+        templates: &[Template],
+        defines: &HashMap<String, bool>,
+    ) -> Option<Vec<Template>> {
+        let flagged = templates.iter().any(|t| !t.tree.flags().is_empty());
+        if defines.is_empty() && !flagged {
+            return None;
+        }
+        Some(templates.iter().map(|t| t.define(defines)).collect())
+    }
+
+    /// Copies the template, replacing each `{{<parent}}` tag with the parent
+    /// template's contents, its `{{$block}}` tags filled by the overrides
+    /// given inside the tag. Blocks without an override render their default
+    /// contents. The template set must be validated first, so each parent
+    /// exists.
+    pub fn inherit(&self, templates: &[Template]) -> Result<Template, Error> {
+        let mut block = Block::empty();
+        let mut parents = vec![self.name.as_str()];
+        self.tree
+            .inherit(
+                templates,
+                &HashMap::new(),
+                &mut parents,
+                Span::default(),
+                &mut block,
+            )
+            .map_err(|name| Error::RecursiveParent {
+                name,
+                path: self.path.clone(),
+            })?;
+        let tree = match block.statements.len() {
+            1 => block.statements.remove(0),
+            _ => Statement::Program(block),
+        };
+
+        Ok(Template {
+            tree,
+            path: self.path.clone(),
+            name: self.name.clone(),
+            front_matter: self.front_matter.clone(),
+        })
+    }
+}
+
+/// Keeps the root templates and the partials they render, in their loaded
+/// order, or every template if there are no roots. Each root must name a
+/// template.
+fn select(templates: Vec<Template>, roots: &[String]) -> Result<Vec<Template>, Error> {
+    if roots.is_empty() {
+        return Ok(templates);
+    }
+
+    let graph = Graph::build(&templates);
+    let keep: HashSet<_> = graph.closure(roots).into_iter().collect();
+    let missing = roots
+        .iter()
+        .filter(|root| !keep.contains(root))
+        .map(|root| Error::TemplateNotFound { name: root.clone() })
+        .collect();
+    Error::all(missing)?;
+
+    Ok(templates
+        .into_iter()
+        .filter(|template| keep.contains(&template.name))
+        .collect())
+}
+
+/// Creates a shortened path name for a template file name. The base directory
+/// being compiled and the file extension is stripped off to create the short
+/// name: `app/templates/include/header.mustache -> include/header`.
+///
+/// Directories are always separated by `/`, as partial tags reference them,
+/// so drive prefixes and `\` separators on Windows produce the same name.
+fn name(base: &Path, path: &Path) -> Result<String, Error> {
+    let relative = path.strip_prefix(base).map_err(|_| {
+        let message = format!("not inside the template directory {}", base.display());
+        Error::io(path, io::Error::new(ErrorKind::InvalidInput, message))
+    })?;
+    let parts: Vec<_> = relative
+        .with_extension("")
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    Ok(parts.join("/"))
+}
+
+/// Tracks the canonical path of each file and directory found, so a
+/// symlink can't load a template twice or recurse forever.
+struct Walk {
+    options: LoadOptions,
+    ignore: Gitignore,
+    include: Gitignore,
+    visited: HashMap<PathBuf, PathBuf>,
+}
+
+impl Walk {
+    /// Lists the template files to parse in the directory tree, skipping
+    /// ignored and excluded paths. Each directory's entries are sorted by
+    /// name, so the templates are listed in the same order on every file
+    /// system and the generated code is identical for identical inputs.
+    fn files(&mut self, base: &Path, dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut files = Vec::new();
+        if !dir.is_dir() {
+            return Ok(files);
+        }
+
+        self.visit(dir)?;
+        let mut entries = fs::read_dir(dir)
+            .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| Error::io(dir, e))?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let file_type = entry.file_type().map_err(|e| Error::io(entry.path(), e))?;
+            if self.options.symlinks == Symlinks::Ignore && file_type.is_symlink() {
+                continue;
+            }
+
+            let path = entry.path();
+            let is_dir = path.is_dir();
+            if path == base.join(IGNORE) || self.ignore.matched(&path, is_dir).is_ignore() {
+                continue;
+            }
+
+            if is_dir {
+                files.append(&mut self.files(base, &path)?);
+            } else if self.included(&path) {
+                self.visit(&path)?;
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Tests whether the file, or a directory containing it, matches an
+    /// include pattern, when the options give any.
+    fn included(&self, path: &Path) -> bool {
+        self.include.is_empty()
+            || self
+                .include
+                .matched_path_or_any_parents(path, false)
+                .is_ignore()
+    }
+
+    /// Records the path as parsed, failing if it was already reached through
+    /// another path.
+    ///
+    /// The link is usually the later path, but a link sorted before the file
+    /// or directory it points to is reached first, so the error names
+    /// whichever of the two is the link.
+    fn visit(&mut self, path: &Path) -> Result<(), Error> {
+        let canonical = fs::canonicalize(path).map_err(|e| Error::io(path, e))?;
+        match self.visited.get(&canonical) {
+            Some(first) if !is_symlink(path) && is_symlink(first) => Err(Error::Symlink {
+                path: first.clone(),
+                target: path.to_path_buf(),
+            }),
+            Some(first) => Err(Error::Symlink {
+                path: path.to_path_buf(),
+                target: first.clone(),
+            }),
+            None => {
+                self.visited.insert(canonical, path.to_path_buf());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Tests whether the path is itself a symbolic link, without following it.
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+/// The file listing template paths to skip, relative to the template root.
+const IGNORE: &str = ".stacheignore";
+
+/// Reads the template root's ignore file, if it has one, along with the
+/// exclude patterns given in the options.
+fn read_ignore(base: &Path, exclude: &[String]) -> Result<Gitignore, Error> {
+    let mut builder = GitignoreBuilder::new(base);
+    let path = base.join(IGNORE);
+    if path.is_file() {
+        if let Some(e) = builder.add(&path) {
+            return Err(Error::Ignore {
+                path,
+                message: e.to_string(),
+            });
+        }
+    }
+    for pattern in exclude {
+        add_pattern(&mut builder, pattern)?;
+    }
+    builder.build().map_err(|e| Error::Ignore {
+        path,
+        message: e.to_string(),
+    })
+}
+
+/// Builds a matcher for the gitignore-style patterns, relative to the
+/// template root.
+fn patterns(base: &Path, patterns: &[String]) -> Result<Gitignore, Error> {
+    let mut builder = GitignoreBuilder::new(base);
+    for pattern in patterns {
+        add_pattern(&mut builder, pattern)?;
+    }
+    builder.build().map_err(|e| Error::Glob {
+        pattern: patterns.join(" "),
+        message: e.to_string(),
+    })
+}
+
+fn add_pattern(builder: &mut GitignoreBuilder, pattern: &str) -> Result<(), Error> {
+    builder
+        .add_line(None, pattern)
+        .map(|_| ())
+        .map_err(|e| Error::Glob {
+            pattern: pattern.to_string(),
+            message: e.to_string(),
+        })
+}
+
+/// Finds the one-based line and column of the byte offset in the text. The
+/// column counts characters, as editors do, rather than bytes.
+fn line_column(text: &[u8], offset: usize) -> (usize, usize) {
+    let line = text[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+    let start = line_start(text, offset);
+    let column = String::from_utf8_lossy(&text[start..offset])
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// Finds the byte offset of the start of the line containing the offset.
+fn line_start(text: &[u8], offset: usize) -> usize {
+    text[..offset]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1)
+}
+
+/// Converts the file's contents to a string, reporting where the first
+/// invalid UTF-8 sequence is and which bytes it contains.
+fn decode(path: &Path, bytes: Vec<u8>) -> Result<String, Error> {
+    String::from_utf8(bytes).map_err(|e| {
+        let bytes = e.as_bytes();
+        let offset = e.utf8_error().valid_up_to();
+        let length = e.utf8_error().error_len().unwrap_or(bytes.len() - offset);
+        let (line, column) = line_column(bytes, offset);
+        Error::Encoding {
+            path: path.to_path_buf(),
+            offset,
+            line,
+            column,
+            bytes: bytes[offset..offset + length].to_vec(),
+        }
+    })
+}
+
+/// Reads and parses the template file, along with its front matter when
+/// the options enable it.
+fn parse(path: &Path, options: &LoadOptions) -> Result<(Statement, Option<FrontMatter>), Error> {
+    let _span = debug_span!("parse", path = %path.display()).entered();
+    let mut file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let size = file.metadata().map_err(|e| Error::io(path, e))?.len();
+    if size > options.max_size {
+        return Err(Error::TooLarge {
+            path: path.to_path_buf(),
+            size,
+            limit: options.max_size,
+        });
+    }
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)
+        .map_err(|e| Error::io(path, e))?;
+    let source = decode(path, bytes)?;
+    debug!(bytes = source.len(), "read template");
+    let template = strip_bom(&source, options);
+
+    let front_matter = match front_matter::split(template) {
+        Some((text, _)) if options.front_matter => {
+            let matter = FrontMatter::parse(text).map_err(|message| Error::FrontMatter {
+                path: path.to_path_buf(),
+                message,
+            })?;
+            Some(matter)
+        }
+        _ => None,
+    };
+
+    let tree = Statement::parse_with(&source, options).map_err(|e| {
+        let position = e.position();
+        let (line, column) = line_column(template.as_bytes(), position);
+        let start = line_start(template.as_bytes(), position);
+        let end = template[position..]
+            .find(['\r', '\n'])
+            .map_or(template.len(), |i| position + i);
+        Error::Parse(Box::new(Diagnostic {
+            path: path.to_path_buf(),
+            line,
+            column,
+            text: template[start..end].to_string(),
+            source: e,
+        }))
+    })?;
+    Ok((tree, front_matter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Error, Statement};
+    use super::{LoadOptions, Symlinks, Template};
+    use std::collections::HashMap;
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+    use tempdir::TempDir;
+
+    #[test]
+    fn name() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/include/header.mustache");
+        let tree = Statement::Content(String::from("test"));
+
+        let template = Template::new(&base, path, tree);
+        assert_eq!("include/header", template.name);
+        assert_eq!("include_header", template.name().id());
+    }
+
+    #[test]
+    fn define_all() {
+        let templates = vec![
+            Template::from_source(
+                "page",
+                "{{#flags.beta}}a{{/flags.beta}}{{^flags.debug}}b{{/flags.debug}}",
+            )
+            .unwrap(),
+            Template::from_source("robot", "{{#name}}{{name}}{{/name}}").unwrap(),
+        ];
+
+        let mut defines = HashMap::new();
+        assert!(Template::define_all(&templates[1..], &defines).is_none());
+
+        defines.insert(String::from("beta"), true);
+        let defined = Template::define_all(&templates, &defines).unwrap();
+        assert_eq!(
+            vec!["page", "robot"],
+            defined.iter().map(|t| t.name.as_str()).collect::<Vec<_>>()
+        );
+        assert!(defined[0].tree.flags().is_empty());
+        assert_eq!(templates[1].tree, defined[1].tree);
+    }
+
+    #[test]
+    fn name_ignores_trailing_separator() {
+        let base = PathBuf::from("./app/templates/");
+        let path = base.join("include/header.mustache");
+        let tree = Statement::Content(String::from("test"));
+
+        let template = Template::new(&base, path, tree);
+        assert_eq!("include/header", template.name);
+    }
+
+    #[test]
+    fn rejects_path_outside_base() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("lib/templates/header.mustache");
+        let tree = Statement::Content(String::from("test"));
+
+        match Template::try_new(&base, path, tree) {
+            Err(Error::Io { path, .. }) => {
+                assert_eq!(PathBuf::from("lib/templates/header.mustache"), path)
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn name_separates_windows_directories_with_slashes() {
+        let tree = || Statement::Content(String::from("test"));
+
+        let base = PathBuf::from(r"C:\app\templates");
+        let path = PathBuf::from(r"C:\app\templates\include\header.mustache");
+        let template = Template::new(&base, path, tree());
+        assert_eq!("include/header", template.name);
+
+        let base = PathBuf::from(r"\\?\UNC\server\share\templates");
+        let path = base.join(r"include\page.header.mustache");
+        let template = Template::new(&base, path, tree());
+        assert_eq!("include/page.header", template.name);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn detects_symlinked_duplicates() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("stache-symlinks").unwrap();
+        let base = dir.path();
+        fs::create_dir_all(base.join("include")).unwrap();
+        File::create(base.join("include/header.mustache")).unwrap();
+        symlink(base, base.join("include/loop")).unwrap();
+
+        let err = Template::parse(base).unwrap_err();
+        let message = format!(
+            "{} is a symlink to {}, a directory containing it",
+            base.join("include/loop").display(),
+            base.display()
+        );
+        assert!(err.to_string().starts_with(&message), "{}", err);
+
+        let templates = Template::parse_with(
+            base,
+            &LoadOptions {
+                symlinks: Symlinks::Ignore,
+                ..LoadOptions::default()
+            },
+        )
+        .unwrap();
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["include/header"], names);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn names_symlink_walked_before_its_target() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("stache-symlinks").unwrap();
+        let base = dir.path();
+        fs::create_dir_all(base.join("pages")).unwrap();
+        File::create(base.join("pages/robots.mustache")).unwrap();
+        symlink(base.join("pages"), base.join("current")).unwrap();
+
+        let err = Template::parse(base).unwrap_err();
+        let message = format!(
+            "{} is a symlink to {}, which is also loaded",
+            base.join("current").display(),
+            base.join("pages").display()
+        );
+        assert!(err.to_string().starts_with(&message), "{}", err);
+    }
+
+    #[test]
+    fn skips_ignored_files() {
+        let dir = TempDir::new("stache-ignore").unwrap();
+        let base = dir.path();
+        fs::create_dir_all(base.join("include")).unwrap();
+        fs::create_dir_all(base.join("drafts")).unwrap();
+        File::create(base.join("include/header.mustache")).unwrap();
+        File::create(base.join("include/.header.mustache.swp")).unwrap();
+        File::create(base.join("drafts/page.mustache")).unwrap();
+        fs::write(base.join(".stacheignore"), "*.swp\n/drafts/\n").unwrap();
+
+        let templates = Template::parse(base).unwrap();
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["include/header"], names);
+    }
+
+    #[test]
+    fn filters_included_and_excluded_files() {
+        let dir = TempDir::new("stache-globs").unwrap();
+        let base = dir.path();
+        fs::create_dir_all(base.join("include")).unwrap();
+        fs::create_dir_all(base.join("node_modules/pkg")).unwrap();
+        fs::create_dir_all(base.join("experimental")).unwrap();
+        File::create(base.join("include/header.mustache")).unwrap();
+        File::create(base.join("include/header.mustache~")).unwrap();
+        File::create(base.join("node_modules/pkg/index.mustache")).unwrap();
+        File::create(base.join("experimental/page.mustache")).unwrap();
+        File::create(base.join("README.md")).unwrap();
+
+        let options = LoadOptions {
+            include: vec!["*.mustache".into()],
+            exclude: vec!["node_modules/".into(), "/experimental/".into()],
+            ..LoadOptions::default()
+        };
+        let templates = Template::parse_with(base, &options).unwrap();
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["include/header"], names);
+
+        let options = LoadOptions {
+            include: vec!["{".into()],
+            ..LoadOptions::default()
+        };
+        match Template::parse_with(base, &options) {
+            Err(Error::Glob { ref pattern, .. }) => assert_eq!("{", pattern),
+            other => panic!("Expected a pattern error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn merges_directories_by_name() {
+        let dir = TempDir::new("stache-merge").unwrap();
+        let (app, theme) = (dir.path().join("app"), dir.path().join("theme"));
+        fs::create_dir_all(app.join("include")).unwrap();
+        fs::create_dir_all(theme.join("include")).unwrap();
+        fs::write(app.join("page.mustache"), "{{> include/header}}").unwrap();
+        fs::write(app.join("include/header.mustache"), "<h1>App</h1>").unwrap();
+        fs::write(theme.join("include/header.mustache"), "<h1>Theme</h1>").unwrap();
+
+        let templates = Template::parse_dirs(&[&app, &theme], &LoadOptions::default()).unwrap();
+        let paths: Vec<_> = templates.iter().map(|t| t.path.clone()).collect();
+        assert_eq!(
+            vec![
+                app.join("page.mustache"),
+                theme.join("include/header.mustache")
+            ],
+            paths
+        );
+        assert!(Template::validate(&templates).is_ok());
+    }
+
+    #[test]
+    fn keeps_root_templates_and_their_partials() {
+        let dir = TempDir::new("stache-roots").unwrap();
+        let (app, admin) = (dir.path().join("app"), dir.path().join("admin"));
+        fs::create_dir_all(app.join("include")).unwrap();
+        fs::create_dir_all(&admin).unwrap();
+        fs::write(app.join("page.mustache"), "{{> include/header}}").unwrap();
+        fs::write(app.join("include/header.mustache"), "{{> include/logo}}").unwrap();
+        fs::write(app.join("include/logo.mustache"), "<img>").unwrap();
+        fs::write(app.join("include/footer.mustache"), "<footer>").unwrap();
+        fs::write(admin.join("users.mustache"), "{{> include/footer}}").unwrap();
+
+        let options = LoadOptions {
+            roots: vec!["page".into()],
+            ..LoadOptions::default()
+        };
+        let templates = Template::parse_with(&app, &options).unwrap();
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["include/header", "include/logo", "page"], names);
+
+        let options = LoadOptions {
+            roots: vec!["users".into()],
+            ..LoadOptions::default()
+        };
+        let templates = Template::parse_dirs(&[&app, &admin], &options).unwrap();
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["include/footer", "users"], names);
+
+        let options = LoadOptions {
+            roots: vec!["page".into(), "missing".into()],
+            ..LoadOptions::default()
+        };
+        match Template::parse_with(&app, &options) {
+            Err(Error::TemplateNotFound { ref name }) => assert_eq!("missing", name),
+            other => panic!("Expected a missing template: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lists_templates_in_name_order() {
+        let dir = TempDir::new("stache-order").unwrap();
+        let base = dir.path();
+        fs::create_dir_all(base.join("b")).unwrap();
+        for name in &["c", "b/z", "a", "b/a", "ba"] {
+            fs::write(base.join(format!("{}.mustache", name)), "").unwrap();
+        }
+
+        let templates = Template::parse(base).unwrap();
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["a", "b/a", "b/z", "ba", "c"], names);
+    }
+
+    #[test]
+    fn limits_template_size() {
+        let dir = TempDir::new("stache-size").unwrap();
+        let base = dir.path();
+        fs::write(base.join("large.mustache"), "0123456789").unwrap();
+
+        let options = LoadOptions {
+            max_size: 4,
+            ..LoadOptions::default()
+        };
+        let err = Template::parse_with(base, &options).unwrap_err();
+        let expected = format!(
+            "{}: template is 10 bytes, larger than the 4 byte limit",
+            base.join("large.mustache").display()
+        );
+        assert_eq!(expected, err.to_string());
+    }
+
+    #[test]
+    fn limits_section_depth() {
+        let dir = TempDir::new("stache-depth").unwrap();
+        let base = dir.path();
+        fs::write(base.join("deep.mustache"), "{{#a}}\n  {{#b}}{{/b}}\n{{/a}}").unwrap();
+
+        let options = LoadOptions {
+            max_depth: 1,
+            ..LoadOptions::default()
+        };
+        let err = Template::parse_with(base, &options).unwrap_err();
+        let expected = format!(
+            "Error parsing {} at line 2, column 6\n  |\n2 |   {{{{#b}}}}{{{{/b}}}}\n  |      ^\n\
+             Section at position 12 is nested more than 1 level deep",
+            base.join("deep.mustache").display()
+        );
+        assert_eq!(expected, err.to_string());
+    }
+
+    #[test]
+    fn reports_invalid_utf8() {
+        let dir = TempDir::new("stache-utf8").unwrap();
+        let base = dir.path();
+        fs::write(
+            base.join("latin1.mustache"),
+            b"<p>\n{{ name }}\ncaf\xe9 \xff</p>",
+        )
+        .unwrap();
+
+        let err = Template::parse(base).unwrap_err();
+        let expected = format!(
+            "{}: invalid UTF-8 at byte 18, line 3, column 4: e9",
+            base.join("latin1.mustache").display()
+        );
+        assert_eq!(expected, err.to_string());
+    }
+
+    #[test]
+    fn reports_parse_error_position() {
+        let dir = TempDir::new("stache-parse").unwrap();
+        let base = dir.path();
+        fs::write(base.join("robots.mustache"), "<ul>\n  {{# robots }}\n</ul>").unwrap();
+
+        match Template::parse(base) {
+            Err(Error::Parse(ref diagnostic)) => {
+                assert_eq!(base.join("robots.mustache"), diagnostic.path);
+                assert_eq!((2, 7), (diagnostic.line, diagnostic.column));
+                assert_eq!("  {{# robots }}", diagnostic.text);
+                assert_eq!("      ^", diagnostic.marker());
+            }
+            other => panic!("Expected a parse error: {:?}", other),
+        }
+
+        let json = Template::parse(base).unwrap_err().json();
+        assert_eq!(1, json.len());
+        assert_eq!("E0004", json[0]["code"]);
+        assert_eq!("parse", json[0]["kind"]);
+        assert_eq!(2, json[0]["span"]["line"]);
+        assert_eq!(7, json[0]["span"]["column"]);
+        assert_eq!(
+            base.join("robots.mustache").to_string_lossy(),
+            json[0]["file"].as_str().unwrap()
+        );
+        assert_eq!(
+            "Section 'robots' opened at line 2 is never closed",
+            json[0]["message"]
+        );
+    }
+
+    #[test]
+    fn reports_parse_error_column_in_characters() {
+        let dir = TempDir::new("stache-parse").unwrap();
+        let base = dir.path();
+        fs::write(base.join("robots.mustache"), "<p>\n  café {{# robots }}").unwrap();
+
+        match Template::parse(base) {
+            Err(Error::Parse(ref diagnostic)) => {
+                assert_eq!((2, 8), (diagnostic.line, diagnostic.column));
+                assert_eq!("  café {{# robots }}", diagnostic.text);
+                assert_eq!("       ^", diagnostic.marker());
+            }
+            other => panic!("Expected a parse error: {:?}", other),
+        }
+
+        fs::write(base.join("robots.mustache"), b"<p>\n  caf\xc3\xa9 \xff").unwrap();
+        match Template::parse(base) {
+            Err(Error::Encoding { line, column, .. }) => assert_eq!((2, 8), (line, column)),
+            other => panic!("Expected an encoding error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_parse_error_position_after_bom() {
+        let dir = TempDir::new("stache-parse").unwrap();
+        let base = dir.path();
+        fs::write(base.join("robots.mustache"), "\u{feff}{{# robots }}").unwrap();
+
+        match Template::parse(base) {
+            Err(Error::Parse(ref diagnostic)) => {
+                assert_eq!((1, 5), (diagnostic.line, diagnostic.column));
+                assert_eq!("{{# robots }}", diagnostic.text);
+            }
+            other => panic!("Expected a parse error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_front_matter() {
+        let dir = TempDir::new("stache-front-matter").unwrap();
+        let base = dir.path();
+        let source = "---\nlayout: app\nrequired: [robots]\n---\n<ul>{{robots}}</ul>\n{{/x}}";
+        fs::write(base.join("robots.mustache"), source).unwrap();
+
+        let options = LoadOptions {
+            front_matter: true,
+            ..LoadOptions::default()
+        };
+        match Template::parse_with(base, &options) {
+            Err(Error::Parse(ref diagnostic)) => assert_eq!(6, diagnostic.line),
+            other => panic!("Expected a parse error: {:?}", other),
+        }
+
+        fs::write(base.join("robots.mustache"), &source[..source.len() - 7]).unwrap();
+        let templates = Template::parse_with(base, &options).unwrap();
+        let matter = templates[0].front_matter.as_ref().unwrap();
+        assert_eq!(Some("app"), matter.layout());
+        assert_eq!(vec!["robots"], matter.required());
+        let expected = Statement::parse("<ul>{{robots}}</ul>").unwrap();
+        assert_eq!(expected, templates[0].tree);
+
+        let templates = Template::parse(base).unwrap();
+        assert!(templates[0].front_matter.is_none());
+
+        fs::write(base.join("robots.mustache"), "---\n- robots\n---\n").unwrap();
+        match Template::parse_with(base, &options) {
+            Err(Error::FrontMatter { ref path, .. }) => {
+                assert_eq!(&base.join("robots.mustache"), path)
+            }
+            other => panic!("Expected a front matter error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_every_parse_error() {
+        let dir = TempDir::new("stache-parse").unwrap();
+        let base = dir.path();
+        fs::create_dir(base.join("includes")).unwrap();
+        fs::write(base.join("robots.mustache"), "{{# robots }}").unwrap();
+        fs::write(base.join("includes/header.mustache"), "{{/ header }}").unwrap();
+        fs::write(base.join("footer.mustache"), "{{ name }}").unwrap();
+
+        let err = Template::parse(base).unwrap_err();
+        let paths: Vec<_> = err
+            .errors()
+            .into_iter()
+            .map(|e| match *e {
+                Error::Parse(ref diagnostic) => diagnostic.path.clone(),
+                ref other => panic!("Expected a parse error: {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                base.join("includes/header.mustache"),
+                base.join("robots.mustache"),
+            ],
+            paths
+        );
+    }
+
+    #[test]
+    fn validates_every_template() {
+        let base = PathBuf::from("app/templates");
+        let template = |name: &str, text: &str| {
+            let path = base.join(name);
+            Template::new(&base, path, Statement::parse(text).unwrap())
+        };
+        let templates = vec![
+            template(
+                "robots.mustache",
+                "{{> missing/b }}{{> missing/a }}{{> robot }}",
+            ),
+            template("robot.mustache", "{{> missing/a }}"),
+            template("robot.html", "hubot"),
+        ];
+
+        let err = Template::validate(&templates).unwrap_err();
+        let messages: Vec<_> = err.errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            vec![
+                "Template `robot` is defined by both app/templates/robot.mustache and app/templates/robot.html",
+                "Undefined partial `missing/a` called in app/templates/robots.mustache",
+                "Undefined partial `missing/b` called in app/templates/robots.mustache",
+                "Undefined partial `missing/a` called in app/templates/robot.mustache",
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn inherits_block_overrides() {
+        let base = PathBuf::from("app/templates");
+        let template = |name: &str, text: &str| {
+            let path = base.join(name);
+            Template::new(&base, path, Statement::parse(text).unwrap())
+        };
+        let templates = vec![
+            template(
+                "layout.mustache",
+                "<h1>{{$title}}Default{{/title}}</h1>{{$body}}{{/body}}",
+            ),
+            template(
+                "page.mustache",
+                "{{<layout}}{{$title}}Page{{/title}}{{$body}}Empty{{/body}}{{/layout}}",
+            ),
+            template(
+                "robots.mustache",
+                "{{<page}}ignored{{$body}}{{name}}{{/body}}{{/page}}",
+            ),
+        ];
+
+        let robots = templates[2].inherit(&templates).unwrap();
+        let expected = Statement::parse("<h1>Page</h1>{{name}}").unwrap();
+        assert_eq!(expected, robots.tree);
+
+        let page = templates[1].inherit(&templates).unwrap();
+        let expected = Statement::parse("<h1>Page</h1>Empty").unwrap();
+        assert_eq!(expected, page.tree);
+    }
+
+    #[test]
+    fn rejects_recursive_parents() {
+        let base = PathBuf::from("app/templates");
+        let template = |name: &str, text: &str| {
+            let path = base.join(name);
+            Template::new(&base, path, Statement::parse(text).unwrap())
+        };
+        let templates = vec![
+            template("a.mustache", "{{<b}}{{/b}}"),
+            template("b.mustache", "{{<a}}{{/a}}"),
+        ];
+
+        let err = templates[0].inherit(&templates).unwrap_err();
+        assert_eq!(
+            "Parent template `a` inherits from itself in app/templates/a.mustache",
+            err.to_string()
+        );
+
+        let err = Template::check(&templates).unwrap_err();
+        assert_eq!(2, err.errors().len());
+    }
+}
@@ -0,0 +1,244 @@
+use serde_json;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use super::{Backend, Compile, Error, Shape, Template};
+
+/// TypeScript declarations describing the context data expected by each
+/// template, to accompany a JavaScript build of the same template set.
+///
+/// Each template receives an exported `<Name>Context` interface. A
+/// `Templates` interface maps template names to their context types, typing
+/// the module's `render(name, context)` dispatch function.
+#[derive(Debug)]
+pub struct Declarations {
+    interfaces: Vec<Interface>,
+}
+
+#[derive(Debug)]
+struct Interface {
+    template: String,
+    name: String,
+    shape: Shape,
+}
+
+impl Declarations {
+    /// Returns the name of the context interface declared for a template.
+    pub fn interface(&self, template: &str) -> Option<&str> {
+        self.interfaces
+            .iter()
+            .find(|interface| interface.template == template)
+            .map(|interface| interface.name.as_str())
+    }
+
+    /// Writes the `Scalar` and `Section` types, each template's context
+    /// interface, and the `Templates` interface, shared by the `.d.ts` source
+    /// and TypeScript modules compiled from the same templates.
+    pub fn emit_types(&self, buf: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            buf,
+            "export type Scalar = string | number | boolean | null;"
+        )?;
+        writeln!(
+            buf,
+            "export type Section<T> = Array<T> | T | boolean | null;"
+        )?;
+
+        for interface in &self.interfaces {
+            writeln!(buf)?;
+            writeln!(buf, "export interface {} {{", interface.name)?;
+            members(buf, &interface.shape, 1)?;
+            writeln!(buf, "}}")?;
+        }
+
+        writeln!(buf)?;
+        writeln!(buf, "export interface Templates {{")?;
+        for interface in &self.interfaces {
+            let key = serde_json::to_string(&interface.template).expect("string serializes");
+            writeln!(buf, "  {}: {};", key, interface.name)?;
+        }
+        writeln!(buf, "}}")
+    }
+}
+
+impl Compile for Declarations {
+    /// Writes the `.d.ts` source to the output buffer.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        self.emit_types(buf)?;
+        writeln!(buf)?;
+        writeln!(
+            buf,
+            "export declare function render<K extends keyof Templates>(name: K, context: Templates[K]): string;"
+        )
+    }
+}
+
+/// The `dts` backend, generating the declarations without render functions.
+#[derive(Debug)]
+pub struct Generator;
+
+impl Backend for Generator {
+    fn name(&self) -> &str {
+        "dts"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(declarations(templates)))
+    }
+}
+
+/// Infers the context shape of each template and declares it as a
+/// TypeScript interface. Feature flags are resolved at compile time, so
+/// they're not declared as context.
+pub fn declarations(templates: &[Template]) -> Declarations {
+    let defined = Template::define_all(templates, &HashMap::new());
+    let templates = defined.as_deref().unwrap_or(templates);
+    let mut names = HashSet::new();
+    let interfaces = templates
+        .iter()
+        .map(|template| Interface {
+            template: template.name.clone(),
+            name: interface_name(&template.name, &mut names),
+            shape: Shape::infer(template, templates),
+        })
+        .collect();
+
+    Declarations { interfaces }
+}
+
+/// Names the context interface declared for a template:
+/// `includes/page-header -> IncludesPageHeaderContext`.
+///
+/// Names that would begin with a digit are prefixed with `T`, and a name
+/// already taken by another template, like `page_header` after
+/// `page-header`, is numbered to keep it unique.
+fn interface_name(template: &str, taken: &mut HashSet<String>) -> String {
+    let mut base = pascal_case(template);
+    if !base.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        base.insert(0, 'T');
+    }
+
+    let mut name = format!("{}Context", base);
+    let mut count = 1;
+    while taken.contains(&name) {
+        count += 1;
+        name = format!("{}{}Context", base, count);
+    }
+    taken.insert(name.clone());
+    name
+}
+
+/// Writes an optional member declaration for each property of the shape.
+/// Mustache renders missing values as empty text, so every key is optional.
+fn members(buf: &mut dyn Write, shape: &Shape, depth: usize) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    for (key, value) in &shape.properties {
+        write!(buf, "{}{}?: ", indent, property(key))?;
+        type_of(buf, value, depth)?;
+        writeln!(buf, ";")?;
+    }
+    Ok(())
+}
+
+/// Writes the type expression for a context value's shape.
+///
+/// Section values may be a list of items, a single item pushed onto the
+/// context stack, or a boolean toggle, which the `Section` type declares
+/// without repeating the item type, so nested sections don't double it.
+fn type_of(buf: &mut dyn Write, shape: &Shape, depth: usize) -> io::Result<()> {
+    if let Some(item) = shape.item() {
+        write!(buf, "Section<")?;
+        type_of(buf, &item, depth)?;
+        write!(buf, ">")
+    } else if !shape.properties.is_empty() {
+        writeln!(buf, "{{")?;
+        members(buf, shape, depth + 1)?;
+        write!(buf, "{}}}", "  ".repeat(depth))
+    } else if shape.interpolated {
+        write!(buf, "Scalar")
+    } else {
+        write!(buf, "unknown")
+    }
+}
+
+/// Quotes property names that are not valid TypeScript identifiers, such as
+/// `valid?` or `first-name`, escaping quotes and backslashes within them.
+fn property(key: &str) -> String {
+    let identifier = key.chars().enumerate().all(|(i, c)| {
+        c == '_' || c == '$' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit())
+    });
+
+    if identifier {
+        String::from(key)
+    } else {
+        serde_json::to_string(key).expect("string serializes")
+    }
+}
+
+/// Creates a type name from a template's short name:
+/// `includes/page-header -> IncludesPageHeader`.
+fn pascal_case(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Compile, Statement, Template};
+    use super::{declarations, interface_name, pascal_case, property};
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    #[test]
+    fn names() {
+        assert_eq!("IncludesPageHeader", pascal_case("includes/page-header"));
+        assert_eq!("name", property("name"));
+        assert_eq!("\"valid?\"", property("valid?"));
+        assert_eq!("\"1st\"", property("1st"));
+        assert_eq!("\"say \\\"hi\\\"\"", property("say \"hi\""));
+        assert_eq!("\"a\\\\b\"", property("a\\b"));
+
+        let mut taken = HashSet::new();
+        assert_eq!(
+            "PageHeaderContext",
+            interface_name("page-header", &mut taken)
+        );
+        assert_eq!(
+            "PageHeader2Context",
+            interface_name("page_header", &mut taken)
+        );
+        assert_eq!("T404Context", interface_name("404", &mut taken));
+        assert_eq!("TContext", interface_name("_", &mut taken));
+    }
+
+    #[test]
+    fn declares_context_interfaces() {
+        let base = PathBuf::from("app/templates");
+        let path = base.join("robots.mustache");
+        let text = "{{ title }}{{#robots}}<li>{{ name.first }}</li>{{/robots}}";
+        let template = Template::new(&base, path, Statement::parse(text).unwrap());
+
+        let mut buf = Vec::new();
+        declarations(&[template]).emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        let expected = "export interface RobotsContext {
+  robots?: Section<{
+    name?: {
+      first?: Scalar;
+    };
+  }>;
+  title?: Scalar;
+}";
+        assert!(source.contains(expected), "{}", source);
+        assert!(source.contains("  \"robots\": RobotsContext;"));
+    }
+}
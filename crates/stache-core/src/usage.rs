@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use super::{Path, Span, Statement, Template};
+
+/// The kind of tag that references a context path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Variable,
+    Html,
+    Section,
+    Inverted,
+    /// A dynamic partial tag, naming the template to render.
+    Partial,
+    /// A partial tag's argument, passed to the partial under its key.
+    Argument,
+}
+
+/// A single reference to a context path within a template.
+#[derive(Debug)]
+pub struct Usage {
+    pub template: String,
+    pub file: PathBuf,
+    pub kind: Kind,
+    pub span: Span,
+    /// The enclosing section paths, outermost first, whose context frames
+    /// the path may be resolved against.
+    pub sections: Vec<String>,
+}
+
+impl fmt::Display for Usage {
+    /// Formats the usage location as `file:line:column`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.file.display(),
+            self.span.line,
+            self.span.column
+        )
+    }
+}
+
+/// An index of every context path referenced by a set of templates, mapping
+/// the dotted path name to each location it is used.
+///
+/// Paths are indexed as written in the template. A `{{ name }}` tag inside a
+/// `{{# robots }}` section is indexed under `name`, with `robots` recorded
+/// as an enclosing section of the usage.
+#[derive(Debug, Default)]
+pub struct Index {
+    paths: BTreeMap<String, Vec<Usage>>,
+}
+
+impl Index {
+    /// Builds the usage index for the templates.
+    pub fn build(templates: &[Template]) -> Self {
+        let mut index = Index::default();
+        for template in templates {
+            let mut sections = Vec::new();
+            index.collect(template, &template.tree, &mut sections);
+        }
+        index
+    }
+
+    /// Returns the locations where the dotted path name is used.
+    pub fn get(&self, path: &str) -> &[Usage] {
+        self.paths.get(path).map(|list| &list[..]).unwrap_or(&[])
+    }
+
+    /// Iterates over each indexed path name and its usages in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Vec<Usage>)> {
+        self.paths.iter()
+    }
+
+    fn collect(&mut self, template: &Template, node: &Statement, sections: &mut Vec<String>) {
+        match *node {
+            Statement::Program(ref block) => {
+                for stmt in &block.statements {
+                    self.collect(template, stmt, sections);
+                }
+            }
+            Statement::Section(ref path, ref block) | Statement::Inverted(ref path, ref block) => {
+                let kind = match *node {
+                    Statement::Section(..) => Kind::Section,
+                    _ => Kind::Inverted,
+                };
+                self.insert(template, path, kind, sections);
+
+                sections.push(path.to_string());
+                for stmt in &block.statements {
+                    self.collect(template, stmt, sections);
+                }
+                sections.pop();
+            }
+            Statement::Variable(ref path) | Statement::Filtered(ref path, _) => {
+                self.insert(template, path, Kind::Variable, sections)
+            }
+            Statement::Html(ref path) => self.insert(template, path, Kind::Html, sections),
+            Statement::DynamicPartial(ref path, _) => {
+                self.insert(template, path, Kind::Partial, sections)
+            }
+            Statement::Parent(_, ref block) | Statement::Block(_, ref block) => {
+                for stmt in &block.statements {
+                    self.collect(template, stmt, sections);
+                }
+            }
+            Statement::Partial(_, _, ref arguments) => {
+                for (_, path) in arguments {
+                    self.insert(template, path, Kind::Argument, sections);
+                }
+            }
+            Statement::Content(_)
+            | Statement::Comment(_)
+            | Statement::Translation(_)
+            | Statement::Pragma(_) => (),
+        }
+    }
+
+    fn insert(&mut self, template: &Template, path: &Path, kind: Kind, sections: &[String]) {
+        let usage = Usage {
+            template: template.name.clone(),
+            file: template.path.clone(),
+            kind,
+            span: path.span,
+            sections: sections.to_vec(),
+        };
+        self.paths.entry(path.to_string()).or_default().push(usage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Statement, Template};
+    use super::{Index, Kind};
+    use std::path::PathBuf;
+
+    #[test]
+    fn indexes_paths_with_positions() {
+        let base = PathBuf::from("app/templates");
+        let path = base.join("robots.mustache");
+        let text = "<ul>\n  {{#robots}}\n  <li>{{ name.first }}</li>\n  {{/robots}}\n</ul>";
+        let template = Template::new(&base, path, Statement::parse(text).unwrap());
+        let index = Index::build(&[template]);
+
+        let robots = index.get("robots");
+        assert_eq!(1, robots.len());
+        assert_eq!(Kind::Section, robots[0].kind);
+        assert_eq!((2, 6), (robots[0].span.line, robots[0].span.column));
+
+        let name = index.get("name.first");
+        assert_eq!(1, name.len());
+        assert_eq!(Kind::Variable, name[0].kind);
+        assert_eq!(vec!["robots"], name[0].sections);
+        assert_eq!("app/templates/robots.mustache:3:10", name[0].to_string());
+
+        assert!(index.get("missing").is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+//! Traverses template syntax trees without matching on each kind of
+//! statement.
+//!
+//! A tool implements `Visitor` with a method for each kind of statement it's
+//! interested in, and passes itself to `Statement::walk`. The statements
+//! inside sections, parent tags, and blocks are visited after their tag, in
+//! order, unless the tag's method returns `false`, and each tag's `leave_`
+//! method is called once they've been visited.
+//!
+//! The tree is walked with an explicit stack rather than by recursion, so
+//! any depth the parser accepts can be visited.
+
+use super::{Block, Path, Pragma, Statement};
+
+/// Visits the statements of a tree in source order. The lifetime is that of
+/// the tree, so a visitor may keep references to the names and paths it's
+/// given.
+pub trait Visitor<'a> {
+    /// Visits a template's top-level block, returning whether to visit its
+    /// statements.
+    fn visit_program(&mut self, _block: &'a Block) -> bool {
+        true
+    }
+
+    /// Visits a section, returning whether to visit its statements.
+    fn visit_section(&mut self, _path: &'a Path, _block: &'a Block) -> bool {
+        true
+    }
+
+    /// Leaves a section after its statements are visited.
+    fn leave_section(&mut self, _path: &'a Path) {}
+
+    /// Visits an inverted section, returning whether to visit its
+    /// statements.
+    fn visit_inverted(&mut self, _path: &'a Path, _block: &'a Block) -> bool {
+        true
+    }
+
+    fn leave_inverted(&mut self, _path: &'a Path) {}
+
+    /// Visits an escaped variable tag, like `{{name}}`.
+    fn visit_variable(&mut self, _path: &'a Path) {}
+
+    /// Visits a variable passed through the filters, in order, like
+    /// `{{name | upcase}}`.
+    fn visit_filtered(&mut self, _path: &'a Path, _filters: &'a [String]) {}
+
+    /// Visits an unescaped variable tag, like `{{{name}}}` or `{{&name}}`.
+    fn visit_html(&mut self, _path: &'a Path) {}
+
+    fn visit_translation(&mut self, _key: &'a str) {}
+
+    /// Visits a partial tag with its standalone indentation, if any, and its
+    /// arguments.
+    fn visit_partial(
+        &mut self,
+        _name: &'a str,
+        _indent: Option<&'a str>,
+        _arguments: &'a [(String, Path)],
+    ) {
+    }
+
+    fn visit_dynamic_partial(&mut self, _path: &'a Path, _indent: Option<&'a str>) {}
+
+    fn visit_content(&mut self, _text: &'a str) {}
+
+    fn visit_comment(&mut self, _text: &'a str) {}
+
+    fn visit_pragma(&mut self, _pragma: Pragma) {}
+
+    /// Visits a parent tag, like `{{<layout}}`, with the blocks it
+    /// overrides, returning whether to visit them.
+    fn visit_parent(&mut self, _name: &'a str, _block: &'a Block) -> bool {
+        true
+    }
+
+    fn leave_parent(&mut self, _name: &'a str) {}
+
+    /// Visits a block tag, like `{{$title}}`, with its default contents,
+    /// returning whether to visit them.
+    fn visit_block(&mut self, _name: &'a str, _block: &'a Block) -> bool {
+        true
+    }
+
+    fn leave_block(&mut self, _name: &'a str) {}
+}
+
+/// A step of the walk: a statement to visit, or a tag whose statements have
+/// all been visited.
+enum Step<'a> {
+    Visit(&'a Statement),
+    Leave(&'a Statement),
+}
+
+impl Statement {
+    /// Passes the statement, and each statement within it in source order,
+    /// to the visitor's method for its kind.
+    pub fn walk<'a, V: Visitor<'a> + ?Sized>(&'a self, visitor: &mut V) {
+        let mut stack = vec![Step::Visit(self)];
+        while let Some(step) = stack.pop() {
+            let node = match step {
+                Step::Visit(node) => node,
+                Step::Leave(node) => {
+                    leave(node, visitor);
+                    continue;
+                }
+            };
+
+            let (descend, block) = match *node {
+                Statement::Program(ref block) => (visitor.visit_program(block), block),
+                Statement::Section(ref path, ref block) => {
+                    (visitor.visit_section(path, block), block)
+                }
+                Statement::Inverted(ref path, ref block) => {
+                    (visitor.visit_inverted(path, block), block)
+                }
+                Statement::Parent(ref name, ref block) => {
+                    (visitor.visit_parent(name, block), block)
+                }
+                Statement::Block(ref name, ref block) => (visitor.visit_block(name, block), block),
+                Statement::Variable(ref path) => {
+                    visitor.visit_variable(path);
+                    continue;
+                }
+                Statement::Filtered(ref path, ref filters) => {
+                    visitor.visit_filtered(path, filters);
+                    continue;
+                }
+                Statement::Html(ref path) => {
+                    visitor.visit_html(path);
+                    continue;
+                }
+                Statement::Translation(ref key) => {
+                    visitor.visit_translation(key);
+                    continue;
+                }
+                Statement::Partial(ref name, ref indent, ref arguments) => {
+                    visitor.visit_partial(name, indent.as_deref(), arguments);
+                    continue;
+                }
+                Statement::DynamicPartial(ref path, ref indent) => {
+                    visitor.visit_dynamic_partial(path, indent.as_deref());
+                    continue;
+                }
+                Statement::Content(ref text) => {
+                    visitor.visit_content(text);
+                    continue;
+                }
+                Statement::Comment(ref text) => {
+                    visitor.visit_comment(text);
+                    continue;
+                }
+                Statement::Pragma(pragma) => {
+                    visitor.visit_pragma(pragma);
+                    continue;
+                }
+            };
+
+            stack.push(Step::Leave(node));
+            if descend {
+                stack.extend(block.statements.iter().rev().map(Step::Visit));
+            }
+        }
+    }
+}
+
+/// Passes a tag whose statements have been visited to the visitor's
+/// `leave_` method for its kind.
+fn leave<'a, V: Visitor<'a> + ?Sized>(node: &'a Statement, visitor: &mut V) {
+    match *node {
+        Statement::Section(ref path, _) => visitor.leave_section(path),
+        Statement::Inverted(ref path, _) => visitor.leave_inverted(path),
+        Statement::Parent(ref name, _) => visitor.leave_parent(name),
+        Statement::Block(ref name, _) => visitor.leave_block(name),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Visitor;
+    use {Block, LoadOptions, Path, Statement};
+
+    /// Records the sections entered and variables found, skipping the
+    /// contents of inverted sections.
+    #[derive(Default)]
+    struct Outline<'a> {
+        events: Vec<String>,
+        paths: Vec<&'a Path>,
+    }
+
+    impl<'a> Visitor<'a> for Outline<'a> {
+        fn visit_section(&mut self, path: &'a Path, _block: &'a Block) -> bool {
+            self.events.push(format!("#{}", path));
+            true
+        }
+
+        fn leave_section(&mut self, path: &'a Path) {
+            self.events.push(format!("/{}", path));
+        }
+
+        fn visit_inverted(&mut self, path: &'a Path, _block: &'a Block) -> bool {
+            self.events.push(format!("^{}", path));
+            false
+        }
+
+        fn visit_variable(&mut self, path: &'a Path) {
+            self.paths.push(path);
+        }
+
+        fn visit_partial(&mut self, name: &'a str, _: Option<&'a str>, _: &'a [(String, Path)]) {
+            self.events.push(format!(">{}", name));
+        }
+    }
+
+    #[test]
+    fn visits_statements_in_order() {
+        let source = "{{a}}{{#b}}{{c}}{{>d}}{{^e}}{{f}}{{/e}}{{/b}}{{$g}}{{h}}{{/g}}";
+        let tree = Statement::parse(source).unwrap();
+
+        let mut outline = Outline::default();
+        tree.walk(&mut outline);
+        assert_eq!(vec!["#b", ">d", "^e", "/b"], outline.events);
+        let names: Vec<_> = outline.paths.iter().map(|p| p.to_string()).collect();
+        assert_eq!(vec!["a", "c", "h"], names);
+    }
+
+    #[test]
+    fn visits_deeply_nested_sections() {
+        let depth = 5000;
+        let source = format!(
+            "{}{{{{x}}}}{}",
+            "{{#a}}".repeat(depth),
+            "{{/a}}".repeat(depth)
+        );
+        let options = LoadOptions {
+            max_depth: depth,
+            ..LoadOptions::default()
+        };
+        let tree = Statement::parse_with(&source, &options).unwrap();
+
+        let mut outline = Outline::default();
+        tree.walk(&mut outline);
+        assert_eq!(2 * depth, outline.events.len());
+        assert_eq!(1, outline.paths.len());
+    }
+}
@@ -0,0 +1,227 @@
+//! Problems found in templates that don't prevent them from compiling, but
+//! are likely mistakes:
+//!
+//! - An unused partial: a template whose file name begins with `_`, marking
+//!   it as a partial, that no template renders.
+//! - An empty section, holding nothing but whitespace and comments.
+//! - Suspicious unescaped output: a `{{{name}}}` or `{{&name}}` tag whose
+//!   key doesn't mention `html`, rendering a value that may not be safe
+//!   markup.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+
+use serde_json::{Map, Value};
+
+use super::error::location;
+use super::{Block, Path, Span, Statement, Template};
+
+/// The kind of problem a warning reports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    UnusedPartial,
+    EmptySection,
+    UnescapedOutput,
+}
+
+impl Kind {
+    /// Names the kind of warning, like `empty_section`, for tools matching
+    /// on it rather than its message.
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::UnusedPartial => "unused_partial",
+            Kind::EmptySection => "empty_section",
+            Kind::UnescapedOutput => "unescaped_output",
+        }
+    }
+
+    /// Returns the warning's stable code, like `W0002`, explained by
+    /// `stache code`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Kind::UnusedPartial => "W0001",
+            Kind::EmptySection => "W0002",
+            Kind::UnescapedOutput => "W0003",
+        }
+    }
+}
+
+/// A likely mistake found in a template file.
+#[derive(Debug)]
+pub struct Warning {
+    pub kind: Kind,
+    pub file: PathBuf,
+    /// The location of the tag warned about, or none for a whole template.
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl Warning {
+    /// Converts the warning to a JSON object with the same fields as an
+    /// error's.
+    pub fn json(&self) -> Value {
+        let mut map = Map::new();
+        map.insert(String::from("level"), Value::from("warning"));
+        map.insert(String::from("code"), Value::from(self.kind.code()));
+        map.insert(String::from("kind"), Value::from(self.kind.name()));
+        map.insert(
+            String::from("file"),
+            Value::from(self.file.to_string_lossy()),
+        );
+        map.insert(
+            String::from("span"),
+            self.span.map_or(Value::Null, |span| {
+                location(span.start, span.line, span.column)
+            }),
+        );
+        map.insert(String::from("message"), Value::from(self.message.as_str()));
+        Value::Object(map)
+    }
+}
+
+impl fmt::Display for Warning {
+    /// Formats the warning as `file:line:column: message`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(
+                f,
+                "{}:{}:{}: {}",
+                self.file.display(),
+                span.line,
+                span.column,
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+/// Finds the warnings in each template, in template order.
+pub fn check(templates: &[Template]) -> Vec<Warning> {
+    let rendered: HashSet<_> = templates.iter().flat_map(|t| t.tree.partials()).collect();
+    let mut warnings = Vec::new();
+    for template in templates {
+        let file = template.name.rsplit('/').next().unwrap_or_default();
+        if file.starts_with('_') && !rendered.contains(template.name.as_str()) {
+            warnings.push(Warning {
+                kind: Kind::UnusedPartial,
+                file: template.path.clone(),
+                span: None,
+                message: format!("Partial `{}` is never rendered", template.name),
+            });
+        }
+        collect(template, &template.tree, &mut warnings);
+    }
+    warnings
+}
+
+fn collect(template: &Template, node: &Statement, warnings: &mut Vec<Warning>) {
+    let mut warn = |kind, path: &Path, message| {
+        warnings.push(Warning {
+            kind,
+            file: template.path.clone(),
+            span: Some(path.span),
+            message,
+        })
+    };
+    match *node {
+        Statement::Section(ref path, ref block) | Statement::Inverted(ref path, ref block)
+            if empty(block) =>
+        {
+            warn(
+                Kind::EmptySection,
+                path,
+                format!("Section `{}` is empty", path),
+            );
+        }
+        Statement::Html(ref path) if !html(path) => {
+            warn(
+                Kind::UnescapedOutput,
+                path,
+                format!("Unescaped output of `{}` may not be safe HTML", path),
+            );
+        }
+        _ => (),
+    }
+
+    match *node {
+        Statement::Program(ref block)
+        | Statement::Section(_, ref block)
+        | Statement::Inverted(_, ref block)
+        | Statement::Parent(_, ref block)
+        | Statement::Block(_, ref block) => {
+            for stmt in &block.statements {
+                collect(template, stmt, warnings);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Tests whether the path's last key names a value holding HTML, like
+/// `body_html`.
+fn html(path: &Path) -> bool {
+    path.keys
+        .last()
+        .is_some_and(|key| key.to_lowercase().contains("html"))
+}
+
+/// Tests whether the block renders nothing but whitespace.
+fn empty(block: &Block) -> bool {
+    block.statements.iter().all(|stmt| match *stmt {
+        Statement::Content(ref text) => text.trim().is_empty(),
+        Statement::Comment(_) => true,
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, Kind};
+    use Template;
+
+    #[test]
+    fn warns_of_likely_mistakes() {
+        let templates = vec![
+            Template::from_source(
+                "page",
+                "{{> include/_header}}\n{{#robots}}\n  {{! none }}\n{{/robots}}",
+            )
+            .unwrap(),
+            Template::from_source("include/_header", "{{{title}}} {{{body_html}}} {{&bio}}")
+                .unwrap(),
+            Template::from_source("include/_footer", "{{#a}}{{b}}{{/a}}").unwrap(),
+        ];
+        let warnings = check(&templates);
+        let kinds: Vec<_> = warnings.iter().map(|w| w.kind).collect();
+        assert_eq!(
+            vec![
+                Kind::EmptySection,
+                Kind::UnescapedOutput,
+                Kind::UnescapedOutput,
+                Kind::UnusedPartial,
+            ],
+            kinds
+        );
+
+        assert_eq!(
+            "app/templates/page.mustache:2:4: Section `robots` is empty",
+            warnings[0].to_string()
+        );
+        assert_eq!(
+            "app/templates/include/_header.mustache:1:4: Unescaped output of `title` may not be safe HTML",
+            warnings[1].to_string()
+        );
+        assert_eq!(
+            "app/templates/include/_footer.mustache: Partial `include/_footer` is never rendered",
+            warnings[3].to_string()
+        );
+
+        let json = warnings[2].json();
+        assert_eq!("warning", json["level"]);
+        assert_eq!("W0003", json["code"]);
+        assert_eq!("unescaped_output", json["kind"]);
+        assert_eq!(1, json["span"]["line"]);
+    }
+}
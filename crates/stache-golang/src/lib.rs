@@ -0,0 +1,438 @@
+extern crate serde_json;
+extern crate stache_core;
+#[macro_use]
+extern crate tracing;
+
+use std::collections::HashMap;
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{self, Write};
+
+use self::runtime::RUNTIME;
+
+use serde_json::Value;
+use stache_core::ir::{self, Op};
+use stache_core::{Backend, Compile, Error, Name, Path, Template};
+
+mod runtime;
+
+/// A program is the final result of translating Mustache templates into a
+/// Go package.
+///
+/// The package exports a `Render<Name>(context)` function for each template,
+/// and a `Render(name, context)` function dispatching to them by template
+/// name, like the Ruby extension's `Templates#render`. Contexts may be maps
+/// with string keys, structs, or pointers to them.
+#[derive(Debug)]
+pub struct Program {
+    package: String,
+    functions: Vec<Function>,
+}
+
+/// A template translated into the body of its render function.
+#[derive(Debug)]
+struct Function {
+    export: String,
+    name: String,
+    body: String,
+}
+
+impl Compile for Program {
+    /// Writes the package clause, imports, and runtime, followed by each
+    /// template's render functions and the exported dispatcher.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        let _span = info_span!("emit", target = "go").entered();
+        writeln!(buf, "// Code generated by stache. DO NOT EDIT.\n")?;
+        writeln!(buf, "package {}\n", self.package)?;
+        writeln!(buf, "import (")?;
+        for import in &["fmt", "reflect", "strings", "unicode", "unicode/utf8"] {
+            writeln!(buf, "\t\"{}\"", import)?;
+        }
+        writeln!(buf, ")\n")?;
+        writeln!(buf, "{}", RUNTIME)?;
+
+        for fun in &self.functions {
+            writeln!(buf, "func template{}(buf *buffer, s *stack) {{", fun.name)?;
+            buf.write_all(fun.body.as_bytes())?;
+            writeln!(buf, "}}\n")?;
+            writeln!(
+                buf,
+                "// Render{} renders the {} template with the context data.",
+                fun.name,
+                string(&fun.export)
+            )?;
+            writeln!(
+                buf,
+                "func Render{}(context interface{{}}) string {{",
+                fun.name
+            )?;
+            writeln!(buf, "\tvar buf buffer")?;
+            writeln!(buf, "\ttemplate{}(&buf, &stack{{data: context}})", fun.name)?;
+            writeln!(buf, "\treturn buf.String()")?;
+            writeln!(buf, "}}\n")?;
+        }
+
+        writeln!(
+            buf,
+            "func findTemplate(name string) func(*buffer, *stack) {{"
+        )?;
+        writeln!(buf, "\tswitch name {{")?;
+        for fun in &self.functions {
+            writeln!(buf, "\tcase {}:", string(&fun.export))?;
+            writeln!(buf, "\t\treturn template{}", fun.name)?;
+        }
+        writeln!(buf, "\t}}")?;
+        writeln!(buf, "\treturn nil")?;
+        writeln!(buf, "}}\n")?;
+
+        writeln!(
+            buf,
+            "// Render renders the named template with the context data."
+        )?;
+        writeln!(
+            buf,
+            "func Render(name string, context interface{{}}) (string, error) {{"
+        )?;
+        writeln!(buf, "\tswitch name {{")?;
+        for fun in &self.functions {
+            writeln!(buf, "\tcase {}:", string(&fun.export))?;
+            writeln!(buf, "\t\treturn Render{}(context), nil", fun.name)?;
+        }
+        writeln!(buf, "\tdefault:")?;
+        writeln!(
+            buf,
+            "\t\treturn \"\", fmt.Errorf(\"template not found: %s\", name)"
+        )?;
+        writeln!(buf, "\t}}")?;
+        writeln!(buf, "}}")
+    }
+}
+
+/// The `go` backend, compiling templates into a Go package.
+#[derive(Debug)]
+pub struct Compiler {
+    /// The name of the generated package.
+    pub package: String,
+}
+
+impl Backend for Compiler {
+    fn name(&self) -> &str {
+        "go"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(link(templates, &self.package)?))
+    }
+}
+
+/// Transforms the AST of each parsed template into a Go render function in
+/// the named package, linking partial calls to the functions of other
+/// templates.
+pub fn link(templates: &[Template], package: &str) -> Result<Program, Error> {
+    let _span = info_span!("link", templates = templates.len()).entered();
+    let program = ir::lower(templates)?;
+    ir::unfiltered(templates, "go")?;
+    ir::lenient(templates, "go")?;
+    validate_names(templates)?;
+
+    // Template content is written line by line when any partial is indented,
+    // since any template may be rendered as that partial.
+    let indented = program.functions.iter().any(|fun| ir::indented(&fun.body));
+    let functions = program
+        .functions
+        .iter()
+        .map(|fun| {
+            let _span = debug_span!("transform", template = %fun.name).entered();
+            let mut body = String::new();
+            transform(&fun.body, indented, 1, &mut body).expect("write to string");
+            Function {
+                export: fun.name.clone(),
+                name: camel(Name::new(&fun.name).id()),
+                body,
+            }
+        })
+        .collect();
+
+    Ok(Program {
+        package: package.to_string(),
+        functions,
+    })
+}
+
+/// Ensures no two templates' names translate to the same Go function name,
+/// like `robot/list` and `robotList`.
+fn validate_names(templates: &[Template]) -> Result<(), Error> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    let mut errors = Vec::new();
+    for template in templates {
+        let name = camel(template.name().id());
+        if let Some(other) = seen.insert(name.clone(), &template.name) {
+            errors.push(Error::Backend(format!(
+                "Templates `{}` and `{}` both render with Go function `Render{}`",
+                other, template.name, name
+            )));
+        }
+    }
+    Error::all(errors)
+}
+
+/// Writes the Go statements performing each operation into the body of the
+/// enclosing function.
+///
+/// Sections are translated into function literals called by the runtime
+/// once for each item the section's path resolves to. Partials call the
+/// render function of the template providing them, and dynamic partials the
+/// function `findTemplate` returns for the name found in the context. A
+/// standalone partial's padding indents each line it renders, which
+/// requires content to be written line by line when the program has one.
+///
+/// Section bodies are translated recursively, relying on the parser's
+/// section depth limit to bound the stack.
+fn transform(ops: &[Op], indented: bool, depth: usize, out: &mut String) -> fmt::Result {
+    let indent = "\t".repeat(depth);
+    for op in ops {
+        match *op {
+            Op::Section(ref path, ref body, _) | Op::Inverted(ref path, ref body) => {
+                let fun = match *op {
+                    Op::Section(..) => "section",
+                    _ => "inverted",
+                };
+                writeln!(
+                    out,
+                    "{}{}(buf, s, {}, func(buf *buffer, s *stack) {{",
+                    indent,
+                    fun,
+                    slice(path)
+                )?;
+                transform(body, indented, depth + 1, out)?;
+                writeln!(out, "{}}})", indent)?;
+            }
+            Op::Partial(ref name, ref padding, ref arguments) => {
+                let name = Name::new(name);
+                indent_push(padding, &indent, out)?;
+                if arguments.is_empty() {
+                    writeln!(out, "{}template{}(buf, s)", indent, camel(name.id()))?;
+                } else {
+                    let keys: Vec<_> = arguments.iter().map(|(key, _)| string(key)).collect();
+                    let paths: Vec<_> = arguments.iter().map(|(_, path)| slice(path)).collect();
+                    writeln!(
+                        out,
+                        "{}template{}(buf, frame(s, []string{{{}}}, [][]string{{{}}}))",
+                        indent,
+                        camel(name.id()),
+                        keys.join(", "),
+                        paths.join(", ")
+                    )?;
+                }
+                indent_pop(padding, &indent, out)?;
+            }
+            Op::DynamicPartial(ref path, ref padding) => {
+                indent_push(padding, &indent, out)?;
+                writeln!(out, "{}dynamicPartial(buf, s, {})", indent, slice(path))?;
+                indent_pop(padding, &indent, out)?;
+            }
+            Op::Content(ref text) if indented => {
+                writeln!(out, "{}content(buf, {})", indent, string(text))?;
+            }
+            Op::Content(ref text) => {
+                writeln!(out, "{}buf.WriteString({})", indent, string(text))?;
+            }
+            Op::Filtered(..) => unreachable!("filters are rejected when linking"),
+            Op::Require(..) => unreachable!("strict pragmas are rejected when linking"),
+            Op::Translate(ref key) => {
+                writeln!(out, "{}translate(buf, {})", indent, string(key))?;
+            }
+            Op::Append(ref path, escape) => {
+                writeln!(
+                    out,
+                    "{}appendValue(buf, s, {}, {})",
+                    indent,
+                    slice(path),
+                    escape
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds a standalone partial's padding to the indentation of the lines it
+/// renders, or nothing for a partial called inline.
+fn indent_push(padding: &Option<String>, indent: &str, out: &mut String) -> fmt::Result {
+    match *padding {
+        Some(ref padding) => writeln!(out, "{}indentPush(buf, {})", indent, string(padding)),
+        None => Ok(()),
+    }
+}
+
+/// Removes the padding added by `indent_push` after the partial's call.
+fn indent_pop(padding: &Option<String>, indent: &str, out: &mut String) -> fmt::Result {
+    match *padding {
+        Some(ref padding) => writeln!(out, "{}indentPop(buf, {})", indent, string(padding)),
+        None => Ok(()),
+    }
+}
+
+/// Converts a template's identifier, like `includes_header`, into the
+/// capitalized form used in its Go function names, like `IncludesHeader`.
+fn camel(id: &str) -> String {
+    id.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Formats the text as a Go interpreted string literal. JSON string escapes
+/// are a subset of Go's.
+fn string(text: &str) -> String {
+    Value::from(text).to_string()
+}
+
+/// Formats the path's keys as a Go string slice literal.
+fn slice(path: &Path) -> String {
+    let keys: Vec<_> = path.keys.iter().map(|key| string(key)).collect();
+    format!("[]string{{{}}}", keys.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{camel, link, string, transform};
+    use stache_core::ir::lower_tree;
+    use stache_core::{Compile, Error, Statement, Template};
+
+    #[test]
+    fn transforms_tree_into_statements() {
+        let tree = Statement::parse(
+            "<p>{{#robots}}{{name.first}}{{> robot}}{{/robots}}{{^robots}}None{{/robots}}{{{bio}}}</p>",
+        )
+        .unwrap();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), false, 1, &mut out).unwrap();
+
+        let expected = "\tbuf.WriteString(\"<p>\")
+\tsection(buf, s, []string{\"robots\"}, func(buf *buffer, s *stack) {
+\t\tappendValue(buf, s, []string{\"name\", \"first\"}, true)
+\t\ttemplateRobot(buf, s)
+\t})
+\tinverted(buf, s, []string{\"robots\"}, func(buf *buffer, s *stack) {
+\t\tbuf.WriteString(\"None\")
+\t})
+\tappendValue(buf, s, []string{\"bio\"}, false)
+\tbuf.WriteString(\"</p>\")
+";
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn escapes_string_literals() {
+        assert_eq!(
+            r#""a \"quote\"\n\\ \u0000 ✓""#,
+            string("a \"quote\"\n\\ \u{0} ✓")
+        );
+    }
+
+    #[test]
+    fn capitalizes_function_names() {
+        assert_eq!("IncludesHeader", camel("includes_header"));
+        assert_eq!("TStatic", camel("t_static"));
+    }
+
+    #[test]
+    fn exports_render_functions() {
+        let templates = vec![
+            Template::from_source("robots", "{{> includes/header}}{{name}}").unwrap(),
+            Template::from_source("includes/header", "<h1>Robots</h1>").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates, "views").unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("\npackage views\n"));
+        assert!(source.contains("func RenderRobots(context interface{}) string {"));
+        assert!(source.contains("func RenderIncludesHeader(context interface{}) string {"));
+        assert!(source.contains("\ttemplateIncludesHeader(buf, s)\n"));
+        assert!(source.contains(
+            "\tcase \"includes/header\":\n\t\treturn RenderIncludesHeader(context), nil"
+        ));
+    }
+
+    #[test]
+    fn finds_dynamic_partials_by_name() {
+        let templates = vec![
+            Template::from_source("robots", "{{#robots}}{{>*kind}}{{/robots}}").unwrap(),
+            Template::from_source("robot", "{{name}}").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates, "views").unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("\t\tdynamicPartial(buf, s, []string{\"kind\"})\n"));
+        assert!(source.contains("\tcase \"robot\":\n\t\treturn templateRobot\n"));
+    }
+
+    #[test]
+    fn indents_standalone_partials() {
+        let templates = vec![
+            Template::from_source("page", "x\n  {{> list}}\ny").unwrap(),
+            Template::from_source("list", "a\n{{>*kind}}\nb\n").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates, "views").unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains(
+            "\tindentPush(buf, \"  \")\n\ttemplateList(buf, s)\n\tindentPop(buf, \"  \")\n"
+        ));
+        assert!(source.contains("\tcontent(buf, \"a\\n\")\n"));
+        assert!(!source.contains("buf.WriteString(\""));
+
+        let templates = vec![
+            Template::from_source("page", "x\n{{> list}} y").unwrap(),
+            Template::from_source("list", "a").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates, "views").unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(!source.contains("indentPush(buf, \""));
+        assert!(source.contains("\tbuf.WriteString(\"x\\n\")\n"));
+    }
+
+    #[test]
+    fn rejects_conflicting_function_names() {
+        let templates = vec![
+            Template::from_source("robot/list", "{{name}}").unwrap(),
+            Template::from_source("robotList", "{{name}}").unwrap(),
+        ];
+        match link(&templates, "views") {
+            Err(Error::Backend(ref message)) => assert!(message.contains("`RenderRobotList`")),
+            other => panic!("expected function name conflict: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn transforms_partial_arguments() {
+        let tree = Statement::parse("{{> card title=user.name }}").unwrap();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), false, 1, &mut out).unwrap();
+        assert_eq!(
+            "\ttemplateCard(buf, frame(s, []string{\"title\"}, [][]string{[]string{\"user\", \"name\"}}))\n",
+            out
+        );
+    }
+
+    #[test]
+    fn rejects_filters() {
+        let templates = vec![Template::from_source("robot", "{{name | upcase}}").unwrap()];
+        match link(&templates, "views") {
+            Err(Error::Backend(ref message)) => assert_eq!(
+                "Template `robot` uses filters, which are not supported by the go target",
+                message
+            ),
+            other => panic!("expected unsupported filters: {:?}", other.map(|_| ())),
+        }
+    }
+}
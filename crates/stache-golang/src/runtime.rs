@@ -0,0 +1,234 @@
+/// The runtime functions called by the generated template functions, which
+/// mirror the Ruby extension's runtime: context stack lookups, HTML
+/// escaping, and section iteration.
+///
+/// Output is written to a `strings.Builder` wrapped with the indentation of
+/// the standalone partials being rendered, added to each line they output.
+///
+/// Context values are read through reflection. A key resolves to a map
+/// entry, or to an exported method or field of a struct, named either
+/// exactly as the key or with its first letter capitalized, so `name` finds
+/// a `Name` field.
+pub const RUNTIME: &str = r#"var escaper = strings.NewReplacer(
+	"&", "&amp;",
+	"<", "&lt;",
+	">", "&gt;",
+	"\"", "&quot;",
+	"'", "&#39;",
+)
+
+// EscapeHTML escapes the HTML special characters in the text, as variable
+// tags do.
+func EscapeHTML(text string) string {
+	return escaper.Replace(text)
+}
+
+// Translate returns the text a translation tag renders for its key. Until
+// it's replaced, tags render their keys.
+var Translate = func(key string) string {
+	return key
+}
+
+type buffer struct {
+	strings.Builder
+	indent    string
+	lineStart bool
+}
+
+func beginLine(buf *buffer) {
+	if buf.lineStart {
+		buf.lineStart = false
+		buf.WriteString(buf.indent)
+	}
+}
+
+func indentPush(buf *buffer, text string) {
+	buf.indent += text
+	buf.lineStart = true
+}
+
+func indentPop(buf *buffer, text string) {
+	buf.indent = buf.indent[:len(buf.indent)-len(text)]
+	buf.lineStart = true
+}
+
+func content(buf *buffer, text string) {
+	if buf.indent == "" {
+		buf.WriteString(text)
+		return
+	}
+	for text != "" {
+		beginLine(buf)
+		end := strings.IndexByte(text, '\n') + 1
+		if end == 0 {
+			end = len(text)
+		}
+		buf.WriteString(text[:end])
+		buf.lineStart = text[end-1] == '\n'
+		text = text[end:]
+	}
+}
+
+func translate(buf *buffer, key string) {
+	beginLine(buf)
+	buf.WriteString(EscapeHTML(Translate(key)))
+}
+
+type stack struct {
+	data   interface{}
+	parent *stack
+}
+
+func names(key string) []string {
+	first, size := utf8.DecodeRuneInString(key)
+	upper := unicode.ToUpper(first)
+	if upper == first {
+		return []string{key}
+	}
+	return []string{key, string(upper) + key[size:]}
+}
+
+func isNil(value interface{}) bool {
+	if value == nil {
+		return true
+	}
+	v := reflect.ValueOf(value)
+	switch v.Kind() {
+	case reflect.Ptr, reflect.Interface, reflect.Map, reflect.Func, reflect.Chan:
+		return v.IsNil()
+	}
+	return false
+}
+
+func fetch(context interface{}, key string) (interface{}, bool) {
+	if key == "." {
+		return context, true
+	}
+	if isNil(context) {
+		return nil, false
+	}
+
+	v := reflect.ValueOf(context)
+	for _, name := range names(key) {
+		method := v.MethodByName(name)
+		if method.IsValid() && method.Type().NumIn() == 0 && method.Type().NumOut() > 0 {
+			return method.Call(nil)[0].Interface(), true
+		}
+	}
+
+	for v.Kind() == reflect.Ptr || v.Kind() == reflect.Interface {
+		if v.IsNil() {
+			return nil, false
+		}
+		v = v.Elem()
+	}
+
+	switch v.Kind() {
+	case reflect.Map:
+		if v.Type().Key().Kind() != reflect.String {
+			return nil, false
+		}
+		value := v.MapIndex(reflect.ValueOf(key).Convert(v.Type().Key()))
+		if !value.IsValid() {
+			return nil, false
+		}
+		return value.Interface(), true
+	case reflect.Struct:
+		for _, name := range names(key) {
+			field, ok := v.Type().FieldByName(name)
+			if ok && field.PkgPath == "" && len(field.Index) == 1 {
+				return v.Field(field.Index[0]).Interface(), true
+			}
+		}
+	}
+	return nil, false
+}
+
+func lookup(s *stack, path []string) (interface{}, bool) {
+	var value interface{}
+	found := false
+	for frame := s; frame != nil && !found; frame = frame.parent {
+		value, found = fetch(frame.data, path[0])
+	}
+	for _, key := range path[1:] {
+		if !found {
+			break
+		}
+		value, found = fetch(value, key)
+	}
+	return value, found
+}
+
+func appendValue(buf *buffer, s *stack, path []string, escape bool) {
+	value, found := lookup(s, path)
+	if !found || isNil(value) {
+		return
+	}
+	text := fmt.Sprint(value)
+	if escape {
+		text = escaper.Replace(text)
+	}
+	beginLine(buf)
+	buf.WriteString(text)
+}
+
+func frame(s *stack, keys []string, paths [][]string) *stack {
+	data := make(map[string]interface{}, len(keys))
+	for i, key := range keys {
+		data[key], _ = lookup(s, paths[i])
+	}
+	return &stack{data: data, parent: s}
+}
+
+func section(buf *buffer, s *stack, path []string, block func(*buffer, *stack)) {
+	value, found := lookup(s, path)
+	if !found || isNil(value) {
+		return
+	}
+
+	v := reflect.ValueOf(value)
+	switch v.Kind() {
+	case reflect.Bool:
+		if v.Bool() {
+			block(buf, s)
+		}
+	case reflect.Slice, reflect.Array:
+		for i := 0; i < v.Len(); i++ {
+			meta := map[string]interface{}{"@index": i, "@first": i == 0, "@last": i == v.Len()-1}
+			block(buf, &stack{data: v.Index(i).Interface(), parent: &stack{data: meta, parent: s}})
+		}
+	default:
+		block(buf, &stack{data: value, parent: s})
+	}
+}
+
+func dynamicPartial(buf *buffer, s *stack, path []string) {
+	value, found := lookup(s, path)
+	if !found || isNil(value) {
+		return
+	}
+	if template := findTemplate(fmt.Sprint(value)); template != nil {
+		template(buf, s)
+	}
+}
+
+func inverted(buf *buffer, s *stack, path []string, block func(*buffer, *stack)) {
+	value, found := lookup(s, path)
+	if !found || isNil(value) {
+		block(buf, s)
+		return
+	}
+
+	v := reflect.ValueOf(value)
+	switch v.Kind() {
+	case reflect.Bool:
+		if !v.Bool() {
+			block(buf, s)
+		}
+	case reflect.Slice, reflect.Array:
+		if v.Len() == 0 {
+			block(buf, s)
+		}
+	}
+}
+"#;
@@ -0,0 +1,309 @@
+//! Translates templates into the precompiled form produced by Hogan.js,
+//! for applications already rendering with its runtime. Each template
+//! becomes a `Hogan.Template` built from the same code object as
+//! `Hogan.compile(text, {asString: true})` returns, so existing loader code
+//! renders it unchanged.
+
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{self, Write};
+
+use stache_core::ir::{self, Op};
+use stache_core::{Backend, Compile, Error, Path, Template};
+
+use super::string;
+
+/// A program is the final result of translating Mustache templates into an
+/// ES module of Hogan.js templates.
+///
+/// The module exports a `templates` object mapping each template name to
+/// its `Hogan.Template`. Partials are resolved by name at render time, so
+/// the object itself is passed as the partials:
+/// `templates.robots.render(context, templates)`.
+#[derive(Debug)]
+pub struct Program {
+    templates: Vec<Function>,
+}
+
+/// A template translated into a Hogan.js code object.
+#[derive(Debug)]
+struct Function {
+    export: String,
+    code: String,
+    partials: Vec<(String, String)>,
+}
+
+impl Compile for Program {
+    /// Writes the Hogan import, followed by the code object of each template.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        let _span = info_span!("emit", target = "hogan").entered();
+        writeln!(buf, "// Generated by stache. Do not edit.\n")?;
+        writeln!(buf, "import Hogan from \"hogan.js\";\n")?;
+        writeln!(buf, "export const templates = {{}};")?;
+
+        for fun in &self.templates {
+            writeln!(buf)?;
+            writeln!(
+                buf,
+                "templates[{}] = new Hogan.Template({{",
+                string(&fun.export)
+            )?;
+            writeln!(buf, "  code: function (c, p, i) {{")?;
+            writeln!(buf, "    var t = this;")?;
+            writeln!(buf, "    t.b(i = i || \"\");")?;
+            buf.write_all(fun.code.as_bytes())?;
+            writeln!(buf, "    return t.fl();")?;
+            writeln!(buf, "  }},")?;
+            if fun.partials.is_empty() {
+                writeln!(buf, "  partials: {{}},")?;
+            } else {
+                writeln!(buf, "  partials: {{")?;
+                for (symbol, name) in &fun.partials {
+                    writeln!(
+                        buf,
+                        "    {}: {{ name: {}, partials: {{}}, subs: {{}} }},",
+                        string(symbol),
+                        string(name)
+                    )?;
+                }
+                writeln!(buf, "  }},")?;
+            }
+            writeln!(buf, "  subs: {{}},")?;
+            writeln!(buf, "}}, \"\", Hogan);")?;
+        }
+        Ok(())
+    }
+}
+
+/// The `hogan` backend, compiling templates into Hogan.js templates.
+#[derive(Debug)]
+pub struct Compiler;
+
+impl Backend for Compiler {
+    fn name(&self) -> &str {
+        "hogan"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(link(templates)?))
+    }
+}
+
+/// Transforms the AST of each parsed template into a Hogan.js code object,
+/// recording the partials each one renders.
+pub fn link(templates: &[Template]) -> Result<Program, Error> {
+    let _span = info_span!("link", templates = templates.len()).entered();
+    let program = ir::lower(templates)?;
+    ir::unfiltered(templates, "hogan")?;
+    ir::untranslated(templates, "hogan")?;
+    ir::unindexed(templates, "hogan")?;
+    ir::lenient(templates, "hogan")?;
+
+    let templates = program
+        .functions
+        .iter()
+        .map(|fun| {
+            let _span = debug_span!("transform", template = %fun.name).entered();
+            let mut code = String::new();
+            let mut partials = Vec::new();
+            transform(&fun.body, 2, true, &mut partials, &mut code).expect("write to string");
+            Function {
+                export: fun.name.clone(),
+                code,
+                partials,
+            }
+        })
+        .collect();
+
+    Ok(Program { templates })
+}
+
+/// Writes the Hogan.js statements performing each operation, as its
+/// compiler generates them, into the body of the code function.
+///
+/// Partial calls refer to a symbol numbered in the order the template
+/// renders them, which the template's `partials` map resolves to the
+/// partial's name. Dynamic partials find the template named by the context
+/// in the partials passed to `render`. A standalone partial is passed its
+/// padding, which the runtime writes at the start of the partial and the
+/// partial writes after each newline, except its template's final one.
+fn transform(
+    ops: &[Op],
+    depth: usize,
+    top: bool,
+    partials: &mut Vec<(String, String)>,
+    out: &mut String,
+) -> fmt::Result {
+    let indent = "  ".repeat(depth);
+    for (i, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Section(ref path, ref body, _) => {
+                writeln!(
+                    out,
+                    "{}if (t.s({}, c, p, 0, 0, 0, \"{{{{ }}}}\")) {{",
+                    indent,
+                    find(path, 1)
+                )?;
+                writeln!(out, "{}  t.rs(c, p, function (c, p, t) {{", indent)?;
+                transform(body, depth + 2, false, partials, out)?;
+                writeln!(out, "{}  }});", indent)?;
+                writeln!(out, "{}  c.pop();", indent)?;
+                writeln!(out, "{}}}", indent)?;
+            }
+            Op::Inverted(ref path, ref body) => {
+                writeln!(
+                    out,
+                    "{}if (!t.s({}, c, p, 1, 0, 0, \"\")) {{",
+                    indent,
+                    find(path, 1)
+                )?;
+                transform(body, depth + 1, false, partials, out)?;
+                writeln!(out, "{}}}", indent)?;
+            }
+            Op::Partial(ref name, ref padding, ref arguments) => {
+                let symbol = format!("<{}{}", name, partials.len());
+                let context = if arguments.is_empty() {
+                    String::from("c")
+                } else {
+                    let values: Vec<_> = arguments
+                        .iter()
+                        .map(|(key, path)| format!("{}: {}", string(key), find(path, 0)))
+                        .collect();
+                    format!("c.concat([{{ {} }}])", values.join(", "))
+                };
+                writeln!(
+                    out,
+                    "{}t.b(t.rp({}, {}, p, {}));",
+                    indent,
+                    string(&symbol),
+                    context,
+                    string(padding.as_ref().map_or("", String::as_str))
+                )?;
+                partials.push((symbol, name.clone()));
+            }
+            Op::DynamicPartial(ref path, ref padding) => {
+                writeln!(out, "{}var n = {};", indent, find(path, 0))?;
+                writeln!(
+                    out,
+                    "{}if (Object.prototype.hasOwnProperty.call(p, n)) t.b(p[n].ri(c, p, {}));",
+                    indent,
+                    string(padding.as_ref().map_or("", String::as_str))
+                )?;
+            }
+            Op::Content(ref text) => {
+                // The template's final newline ends the partial's last line,
+                // so isn't followed by its indentation.
+                let last = top && i == ops.len() - 1 && text.ends_with('\n');
+                let lines: Vec<_> = text.split('\n').collect();
+                for (n, line) in lines.iter().enumerate() {
+                    if !line.is_empty() {
+                        writeln!(out, "{}t.b({});", indent, string(line))?;
+                    }
+                    if n + 2 == lines.len() && last {
+                        writeln!(out, "{}t.b(\"\\n\");", indent)?;
+                    } else if n + 1 < lines.len() {
+                        writeln!(out, "{}t.b(\"\\n\" + i);", indent)?;
+                    }
+                }
+            }
+            Op::Filtered(..) => unreachable!("filters are rejected when linking"),
+            Op::Translate(..) => unreachable!("translations are rejected when linking"),
+            Op::Append(ref path, escape) => {
+                let fun = if escape { "v" } else { "t" };
+                writeln!(out, "{}t.b(t.{}({}));", indent, fun, find(path, 0))?;
+            }
+            Op::Require(..) => unreachable!("strict pragmas are rejected when linking"),
+        }
+    }
+    Ok(())
+}
+
+/// Formats the runtime's context lookup of the path. Like Hogan's compiler,
+/// dotted names and the implicit iterator use the `d` method.
+fn find(path: &Path, found: u8) -> String {
+    let key = path.keys.join(".");
+    let method = if key.contains('.') { "d" } else { "f" };
+    format!("t.{}({}, c, p, {})", method, string(&key), found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{link, transform};
+    use stache_core::ir::lower_tree;
+    use stache_core::{Compile, Statement, Template};
+    use std::path::PathBuf;
+
+    #[test]
+    fn transforms_tree_into_statements() {
+        let tree = Statement::parse(
+            "<p>{{#robots}}{{name.first}}{{> robot}}{{/robots}}{{^robots}}None{{/robots}}{{{bio}}}</p>",
+        )
+        .unwrap();
+        let mut partials = Vec::new();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), 0, true, &mut partials, &mut out).unwrap();
+
+        let expected = r#"t.b("<p>");
+if (t.s(t.f("robots", c, p, 1), c, p, 0, 0, 0, "{{ }}")) {
+  t.rs(c, p, function (c, p, t) {
+    t.b(t.v(t.d("name.first", c, p, 0)));
+    t.b(t.rp("<robot0", c, p, ""));
+  });
+  c.pop();
+}
+if (!t.s(t.f("robots", c, p, 1), c, p, 1, 0, 0, "")) {
+  t.b("None");
+}
+t.b(t.t(t.f("bio", c, p, 0)));
+t.b("</p>");
+"#;
+        assert_eq!(expected, out);
+        assert_eq!(
+            vec![(String::from("<robot0"), String::from("robot"))],
+            partials
+        );
+    }
+
+    #[test]
+    fn passes_standalone_partial_padding() {
+        let tree = Statement::parse("<ul>\n  {{> item}}\n  {{>*kind}}\n</ul>\n").unwrap();
+        let mut partials = Vec::new();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), 0, true, &mut partials, &mut out).unwrap();
+
+        let expected = r#"t.b("<ul>");
+t.b("\n" + i);
+t.b(t.rp("<item0", c, p, "  "));
+var n = t.f("kind", c, p, 0);
+if (Object.prototype.hasOwnProperty.call(p, n)) t.b(p[n].ri(c, p, "  "));
+t.b("</ul>");
+t.b("\n");
+"#;
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn exports_hogan_templates() {
+        let base = PathBuf::from("app/templates");
+        let templates = vec![
+            Template::new(
+                &base,
+                base.join("robots.mustache"),
+                Statement::parse("{{> includes/header}}").unwrap(),
+            ),
+            Template::new(
+                &base,
+                base.join("includes/header.mustache"),
+                Statement::parse("<h1>Robots</h1>").unwrap(),
+            ),
+        ];
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("templates[\"robots\"] = new Hogan.Template({\n"));
+        assert!(source.contains(
+            "    \"<includes/header0\": { name: \"includes/header\", partials: {}, subs: {} },\n"
+        ));
+        assert!(source.contains("}, \"\", Hogan);\n"));
+    }
+}
@@ -0,0 +1,357 @@
+extern crate serde_json;
+extern crate stache_core;
+#[macro_use]
+extern crate tracing;
+
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{self, Write};
+
+use self::runtime::RUNTIME;
+
+use serde_json::Value;
+use stache_core::ir::{self, Op};
+use stache_core::{Backend, Compile, Error, Name, Path, Template};
+
+pub mod hogan;
+mod runtime;
+#[cfg(feature = "typescript")]
+pub mod typescript;
+
+/// A program is the final result of translating Mustache templates into a
+/// JavaScript ES module.
+///
+/// The module exports a `render_<name>(context)` function for each template,
+/// and a `render(name, context)` function dispatching to them by template
+/// name, like the Ruby extension's `Templates#render`.
+#[derive(Debug)]
+pub struct Program {
+    functions: Vec<Function>,
+}
+
+/// A template translated into the body of its render function.
+#[derive(Debug)]
+struct Function {
+    export: String,
+    id: String,
+    body: String,
+}
+
+impl Compile for Program {
+    /// Writes the runtime, followed by each template's render function and
+    /// the exported dispatcher.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        let _span = info_span!("emit", target = "javascript").entered();
+        writeln!(buf, "// Generated by stache. Do not edit.\n")?;
+        writeln!(buf, "{}", RUNTIME)?;
+
+        for fun in &self.functions {
+            writeln!(buf, "function template_{}(buf, stack) {{", fun.id)?;
+            buf.write_all(fun.body.as_bytes())?;
+            writeln!(buf, "}}\n")?;
+            writeln!(buf, "export function render_{}(context) {{", fun.id)?;
+            writeln!(buf, "  return run(template_{}, context);", fun.id)?;
+            writeln!(buf, "}}\n")?;
+        }
+
+        writeln!(buf, "const TEMPLATES = new Map([")?;
+        for fun in &self.functions {
+            writeln!(buf, "  [{}, template_{}],", string(&fun.export), fun.id)?;
+        }
+        writeln!(buf, "]);\n")?;
+
+        writeln!(buf, "export function render(name, context) {{")?;
+        writeln!(buf, "  switch (name) {{")?;
+        for fun in &self.functions {
+            writeln!(buf, "    case {}:", string(&fun.export))?;
+            writeln!(buf, "      return run(template_{}, context);", fun.id)?;
+        }
+        writeln!(buf, "    default:")?;
+        writeln!(
+            buf,
+            "      throw new Error(\"Template not found: \" + name);"
+        )?;
+        writeln!(buf, "  }}")?;
+        writeln!(buf, "}}")
+    }
+}
+
+/// The `js` backend, compiling templates into a JavaScript module.
+#[derive(Debug)]
+pub struct Compiler;
+
+impl Backend for Compiler {
+    fn name(&self) -> &str {
+        "js"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(link(templates)?))
+    }
+}
+
+/// Transforms the AST of each parsed template into a JavaScript render
+/// function, linking partial calls to the functions of other templates.
+pub fn link(templates: &[Template]) -> Result<Program, Error> {
+    let _span = info_span!("link", templates = templates.len()).entered();
+    let program = ir::lower(templates)?;
+    ir::unfiltered(templates, "js")?;
+
+    // Template content is appended line by line when any partial is indented,
+    // since any template may be rendered as that partial.
+    let indented = program.functions.iter().any(|fun| ir::indented(&fun.body));
+    let functions = program
+        .functions
+        .iter()
+        .map(|fun| {
+            let _span = debug_span!("transform", template = %fun.name).entered();
+            let mut body = String::new();
+            transform(&fun.body, indented, 1, &mut body).expect("write to string");
+            Function {
+                export: fun.name.clone(),
+                id: Name::new(&fun.name).id().to_string(),
+                body,
+            }
+        })
+        .collect();
+
+    Ok(Program { functions })
+}
+
+/// Writes the JavaScript statements performing each operation into the
+/// body of the enclosing function.
+///
+/// Sections are translated into arrow functions called by the runtime once
+/// for each item the section's path resolves to. Partials call the render
+/// function of the template providing them, and dynamic partials look up
+/// the function by the template name found in the context. A standalone
+/// partial's padding indents each line it renders, which requires content
+/// to be appended line by line when the program has one.
+///
+/// Section bodies are translated recursively, relying on the parser's
+/// section depth limit to bound the stack.
+fn transform(ops: &[Op], indented: bool, depth: usize, out: &mut String) -> fmt::Result {
+    let indent = "  ".repeat(depth);
+    for op in ops {
+        match *op {
+            Op::Section(ref path, ref body, _) | Op::Inverted(ref path, ref body) => {
+                let fun = match *op {
+                    Op::Section(..) => "section",
+                    _ => "inverted",
+                };
+                writeln!(
+                    out,
+                    "{}{}(buf, stack, {}, (buf, stack) => {{",
+                    indent,
+                    fun,
+                    array(path)
+                )?;
+                transform(body, indented, depth + 1, out)?;
+                writeln!(out, "{}}});", indent)?;
+            }
+            Op::Partial(ref name, ref padding, ref arguments) => {
+                let name = Name::new(name);
+                indent_push(padding, &indent, out)?;
+                if arguments.is_empty() {
+                    writeln!(out, "{}template_{}(buf, stack);", indent, name.id())?;
+                } else {
+                    let keys: Vec<_> = arguments.iter().map(|(key, _)| string(key)).collect();
+                    let paths: Vec<_> = arguments.iter().map(|(_, path)| array(path)).collect();
+                    writeln!(
+                        out,
+                        "{}template_{}(buf, frame(stack, [{}], [{}]));",
+                        indent,
+                        name.id(),
+                        keys.join(", "),
+                        paths.join(", ")
+                    )?;
+                }
+                indent_pop(padding, &indent, out)?;
+            }
+            Op::DynamicPartial(ref path, ref padding) => {
+                indent_push(padding, &indent, out)?;
+                writeln!(out, "{}partial(buf, stack, {});", indent, array(path))?;
+                indent_pop(padding, &indent, out)?;
+            }
+            Op::Content(ref text) if indented => {
+                writeln!(out, "{}content(buf, {});", indent, string(text))?;
+            }
+            Op::Content(ref text) => {
+                writeln!(out, "{}buf.push({});", indent, string(text))?;
+            }
+            Op::Filtered(..) => unreachable!("filters are rejected when linking"),
+            Op::Translate(ref key) => {
+                writeln!(out, "{}translate(buf, {});", indent, string(key))?;
+            }
+            Op::Append(ref path, escape) => {
+                writeln!(
+                    out,
+                    "{}append(buf, stack, {}, {});",
+                    indent,
+                    array(path),
+                    escape
+                )?;
+            }
+            Op::Require(ref path, escape) => {
+                writeln!(
+                    out,
+                    "{}appendRequired(buf, stack, {}, {});",
+                    indent,
+                    array(path),
+                    escape
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds a standalone partial's padding to the indentation of the lines it
+/// renders, or nothing for a partial called inline.
+fn indent_push(padding: &Option<String>, indent: &str, out: &mut String) -> fmt::Result {
+    match *padding {
+        Some(ref padding) => writeln!(out, "{}indentPush(buf, {});", indent, string(padding)),
+        None => Ok(()),
+    }
+}
+
+/// Removes the padding added by `indent_push` after the partial's call.
+fn indent_pop(padding: &Option<String>, indent: &str, out: &mut String) -> fmt::Result {
+    match *padding {
+        Some(ref padding) => writeln!(out, "{}indentPop(buf, {});", indent, string(padding)),
+        None => Ok(()),
+    }
+}
+
+/// Formats the text as a JavaScript string literal.
+fn string(text: &str) -> String {
+    Value::from(text).to_string()
+}
+
+/// Formats the path's keys as a JavaScript array literal.
+fn array(path: &Path) -> String {
+    Value::from(path.keys.clone()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{link, string, transform};
+    use stache_core::ir::lower_tree;
+    use stache_core::{Compile, Error, Statement, Template};
+
+    #[test]
+    fn transforms_tree_into_statements() {
+        let tree = Statement::parse(
+            "<p>{{#robots}}{{name.first}}{{/robots}}{{^robots}}None{{/robots}}{{{bio}}}</p>",
+        )
+        .unwrap();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), false, 1, &mut out).unwrap();
+
+        let expected = r#"  buf.push("<p>");
+  section(buf, stack, ["robots"], (buf, stack) => {
+    append(buf, stack, ["name","first"], true);
+  });
+  inverted(buf, stack, ["robots"], (buf, stack) => {
+    buf.push("None");
+  });
+  append(buf, stack, ["bio"], false);
+  buf.push("</p>");
+"#;
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn transforms_partial_arguments() {
+        let tree = Statement::parse("{{> card title=user.name }}").unwrap();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), false, 1, &mut out).unwrap();
+        assert_eq!(
+            "  template_card(buf, frame(stack, [\"title\"], [[\"user\",\"name\"]]));\n",
+            out
+        );
+    }
+
+    #[test]
+    fn transforms_translations() {
+        let tree = Statement::Translation(String::from("checkout.title"));
+        let mut out = String::new();
+        transform(&lower_tree(&tree), false, 1, &mut out).unwrap();
+        assert_eq!("  translate(buf, \"checkout.title\");\n", out);
+    }
+
+    #[test]
+    fn escapes_string_literals() {
+        assert_eq!(
+            r#""a \"quote\"\n\\ \u0000""#,
+            string("a \"quote\"\n\\ \u{0}")
+        );
+    }
+
+    #[test]
+    fn exports_render_functions() {
+        let templates = vec![
+            Template::from_source("robots", "{{> includes/header}}{{name}}").unwrap(),
+            Template::from_source("includes/header", "<h1>Robots</h1>").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("export function render_robots(context) {"));
+        assert!(source.contains("export function render_includes_header(context) {"));
+        assert!(source.contains("  template_includes_header(buf, stack);\n"));
+        assert!(source.contains(
+            "    case \"includes/header\":\n      return run(template_includes_header, context);"
+        ));
+    }
+
+    #[test]
+    fn indents_standalone_partials() {
+        let templates = vec![
+            Template::from_source("page", "x\n  {{> list}}\ny").unwrap(),
+            Template::from_source("list", "a\n{{>*kind}}\nb\n").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains(
+            "  indentPush(buf, \"  \");\n  template_list(buf, stack);\n  indentPop(buf, \"  \");\n"
+        ));
+        assert!(source.contains("  content(buf, \"a\\n\");\n"));
+        assert!(!source.contains("buf.push(\""));
+
+        let templates = vec![
+            Template::from_source("page", "x\n{{> list}} y").unwrap(),
+            Template::from_source("list", "a").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(!source.contains("indentPush(buf, \""));
+        assert!(source.contains("  buf.push(\"x\\n\");\n"));
+    }
+
+    #[test]
+    fn finds_dynamic_partials_by_name() {
+        let templates = vec![
+            Template::from_source("robots", "{{#robots}}{{>*kind}}{{/robots}}").unwrap(),
+            Template::from_source("robot", "{{name}}").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("    partial(buf, stack, [\"kind\"]);\n"));
+        assert!(source.contains("  [\"robot\", template_robot],\n"));
+    }
+
+    #[test]
+    fn validates_partial_references() {
+        let templates = vec![Template::from_source("robots", "{{> missing}}").unwrap()];
+        match link(&templates) {
+            Err(Error::UnknownPartial { ref name, .. }) => assert_eq!("missing", name),
+            other => panic!("expected unknown partial error: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+/// The runtime functions called by the generated template functions, which
+/// mirror the Ruby extension's runtime: context stack lookups, HTML
+/// escaping, and section iteration. Dynamic partials find their template
+/// function in the `TEMPLATES` map the module defines after the templates.
+///
+/// Render output is collected as an array of strings, joined once when the
+/// template is finished rendering. The array also holds the indentation of
+/// the standalone partials being rendered, added to each line they output.
+pub const RUNTIME: &str = r#"const ESCAPES = {
+  "&": "&amp;",
+  "<": "&lt;",
+  ">": "&gt;",
+  '"': "&quot;",
+  "'": "&#39;",
+};
+
+export function escapeHtml(value) {
+  return String(value).replace(/[&<>"']/g, (c) => ESCAPES[c]);
+}
+
+let translator = (key) => key;
+
+export function setTranslator(fn) {
+  translator = fn;
+}
+
+function beginLine(buf) {
+  if (buf.lineStart) {
+    buf.lineStart = false;
+    buf.push(buf.indent);
+  }
+}
+
+function indentPush(buf, text) {
+  buf.indent += text;
+  buf.lineStart = true;
+}
+
+function indentPop(buf, text) {
+  buf.indent = buf.indent.slice(0, buf.indent.length - text.length);
+  buf.lineStart = true;
+}
+
+function content(buf, text) {
+  if (!buf.indent) {
+    buf.push(text);
+    return;
+  }
+  for (const line of text.split(/(?<=\n)/)) {
+    beginLine(buf);
+    buf.push(line);
+    buf.lineStart = line.endsWith("\n");
+  }
+}
+
+function translate(buf, key) {
+  beginLine(buf);
+  buf.push(escapeHtml(translator(key)));
+}
+
+function fetch(context, key) {
+  if (key === ".") {
+    return context;
+  }
+  if (context === null || context === undefined) {
+    return undefined;
+  }
+  if (context === false) {
+    return false;
+  }
+
+  const value = context[key];
+  return typeof value === "function" ? value.call(context) : value;
+}
+
+function lookup(stack, path) {
+  let value;
+  let frame = stack;
+  do {
+    value = fetch(frame.data, path[0]);
+  } while (value === undefined && (frame = frame.parent));
+
+  for (let i = 1; i < path.length && value !== undefined; i++) {
+    value = fetch(value, path[i]);
+  }
+  return value;
+}
+
+function append(buf, stack, path, escape) {
+  const value = lookup(stack, path);
+  if (value === null || value === undefined) {
+    return;
+  }
+  beginLine(buf);
+  buf.push(escape ? escapeHtml(value) : String(value));
+}
+
+function appendRequired(buf, stack, path, escape) {
+  const value = lookup(stack, path);
+  if (value === null || value === undefined) {
+    throw new Error("Missing template value: " + path.join("."));
+  }
+  beginLine(buf);
+  buf.push(escape ? escapeHtml(value) : String(value));
+}
+
+function frame(stack, keys, paths) {
+  const data = {};
+  keys.forEach((key, i) => {
+    data[key] = lookup(stack, paths[i]) ?? null;
+  });
+  return { data, parent: stack };
+}
+
+function section(buf, stack, path, block) {
+  const value = lookup(stack, path);
+  if (Array.isArray(value)) {
+    value.forEach((item, i) => {
+      const meta = { data: { "@index": i, "@first": i === 0, "@last": i === value.length - 1 }, parent: stack };
+      block(buf, { data: item, parent: meta });
+    });
+  } else if (value === true) {
+    block(buf, stack);
+  } else if (value !== null && value !== undefined && value !== false) {
+    block(buf, { data: value, parent: stack });
+  }
+}
+
+function inverted(buf, stack, path, block) {
+  const value = lookup(stack, path);
+  if (Array.isArray(value) ? value.length === 0 : value === null || value === undefined || value === false) {
+    block(buf, stack);
+  }
+}
+
+function partial(buf, stack, path) {
+  const value = lookup(stack, path);
+  const template = value === null || value === undefined ? undefined : TEMPLATES.get(String(value));
+  if (template) {
+    template(buf, stack);
+  }
+}
+
+function run(template, context) {
+  const buf = Object.assign([], { indent: "", lineStart: false });
+  template(buf, { data: context, parent: null });
+  return buf.join("");
+}
+"#;
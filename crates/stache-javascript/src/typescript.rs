@@ -0,0 +1,333 @@
+//! Translates templates into a TypeScript module exporting the same render
+//! functions as the JavaScript module, with a context interface for each
+//! template inferred from the paths it references. Callers passing context
+//! data with a misspelled key, or a list where text is rendered, fail to
+//! type check rather than rendering empty text.
+
+use std::io::{self, Write};
+
+use stache_core::ir;
+use stache_core::typescript::{self, Declarations};
+use stache_core::{Backend, Compile, Error, Name, Template};
+
+use super::{string, transform};
+
+/// The typed runtime functions called by the generated template functions,
+/// identical in behavior to the JavaScript module's runtime.
+const RUNTIME: &str = r#"type Stack = { data: unknown; parent: Stack | null };
+type Output = string[] & { indent: string; lineStart: boolean };
+type Block = (buf: Output, stack: Stack) => void;
+
+const ESCAPES: Record<string, string> = {
+  "&": "&amp;",
+  "<": "&lt;",
+  ">": "&gt;",
+  '"': "&quot;",
+  "'": "&#39;",
+};
+
+export function escapeHtml(value: unknown): string {
+  return String(value).replace(/[&<>"']/g, (c) => ESCAPES[c]);
+}
+
+let translator = (key: string): string => key;
+
+export function setTranslator(fn: (key: string) => string): void {
+  translator = fn;
+}
+
+function beginLine(buf: Output): void {
+  if (buf.lineStart) {
+    buf.lineStart = false;
+    buf.push(buf.indent);
+  }
+}
+
+function indentPush(buf: Output, text: string): void {
+  buf.indent += text;
+  buf.lineStart = true;
+}
+
+function indentPop(buf: Output, text: string): void {
+  buf.indent = buf.indent.slice(0, buf.indent.length - text.length);
+  buf.lineStart = true;
+}
+
+function content(buf: Output, text: string): void {
+  if (!buf.indent) {
+    buf.push(text);
+    return;
+  }
+  for (const line of text.split(/(?<=\n)/)) {
+    beginLine(buf);
+    buf.push(line);
+    buf.lineStart = line.endsWith("\n");
+  }
+}
+
+function translate(buf: Output, key: string): void {
+  beginLine(buf);
+  buf.push(escapeHtml(translator(key)));
+}
+
+function fetch(context: unknown, key: string): unknown {
+  if (key === ".") {
+    return context;
+  }
+  if (context === null || context === undefined) {
+    return undefined;
+  }
+  if (context === false) {
+    return false;
+  }
+
+  const value = (context as Record<string, unknown>)[key];
+  return typeof value === "function" ? value.call(context) : value;
+}
+
+function lookup(stack: Stack, path: string[]): unknown {
+  let value: unknown;
+  for (let frame: Stack | null = stack; frame && value === undefined; frame = frame.parent) {
+    value = fetch(frame.data, path[0]);
+  }
+
+  for (let i = 1; i < path.length && value !== undefined; i++) {
+    value = fetch(value, path[i]);
+  }
+  return value;
+}
+
+function append(buf: Output, stack: Stack, path: string[], escape: boolean): void {
+  const value = lookup(stack, path);
+  if (value === null || value === undefined) {
+    return;
+  }
+  beginLine(buf);
+  buf.push(escape ? escapeHtml(value) : String(value));
+}
+
+function appendRequired(buf: Output, stack: Stack, path: string[], escape: boolean): void {
+  const value = lookup(stack, path);
+  if (value === null || value === undefined) {
+    throw new Error("Missing template value: " + path.join("."));
+  }
+  beginLine(buf);
+  buf.push(escape ? escapeHtml(value) : String(value));
+}
+
+function frame(stack: Stack, keys: string[], paths: string[][]): Stack {
+  const data: Record<string, unknown> = {};
+  keys.forEach((key, i) => {
+    data[key] = lookup(stack, paths[i]) ?? null;
+  });
+  return { data, parent: stack };
+}
+
+function section(buf: Output, stack: Stack, path: string[], block: Block): void {
+  const value = lookup(stack, path);
+  if (Array.isArray(value)) {
+    value.forEach((item, i) => {
+      const meta = { data: { "@index": i, "@first": i === 0, "@last": i === value.length - 1 }, parent: stack };
+      block(buf, { data: item, parent: meta });
+    });
+  } else if (value === true) {
+    block(buf, stack);
+  } else if (value !== null && value !== undefined && value !== false) {
+    block(buf, { data: value, parent: stack });
+  }
+}
+
+function inverted(buf: Output, stack: Stack, path: string[], block: Block): void {
+  const value = lookup(stack, path);
+  if (Array.isArray(value) ? value.length === 0 : value === null || value === undefined || value === false) {
+    block(buf, stack);
+  }
+}
+
+function partial(buf: Output, stack: Stack, path: string[]): void {
+  const value = lookup(stack, path);
+  const template = value === null || value === undefined ? undefined : TEMPLATES.get(String(value));
+  if (template) {
+    Template::from_source(buf, stack).unwrap();
+  }
+}
+
+function run(template: Block, context: unknown): string {
+  const buf: Output = Object.assign([], { indent: "", lineStart: false });
+  Template::from_source(buf, { data: context, parent: null }).unwrap();
+  return buf.join("");
+}
+"#;
+
+/// A program is the final result of translating Mustache templates into a
+/// TypeScript module.
+///
+/// The module exports the context interfaces declared by
+/// `stache_core::typescript`, a typed `render_<name>(context)` function for
+/// each template, and a `render(name, context)` function accepting only the
+/// context type of the named template.
+#[derive(Debug)]
+pub struct Program {
+    declarations: Declarations,
+    functions: Vec<Function>,
+}
+
+/// A template translated into the body of its render function.
+#[derive(Debug)]
+struct Function {
+    export: String,
+    id: String,
+    interface: String,
+    body: String,
+}
+
+impl Compile for Program {
+    /// Writes the context types and runtime, followed by each template's
+    /// render function and the exported dispatcher.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        let _span = info_span!("emit", target = "typescript").entered();
+        writeln!(buf, "// Generated by stache. Do not edit.\n")?;
+        self.declarations.emit_types(buf)?;
+        writeln!(buf)?;
+        writeln!(buf, "{}", RUNTIME)?;
+
+        for fun in &self.functions {
+            writeln!(
+                buf,
+                "function template_{}(buf: Output, stack: Stack): void {{",
+                fun.id
+            )?;
+            buf.write_all(fun.body.as_bytes())?;
+            writeln!(buf, "}}\n")?;
+            writeln!(
+                buf,
+                "export function render_{}(context: {}): string {{",
+                fun.id, fun.interface
+            )?;
+            writeln!(buf, "  return run(template_{}, context);", fun.id)?;
+            writeln!(buf, "}}\n")?;
+        }
+
+        writeln!(buf, "const TEMPLATES = new Map<string, Block>([")?;
+        for fun in &self.functions {
+            writeln!(buf, "  [{}, template_{}],", string(&fun.export), fun.id)?;
+        }
+        writeln!(buf, "]);\n")?;
+
+        writeln!(
+            buf,
+            "export function render<K extends keyof Templates>(name: K, context: Templates[K]): string {{"
+        )?;
+        writeln!(buf, "  switch (name) {{")?;
+        for fun in &self.functions {
+            writeln!(buf, "    case {}:", string(&fun.export))?;
+            writeln!(buf, "      return run(template_{}, context);", fun.id)?;
+        }
+        writeln!(buf, "    default:")?;
+        writeln!(
+            buf,
+            "      throw new Error(\"Template not found: \" + String(name));"
+        )?;
+        writeln!(buf, "  }}")?;
+        writeln!(buf, "}}")
+    }
+}
+
+/// The `ts` backend, compiling templates into a TypeScript module.
+#[derive(Debug)]
+pub struct Compiler;
+
+impl Backend for Compiler {
+    fn name(&self) -> &str {
+        "ts"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(link(templates)?))
+    }
+}
+
+/// Transforms the AST of each parsed template into a typed TypeScript render
+/// function, linking partial calls to the functions of other templates.
+pub fn link(templates: &[Template]) -> Result<Program, Error> {
+    let _span = info_span!("link", templates = templates.len()).entered();
+    let program = ir::lower(templates)?;
+    ir::unfiltered(templates, "ts")?;
+    let declarations = typescript::declarations(templates);
+
+    let indented = program.functions.iter().any(|fun| ir::indented(&fun.body));
+    let functions = program
+        .functions
+        .iter()
+        .map(|fun| {
+            let _span = debug_span!("transform", template = %fun.name).entered();
+            let mut body = String::new();
+            transform(&fun.body, indented, 1, &mut body).expect("write to string");
+            Function {
+                export: fun.name.clone(),
+                id: Name::new(&fun.name).id().to_string(),
+                interface: declarations
+                    .interface(&fun.name)
+                    .expect("declared interface")
+                    .to_string(),
+                body,
+            }
+        })
+        .collect();
+
+    Ok(Program {
+        declarations,
+        functions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::link;
+    use stache_core::{Compile, Template};
+
+    #[test]
+    fn exports_typed_render_functions() {
+        let templates = vec![
+            Template::from_source(
+                "robots",
+                "{{> includes/header}}{{#robots}}{{name}}{{/robots}}",
+            )
+            .unwrap(),
+            Template::from_source("includes/header", "<h1>{{title}}</h1>").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("export interface RobotsContext {\n"));
+        assert!(source.contains("export interface IncludesHeaderContext {\n  title?: Scalar;\n}"));
+        assert!(source.contains("  \"includes/header\": IncludesHeaderContext;\n"));
+        assert!(source.contains(
+            "function template_robots(buf: Output, stack: Stack): void {\n  template_includes_header(buf, stack);\n"
+        ));
+        assert!(source.contains("export function render_robots(context: RobotsContext): string {"));
+        assert!(source.contains(
+            "export function render<K extends keyof Templates>(name: K, context: Templates[K]): string {"
+        ));
+        assert!(source.contains(
+            "    case \"includes/header\":\n      return run(template_includes_header, context);"
+        ));
+    }
+
+    #[test]
+    fn indents_standalone_partials() {
+        let templates = vec![
+            Template::from_source("page", "x\n  {{> list}}\ny").unwrap(),
+            Template::from_source("list", "a\nb\n").unwrap(),
+        ];
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains(
+            "  indentPush(buf, \"  \");\n  template_list(buf, stack);\n  indentPop(buf, \"  \");\n"
+        ));
+        assert!(source.contains("  content(buf, \"a\\nb\\n\");\n"));
+    }
+}
@@ -0,0 +1,441 @@
+//! Compiles templates into the C library, built as a shared library, with a
+//! generated C# class calling it through P/Invoke. The class marshals
+//! `Dictionary<string, object>` contexts, and nested lists and dictionaries,
+//! into the library's `struct ctx` values for each render call.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path as FilePath;
+
+use stache_core::{Backend, Compile, Error, Template};
+
+use super::{banner, constant, link_with, Options, Target};
+
+/// The marshaling layer and native imports of the generated class, which
+/// copy a context into unmanaged memory for the duration of a render call.
+/// Indented for the body of the `Templates` class.
+const BINDINGS: &str = r#"private const int CtxNull = 0;
+private const int CtxBool = 1;
+private const int CtxInteger = 2;
+private const int CtxString = 3;
+private const int CtxList = 4;
+private const int CtxMap = 5;
+
+[StructLayout(LayoutKind.Explicit)]
+private struct CtxValue
+{
+    [FieldOffset(0)] public byte Boolean;
+    [FieldOffset(0)] public long Integer;
+    [FieldOffset(0)] public IntPtr Pointer;
+}
+
+[StructLayout(LayoutKind.Sequential)]
+private struct Ctx
+{
+    public int Type;
+    public UIntPtr Length;
+    public CtxValue As;
+}
+
+[StructLayout(LayoutKind.Sequential)]
+private struct CtxEntry
+{
+    public IntPtr Key;
+    public Ctx Value;
+}
+
+private sealed class Marshaler : IDisposable
+{
+    private readonly List<IntPtr> allocations = new List<IntPtr>();
+
+    public IntPtr Root(object value)
+    {
+        IntPtr ptr = Allocate(Marshal.SizeOf<Ctx>());
+        Marshal.StructureToPtr(ToCtx(value), ptr, false);
+        return ptr;
+    }
+
+    public void Dispose()
+    {
+        foreach (IntPtr ptr in allocations)
+        {
+            Marshal.FreeHGlobal(ptr);
+        }
+        allocations.Clear();
+    }
+
+    private IntPtr Allocate(int size)
+    {
+        IntPtr ptr = Marshal.AllocHGlobal(Math.Max(size, 1));
+        allocations.Add(ptr);
+        return ptr;
+    }
+
+    private IntPtr Utf8(string value, out int length)
+    {
+        byte[] bytes = Encoding.UTF8.GetBytes(value);
+        IntPtr ptr = Allocate(bytes.Length + 1);
+        Marshal.Copy(bytes, 0, ptr, bytes.Length);
+        Marshal.WriteByte(ptr, bytes.Length, 0);
+        length = bytes.Length;
+        return ptr;
+    }
+
+    private Ctx ToCtx(object value)
+    {
+        var ctx = new Ctx();
+        switch (value)
+        {
+            case null:
+                ctx.Type = CtxNull;
+                break;
+            case bool boolean:
+                ctx.Type = CtxBool;
+                ctx.As.Boolean = (byte)(boolean ? 1 : 0);
+                break;
+            case sbyte _: case byte _: case short _: case ushort _: case int _: case uint _: case long _:
+                ctx.Type = CtxInteger;
+                ctx.As.Integer = Convert.ToInt64(value);
+                break;
+            case string text:
+                ctx.Type = CtxString;
+                ctx.As.Pointer = Utf8(text, out int length);
+                ctx.Length = (UIntPtr)(uint)length;
+                break;
+            case IDictionary map:
+            {
+                int size = Marshal.SizeOf<CtxEntry>();
+                IntPtr entries = Allocate(size * map.Count);
+                int i = 0;
+                foreach (DictionaryEntry pair in map)
+                {
+                    var entry = new CtxEntry
+                    {
+                        Key = Utf8(Convert.ToString(pair.Key, CultureInfo.InvariantCulture), out int _),
+                        Value = ToCtx(pair.Value),
+                    };
+                    Marshal.StructureToPtr(entry, entries + size * i++, false);
+                }
+                ctx.Type = CtxMap;
+                ctx.As.Pointer = entries;
+                ctx.Length = (UIntPtr)(uint)i;
+                break;
+            }
+            case IDictionary<string, object> generic:
+                return ToCtx(new Dictionary<string, object>(generic));
+            case IEnumerable list:
+            {
+                var items = new List<Ctx>();
+                foreach (object item in list)
+                {
+                    items.Add(ToCtx(item));
+                }
+                int size = Marshal.SizeOf<Ctx>();
+                IntPtr ptr = Allocate(size * items.Count);
+                for (int i = 0; i < items.Count; i++)
+                {
+                    Marshal.StructureToPtr(items[i], ptr + size * i, false);
+                }
+                ctx.Type = CtxList;
+                ctx.As.Pointer = ptr;
+                ctx.Length = (UIntPtr)(uint)items.Count;
+                break;
+            }
+            default:
+                ctx.Type = CtxString;
+                ctx.As.Pointer = Utf8(Convert.ToString(value, CultureInfo.InvariantCulture), out int formatted);
+                ctx.Length = (UIntPtr)(uint)formatted;
+                break;
+        }
+        return ctx;
+    }
+}
+
+/// <summary>
+/// Renders the named template with the context data.
+/// </summary>
+/// <exception cref="KeyNotFoundException">The template isn't found.</exception>
+/// <exception cref="OutOfMemoryException">The output can't be allocated.</exception>
+public static string Render(string name, IDictionary<string, object> context)
+{
+    using (var marshaler = new Marshaler())
+    {
+        IntPtr output = NativeRender(name, marshaler.Root(context));
+        if (output == IntPtr.Zero)
+        {
+            if (!Names.Contains(name))
+            {
+                throw new KeyNotFoundException("Template not found: " + name);
+            }
+            throw new OutOfMemoryException("Template output allocation failed: " + name);
+        }
+
+        try
+        {
+            return Marshal.PtrToStringUTF8(output);
+        }
+        finally
+        {
+            NativeFree(output);
+        }
+    }
+}
+"#;
+
+/// A program is the final result of translating Mustache templates into a
+/// C shared library and its C# binding.
+#[derive(Debug)]
+pub struct Program {
+    library: super::Program,
+    options: Options,
+    methods: Vec<(String, String)>,
+    names: Vec<String>,
+}
+
+impl Compile for Program {
+    /// Writes the C library source, followed by the function freeing the
+    /// rendered strings it returns to .NET.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        let _span = info_span!("emit", target = "dotnet").entered();
+        self.library.emit(buf)?;
+        writeln!(buf)?;
+        writeln!(
+            buf,
+            "void {}_free(char *output) {{ free(output); }}",
+            self.options.library()
+        )
+    }
+
+    /// Writes the C library's header and the C# binding class next to the
+    /// library source.
+    fn write_support(&self, dir: &FilePath) -> io::Result<()> {
+        self.library.write_support(dir)?;
+
+        let name = self.binding_name();
+        let _span = info_span!("write_binding", name = %name).entered();
+        let mut binding = BufWriter::new(File::create(dir.join(name))?);
+        self.emit_binding(&mut binding)?;
+        binding.flush()
+    }
+}
+
+impl Program {
+    /// Returns the file name of the C# binding class.
+    pub fn binding_name(&self) -> String {
+        format!("{}.cs", constant(self.options.library()))
+    }
+
+    /// Writes the C# class importing the library's render function, with a
+    /// render method for each template.
+    fn emit_binding(&self, buf: &mut dyn Write) -> io::Result<()> {
+        for line in banner(&self.options) {
+            writeln!(buf, "// {}", line)?;
+        }
+        for import in &[
+            "System",
+            "System.Collections",
+            "System.Collections.Generic",
+            "System.Globalization",
+            "System.Runtime.InteropServices",
+            "System.Text",
+        ] {
+            writeln!(buf, "using {};", import)?;
+        }
+
+        let library = self.options.library();
+        let mut namespace = String::from("Stache");
+        if let Some(ref bundle) = self.options.bundle {
+            namespace.push('.');
+            namespace.push_str(&constant(bundle));
+        }
+
+        writeln!(buf)?;
+        writeln!(buf, "namespace {}", namespace)?;
+        writeln!(buf, "{{")?;
+        writeln!(buf, "    public static class Templates")?;
+        writeln!(buf, "    {{")?;
+        writeln!(
+            buf,
+            "        private const string Library = \"{}\";",
+            library
+        )?;
+        writeln!(buf)?;
+        writeln!(
+            buf,
+            "        private static readonly HashSet<string> Names = new HashSet<string> {{ {} }};",
+            self.names
+                .iter()
+                .map(|name| string(name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        writeln!(buf)?;
+        writeln!(
+            buf,
+            "        [DllImport(Library, EntryPoint = \"{}\", CallingConvention = CallingConvention.Cdecl)]",
+            self.options.render_name()
+        )?;
+        writeln!(
+            buf,
+            "        private static extern IntPtr NativeRender([MarshalAs(UnmanagedType.LPUTF8Str)] string name, IntPtr context);"
+        )?;
+        writeln!(buf)?;
+        writeln!(
+            buf,
+            "        [DllImport(Library, EntryPoint = \"{}_free\", CallingConvention = CallingConvention.Cdecl)]",
+            library
+        )?;
+        writeln!(
+            buf,
+            "        private static extern void NativeFree(IntPtr output);"
+        )?;
+        writeln!(buf)?;
+        for line in BINDINGS.lines() {
+            if line.is_empty() {
+                writeln!(buf)?;
+            } else {
+                writeln!(buf, "        {}", line)?;
+            }
+        }
+
+        for (method, template) in &self.methods {
+            writeln!(buf)?;
+            writeln!(
+                buf,
+                "        /// <summary>Renders the {} template.</summary>",
+                string(template)
+            )?;
+            writeln!(
+                buf,
+                "        public static string Render{}(IDictionary<string, object> context) => Render({}, context);",
+                method,
+                string(template)
+            )?;
+        }
+        writeln!(buf, "    }}")?;
+        writeln!(buf, "}}")
+    }
+}
+
+/// The `dotnet` backend, compiling templates into a C shared library and its
+/// C# binding.
+#[derive(Debug)]
+pub struct Compiler {
+    pub options: Options,
+}
+
+impl Backend for Compiler {
+    fn name(&self) -> &str {
+        "dotnet"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(link(templates, &self.options)?))
+    }
+}
+
+/// Links the templates into a C library, naming a C# render method for each
+/// template and alias.
+pub fn link(templates: &[Template], options: &Options) -> Result<Program, Error> {
+    let options = Options {
+        target: Target::Library,
+        ..options.clone()
+    };
+    let library = link_with(templates, &options)?;
+
+    let mut names: Vec<String> = templates.iter().map(|t| t.name.clone()).collect();
+    names.extend(options.aliases.iter().map(|(alias, _)| alias.clone()));
+
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    let mut errors = Vec::new();
+    let mut methods = Vec::new();
+    for name in &names {
+        let method = constant(options.name(name).id());
+        if let Some(other) = seen.insert(method.clone(), name) {
+            errors.push(Error::Backend(format!(
+                "Templates `{}` and `{}` both render with C# method `Render{}`",
+                other, name, method
+            )));
+        }
+        methods.push((method, name.clone()));
+    }
+    Error::all(errors)?;
+
+    Ok(Program {
+        library,
+        options,
+        methods,
+        names,
+    })
+}
+
+/// Formats the text as a C# regular string literal.
+fn string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{link, string};
+    use stache_core::{Compile, Error, Template};
+    use Options;
+
+    #[test]
+    fn escapes_string_literals() {
+        assert_eq!(r#""a \"b\"\\\u000a""#, string("a \"b\"\\\n"));
+    }
+
+    #[test]
+    fn exports_free_function_and_binding() {
+        let templates = vec![
+            Template::from_source("robots", "{{> includes/header}}{{name}}").unwrap(),
+            Template::from_source("includes/header", "<h1>Robots</h1>").unwrap(),
+        ];
+        let options = Options {
+            bundle: Some(String::from("admin")),
+            ..Options::default()
+        };
+        let program = link(&templates, &options).unwrap();
+        assert_eq!("Admin.cs", program.binding_name());
+
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(source.starts_with("#include \"admin.h\"\n"));
+        assert!(source.ends_with("void admin_free(char *output) { free(output); }\n"));
+
+        let mut buf = Vec::new();
+        program.emit_binding(&mut buf).unwrap();
+        let binding = String::from_utf8(buf).unwrap();
+        assert!(binding.contains("namespace Stache.Admin\n{\n    public static class Templates\n"));
+        assert!(binding.contains("private const string Library = \"admin\";"));
+        assert!(binding.contains("EntryPoint = \"admin_render\""));
+        assert!(binding.contains(
+            "public static string RenderIncludesHeader(IDictionary<string, object> context) => Render(\"includes/header\", context);"
+        ));
+    }
+
+    #[test]
+    fn rejects_conflicting_method_names() {
+        let templates = vec![
+            Template::from_source("robot/list", "{{name}}").unwrap(),
+            Template::from_source("robotList", "{{name}}").unwrap(),
+        ];
+        match link(&templates, &Options::default()) {
+            Err(Error::Backend(ref message)) => assert!(message.contains("`RenderRobotList`")),
+            other => panic!("expected method name conflict: {:?}", other.map(|_| ())),
+        }
+    }
+}
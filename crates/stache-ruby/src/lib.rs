@@ -0,0 +1,3034 @@
+#[cfg(feature = "parallel")]
+extern crate rayon;
+extern crate serde_json;
+extern crate stache_core;
+#[macro_use]
+extern crate tracing;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as FmtWrite};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path as FilePath;
+use std::slice;
+
+use self::runtime::{
+    ADDON_HEADER, ADDON_RUNTIME, HEADER, LIBRARY_API, LIBRARY_HEADER, LIBRARY_RUNTIME, RUNTIME,
+};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde_json::{Map, Value};
+use stache_core::ir::{self, Op};
+use stache_core::{Backend, Compile, Error, Name, Path, Pragma, Template};
+
+pub mod dotnet;
+mod pretty;
+mod runtime;
+pub mod source;
+
+/// The compiler version recorded in generated source provenance.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A program is the final result of Mustache AST to Ruby extension source
+/// translation that is presented to the main compiler driver for output.
+///
+/// It contains all external rendering functions generated by the translator,
+/// which are exposed to Ruby code after the extension is compiled, or to the
+/// C or JavaScript code loading the templates as a library or addon.
+#[derive(Debug)]
+pub struct Program {
+    global: Scope,
+    options: Options,
+}
+
+/// Code generation settings for the Ruby extension output.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The kind of C program generated from the templates.
+    pub target: Target,
+
+    /// Partial template names whose output is cached at runtime. A cached
+    /// partial is rendered once per context stack during a render call, or
+    /// once per `cache_key` value across render calls when its context
+    /// provides one.
+    pub memoize: Vec<String>,
+
+    /// An identifier naming this compiled template set, allowing several
+    /// independently compiled extensions to be loaded into one process.
+    ///
+    /// The bundle names the extension's `Init_<bundle>` function and nests
+    /// its classes in a `Stache::<Bundle>` module. Without a bundle, the
+    /// extension is initialized by `Init_stache` and defines
+    /// `Stache::Templates`.
+    pub bundle: Option<String>,
+
+    /// The Ruby module path enclosing the templates class, like
+    /// `Admin::Views`, in place of the `Stache` module and bundle.
+    pub module: Option<String>,
+
+    /// The name of the templates class, in place of `Templates`.
+    pub class: Option<String>,
+
+    /// The extension's `Init_` function name suffix, in place of the bundle
+    /// name, for an extension built as a library of another name.
+    pub init: Option<String>,
+
+    /// The initial size, in bytes, of each `Templates` instance's render
+    /// buffer.
+    pub capacity: usize,
+
+    /// The percentage by which the render buffer grows when it runs out of
+    /// space. Must be greater than 100.
+    pub growth: usize,
+
+    /// Names generated functions and strings with a short hash of their
+    /// template's name rather than the name itself. A comment at the top of
+    /// the output maps each hashed name back to its template.
+    pub short_names: bool,
+
+    /// Indents the generated source consistently, placing each statement on
+    /// its own line, so changes to the output are easier to review.
+    pub pretty: bool,
+
+    /// Builds the runtime with bounds assertions and, under AddressSanitizer,
+    /// poisons unused and freed buffer memory, for running the extension's
+    /// tests with `-fsanitize=address,undefined`.
+    pub sanitize: bool,
+
+    /// Renders directly into a Ruby string, allocated by each render call
+    /// and returned as its result, rather than copying the output from a
+    /// reused native buffer. Very large outputs avoid the final copy and are
+    /// fully accounted for by Ruby's garbage collector.
+    pub string_buffer: bool,
+
+    /// Records every context lookup made while rendering with the
+    /// `Templates#render_trace` debug method, which returns the output along
+    /// with each lookup's path, the index of the stack frame that resolved
+    /// it, and its value.
+    pub trace_lookups: bool,
+
+    /// Repeats a section over a Hash's key/value pairs, as it repeats over a
+    /// list's items, rendering each value with its key as `@key`, rather
+    /// than rendering the section once with the Hash as its context.
+    pub iterate_hashes: bool,
+
+    /// Embeds each section's raw source text in the extension, to pass to a
+    /// `Proc` found as the section's value. Without it, a section `Proc`
+    /// raises when rendered, and sections cost only their functions.
+    pub lambdas: bool,
+
+    /// Emits the runtime as a separate header and source file, written with
+    /// `Program::write_runtime`, which the generated template source
+    /// includes rather than containing its own copy of the runtime.
+    pub shared_runtime: bool,
+
+    /// How interpolating a nil or missing context value renders.
+    pub missing: Missing,
+
+    /// Trailing whitespace removed from a template's rendered output.
+    pub trim: Trim,
+
+    /// Template names whose output is trimmed. When empty, the output of
+    /// every template is trimmed.
+    pub trim_only: Vec<String>,
+
+    /// Additional names each rendering an existing template, as pairs of
+    /// alias and template name, so a renamed template can still be rendered
+    /// by its old name.
+    pub aliases: Vec<(String, String)>,
+
+    /// Context paths treated as constants, like `beta_ui`. Sections over a
+    /// defined path are evaluated at compile time, so only the branch taken
+    /// is compiled.
+    pub defines: HashMap<String, bool>,
+
+    /// Text, like a license notice, written in a comment at the top of each
+    /// generated file.
+    pub banner: Option<String>,
+
+    /// Records where the generated source came from in a do-not-edit notice
+    /// at the top of each file and in the extension's `MANIFEST` constant,
+    /// so generated sources checked into other repositories can be traced
+    /// back to their templates.
+    pub provenance: Option<Provenance>,
+}
+
+/// The kind of C program the templates are compiled into. All share the
+/// generated template functions and render buffer, differing in how context
+/// values are represented and how rendering is exposed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    /// A Ruby extension rendering Ruby hashes and objects.
+    Extension,
+    /// A freestanding C library without Ruby headers, rendering the `struct
+    /// ctx` values declared by its header with a `<library>_render`
+    /// function, for linking templates into C programs.
+    Library,
+    /// A Node.js native addon built against N-API, rendering JavaScript
+    /// objects with its exported `render(name, context)` function.
+    Addon,
+}
+
+impl Target {
+    /// Returns the target's name, as given to `--emit`.
+    fn name(self) -> &'static str {
+        match self {
+            Target::Extension => "ruby",
+            Target::Library => "c",
+            Target::Addon => "node",
+        }
+    }
+}
+
+/// The origin of a generated extension's templates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Provenance {
+    /// The template directory, as given to the compiler.
+    pub source: String,
+    /// The source control revision of the templates, like a commit hash.
+    pub revision: Option<String>,
+}
+
+/// The trailing whitespace removed from rendered output, as needed by
+/// templates generating email subjects or inline fragments.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trim {
+    /// Keeps the output as rendered.
+    Keep,
+    /// Removes one final `\n` or `\r\n` line ending.
+    Newline,
+    /// Removes all trailing spaces, tabs, and line endings.
+    Whitespace,
+}
+
+/// The output of an interpolation tag whose value is nil or not found in
+/// the context stack.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Missing {
+    /// Renders nothing, as the Mustache spec requires.
+    Empty,
+    /// Renders the text as written, without escaping, to make missing data
+    /// visible in staging builds.
+    Placeholder(String),
+    /// Raises a `KeyError` naming the tag's context path.
+    Raise,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            target: Target::Extension,
+            memoize: Vec::new(),
+            bundle: None,
+            module: None,
+            class: None,
+            init: None,
+            capacity: 2048,
+            growth: 200,
+            short_names: false,
+            pretty: false,
+            sanitize: false,
+            string_buffer: false,
+            trace_lookups: false,
+            iterate_hashes: false,
+            lambdas: false,
+            shared_runtime: false,
+            missing: Missing::Empty,
+            trim: Trim::Keep,
+            trim_only: Vec::new(),
+            aliases: Vec::new(),
+            defines: HashMap::new(),
+            banner: None,
+            provenance: None,
+        }
+    }
+}
+
+impl Options {
+    /// Returns the shared library name the extension must be built as, which
+    /// Ruby uses to find its `Init_` function.
+    pub fn library(&self) -> &str {
+        self.bundle
+            .as_ref()
+            .map_or("stache", |bundle| bundle.as_str())
+    }
+
+    /// Returns the Ruby module names enclosing the generated classes, from
+    /// outermost to innermost.
+    fn namespace(&self) -> Vec<String> {
+        if let Some(ref module) = self.module {
+            return module.split("::").map(String::from).collect();
+        }
+        let mut modules = vec![String::from("Stache")];
+        if let Some(ref bundle) = self.bundle {
+            modules.push(constant(bundle));
+        }
+        modules
+    }
+
+    /// Returns the name of the class defining the `render` method.
+    fn class(&self) -> &str {
+        self.class.as_deref().unwrap_or("Templates")
+    }
+
+    /// Returns the name of the function Ruby calls when the extension is
+    /// required, without its `Init_` prefix.
+    fn init(&self) -> &str {
+        self.init.as_deref().unwrap_or_else(|| self.library())
+    }
+
+    /// Returns the trailing whitespace to remove from the template's output.
+    fn trim(&self, template: &str) -> Trim {
+        if self.trim_only.is_empty() || self.trim_only.iter().any(|name| name == template) {
+            self.trim
+        } else {
+            Trim::Keep
+        }
+    }
+
+    /// Creates the name generator for a template's functions and strings.
+    fn name(&self, template: &str) -> Name {
+        if self.short_names {
+            Name::short(template)
+        } else {
+            Name::new(template)
+        }
+    }
+
+    /// Returns the name of the C library's public render function.
+    pub fn render_name(&self) -> String {
+        format!("{}_render", self.library())
+    }
+
+    /// Returns the file name of the C library's public header.
+    pub fn header_name(&self) -> String {
+        format!("{}.h", self.library())
+    }
+
+    /// Returns the file name, without extension, of the shared runtime's
+    /// header and source files.
+    pub fn runtime_name(&self) -> String {
+        format!("{}_runtime", self.library())
+    }
+
+    /// Returns the name of the buffer's typed data, as reported by Ruby's
+    /// `ObjectSpace` and memory profilers.
+    fn buffer_type(&self) -> String {
+        match self.bundle {
+            Some(ref bundle) => format!("stache-{}-buffer", bundle),
+            None => String::from("stache-buffer"),
+        }
+    }
+}
+
+impl Program {
+    fn new(options: Options) -> Self {
+        Program {
+            global: Scope::new(Name::new("global")),
+            options,
+        }
+    }
+}
+
+impl Compile for Program {
+    /// Writes the final translated source code to an output buffer.
+    ///
+    /// This emits fully-formed Ruby extension source code that may be input
+    /// into a mkmf build process, creating a dynamically loadable shared
+    /// object file.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        let _span = info_span!("emit", library = self.options.library()).entered();
+        self.emit_banner(buf)?;
+        match self.options.target {
+            Target::Extension if self.options.shared_runtime => {
+                writeln!(buf, "#include \"{}.h\"", self.options.runtime_name())?;
+            }
+            Target::Extension => {
+                self.emit_config(buf)?;
+                emit_static_api(buf)?;
+                writeln!(buf, "{}", HEADER)?;
+                writeln!(buf, "{}", RUNTIME)?;
+            }
+            Target::Library => {
+                writeln!(buf, "#include \"{}\"", self.options.header_name())?;
+                self.emit_config(buf)?;
+                emit_static_api(buf)?;
+                writeln!(buf, "{}", LIBRARY_HEADER)?;
+                writeln!(buf, "{}", LIBRARY_RUNTIME)?;
+            }
+            Target::Addon => {
+                self.emit_config(buf)?;
+                emit_static_api(buf)?;
+                writeln!(buf, "{}", ADDON_HEADER)?;
+                writeln!(buf, "{}", ADDON_RUNTIME)?;
+            }
+        }
+
+        if self.options.pretty {
+            let mut source = Vec::new();
+            self.emit_program(&mut source)?;
+            let source = pretty::format(&String::from_utf8_lossy(&source));
+            buf.write_all(source.as_bytes())
+        } else {
+            self.emit_program(buf)
+        }
+    }
+
+    /// Writes the C library's header, or the extension's shared runtime when
+    /// enabled, next to the generated source.
+    fn write_support(&self, dir: &FilePath) -> io::Result<()> {
+        match self.options.target {
+            Target::Library => self.write_header(dir),
+            Target::Extension if self.options.shared_runtime => self.write_runtime(dir),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Program {
+    /// Writes the shared runtime's header and source files into the
+    /// directory, alongside the template source that includes them.
+    pub fn write_runtime(&self, dir: &FilePath) -> io::Result<()> {
+        let name = self.options.runtime_name();
+        let _span = info_span!("write_runtime", name = %name).entered();
+
+        let mut header = BufWriter::new(File::create(dir.join(format!("{}.h", name)))?);
+        self.emit_runtime_header(&mut header)?;
+        header.flush()?;
+
+        let mut source = BufWriter::new(File::create(dir.join(format!("{}.c", name)))?);
+        self.emit_runtime_source(&mut source)?;
+        source.flush()
+    }
+
+    /// Writes the C library's public header into the directory, alongside
+    /// the template source that includes it.
+    pub fn write_header(&self, dir: &FilePath) -> io::Result<()> {
+        let name = self.options.header_name();
+        let _span = info_span!("write_header", name = %name).entered();
+
+        let mut header = BufWriter::new(File::create(dir.join(name))?);
+        self.emit_header(&mut header)?;
+        header.flush()
+    }
+
+    /// Writes the C library's header, declaring the context value types and
+    /// the render function.
+    fn emit_header(&self, buf: &mut dyn Write) -> io::Result<()> {
+        self.emit_banner(buf)?;
+        let guard = self.options.library().to_uppercase();
+        writeln!(buf, "#ifndef {}_H", guard)?;
+        writeln!(buf, "#define {}_H", guard)?;
+        writeln!(buf, "{}", LIBRARY_API)?;
+        writeln!(buf, "/*")?;
+        writeln!(
+            buf,
+            " * Renders the named template, returning a string allocated with malloc"
+        )?;
+        writeln!(
+            buf,
+            " * that the caller frees, or NULL if the template isn't found or memory"
+        )?;
+        writeln!(buf, " * allocation fails.")?;
+        writeln!(buf, " */")?;
+        writeln!(
+            buf,
+            "char *{}(const char *name, const struct ctx *context);\n",
+            self.options.render_name()
+        )?;
+        writeln!(buf, "#endif")
+    }
+
+    /// Writes the configured banner text and provenance notice in a comment
+    /// at the top of a generated file.
+    fn emit_banner(&self, buf: &mut dyn Write) -> io::Result<()> {
+        let lines = banner(&self.options);
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(buf, "/*")?;
+        for line in lines {
+            if line.is_empty() {
+                writeln!(buf, " *")?;
+            } else {
+                writeln!(buf, " * {}", line.replace("*/", "* /"))?;
+            }
+        }
+        writeln!(buf, " */")
+    }
+
+    /// Returns the JSON manifest describing the compiler, template source,
+    /// and templates the extension was generated from.
+    fn manifest(&self, provenance: &Provenance) -> String {
+        let templates = self
+            .global
+            .functions
+            .iter()
+            .filter_map(|fun| fun.export.as_ref());
+        manifest(provenance, templates)
+    }
+
+    /// Writes the runtime settings chosen by the code generation options.
+    fn emit_config(&self, buf: &mut dyn Write) -> io::Result<()> {
+        match self.options.target {
+            Target::Extension => {
+                writeln!(
+                    buf,
+                    "#define STACHE_BUFFER_TYPE \"{}\"",
+                    self.options.buffer_type()
+                )?;
+                writeln!(buf, "#define STACHE_CLASS \"{}\"", self.options.class())?;
+            }
+            Target::Library => writeln!(buf, "#define STACHE_LIBRARY 1")?,
+            Target::Addon => writeln!(buf, "#define STACHE_ADDON 1")?,
+        }
+        writeln!(
+            buf,
+            "#define STACHE_BUFFER_CAPACITY {}",
+            self.options.capacity
+        )?;
+        writeln!(buf, "#define STACHE_BUFFER_GROWTH {}", self.options.growth)?;
+        if self.options.sanitize {
+            writeln!(buf, "#define STACHE_SANITIZE 1")?;
+        }
+        if self.options.string_buffer {
+            writeln!(buf, "#define STACHE_STRING_BUFFER 1")?;
+        }
+        if self.options.trace_lookups {
+            writeln!(buf, "#define STACHE_TRACE 1")?;
+        }
+        if self.options.iterate_hashes {
+            writeln!(buf, "#define STACHE_ITERATE_HASHES 1")?;
+        }
+        match self.options.missing {
+            Missing::Empty => (),
+            Missing::Placeholder(ref text) => {
+                let mut literal = String::new();
+                clean(text, &mut literal);
+                writeln!(buf, "#define STACHE_MISSING_PLACEHOLDER \"{}\"", literal)?;
+            }
+            Missing::Raise => writeln!(buf, "#define STACHE_MISSING_RAISE 1")?,
+        }
+        Ok(())
+    }
+
+    /// Writes the shared runtime header, declaring runtime functions as
+    /// hidden symbols so independently built bundles loaded into one
+    /// process don't resolve each other's functions.
+    fn emit_runtime_header(&self, buf: &mut dyn Write) -> io::Result<()> {
+        self.emit_banner(buf)?;
+        let guard = self.options.runtime_name().to_uppercase();
+        writeln!(buf, "#ifndef {}_H", guard)?;
+        writeln!(buf, "#define {}_H\n", guard)?;
+        self.emit_config(buf)?;
+        writeln!(buf, "#if defined(__GNUC__)")?;
+        writeln!(
+            buf,
+            "#define STACHE_API __attribute__((visibility(\"hidden\")))"
+        )?;
+        writeln!(buf, "#else")?;
+        writeln!(buf, "#define STACHE_API")?;
+        writeln!(buf, "#endif")?;
+        writeln!(buf, "{}", HEADER)?;
+        writeln!(buf, "#endif")
+    }
+
+    /// Writes the shared runtime source file.
+    fn emit_runtime_source(&self, buf: &mut dyn Write) -> io::Result<()> {
+        self.emit_banner(buf)?;
+        writeln!(buf, "#include \"{}.h\"", self.options.runtime_name())?;
+        writeln!(buf, "{}", RUNTIME)
+    }
+
+    /// Writes the source code generated from the templates, following the
+    /// runtime.
+    fn emit_program(&self, buf: &mut dyn Write) -> io::Result<()> {
+        // Emit hashed name mapping.
+        if self.options.short_names {
+            writeln!(buf, "/*")?;
+            for fun in &self.global.functions {
+                if let Some(ref export) = fun.export {
+                    writeln!(buf, " * {}: {}", fun.name, export)?;
+                }
+            }
+            writeln!(buf, " */")?;
+        }
+
+        // Emit string content declarations.
+        for string in &self.global.strings {
+            string.emit(buf)?;
+        }
+
+        writeln!(buf, "")?;
+
+        // Constant templates are rendered from their precomputed content, so
+        // their functions are only needed as partials.
+        let functions: Vec<_> = self
+            .global
+            .functions
+            .iter()
+            .filter(|fun| fun.constant.is_none() || self.referenced(fun))
+            .collect();
+
+        // Emit function declarations.
+        for fun in &functions {
+            writeln!(buf, "{};", fun.decl)?;
+        }
+        if self.global.dynamic {
+            writeln!(buf, "{};", DYNAMIC_PARTIAL)?;
+        }
+
+        writeln!(buf, "")?;
+
+        // Emit function definitions.
+        for fun in &functions {
+            fun.emit(buf)?
+        }
+        if self.global.dynamic {
+            self.emit_dynamic_partial(buf)?;
+        }
+
+        // Emit public render function. Constant templates return their
+        // precomputed content before the render buffer is prepared.
+        let mut constants = Vec::new();
+        let mut renders = Vec::new();
+        for fun in &self.global.functions {
+            if let Some(ref export) = fun.export {
+                let invokes = if fun.constant.is_some() {
+                    &mut constants
+                } else {
+                    &mut renders
+                };
+                invokes.push(fun.invoke_if(export, self.options.target));
+                for (alias, _) in self.options.aliases.iter().filter(|(_, t)| t == export) {
+                    invokes.push(fun.invoke_if(alias, self.options.target));
+                }
+            }
+        }
+
+        match self.options.target {
+            Target::Extension => (),
+            Target::Library => return self.emit_library_render(buf, &constants, &renders),
+            Target::Addon => return self.emit_addon_render(buf, &constants, &renders),
+        }
+
+        let render = if renders.is_empty() {
+            String::from("rb_raise(rb_eArgError, \"Template not found\");")
+        } else {
+            format!(
+                r#"const struct stack stack = {{ .data = context, .parent = NULL }};
+
+                   struct buffer *buf = templates_get_buf(self);
+                   buffer_clear(buf);
+                   rb_hash_clear(buf->memo);
+
+                   {} else {{
+                       rb_raise(rb_eArgError, "Template not found");
+                   }}
+
+                   return buffer_string(buf);"#,
+                renders.join(" else ")
+            )
+        };
+
+        writeln!(
+            buf,
+            r#"STACHE_API VALUE render(VALUE self, VALUE name, VALUE context) {{
+                   const char *ptr = StringValuePtr(name);
+                   const long length = RSTRING_LEN(name);
+
+                   {}
+                   {}
+               }}"#,
+            constants.join("\n"),
+            render
+        )?;
+
+        // Emit extension entry point.
+        self.emit_init(buf)
+    }
+
+    /// Writes the function rendering dynamic partials, which looks up the
+    /// template name in the context and dispatches on it like the public
+    /// render function, rendering nothing for unknown names.
+    fn emit_dynamic_partial(&self, buf: &mut dyn Write) -> io::Result<()> {
+        let mut invokes = Vec::new();
+        let mut longest = 0;
+        for fun in &self.global.functions {
+            if let Some(ref export) = fun.export {
+                let aliases = self.options.aliases.iter().filter(|(_, t)| t == export);
+                for name in Some(export)
+                    .into_iter()
+                    .chain(aliases.map(|(alias, _)| alias))
+                {
+                    invokes.push(fun.dispatch_if(name));
+                    longest = longest.max(name.len());
+                }
+            }
+        }
+
+        writeln!(
+            buf,
+            r#"{} {{
+                   char ptr[{}];
+                   size_t length;
+                   if (!partial_name(buf, stack, path, ptr, sizeof(ptr), &length)) {{
+                       return;
+                   }}
+
+                   {}
+               }}"#,
+            DYNAMIC_PARTIAL,
+            longest + 1,
+            invokes.join(" else ")
+        )
+    }
+
+    /// Tests whether another function, or the dynamic partial dispatcher,
+    /// calls the function.
+    fn referenced(&self, fun: &Function) -> bool {
+        let calls = |body: &str| {
+            body.match_indices(&fun.name).any(|(i, name)| {
+                let identifier = |c: char| c == '_' || c.is_ascii_alphanumeric();
+                !body[..i].ends_with(identifier) && !body[i + name.len()..].starts_with(identifier)
+            })
+        };
+        (self.global.dynamic && fun.export.is_some())
+            || self
+                .global
+                .functions
+                .iter()
+                .any(|other| other.name != fun.name && calls(&other.body))
+    }
+
+    /// Writes the C library's public render function, which returns the
+    /// rendered output as a new string, or `NULL` when the template isn't
+    /// found or memory allocation fails.
+    fn emit_library_render(
+        &self,
+        buf: &mut dyn Write,
+        constants: &[String],
+        renders: &[String],
+    ) -> io::Result<()> {
+        let render = if renders.is_empty() {
+            String::from("return NULL;")
+        } else {
+            format!(
+                r#"const struct stack stack = {{ .data = context, .parent = NULL }};
+
+                   struct buffer buffer = {{ .failed = false }};
+                   struct buffer *buf = &buffer;
+                   if (!buffer_init(buf)) {{
+                       return NULL;
+                   }}
+
+                   {} else {{
+                       buffer_destroy(buf);
+                       return NULL;
+                   }}
+
+                   return buffer_finish(buf);"#,
+                renders.join(" else ")
+            )
+        };
+
+        writeln!(
+            buf,
+            r#"char *{}(const char *name, const struct ctx *context) {{
+                   const char *ptr = name;
+                   const size_t length = strlen(name);
+
+                   {}
+                   {}
+               }}"#,
+            self.options.render_name(),
+            constants.join("\n"),
+            render
+        )
+    }
+
+    /// Writes the Node.js addon's render function, which throws when the
+    /// template isn't found. Names longer than every exported name can't
+    /// match, so the name is only copied into a buffer of that size.
+    fn emit_addon_render(
+        &self,
+        buf: &mut dyn Write,
+        constants: &[String],
+        renders: &[String],
+    ) -> io::Result<()> {
+        let longest = self
+            .global
+            .functions
+            .iter()
+            .filter_map(|fun| fun.export.as_ref())
+            .chain(self.options.aliases.iter().map(|(alias, _)| alias))
+            .map(String::len)
+            .max()
+            .unwrap_or(0);
+
+        let render = if renders.is_empty() {
+            String::from(
+                r#"template_not_found(env, argv[0]);
+                   return NULL;"#,
+            )
+        } else {
+            format!(
+                r#"const struct stack stack = {{ .data = argv[1], .parent = NULL }};
+
+                   struct buffer buffer = {{ .failed = false, .env = env }};
+                   struct buffer *buf = &buffer;
+                   if (!buffer_init(buf)) {{
+                       napi_throw_error(env, NULL, "Memory allocation failed");
+                       return NULL;
+                   }}
+
+                   {} else {{
+                       buffer_destroy(buf);
+                       template_not_found(env, argv[0]);
+                       return NULL;
+                   }}
+
+                   return buffer_result(buf);"#,
+                renders.join(" else ")
+            )
+        };
+
+        writeln!(
+            buf,
+            r#"STACHE_API napi_value render(napi_env env, napi_callback_info info) {{
+                   size_t argc = 2;
+                   napi_value argv[2];
+                   if (napi_get_cb_info(env, info, &argc, argv, NULL, NULL) != napi_ok) {{
+                       return NULL;
+                   }}
+
+                   char ptr[{}];
+                   size_t length;
+                   if (argc < 1 || napi_get_value_string_utf8(env, argv[0], NULL, 0, &length) != napi_ok) {{
+                       napi_throw_type_error(env, NULL, "Template name must be a string");
+                       return NULL;
+                   }}
+                   if (length < sizeof(ptr)) {{
+                       napi_get_value_string_utf8(env, argv[0], ptr, sizeof(ptr), &length);
+                   }}
+
+                   {}
+                   {}
+               }}"#,
+            longest + 1,
+            constants.join("\n"),
+            render
+        )
+    }
+
+    /// Writes the `Init_` function Ruby calls when the extension is required,
+    /// defining the template classes inside the configured namespace.
+    fn emit_init(&self, buf: &mut dyn Write) -> io::Result<()> {
+        writeln!(buf, "\nvoid Init_{}() {{", self.options.init())?;
+        writeln!(buf, "VALUE namespace = rb_cObject;")?;
+        for module in self.options.namespace() {
+            writeln!(
+                buf,
+                "namespace = rb_define_module_under(namespace, \"{}\");",
+                module
+            )?;
+        }
+        if let Some(ref provenance) = self.options.provenance {
+            let mut manifest = String::new();
+            clean(&self.manifest(provenance), &mut manifest);
+            writeln!(
+                buf,
+                "rb_define_const(namespace, \"MANIFEST\", rb_obj_freeze(rb_str_new_cstr(\"{}\")));",
+                manifest
+            )?;
+        }
+        writeln!(buf, "init_templates(namespace);")?;
+        writeln!(buf, "}}")
+    }
+}
+
+/// A store for functions created by the translation process of an input
+/// template to source code output.
+///
+/// Scopes have name generators that are used in function naming, providing a
+/// stable name that other scopes may rely on for partial template function
+/// calls.
+///
+/// Each template is translated into its own scope, independently of the
+/// others, so templates may be translated in parallel. The scopes are then
+/// merged in template order into the Program's global scope, which shares
+/// identical content strings and section functions between templates so
+/// each is emitted only once.
+#[derive(Debug)]
+struct Scope {
+    name: Name,
+    functions: Vec<Function>,
+    strings: Vec<StaticString>,
+    sections: HashMap<String, usize>,
+    contents: HashMap<String, usize>,
+    dynamic: bool,
+    indent: bool,
+}
+
+impl Scope {
+    fn new(name: Name) -> Self {
+        Scope {
+            name: name,
+            functions: Vec::new(),
+            strings: Vec::new(),
+            sections: HashMap::new(),
+            contents: HashMap::new(),
+            dynamic: false,
+            indent: false,
+        }
+    }
+
+    /// Advances the scope's name generator to the next unique identifier. This
+    /// should be called before descending another level in the recursive
+    /// tree translation process.
+    fn next(&mut self) -> &mut Self {
+        self.name.next();
+        self
+    }
+
+    /// Adds a function to this scope.
+    fn register(&mut self, fun: Function) {
+        self.functions.push(fun);
+    }
+
+    /// Adds a section function to this scope, returning the name to call it
+    /// by. A section whose body matches one already in scope is discarded
+    /// in favor of the existing function.
+    fn section(&mut self, fun: Function) -> &str {
+        let index = match self.sections.get(&fun.body) {
+            Some(&index) => index,
+            None => {
+                self.sections.insert(fun.body.clone(), self.functions.len());
+                self.functions.push(fun);
+                self.functions.len() - 1
+            }
+        };
+        &self.functions[index].name
+    }
+
+    /// Adds a constant string value to this scope, returning the name of its
+    /// global. Identical strings share a single global.
+    fn content(&mut self, string: StaticString) -> &str {
+        let index = match self.contents.get(&string.value) {
+            Some(&index) => index,
+            None => {
+                self.contents
+                    .insert(string.value.clone(), self.strings.len());
+                self.strings.push(string);
+                self.strings.len() - 1
+            }
+        };
+        &self.strings[index].name
+    }
+
+    /// Moves the strings and functions of a translated template's scope into
+    /// this one.
+    ///
+    /// A string or section function identical to one already in scope is
+    /// dropped, and references to it in the merged functions are renamed to
+    /// the existing global. Functions are registered after the strings and
+    /// sections they call, so each body is renamed before it's compared.
+    fn merge(&mut self, other: Scope) {
+        let mut renames = HashMap::new();
+        self.dynamic |= other.dynamic;
+
+        for string in other.strings {
+            let name = string.name.clone();
+            let existing = self.content(string);
+            if existing != name {
+                renames.insert(name, existing.to_string());
+            }
+        }
+
+        for mut fun in other.functions {
+            if !renames.is_empty() {
+                fun.body = rename(&fun.body, &renames);
+                if let Some(ref mut constant) = fun.constant {
+                    if let Some(name) = renames.get(&constant.name) {
+                        constant.name = name.clone();
+                    }
+                }
+            }
+            if fun.export.is_some() {
+                self.register(fun);
+            } else {
+                let name = fun.name.clone();
+                let existing = self.section(fun);
+                if existing != name {
+                    renames.insert(name, existing.to_string());
+                }
+            }
+        }
+    }
+
+    /// Returns the template path used to generate function names in this
+    /// scope (e.g. "includes/header").
+    fn base_name(&self) -> String {
+        self.name.base.clone()
+    }
+}
+
+#[derive(Debug)]
+struct StaticString {
+    name: String,
+    value: String,
+}
+
+impl StaticString {
+    /// Writes the raw content string global to the buffer.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            buf,
+            "static const char *{} = \"{}\";",
+            self.name, self.value
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    decl: String,
+    body: String,
+    export: Option<String>,
+    hint: usize,
+    trim: Trim,
+    constant: Option<Constant>,
+}
+
+/// The precomputed output of a template containing only static content,
+/// directly or through its partials, and the global holding it.
+#[derive(Debug)]
+struct Constant {
+    name: String,
+    text: String,
+}
+
+impl Function {
+    /// Writes the function definition to the buffer. A body of static
+    /// content never reads the context stack, so it's marked as used to keep
+    /// `-Wextra` builds quiet.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        writeln!(buf, "{} {{", self.decl)?;
+        if !self.body.contains("stack") {
+            writeln!(buf, "(void)stack;")?;
+        }
+        buf.write_all(self.body.as_bytes())?;
+        writeln!(buf, "}}\n")
+    }
+
+    /// Builds a conditional statement to call the function if the template
+    /// name matches the exported name, like "includes/header", or an alias
+    /// of it.
+    fn invoke_if(&self, export: &str, target: Target) -> String {
+        let mut path = String::new();
+        clean(export, &mut path);
+
+        if let Some(ref constant) = self.constant {
+            let copy = match target {
+                Target::Extension => "rb_str_new(",
+                Target::Library => "string_copy(",
+                Target::Addon => "string_value(env, ",
+            };
+            return format!(
+                "if (length == {len} && strncmp(ptr, \"{path}\", {len}) == 0) {{
+                     return {copy}{name}, {size});
+                 }}",
+                copy = copy,
+                len = export.len(),
+                path = path,
+                name = constant.name,
+                size = trimmed(&constant.text, self.trim).len()
+            );
+        }
+
+        let reserve = if self.hint > 0 {
+            let fail = match target {
+                Target::Extension => {
+                    "buffer_clear(buf);
+                     rb_raise(rb_eRuntimeError, \"Memory allocation failed\");"
+                }
+                Target::Library => {
+                    "buffer_destroy(buf);
+                     return NULL;"
+                }
+                Target::Addon => {
+                    "buffer_destroy(buf);
+                     napi_throw_error(env, NULL, \"Memory allocation failed\");
+                     return NULL;"
+                }
+            };
+            format!(
+                "if (!buffer_reserve(buf, {})) {{
+                     {}
+                 }}",
+                self.hint, fail
+            )
+        } else {
+            String::new()
+        };
+        let trim = match self.trim {
+            Trim::Keep => "",
+            Trim::Newline => "buffer_trim(buf, false);",
+            Trim::Whitespace => "buffer_trim(buf, true);",
+        };
+
+        format!(
+            "if (length == {len} && strncmp(ptr, \"{path}\", {len}) == 0) {{
+                 {reserve}
+                 {fun}(buf, &stack);
+                 {trim}
+             }}",
+            len = export.len(),
+            path = path,
+            reserve = reserve,
+            fun = self.name,
+            trim = trim
+        )
+    }
+
+    /// Builds a conditional statement to call the function as a dynamic
+    /// partial if the name found in the context matches the exported name
+    /// or an alias of it.
+    fn dispatch_if(&self, export: &str) -> String {
+        let mut path = String::new();
+        clean(export, &mut path);
+        format!(
+            "if (length == {len} && strncmp(ptr, \"{path}\", {len}) == 0) {{
+                 {fun}(buf, stack);
+             }}",
+            len = export.len(),
+            path = path,
+            fun = self.name
+        )
+    }
+}
+
+/// The declaration of the function rendering dynamic partials, generated
+/// with each program that uses them.
+const DYNAMIC_PARTIAL: &str =
+    "static void dynamic_partial(struct buffer *buf, const struct stack *stack, const struct path *path)";
+
+/// Walks a template's operations, translating them into the corresponding
+/// Ruby extension source code and registering the template's render
+/// function.
+///
+/// Sections are extracted into top-level functions paired with a function
+/// call at the location the section appeared in the template. Partials are
+/// similarly translated into a function call which is expected to be provided
+/// by another template in the final tree. Dynamic partials call a function
+/// dispatching on the template name found in the context.
+///
+/// Operations are written directly into the body of the enclosing function,
+/// so translating a template allocates one buffer per generated function
+/// rather than a string per operation. The operations are walked with an
+/// explicit stack of those bodies, so deeply nested sections can't overflow
+/// the call stack.
+fn transform(scope: &mut Scope, options: &Options, body: &[Op]) -> fmt::Result {
+    let mut stack = vec![Frame {
+        op: None,
+        ops: body.iter(),
+        body: String::new(),
+    }];
+
+    while let Some(frame) = stack.last_mut() {
+        match frame.ops.next() {
+            Some(op) => {
+                let child = enter(scope.next(), options, op, &mut frame.body)?;
+                if let Some(child) = child {
+                    stack.push(child);
+                }
+            }
+            None => {
+                let frame = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => leave(scope, options, frame, &mut parent.body)?,
+                    None => {
+                        let render = render(scope, frame.body);
+                        scope.register(render);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A section, or the template itself, whose operations are being translated
+/// into a function body.
+struct Frame<'a> {
+    op: Option<&'a Op>,
+    ops: slice::Iter<'a, Op>,
+    body: String,
+}
+
+/// Translates an operation without a body into the enclosing function's
+/// body, or returns a frame to collect the body of a section.
+fn enter<'a>(
+    scope: &mut Scope,
+    options: &Options,
+    op: &'a Op,
+    out: &mut String,
+) -> Result<Option<Frame<'a>>, fmt::Error> {
+    match *op {
+        Op::Section(_, ref body, _) | Op::Inverted(_, ref body) => {
+            return Ok(Some(Frame {
+                op: Some(op),
+                ops: body.iter(),
+                body: String::new(),
+            }));
+        }
+        Op::Partial(ref name, ref padding, ref arguments) => {
+            let name = options.name(name);
+            indent_push(padding, out);
+            // Each call's arguments frame is new, so it could never be found
+            // in the memo: partials with arguments always render directly.
+            if !arguments.is_empty() {
+                out.push_str("{ ");
+                arguments_ary(arguments, out)?;
+                write!(
+                    out,
+                    " partial_with(buf, stack, keys, paths, {}, render_{}); }}",
+                    arguments.len(),
+                    name.id()
+                )?;
+            } else if options.memoize.contains(&name.base) {
+                let mut literal = String::with_capacity(name.base.len());
+                clean(&name.base, &mut literal);
+                write!(
+                    out,
+                    "memoize(buf, stack, \"{}\", render_{});",
+                    literal,
+                    name.id()
+                )?;
+            } else {
+                write!(out, "render_{}(buf, stack);", name.id())?;
+            }
+            indent_pop(padding, out);
+        }
+        Op::DynamicPartial(ref path, ref padding) => {
+            scope.dynamic = true;
+            indent_push(padding, out);
+            out.push_str("{ ");
+            path_ary(path, out)?;
+            out.push_str(" dynamic_partial(buf, stack, &path); }");
+            indent_pop(padding, out);
+        }
+        Op::Content(ref text) => {
+            let mut value = String::with_capacity(text.len());
+            clean(text, &mut value);
+
+            let string = StaticString {
+                name: format!("content_{}", scope.next().name),
+                value,
+            };
+
+            let append = if scope.indent {
+                "append_content"
+            } else {
+                "buffer_append"
+            };
+            writeln!(
+                out,
+                "{}(buf, {}, {});",
+                append,
+                scope.content(string),
+                text.len()
+            )?;
+        }
+        Op::Append(ref path, escape) | Op::Require(ref path, escape) => {
+            let append = match *op {
+                Op::Require(..) => "append_required",
+                _ => "append_value",
+            };
+            out.push_str("{ ");
+            path_ary(path, out)?;
+            writeln!(out, " {}(buf, stack, &path, {}); }}", append, escape)?;
+        }
+        Op::Translate(ref key) => {
+            let mut value = String::with_capacity(key.len());
+            clean(key, &mut value);
+            writeln!(
+                out,
+                "append_translation(buf, \"{}\", {});",
+                value,
+                key.len()
+            )?;
+        }
+        Op::Filtered(ref path, ref filters) => {
+            let names: Vec<_> = filters.iter().map(|name| format!("\"{}\"", name)).collect();
+            write!(
+                out,
+                "{{ static const char *const filters[] = {{ {} }}; ",
+                names.join(", ")
+            )?;
+            path_ary(path, out)?;
+            writeln!(
+                out,
+                " append_filtered(buf, stack, &path, filters, {}); }}",
+                filters.len()
+            )?;
+        }
+    }
+
+    Ok(None)
+}
+
+/// Finishes a section once its body is translated, extracting it into a
+/// function called from the enclosing body.
+fn leave(scope: &mut Scope, options: &Options, frame: Frame, out: &mut String) -> fmt::Result {
+    match *frame.op.expect("section frame") {
+        Op::Section(ref path, _, ref source) => {
+            let fun = section(scope, frame.body);
+            out.push_str("{ ");
+            path_ary(path, out)?;
+            write!(out, " section(buf, stack, &path, {}", scope.section(fun))?;
+
+            // Ruby lambdas receive the section's raw source text.
+            if options.target == Target::Extension && !options.lambdas {
+                out.push_str(", NULL, 0");
+            } else if options.target == Target::Extension {
+                let mut value = String::with_capacity(source.len());
+                clean(source, &mut value);
+                let string = StaticString {
+                    name: format!("content_{}", scope.next().name),
+                    value,
+                };
+                write!(out, ", {}, {}", scope.content(string), source.len())?;
+            }
+            writeln!(out, "); }}")?;
+        }
+        Op::Inverted(ref path, _) => {
+            let fun = section(scope, frame.body);
+            out.push_str("{ ");
+            path_ary(path, out)?;
+            writeln!(
+                out,
+                " inverted(buf, stack, &path, {}); }}",
+                scope.section(fun)
+            )?;
+        }
+        _ => unreachable!("only sections have frames within a template"),
+    }
+
+    Ok(())
+}
+
+/// Wraps a template's translated operations in its private render function.
+fn render(scope: &Scope, body: String) -> Function {
+    let id = scope.name.id().to_string();
+    Function {
+        name: format!("render_{}", id),
+        decl: format!(
+            "static void render_{}(struct buffer *buf, const struct stack *stack)",
+            id
+        ),
+        body,
+        export: Some(scope.base_name()),
+        hint: 0,
+        trim: Trim::Keep,
+        constant: None,
+    }
+}
+
+/// Opens a block indenting the lines of a standalone partial's output by its
+/// padding, or nothing for a partial called inline.
+fn indent_push(padding: &Option<String>, out: &mut String) {
+    if let Some(ref padding) = *padding {
+        let mut text = String::with_capacity(padding.len());
+        clean(padding, &mut text);
+        out.push_str("{ struct indent indent; indent_push(buf, &indent, \"");
+        out.push_str(&text);
+        out.push_str(&format!("\", {}); ", padding.len()));
+    }
+}
+
+/// Closes the block opened by `indent_push` after the partial's call.
+fn indent_pop(padding: &Option<String>, out: &mut String) {
+    if padding.is_some() {
+        out.push_str(" indent_pop(buf, &indent); }");
+    }
+    out.push('\n');
+}
+
+/// Wraps a section's translated statements in a function to be called once
+/// for each item the section's path resolves to.
+fn section(scope: &mut Scope, body: String) -> Function {
+    let name = format!("section_{}", scope.next().name);
+    Function {
+        decl: format!(
+            "static void {}(struct buffer *buf, const struct stack *stack)",
+            name
+        ),
+        name,
+        body,
+        export: None,
+        hint: 0,
+        trim: Trim::Keep,
+        constant: None,
+    }
+}
+
+/// Translates a template containing only static content into a render
+/// function that appends its precomputed text in one call, for use as a
+/// partial, and that `render` returns directly without a render buffer.
+fn precompute(scope: &mut Scope, text: &str) -> Function {
+    let mut value = String::with_capacity(text.len());
+    clean(text, &mut value);
+    let string = StaticString {
+        name: format!("content_{}", scope.next().name),
+        value,
+    };
+    let name = scope.content(string).to_string();
+    let append = if scope.indent {
+        "append_content"
+    } else {
+        "buffer_append"
+    };
+
+    let body = format!("{}(buf, {}, {});\n", append, name, text.len());
+    Function {
+        constant: Some(Constant {
+            name,
+            text: text.to_string(),
+        }),
+        ..render(scope, body)
+    }
+}
+
+/// The `ruby`, `c`, and `node` backends, compiling templates into the C
+/// program selected by the options' target.
+#[derive(Debug)]
+pub struct Compiler {
+    pub options: Options,
+}
+
+impl Backend for Compiler {
+    fn name(&self) -> &str {
+        self.options.target.name()
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(link_with(templates, &self.options)?))
+    }
+}
+
+/// Transforms the AST of each parsed template into a source code tree
+/// and links each template together into a single executable program.
+pub fn link(templates: &[Template]) -> Result<Program, Error> {
+    link_with(templates, &Options::default())
+}
+
+/// Links the templates into a program, applying the code generation settings
+/// to each translated template.
+pub fn link_with(templates: &[Template], options: &Options) -> Result<Program, Error> {
+    let _span = info_span!("link", templates = templates.len()).entered();
+    let defined = Template::define_all(templates, &options.defines);
+    let templates = defined.as_deref().unwrap_or(templates);
+
+    let lowered = ir::lower(templates)?;
+    validate_aliases(templates, &options.aliases)?;
+    validate_trimmed(templates, &options.trim_only)?;
+    if options.target != Target::Extension {
+        validate_target(templates, options)?;
+    }
+
+    // The templates are merged in name order, so identical strings and
+    // sections shared between templates are named the same however they
+    // were loaded.
+    let mut functions = lowered.functions;
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    let functions = &functions[..];
+
+    // Template content is appended line by line when any partial is indented,
+    // since any template may be rendered as that partial.
+    let indent = functions.iter().any(|fun| ir::indented(&fun.body));
+    let translate = |fun: &ir::Function| translate(fun, functions, options, indent);
+    #[cfg(feature = "parallel")]
+    let scopes: Vec<_> = functions.par_iter().map(translate).collect();
+    #[cfg(not(feature = "parallel"))]
+    let scopes: Vec<_> = functions.iter().map(translate).collect();
+
+    let mut program = Program::new(options.clone());
+    for scope in scopes {
+        program.global.merge(scope);
+    }
+
+    info!(
+        functions = program.global.functions.len(),
+        strings = program.global.strings.len(),
+        "linked program"
+    );
+    Ok(program)
+}
+
+/// Returns the lines of the configured banner text and provenance notice
+/// written at the top of each generated file.
+fn banner(options: &Options) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    if let Some(ref banner) = options.banner {
+        lines.extend(
+            banner
+                .trim_end()
+                .lines()
+                .map(|line| line.trim_end().to_string()),
+        );
+    }
+
+    if let Some(ref provenance) = options.provenance {
+        let notice = match provenance.revision {
+            Some(ref revision) => format!(
+                "Generated by stache {} from {} at {}.",
+                VERSION, provenance.source, revision
+            ),
+            None => format!(
+                "Generated by stache {} from {}.",
+                VERSION, provenance.source
+            ),
+        };
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(notice);
+        lines.push(String::from(
+            "Do not edit this file; recompile the templates instead.",
+        ));
+    }
+    lines
+}
+
+/// Builds the JSON manifest describing the compiler, template source, and
+/// template names a program was generated from.
+fn manifest<'a>(provenance: &Provenance, templates: impl Iterator<Item = &'a String>) -> String {
+    let templates = templates.cloned().map(Value::String).collect();
+    let revision = provenance
+        .revision
+        .clone()
+        .map_or(Value::Null, Value::String);
+
+    let mut manifest = Map::new();
+    manifest.insert("generator".into(), Value::String("stache".into()));
+    manifest.insert("version".into(), Value::String(VERSION.into()));
+    manifest.insert("source".into(), Value::String(provenance.source.clone()));
+    manifest.insert("revision".into(), revision);
+    manifest.insert("templates".into(), Value::Array(templates));
+    Value::Object(manifest).to_string()
+}
+
+/// Ensures each alias names an existing template and can't be confused with
+/// another template or alias.
+fn validate_aliases(templates: &[Template], aliases: &[(String, String)]) -> Result<(), Error> {
+    let names: HashSet<_> = templates.iter().map(|t| &t.name).collect();
+    let mut seen = HashSet::new();
+    let mut errors = Vec::new();
+    for (alias, name) in aliases {
+        if !names.contains(name) {
+            errors.push(Error::UnknownTemplate {
+                name: name.clone(),
+                alias: alias.clone(),
+            });
+        }
+        if names.contains(alias) || !seen.insert(alias) {
+            errors.push(Error::AliasConflict {
+                alias: alias.clone(),
+            });
+        }
+    }
+    Error::all(errors)
+}
+
+/// Ensures each template whose output is trimmed exists, so a misspelled
+/// name isn't silently left untrimmed.
+fn validate_trimmed(templates: &[Template], trim_only: &[String]) -> Result<(), Error> {
+    let errors = trim_only
+        .iter()
+        .filter(|&name| !templates.iter().any(|t| &t.name == name))
+        .map(|name| Error::TemplateNotFound { name: name.clone() })
+        .collect();
+    Error::all(errors)
+}
+
+/// Ensures the options only use features the C library and Node.js addon
+/// runtimes provide, as they have no Ruby objects to cache output in, record
+/// lookups into, raise exceptions with, call as filter or translation hooks,
+/// or build partial argument frames from, and no list positions in their
+/// context frames.
+fn validate_target(templates: &[Template], options: &Options) -> Result<(), Error> {
+    let filtered = templates.iter().any(|t| !t.tree.filters().is_empty());
+    let arguments = templates.iter().any(|t| t.tree.has_arguments());
+    let translated = templates.iter().any(|t| !t.tree.translations().is_empty());
+    let metadata = templates.iter().any(|t| t.tree.has_metadata());
+    let strict = templates.iter().any(|t| t.tree.has_pragma(Pragma::Strict));
+    let unsupported = [
+        ("Filters are", filtered),
+        ("Translations are", translated),
+        ("Partial arguments are", arguments),
+        ("Loop metadata is", metadata),
+        ("Strict pragmas are", strict),
+        ("Memoized partials are", !options.memoize.is_empty()),
+        ("Ruby string buffers are", options.string_buffer),
+        ("Lookup tracing is", options.trace_lookups),
+        ("Iterating hashes is", options.iterate_hashes),
+        ("Lambdas are", options.lambdas),
+        ("Shared runtime files are", options.shared_runtime),
+        ("Ruby module names are", options.module.is_some()),
+        ("Ruby class names are", options.class.is_some()),
+        ("Init function names are", options.init.is_some()),
+        (
+            "Raising on missing values is",
+            options.missing == Missing::Raise,
+        ),
+    ];
+    let errors = unsupported
+        .iter()
+        .filter(|&&(_, used)| used)
+        .map(|&(option, _)| {
+            Error::Backend(format!(
+                "{} not supported by the {} target",
+                option,
+                options.target.name()
+            ))
+        })
+        .collect();
+    Error::all(errors)
+}
+
+/// Writes the `STACHE_API` definition for a runtime emitted into the template
+/// source, which may leave some runtime functions unused.
+fn emit_static_api(buf: &mut dyn Write) -> io::Result<()> {
+    writeln!(buf, "#if defined(__GNUC__)")?;
+    writeln!(buf, "#define STACHE_API static __attribute__((unused))")?;
+    writeln!(buf, "#else")?;
+    writeln!(buf, "#define STACHE_API static")?;
+    writeln!(buf, "#endif")
+}
+
+/// Translates a template's lowered render function into a new scope of its
+/// own.
+///
+/// Generated names are derived from the template's name and each
+/// operation's position in its body, so the result doesn't depend on which
+/// templates were translated before it.
+fn translate(
+    template: &ir::Function,
+    functions: &[ir::Function],
+    options: &Options,
+    indent: bool,
+) -> Scope {
+    let _span = debug_span!("transform", template = %template.name).entered();
+    let mut scope = Scope::new(options.name(&template.name));
+    scope.indent = indent;
+    match static_text(template, functions) {
+        Some(text) => {
+            let render = precompute(&mut scope, &text);
+            scope.register(render);
+        }
+        None => transform(&mut scope, options, &template.body).expect("write to string"),
+    }
+
+    let hint = static_size(template, functions);
+    for fun in scope.functions.iter_mut().filter(|f| f.export.is_some()) {
+        fun.hint = hint;
+        fun.trim = options.trim(&template.name);
+    }
+    debug!(
+        functions = scope.functions.len(),
+        static_size = hint,
+        constant = scope.functions.iter().any(|f| f.constant.is_some()),
+        "transformed template"
+    );
+    scope
+}
+
+/// Replaces each identifier in the generated code that's a key in the map
+/// with its value, leaving string literals untouched.
+fn rename(code: &str, names: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(code.len());
+    let mut chars = code.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c == '"' {
+            out.push(c);
+            let mut escaped = false;
+            for (_, next) in chars.by_ref() {
+                out.push(next);
+                if escaped {
+                    escaped = false;
+                } else if next == '\\' {
+                    escaped = true;
+                } else if next == '"' {
+                    break;
+                }
+            }
+        } else if c == '_' || c.is_ascii_alphanumeric() {
+            let mut end = start + c.len_utf8();
+            while let Some(&(i, next)) = chars.peek() {
+                if next != '_' && !next.is_ascii_alphanumeric() {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            let word = &code[start..end];
+            out.push_str(names.get(word).map_or(word, |name| name.as_str()));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Sums the length of the static content a template renders, including the
+/// content of its partials, to size the render buffer before rendering.
+///
+/// Section content is counted once, regardless of how many times the section
+/// is repeated at runtime, and a partial that includes itself is counted
+/// once.
+fn static_size(template: &ir::Function, functions: &[ir::Function]) -> usize {
+    // The operations left to count in each enclosing body, along with the
+    // name of the template whose body it is, or none for a section's.
+    let mut frames = vec![(template.body.iter(), Some(&template.name))];
+    let mut size = 0;
+    while let Some(frame) = frames.last_mut() {
+        let op = match frame.0.next() {
+            Some(op) => op,
+            None => {
+                frames.pop();
+                continue;
+            }
+        };
+        match *op {
+            Op::Section(_, ref body, _) | Op::Inverted(_, ref body) => {
+                frames.push((body.iter(), None));
+            }
+            Op::Partial(ref name, ..) => {
+                if frames.iter().any(|&(_, visiting)| visiting == Some(name)) {
+                    continue;
+                }
+                if let Some(partial) = functions.iter().find(|fun| &fun.name == name) {
+                    frames.push((partial.body.iter(), Some(name)));
+                }
+            }
+            Op::Content(ref text) => size += text.len(),
+            _ => (),
+        }
+    }
+    size
+}
+
+/// Concatenates the text a template renders if it contains only static
+/// content and comments, directly or through its partials. Templates with
+/// tags that read the context, or that include themselves, have no static
+/// text.
+fn static_text(template: &ir::Function, functions: &[ir::Function]) -> Option<String> {
+    // The operations left in each template being concatenated, with its
+    // name, the padding of the partial tag including it, and its text.
+    let mut frames: Vec<(slice::Iter<Op>, &String, &Option<String>, String)> =
+        vec![(template.body.iter(), &template.name, &None, String::new())];
+    loop {
+        let frame = frames.last_mut().expect("template frame");
+        let op = match frame.0.next() {
+            Some(op) => op,
+            None => {
+                let (_, _, padding, text) = frames.pop().expect("template frame");
+                let parent = match frames.last_mut() {
+                    Some(parent) => &mut parent.3,
+                    None => return Some(text),
+                };
+                match *padding {
+                    Some(ref padding) => parent.push_str(&indent_lines(&text, padding)),
+                    None => parent.push_str(&text),
+                }
+                continue;
+            }
+        };
+        match *op {
+            Op::Content(ref content) => frame.3.push_str(content),
+            Op::Partial(ref name, ref padding, _) => {
+                if frames.iter().any(|frame| frame.1 == name) {
+                    return None;
+                }
+                let partial = functions.iter().find(|fun| &fun.name == name)?;
+                frames.push((partial.body.iter(), name, padding, String::new()));
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Adds a standalone partial's indentation to the start of each line of its
+/// static text.
+fn indent_lines(text: &str, indent: &str) -> String {
+    text.split_inclusive('\n')
+        .map(|line| format!("{}{}", indent, line))
+        .collect()
+}
+
+/// Removes the trailing whitespace from a constant template's text, as
+/// `buffer_trim` does from rendered output.
+fn trimmed(text: &str, trim: Trim) -> &str {
+    match trim {
+        Trim::Keep => text,
+        Trim::Newline => match text.strip_suffix('\n') {
+            Some(text) => text.strip_suffix('\r').unwrap_or(text),
+            None => text,
+        },
+        Trim::Whitespace => text.trim_end_matches(&[' ', '\t', '\r', '\n', '\x0c', '\x0b'][..]),
+    }
+}
+
+/// Replaces string literal characters considered invalid inside a cstr with
+/// their escaped counterparts, appending the escaped text to the buffer.
+///
+/// Printable ASCII is copied as is, apart from `?`, which is escaped so
+/// sequences like `??=` aren't read as trigraphs. Other control characters
+/// and each byte of multi-byte UTF-8 characters are written as three digit
+/// octal escapes, which, unlike hex escapes, never absorb a following digit.
+/// The literal's bytes then match the template's exactly.
+fn clean(text: &str, out: &mut String) {
+    for &byte in text.as_bytes() {
+        match byte {
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            b'?' => out.push_str("\\?"),
+            b'\r' => out.push_str("\\r"),
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            0x0c => out.push_str("\\f"),
+            0x20..=0x7e => out.push(byte as char),
+            _ => {
+                out.push('\\');
+                for shift in &[6, 3, 0] {
+                    out.push((b'0' + (byte >> shift & 0o7)) as char);
+                }
+            }
+        }
+    }
+}
+
+/// Creates a Ruby constant name from a bundle identifier:
+/// `admin_panel -> AdminPanel`.
+fn constant(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Transforms a Mustache variable key path into the source code to build a
+/// Ruby array. At runtime, each key in the array is recursively processed to
+/// find the replacement text for a Mustache expression.
+fn path_ary(path: &Path, out: &mut String) -> fmt::Result {
+    write!(
+        out,
+        "static const struct path path = {{ .keys = {{ {} }}, .length = {} }};",
+        literals(&path.keys).join(", "),
+        path.keys.len()
+    )
+}
+
+/// Formats each key as a C string literal.
+fn literals(keys: &[String]) -> Vec<String> {
+    keys.iter()
+        .map(|key| {
+            let mut literal = String::new();
+            clean(key, &mut literal);
+            format!("\"{}\"", literal)
+        })
+        .collect()
+}
+
+/// Writes the static arrays of a partial's argument keys and the paths of
+/// their values, in the same order.
+fn arguments_ary(arguments: &[(String, Path)], out: &mut String) -> fmt::Result {
+    let keys: Vec<_> = arguments.iter().map(|(key, _)| key.clone()).collect();
+    write!(
+        out,
+        "static const char *const keys[] = {{ {} }}; ",
+        literals(&keys).join(", ")
+    )?;
+    out.push_str("static const struct path paths[] = { ");
+    for (i, (_, path)) in arguments.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write!(
+            out,
+            "{{ .keys = {{ {} }}, .length = {} }}",
+            literals(&path.keys).join(", "),
+            path.keys.len()
+        )?;
+    }
+    out.push_str(" };");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::runtime::{ADDON_RUNTIME, LIBRARY_RUNTIME, RUNTIME};
+    use super::{
+        clean, link, link_with, rename, transform, Compiler, Missing, Options, Provenance, Scope,
+        Target, Trim, VERSION,
+    };
+    use stache_core::fixtures::Set;
+    use stache_core::ir::{lower_tree, Op};
+    use stache_core::{Backend, Compile, Error, LoadOptions, Name, Statement, Template};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn validates_valid_partial_reference() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/machines/robots.mustache");
+        let tree = Statement::Partial(String::from("machines/robot"), None, Vec::new());
+        let master = Template::new(&base, path, tree);
+
+        let path = PathBuf::from("app/templates/machines/robot.mustache");
+        let tree = Statement::Content(String::from("hubot"));
+        let detail = Template::new(&base, path, tree);
+
+        let templates = vec![master, detail];
+        match link(&templates) {
+            Ok(_) => (),
+            Err(e) => panic!("Must link valid partials: {}", e),
+        }
+    }
+
+    #[test]
+    fn validates_invalid_partial_reference() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/machines/robots.mustache");
+        let tree = Statement::Partial(String::from("machines/unknown"), None, Vec::new());
+
+        let master = Template::new(&base, path, tree);
+
+        let path = PathBuf::from("app/templates/machines/robot.mustache");
+        let tree = Statement::Content(String::from("hubot"));
+        let detail = Template::new(&base, path, tree);
+
+        let templates = vec![master, detail];
+        match link(&templates) {
+            Err(Error::UnknownPartial { ref name, ref path }) => {
+                assert_eq!("machines/unknown", name);
+                assert_eq!(Path::new("app/templates/machines/robots.mustache"), path);
+            }
+            _ => panic!("Must enforce partial references"),
+        }
+    }
+
+    #[test]
+    fn validates_unique_template_names() {
+        let base = PathBuf::from("app/templates");
+        let tree = || Statement::Content(String::from("hubot"));
+        let first = PathBuf::from("app/templates/robot.mustache");
+        let second = PathBuf::from("app/templates/robot.html");
+        let templates = vec![
+            Template::new(&base, first, tree()),
+            Template::new(&base, second, tree()),
+        ];
+
+        match link(&templates) {
+            Err(Error::DuplicateTemplate {
+                ref name,
+                ref first,
+                ref second,
+            }) => {
+                assert_eq!("robot", name);
+                assert_eq!(Path::new("app/templates/robot.mustache"), first);
+                assert_eq!(Path::new("app/templates/robot.html"), second);
+            }
+            _ => panic!("Must enforce unique template names"),
+        }
+    }
+
+    #[test]
+    fn transforms_tree_into_functions() {
+        let text = "
+            {{> includes/header }}
+            <ul>
+                {{# robots}}
+                    <li>{{ name.first }}</li>
+                {{/ robots}}
+                {{^ robots}}
+                    {{! else clause }}
+                    No robots
+                {{/ robots}}
+            </ul>
+            {{> includes/footer }}
+            {{{ unescaped.html }}}
+        ";
+
+        match Statement::parse(text) {
+            Ok(tree) => {
+                let mut scope = Scope::new(Name::new("machines/robot"));
+                transform(&mut scope, &Options::default(), &lower_tree(&tree)).unwrap();
+
+                // One for each section, private render, and exported template function.
+                let names: Vec<_> = scope.functions.iter().map(|fun| &fun.name).collect();
+                assert_eq!(
+                    vec![
+                        "section_machines_robot_12",
+                        "section_machines_robot_16",
+                        "render_machines_robot",
+                    ],
+                    names
+                );
+
+                // Single exported function name.
+                let exports: Vec<_> = scope
+                    .functions
+                    .iter()
+                    .filter_map(|fun| fun.export.as_ref())
+                    .collect();
+                assert_eq!(vec!["machines/robot"], exports);
+            }
+            Err(e) => panic!("Failed to parse tree: {}", e),
+        }
+    }
+
+    #[test]
+    fn transforms_deeply_nested_sections() {
+        let depth = 5000;
+        let text = format!("{}x{}", "{{#a}}".repeat(depth), "{{/a}}".repeat(depth));
+        let options = LoadOptions {
+            max_depth: depth,
+            ..LoadOptions::default()
+        };
+        let tree = Statement::parse_with(&text, &options).unwrap();
+
+        let mut scope = Scope::new(Name::new("deep"));
+        transform(&mut scope, &Options::default(), &lower_tree(&tree)).unwrap();
+        assert_eq!(depth + 1, scope.functions.len());
+    }
+
+    #[test]
+    fn transforms_extended_identifiers() {
+        let options = LoadOptions {
+            extended_identifiers: true,
+            ..LoadOptions::default()
+        };
+        let tree = Statement::parse_with("{{ $schema.prix?? }}", &options).unwrap();
+
+        let mut scope = Scope::new(Name::new("product"));
+        transform(&mut scope, &Options::default(), &lower_tree(&tree)).unwrap();
+        let render = &scope.functions[0].body;
+        assert_eq!(
+            "{ static const struct path path = { .keys = { \"$schema\", \"prix\\?\\?\" }, .length = 2 }; append_value(buf, stack, &path, true); }\n",
+            render
+        );
+    }
+
+    #[test]
+    fn transforms_pragmas() {
+        let tree = Statement::parse("{{%STRICT}}{{%UNESCAPED}}{{name}}").unwrap();
+
+        let mut scope = Scope::new(Name::new("robot"));
+        transform(&mut scope, &Options::default(), &lower_tree(&tree)).unwrap();
+        let render = &scope.functions[0].body;
+        assert_eq!(
+            "{ static const struct path path = { .keys = { \"name\" }, .length = 1 }; append_required(buf, stack, &path, false); }\n",
+            render
+        );
+    }
+
+    #[test]
+    fn transforms_translations() {
+        let options = LoadOptions {
+            translations: true,
+            ..LoadOptions::default()
+        };
+        let tree = Statement::parse_with("{{t \"checkout.title\"}}", &options).unwrap();
+
+        let mut scope = Scope::new(Name::new("checkout"));
+        transform(&mut scope, &Options::default(), &lower_tree(&tree)).unwrap();
+        let render = &scope.functions[0].body;
+        assert_eq!("append_translation(buf, \"checkout.title\", 14);\n", render);
+    }
+
+    #[test]
+    fn transforms_memoized_partial() {
+        let ops = vec![Op::Partial(
+            String::from("includes/header"),
+            None,
+            Vec::new(),
+        )];
+        let options = Options {
+            memoize: vec![String::from("includes/header")],
+            ..Options::default()
+        };
+
+        let mut scope = Scope::new(Name::new("robots"));
+        transform(&mut scope, &options, &ops).unwrap();
+        assert_eq!(
+            "memoize(buf, stack, \"includes/header\", render_includes_header);\n",
+            scope.functions[0].body
+        );
+
+        transform(&mut scope, &Options::default(), &ops).unwrap();
+        assert_eq!(
+            "render_includes_header(buf, stack);\n",
+            scope.functions[1].body
+        );
+
+        let ops = vec![Op::Partial(String::from("say \"hi\"\\"), None, Vec::new())];
+        let options = Options {
+            memoize: vec![String::from("say \"hi\"\\")],
+            ..Options::default()
+        };
+        transform(&mut scope, &options, &ops).unwrap();
+        assert!(scope.functions[2]
+            .body
+            .starts_with("memoize(buf, stack, \"say \\\"hi\\\"\\\\\", render_"));
+    }
+
+    #[test]
+    fn escapes_string_literals() {
+        let mut out = String::new();
+        clean("<a href=\"\\\">\r\n", &mut out);
+        assert_eq!("<a href=\\\"\\\\\\\">\\r\\n", out);
+
+        let mut out = String::new();
+        clean("\tcafé\x0c??=\x00\x7f1", &mut out);
+        assert_eq!("\\tcaf\\303\\251\\f\\?\\?=\\000\\1771", out);
+    }
+
+    #[test]
+    fn writes_statements_into_function_body() {
+        let tree = Statement::parse("<b>{{ name.first }}</b>").unwrap();
+        let mut scope = Scope::new(Name::new("robot"));
+        transform(&mut scope, &Options::default(), &lower_tree(&tree)).unwrap();
+
+        let expected = "buffer_append(buf, content_robot_2, 3);
+{ static const struct path path = { .keys = { \"name\", \"first\" }, .length = 2 }; append_value(buf, stack, &path, true); }
+buffer_append(buf, content_robot_5, 4);
+";
+        assert_eq!(expected, scope.functions[0].body);
+    }
+
+    #[test]
+    fn passes_section_source_to_lambdas() {
+        let tree = Statement::parse("{{#wrap}}<b>{{ name }}</b>{{/wrap}}").unwrap();
+        let mut scope = Scope::new(Name::new("robot"));
+        transform(&mut scope, &Options::default(), &lower_tree(&tree)).unwrap();
+
+        let body = &scope.functions.last().unwrap().body;
+        assert!(body.contains("section(buf, stack, &path, section_robot_7, NULL, 0); }"));
+        assert_eq!(2, scope.strings.len());
+
+        let options = Options {
+            lambdas: true,
+            ..Options::default()
+        };
+        let mut scope = Scope::new(Name::new("robot"));
+        transform(&mut scope, &options, &lower_tree(&tree)).unwrap();
+
+        let body = &scope.functions.last().unwrap().body;
+        assert!(
+            body.contains("section(buf, stack, &path, section_robot_7, content_robot_8, 17); }")
+        );
+        assert_eq!("<b>{{ name }}</b>", scope.strings[2].value);
+
+        let options = Options {
+            target: Target::Library,
+            ..Options::default()
+        };
+        let mut scope = Scope::new(Name::new("robot"));
+        transform(&mut scope, &options, &lower_tree(&tree)).unwrap();
+
+        let body = &scope.functions.last().unwrap().body;
+        assert!(body.contains("section(buf, stack, &path, section_robot_7); }"));
+    }
+
+    #[test]
+    fn dispatches_dynamic_partials() {
+        let base = PathBuf::from("app/templates");
+        let template = |name: &str, text: &str| {
+            let path = base.join(name);
+            Template::new(&base, path, Statement::parse(text).unwrap())
+        };
+        let templates = vec![
+            template("page.mustache", "{{#items}}{{>*kind}}{{/items}}"),
+            template("robot.mustache", "{{name}}"),
+        ];
+
+        let program = link(&templates).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("dynamic_partial(buf, stack, &path); }"));
+        assert!(source.contains("char ptr[6];"));
+        assert!(source.contains("if (length == 5 && strncmp(ptr, \"robot\", 5) == 0) {"));
+
+        let templates = vec![template("robot.mustache", "{{name}}")];
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(!source.contains("dynamic_partial"));
+    }
+
+    #[test]
+    fn indents_standalone_partials() {
+        let base = PathBuf::from("app/templates");
+        let template = |name: &str, text: &str| {
+            let path = base.join(name);
+            Template::new(&base, path, Statement::parse(text).unwrap())
+        };
+        let templates = vec![
+            template("list.mustache", "<ul>\n\t{{> item}}\n</ul>\n"),
+            template("item.mustache", "<li>{{name}}</li>\n"),
+            template("static.mustache", "  {{> text}}\n"),
+            template("text.mustache", "a\nb\n"),
+        ];
+
+        let program = link(&templates).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains(
+            "{ struct indent indent; indent_push(buf, &indent, \"\\t\", 1); \
+             render_item(buf, stack); indent_pop(buf, &indent); }"
+        ));
+        assert!(source.contains("append_content(buf, content_item_2, 4);"));
+        assert!(source.contains("\"  a\\n  b\\n\""));
+
+        let templates = vec![template("item.mustache", "<li>{{name}}</li>\n")];
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(!source.contains("append_content(buf"));
+    }
+
+    #[test]
+    fn calls_filter_helpers() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let tree = Statement::parse("{{ name.first | upcase | truncate }}").unwrap();
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(source.contains(
+            "{ static const char *const filters[] = { \"upcase\", \"truncate\" }; static const struct path path = { .keys = { \"name\", \"first\" }, .length = 2 }; append_filtered(buf, stack, &path, filters, 2); }"
+        ));
+        assert!(source.contains("rb_define_method(Templates, \"register_helper\""));
+    }
+
+    #[test]
+    fn pushes_partial_arguments() {
+        let base = PathBuf::from("app/templates");
+        let template = |name: &str, text: &str| {
+            let path = base.join(name);
+            Template::new(&base, path, Statement::parse(text).unwrap())
+        };
+        let templates = vec![
+            template("robot.mustache", "{{> card title=user.name body=. }}"),
+            template("card.mustache", "{{ title }}"),
+        ];
+
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(source.contains(
+            "{ static const char *const keys[] = { \"title\", \"body\" }; static const struct path paths[] = { { .keys = { \"user\", \"name\" }, .length = 2 }, { .keys = { \".\" }, .length = 1 } }; partial_with(buf, stack, keys, paths, 2, render_card); }"
+        ));
+    }
+
+    #[test]
+    fn inlines_parent_templates() {
+        let base = PathBuf::from("app/templates");
+        let template = |name: &str, text: &str| {
+            let path = base.join(name);
+            Template::new(&base, path, Statement::parse(text).unwrap())
+        };
+        let templates = vec![
+            template(
+                "layout.mustache",
+                "<title>{{$title}}Robots{{/title}}</title>",
+            ),
+            template(
+                "robot.mustache",
+                "{{<layout}}{{$title}}{{name}}{{/title}}{{/layout}}",
+            ),
+        ];
+
+        let program = link(&templates).unwrap();
+        let robot = program
+            .global
+            .functions
+            .iter()
+            .find(|fun| fun.name == "render_robot")
+            .unwrap();
+        assert!(robot
+            .body
+            .contains("append_value(buf, stack, &path, true);"));
+        assert!(!robot.body.contains("render_layout"));
+    }
+
+    #[test]
+    fn shares_identical_sections() {
+        let base = PathBuf::from("app/templates");
+        let text =
+            "{{#robots}}<li>{{ name }}</li>{{/robots}}{{^robots}}<li>{{ name }}</li>{{/robots}}";
+
+        let path = PathBuf::from("app/templates/robots.mustache");
+        let master = Template::new(&base, path, Statement::parse(text).unwrap());
+
+        let path = PathBuf::from("app/templates/widgets.mustache");
+        let tree =
+            Statement::parse("<ul>{{#machines}}<li>{{ name }}</li>{{/machines}}</ul>").unwrap();
+        let detail = Template::new(&base, path, tree);
+
+        let program = link(&[detail, master]).unwrap();
+        let names: Vec<_> = program
+            .global
+            .functions
+            .iter()
+            .map(|fun| &fun.name)
+            .collect();
+        assert_eq!(
+            vec!["section_robots_7", "render_robots", "render_widgets"],
+            names
+        );
+
+        let strings: Vec<_> = program
+            .global
+            .strings
+            .iter()
+            .map(|string| &string.value)
+            .collect();
+        assert_eq!(vec!["<li>", "</li>", "<ul>", "</ul>"], strings);
+    }
+
+    #[test]
+    fn emits_identical_code_for_any_template_order() {
+        let base = PathBuf::from("app/templates");
+        let template = |name: &str, text: &str| {
+            let path = base.join(format!("{}.mustache", name));
+            Template::new(&base, path, Statement::parse(text).unwrap())
+        };
+        let emit = |templates: &[Template]| {
+            let mut buf = Vec::new();
+            link(templates).unwrap().emit(&mut buf).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+
+        let text = "{{#robots}}<li>{{ name }}</li>{{/robots}}";
+        let forward = emit(&[template("a", text), template("b", text)]);
+        let reverse = emit(&[template("b", text), template("a", text)]);
+        assert_eq!(forward, reverse);
+    }
+
+    #[test]
+    fn reserves_runtime_symbols() {
+        let functions = RUNTIME
+            .lines()
+            .chain(LIBRARY_RUNTIME.lines())
+            .chain(ADDON_RUNTIME.lines())
+            .filter(|line| line.starts_with("static ") || line.starts_with("STACHE_API "))
+            .filter(|line| line.contains('('))
+            .filter_map(|line| line.split('(').next())
+            .filter_map(|decl| decl.rsplit([' ', '*']).next())
+            .collect::<Vec<_>>();
+        assert!(functions.contains(&"buffer_append"));
+
+        for symbol in functions {
+            assert_ne!(symbol, Name::new(symbol).id(), "{} is not reserved", symbol);
+        }
+    }
+
+    #[test]
+    fn reserves_static_content_size() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robots.mustache");
+        let tree = Statement::parse("<ul>{{#robots}}{{> robot}}{{/robots}}</ul>").unwrap();
+        let master = Template::new(&base, path, tree);
+
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let tree = Statement::parse("<li>{{ name }}</li>").unwrap();
+        let detail = Template::new(&base, path, tree);
+
+        let options = Options {
+            capacity: 4096,
+            growth: 150,
+            ..Options::default()
+        };
+        let program = link_with(&[master, detail], &options).unwrap();
+
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("#define STACHE_BUFFER_CAPACITY 4096"));
+        assert!(source.contains("#define STACHE_BUFFER_GROWTH 150"));
+        assert!(source.contains("if (!buffer_reserve(buf, 18)) {"));
+        assert!(source.contains("if (!buffer_reserve(buf, 9)) {"));
+    }
+
+    #[test]
+    fn shortens_symbol_names() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/admin/settings/billing.mustache");
+        let tree = Statement::parse("{{#invoices}}<li>{{/invoices}}{{> robot}}").unwrap();
+        let master = Template::new(&base, path, tree);
+
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let detail = Template::new(&base, path, Statement::Content(String::from("hubot")));
+
+        let options = Options {
+            short_names: true,
+            ..Options::default()
+        };
+        let program = link_with(&[master, detail], &options).unwrap();
+
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        let billing = Name::short("admin/settings/billing");
+        let robot = Name::short("robot");
+        assert!(!source.contains("admin_settings_billing"));
+        assert!(source.contains(&format!(
+            " * render_{}: admin/settings/billing",
+            billing.id()
+        )));
+        assert!(source.contains(&format!("static void section_{}_", billing.id())));
+        assert!(source.contains(&format!("render_{}(buf, stack);", robot.id())));
+        assert!(source.contains("strncmp(ptr, \"admin/settings/billing\", 22)"));
+    }
+
+    #[test]
+    fn defines_sanitizer_build() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let tree = Statement::Content(String::from("hubot"));
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        assert!(!String::from_utf8(buf)
+            .unwrap()
+            .contains("#define STACHE_SANITIZE"));
+
+        let options = Options {
+            sanitize: true,
+            ..Options::default()
+        };
+        let mut buf = Vec::new();
+        link_with(&templates, &options)
+            .unwrap()
+            .emit(&mut buf)
+            .unwrap();
+        assert!(String::from_utf8(buf)
+            .unwrap()
+            .contains("#define STACHE_SANITIZE 1"));
+    }
+
+    #[test]
+    fn defines_string_buffer() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let tree = Statement::parse("{{name}}").unwrap();
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(!source.contains("#define STACHE_STRING_BUFFER"));
+        assert!(source.contains("return buffer_string(buf);"));
+
+        let options = Options {
+            string_buffer: true,
+            ..Options::default()
+        };
+        let mut buf = Vec::new();
+        link_with(&templates, &options)
+            .unwrap()
+            .emit(&mut buf)
+            .unwrap();
+        assert!(String::from_utf8(buf)
+            .unwrap()
+            .contains("#define STACHE_STRING_BUFFER 1"));
+    }
+
+    #[test]
+    fn records_provenance() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let tree = Statement::parse("{{name}}").unwrap();
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(!source.starts_with("/*"));
+        assert!(!source.contains("MANIFEST"));
+
+        let options = Options {
+            banner: Some(String::from("Copyright GitHub\nMIT License */\n")),
+            provenance: Some(Provenance {
+                source: String::from("app/templates"),
+                revision: Some(String::from("4b825dc")),
+            }),
+            ..Options::default()
+        };
+        let mut buf = Vec::new();
+        link_with(&templates, &options)
+            .unwrap()
+            .emit(&mut buf)
+            .unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        let banner = format!(
+            "/*
+ * Copyright GitHub
+ * MIT License * /
+ *
+ * Generated by stache {} from app/templates at 4b825dc.
+ * Do not edit this file; recompile the templates instead.
+ */
+",
+            VERSION
+        );
+        assert!(source.starts_with(&banner));
+
+        let manifest = format!(
+            "{{\\\"generator\\\":\\\"stache\\\",\\\"revision\\\":\\\"4b825dc\\\",\
+             \\\"source\\\":\\\"app/templates\\\",\\\"templates\\\":[\\\"robot\\\"],\
+             \\\"version\\\":\\\"{}\\\"}}",
+            VERSION
+        );
+        assert!(source.contains(&format!(
+            "rb_define_const(namespace, \"MANIFEST\", rb_obj_freeze(rb_str_new_cstr(\"{}\")));",
+            manifest
+        )));
+    }
+
+    #[test]
+    fn defines_lookup_tracing() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let tree = Statement::Content(String::from("hubot"));
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        assert!(!String::from_utf8(buf)
+            .unwrap()
+            .contains("#define STACHE_TRACE"));
+
+        let options = Options {
+            trace_lookups: true,
+            ..Options::default()
+        };
+        let mut buf = Vec::new();
+        link_with(&templates, &options)
+            .unwrap()
+            .emit(&mut buf)
+            .unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(source.contains("#define STACHE_TRACE 1"));
+        assert!(source.contains("\"render_trace\", templates_render_trace, 2"));
+    }
+
+    #[test]
+    fn defines_hash_iteration() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/settings.mustache");
+        let tree = Statement::parse("{{#settings}}{{@key}}={{.}}{{/settings}}").unwrap();
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let mut buf = Vec::new();
+        link(&templates).unwrap().emit(&mut buf).unwrap();
+        assert!(!String::from_utf8(buf)
+            .unwrap()
+            .contains("#define STACHE_ITERATE_HASHES"));
+
+        let options = Options {
+            iterate_hashes: true,
+            ..Options::default()
+        };
+        let mut buf = Vec::new();
+        link_with(&templates, &options)
+            .unwrap()
+            .emit(&mut buf)
+            .unwrap();
+        assert!(String::from_utf8(buf)
+            .unwrap()
+            .contains("#define STACHE_ITERATE_HASHES 1"));
+    }
+
+    #[test]
+    fn shares_runtime_files() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let tree = Statement::Content(String::from("hubot"));
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let options = Options {
+            bundle: Some(String::from("admin")),
+            shared_runtime: true,
+            ..Options::default()
+        };
+        let program = link_with(&templates, &options).unwrap();
+
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(source.starts_with("#include \"admin_runtime.h\"\n"));
+        assert!(!source.contains("struct buffer {"));
+        assert!(source.contains("STACHE_API VALUE render("));
+
+        let mut buf = Vec::new();
+        program.emit_runtime_header(&mut buf).unwrap();
+        let header = String::from_utf8(buf).unwrap();
+        assert!(header.starts_with("#ifndef ADMIN_RUNTIME_H\n#define ADMIN_RUNTIME_H\n"));
+        assert!(header.contains("#define STACHE_BUFFER_TYPE \"stache-admin-buffer\""));
+        assert!(header.contains("struct buffer {"));
+
+        let mut buf = Vec::new();
+        program.emit_runtime_source(&mut buf).unwrap();
+        let runtime = String::from_utf8(buf).unwrap();
+        assert!(runtime.starts_with("#include \"admin_runtime.h\"\n"));
+        assert!(runtime.contains("STACHE_API void init_templates(VALUE namespace) {"));
+    }
+
+    #[test]
+    fn builds_c_library() {
+        let base = PathBuf::from("app/templates");
+        let templates = vec![
+            Template::new(
+                &base,
+                PathBuf::from("app/templates/robot.mustache"),
+                Statement::parse("{{name}}").unwrap(),
+            ),
+            Template::new(
+                &base,
+                PathBuf::from("app/templates/footer.mustache"),
+                Statement::Content(String::from("</html>")),
+            ),
+        ];
+
+        let options = Options {
+            target: Target::Library,
+            bundle: Some(String::from("admin")),
+            ..Options::default()
+        };
+        let program = link_with(&templates, &options).unwrap();
+
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(source.starts_with("#include \"admin.h\"\n#define STACHE_LIBRARY 1\n"));
+        assert!(!source.contains("ruby.h"));
+        assert!(!source.contains("VALUE"));
+        assert!(
+            source.contains("char *admin_render(const char *name, const struct ctx *context) {")
+        );
+        assert!(source.contains("return string_copy(content_footer_1, 7);"));
+        assert!(source.contains("return buffer_finish(buf);"));
+
+        let mut buf = Vec::new();
+        program.emit_header(&mut buf).unwrap();
+        let header = String::from_utf8(buf).unwrap();
+        assert!(header.starts_with("#ifndef ADMIN_H\n#define ADMIN_H\n"));
+        assert!(header.contains("struct ctx {"));
+        assert!(header.contains("char *admin_render(const char *name, const struct ctx *context);"));
+    }
+
+    #[test]
+    fn links_through_backend_by_target_name() {
+        let base = PathBuf::from("app/templates");
+        let templates = vec![Template::new(
+            &base,
+            PathBuf::from("app/templates/robot.mustache"),
+            Statement::Content(String::from("hubot")),
+        )];
+
+        let compiler = Compiler {
+            options: Options {
+                target: Target::Library,
+                ..Options::default()
+            },
+        };
+        assert_eq!("c", compiler.name());
+
+        let program = compiler.link(&templates).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(source.starts_with("#include \"stache.h\"\n"));
+    }
+
+    #[test]
+    fn builds_node_addon() {
+        let base = PathBuf::from("app/templates");
+        let templates = vec![
+            Template::new(
+                &base,
+                PathBuf::from("app/templates/robot.mustache"),
+                Statement::parse("{{name}}").unwrap(),
+            ),
+            Template::new(
+                &base,
+                PathBuf::from("app/templates/footer.mustache"),
+                Statement::Content(String::from("</html>")),
+            ),
+        ];
+
+        let options = Options {
+            target: Target::Addon,
+            aliases: vec![(String::from("robots/old"), String::from("robot"))],
+            ..Options::default()
+        };
+        let mut buf = Vec::new();
+        link_with(&templates, &options)
+            .unwrap()
+            .emit(&mut buf)
+            .unwrap();
+        let source = String::from_utf8(buf).unwrap();
+        assert!(source.starts_with("#define STACHE_ADDON 1\n"));
+        assert!(source.contains("#include <node_api.h>"));
+        assert!(!source.contains("ruby.h"));
+        assert!(source.contains("char ptr[11];"));
+        assert!(source.contains("return string_value(env, content_footer_1, 7);"));
+        assert!(source.contains("return buffer_result(buf);"));
+        assert!(source.contains("NAPI_MODULE_INIT() {"));
+    }
+
+    #[test]
+    fn rejects_unsupported_library_options() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let source = "{{name | upcase}}{{> robot title=name }}{{#a}}{{@index}}{{/a}}";
+        let tree = Statement::parse(source).unwrap();
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let options = Options {
+            target: Target::Library,
+            memoize: vec![String::from("robot")],
+            missing: Missing::Raise,
+            ..Options::default()
+        };
+        match link_with(&templates, &options) {
+            Err(e @ Error::Multiple(_)) => assert_eq!(5, e.errors().len()),
+            other => panic!(
+                "expected unsupported option errors: {:?}",
+                other.map(|_| ())
+            ),
+        }
+    }
+
+    #[test]
+    fn names_extension_after_bundle() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let tree = Statement::Content(String::from("hubot"));
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let options = Options {
+            bundle: Some(String::from("admin_panel")),
+            ..Options::default()
+        };
+        let program = link_with(&templates, &options).unwrap();
+
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("#define STACHE_BUFFER_TYPE \"stache-admin_panel-buffer\""));
+        assert!(source.contains("void Init_admin_panel() {"));
+        assert!(source.contains("rb_define_module_under(namespace, \"AdminPanel\");"));
+        assert!(!source.contains("Init_stache"));
+    }
+
+    #[test]
+    fn names_module_class_and_init() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robot.mustache");
+        let tree = Statement::Content(String::from("hubot"));
+        let templates = vec![Template::new(&base, path, tree)];
+
+        let options = Options {
+            bundle: Some(String::from("admin")),
+            module: Some(String::from("Admin::Views")),
+            class: Some(String::from("Renderer")),
+            init: Some(String::from("admin_views")),
+            ..Options::default()
+        };
+        let program = link_with(&templates, &options).unwrap();
+
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("#define STACHE_CLASS \"Renderer\""));
+        assert!(source.contains("void Init_admin_views() {"));
+        assert!(source.contains(
+            "namespace = rb_define_module_under(namespace, \"Admin\");\nnamespace = rb_define_module_under(namespace, \"Views\");\n"
+        ));
+        assert!(!source.contains("\"Stache\""));
+
+        let options = Options {
+            target: Target::Library,
+            class: Some(String::from("Renderer")),
+            ..Options::default()
+        };
+        match link_with(&templates, &options) {
+            Err(Error::Backend(message)) => {
+                assert_eq!(
+                    "Ruby class names are not supported by the c target",
+                    message
+                )
+            }
+            other => panic!("expected a backend error: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn configures_missing_values() {
+        let program = link_with(&Vec::new(), &Options::default()).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        assert!(!String::from_utf8(buf)
+            .unwrap()
+            .contains("#define STACHE_MISSING"));
+
+        let options = Options {
+            missing: Missing::Placeholder(String::from("\"nil\" \u{2014}")),
+            ..Options::default()
+        };
+        let program = link_with(&Vec::new(), &options).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        assert!(String::from_utf8(buf)
+            .unwrap()
+            .contains(r#"#define STACHE_MISSING_PLACEHOLDER "\"nil\" \342\200\224""#));
+
+        let options = Options {
+            missing: Missing::Raise,
+            ..Options::default()
+        };
+        let program = link_with(&Vec::new(), &options).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        assert!(String::from_utf8(buf)
+            .unwrap()
+            .contains("#define STACHE_MISSING_RAISE 1"));
+    }
+
+    #[test]
+    fn trims_selected_templates() {
+        let base = PathBuf::from("app/templates");
+        let tree = || Statement::parse("{{name}}\n").unwrap();
+        let templates = vec![
+            Template::new(
+                &base,
+                PathBuf::from("app/templates/subject.mustache"),
+                tree(),
+            ),
+            Template::new(&base, PathBuf::from("app/templates/body.mustache"), tree()),
+        ];
+
+        let options = Options {
+            trim: Trim::Newline,
+            trim_only: vec![String::from("subject")],
+            ..Options::default()
+        };
+        let program = link_with(&templates, &options).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert_eq!(1, source.matches("buffer_trim(buf, false);").count());
+        let subject = source.find("render_subject(buf, &stack);").unwrap();
+        assert!(source[subject..].starts_with(
+            "render_subject(buf, &stack);\n                 buffer_trim(buf, false);"
+        ));
+
+        let options = Options {
+            trim_only: vec![String::from("subjet")],
+            ..options
+        };
+        match link_with(&templates, &options) {
+            Err(Error::TemplateNotFound { name }) => assert_eq!("subjet", name),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn precomputes_constant_templates() {
+        let base = PathBuf::from("app/templates");
+        let template = |name: &str, source: &str| {
+            let path = PathBuf::from(format!("app/templates/{}.mustache", name));
+            Template::new(&base, path, Statement::parse(source).unwrap())
+        };
+        let templates = vec![
+            template("header", "<h1>Robots</h1>\n"),
+            template("footer", "{{! Static }}{{> header}}bye\n"),
+            template("page", "{{> header}}{{name}}"),
+        ];
+
+        let options = Options {
+            trim: Trim::Newline,
+            ..Options::default()
+        };
+        let program = link_with(&templates, &options).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("\"<h1>Robots</h1>\\nbye\\n\""));
+        assert!(source.contains("return rb_str_new(content_header_1, 15);"));
+        assert!(source.contains("return rb_str_new(content_footer_1, 19);"));
+        assert!(source.contains("render_page(buf, &stack);"));
+        assert!(source.contains("render_header(buf, stack);"));
+
+        let constant = source.find("return rb_str_new(content_footer_1").unwrap();
+        let buffer = source.rfind("templates_get_buf(self);\n").unwrap();
+        assert!(constant < buffer);
+    }
+
+    #[test]
+    fn eliminates_defined_branches() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robots.mustache");
+        let source = "{{#beta}}{{> beta/robots}}{{/beta}}{{^beta}}{{name}}{{/beta}}";
+        let templates = vec![Template::new(
+            &base,
+            path,
+            Statement::parse(source).unwrap(),
+        )];
+
+        assert!(link(&templates).is_err());
+
+        let mut options = Options::default();
+        options.defines.insert(String::from("beta"), false);
+        let program = link_with(&templates, &options).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(!source.contains("section(buf"));
+        assert!(!source.contains("inverted(buf"));
+        assert!(source
+            .contains("{ \"name\" }, .length = 1 }; append_value(buf, stack, &path, true); }"));
+    }
+
+    #[test]
+    fn eliminates_undefined_flags() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robots.mustache");
+        let source = "{{#flags.beta}}{{> beta/robots}}{{/flags.beta}}{{name}}";
+        let templates = vec![Template::new(
+            &base,
+            path,
+            Statement::parse(source).unwrap(),
+        )];
+
+        let program = link(&templates).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(!source.contains("section(buf"));
+        assert!(!source.contains("\"flags\""));
+
+        let mut options = Options::default();
+        options.defines.insert(String::from("beta"), true);
+        assert!(link_with(&templates, &options).is_err());
+    }
+
+    #[test]
+    fn renders_template_aliases() {
+        let base = PathBuf::from("app/templates");
+        let path = PathBuf::from("app/templates/robots/index.mustache");
+        let templates = vec![Template::new(
+            &base,
+            path,
+            Statement::parse("{{name}}").unwrap(),
+        )];
+
+        let options = Options {
+            aliases: vec![(String::from("robots"), String::from("robots/index"))],
+            ..Options::default()
+        };
+        let program = link_with(&templates, &options).unwrap();
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("if (length == 12 && strncmp(ptr, \"robots/index\", 12) == 0)"));
+        assert!(source.contains("if (length == 6 && strncmp(ptr, \"robots\", 6) == 0)"));
+        assert_eq!(
+            2,
+            source.matches("render_robots_index(buf, &stack);").count()
+        );
+
+        let options = Options {
+            aliases: vec![
+                (String::from("robots/index"), String::from("robots")),
+                (String::from("robots"), String::from("robots/index")),
+            ],
+            ..Options::default()
+        };
+        let messages: Vec<_> = match link_with(&templates, &options) {
+            Err(e) => e.errors().iter().map(|e| e.to_string()).collect(),
+            Ok(_) => panic!("Must validate aliases"),
+        };
+        assert_eq!(
+            vec![
+                "Undefined template `robots` aliased as `robots/index`",
+                "Alias `robots/index` is already the name of a template or alias",
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn exposes_html_escaper() {
+        let program = link_with(&Vec::new(), &Options::default()).unwrap();
+
+        let mut buf = Vec::new();
+        program.emit(&mut buf).unwrap();
+        let source = String::from_utf8(buf).unwrap();
+
+        assert!(source.contains("static VALUE escape_html(VALUE self, VALUE value) {"));
+        assert!(source
+            .contains("rb_define_module_function(namespace, \"escape_html\", escape_html, 1);"));
+    }
+
+    #[test]
+    fn renames_identifiers_outside_strings() {
+        let mut names = HashMap::new();
+        names.insert(String::from("section_b_2"), String::from("section_a_2"));
+
+        let code = "{ static const struct path path = { .keys = { \"section_b_2\" } }; \
+                    section(buf, stack, &path, section_b_2); }";
+        let expected = "{ static const struct path path = { .keys = { \"section_b_2\" } }; \
+                        section(buf, stack, &path, section_a_2); }";
+        assert_eq!(expected, rename(code, &names));
+        assert_eq!("section_b_23()", rename("section_b_23()", &names));
+    }
+
+    #[test]
+    fn links_fixture_sets() {
+        for set in &[Set::Small, Set::Deep, Set::Wide] {
+            assert!(link(&set.templates()).is_ok(), "{}", set.name());
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Reformats generated C source with consistent indentation.
+///
+/// Statements are placed one per line and indented four spaces per block,
+/// with function bodies opening on their own line, following the WebKit
+/// style recommended for formatting the extension with clang-format.
+/// Initializer lists, string literals, and comments are kept as written.
+pub fn format(source: &str) -> String {
+    let mut printer = Printer::default();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                let mut literal = c.to_string();
+                let mut escaped = false;
+                for next in chars.by_ref() {
+                    literal.push(next);
+                    if escaped {
+                        escaped = false;
+                    } else if next == '\\' {
+                        escaped = true;
+                    } else if next == c {
+                        break;
+                    }
+                }
+                printer.token(&literal);
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                let mut comment = c.to_string();
+                for next in chars.by_ref() {
+                    comment.push(next);
+                    if comment.len() > 3 && comment.ends_with("*/") {
+                        break;
+                    }
+                }
+                printer.token(&comment);
+                printer.newline();
+            }
+            '{' => printer.open(),
+            '}' => printer.close(),
+            ';' => {
+                printer.token(";");
+                if printer.parens == 0 && !printer.initializer() {
+                    printer.newline();
+                }
+            }
+            '(' => {
+                printer.parens += 1;
+                printer.token("(");
+            }
+            ')' => {
+                printer.parens = printer.parens.saturating_sub(1);
+                printer.token(")");
+            }
+            c if c.is_whitespace() => {
+                let mut lines = (c == '\n') as usize;
+                while let Some(&next) = chars.peek() {
+                    if !next.is_whitespace() {
+                        break;
+                    }
+                    lines += (next == '\n') as usize;
+                    chars.next();
+                }
+                printer.space = true;
+                printer.blank |= lines > 1;
+            }
+            c if c == '_' || c.is_alphanumeric() => {
+                let word = identifier(c, &mut chars);
+                printer.token(&word);
+            }
+            c => printer.token(&c.to_string()),
+        }
+    }
+
+    printer.newline();
+    printer.out
+}
+
+/// Reads the rest of an identifier or number beginning with the character.
+fn identifier(first: char, chars: &mut Peekable<Chars>) -> String {
+    let mut word = first.to_string();
+    while let Some(&next) = chars.peek() {
+        if next != '_' && !next.is_alphanumeric() {
+            break;
+        }
+        word.push(next);
+        chars.next();
+    }
+    word
+}
+
+#[derive(Debug, PartialEq)]
+enum Brace {
+    Block,
+    Initializer,
+}
+
+#[derive(Debug, Default)]
+struct Printer {
+    out: String,
+    braces: Vec<Brace>,
+    parens: usize,
+    /// Nothing has been written to the current line yet.
+    fresh: bool,
+    /// Whitespace separated the previous token from the next.
+    space: bool,
+    /// An empty line separated the previous token from the next.
+    blank: bool,
+    /// A block was just closed, so the next token starts a new line unless it
+    /// continues the statement, as in `} else {`.
+    closed: bool,
+    /// The last character written.
+    last: char,
+}
+
+impl Printer {
+    fn depth(&self) -> usize {
+        self.braces.iter().filter(|b| **b == Brace::Block).count()
+    }
+
+    fn initializer(&self) -> bool {
+        self.braces.last() == Some(&Brace::Initializer)
+    }
+
+    /// Writes a token, preceded by a line break and indentation or a single
+    /// space as the source's whitespace requires.
+    fn token(&mut self, token: &str) {
+        if self.closed {
+            self.closed = false;
+            if token == "else" {
+                self.space = true;
+            } else if token != ";" {
+                self.newline();
+            }
+        }
+
+        if self.fresh || self.out.is_empty() {
+            if self.blank && !self.out.is_empty() {
+                self.out.push('\n');
+            }
+            for _ in 0..self.depth() {
+                self.out.push_str("    ");
+            }
+        } else if self.space {
+            self.out.push(' ');
+        }
+
+        self.out.push_str(token);
+        self.last = token.chars().last().unwrap_or(self.last);
+        self.fresh = false;
+        self.space = false;
+        self.blank = false;
+    }
+
+    fn newline(&mut self) {
+        if !self.fresh && !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.fresh = true;
+        self.space = false;
+    }
+
+    fn open(&mut self) {
+        if self.initializer() || self.last == '=' || self.last == ',' {
+            self.token("{");
+            self.braces.push(Brace::Initializer);
+            return;
+        }
+
+        self.blank = false;
+        if self.depth() == 0 {
+            self.newline();
+        } else {
+            self.space = true;
+        }
+        self.token("{");
+        self.braces.push(Brace::Block);
+        self.newline();
+        self.blank = false;
+    }
+
+    fn close(&mut self) {
+        match self.braces.pop() {
+            Some(Brace::Initializer) => self.token("}"),
+            _ => {
+                self.blank = false;
+                self.newline();
+                self.closed = false;
+                self.token("}");
+                self.closed = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+
+    #[test]
+    fn indents_blocks() {
+        let source = "static void section_a_1(struct buffer *buf, const struct stack *stack) {
+buffer_append(buf, content_a_2, 4);
+{ static const struct path path = { .keys = { \"name\" }, .length = 1 }; append_value(buf, stack, &path, true); }
+}
+
+static VALUE render(VALUE self) {
+                   if (length == 1 && strncmp(ptr, \"a\", 1) == 0) {
+                 render_a(buf, &stack);
+             }
+                   else {
+                       rb_raise(rb_eArgError, \"Template {not} found\");
+                   }
+}
+";
+
+        let expected = "static void section_a_1(struct buffer *buf, const struct stack *stack)
+{
+    buffer_append(buf, content_a_2, 4);
+    {
+        static const struct path path = { .keys = { \"name\" }, .length = 1 };
+        append_value(buf, stack, &path, true);
+    }
+}
+
+static VALUE render(VALUE self)
+{
+    if (length == 1 && strncmp(ptr, \"a\", 1) == 0) {
+        render_a(buf, &stack);
+    } else {
+        rb_raise(rb_eArgError, \"Template {not} found\");
+    }
+}
+";
+        assert_eq!(expected, format(source));
+        assert_eq!(expected, format(expected));
+    }
+}
@@ -0,0 +1,1864 @@
+/// The sanitizer assertion and memory poisoning macros shared by the Ruby
+/// extension and C library runtimes.
+macro_rules! sanitizer {
+    () => {
+        r#"#ifdef STACHE_SANITIZE
+#include <assert.h>
+#define STACHE_ASSERT(condition) assert(condition)
+#if defined(__has_feature)
+#if __has_feature(address_sanitizer)
+#define STACHE_ASAN
+#endif
+#endif
+#if defined(__SANITIZE_ADDRESS__) && !defined(STACHE_ASAN)
+#define STACHE_ASAN
+#endif
+#else
+#define STACHE_ASSERT(condition) ((void)0)
+#endif
+
+#ifdef STACHE_ASAN
+#include <sanitizer/asan_interface.h>
+#define STACHE_POISON(data, size) ASAN_POISON_MEMORY_REGION(data, size)
+#define STACHE_UNPOISON(data, size) ASAN_UNPOISON_MEMORY_REGION(data, size)
+#else
+#define STACHE_POISON(data, size) ((void)(data), (void)(size))
+#define STACHE_UNPOISON(data, size) ((void)(data), (void)(size))
+#endif
+
+"#
+    };
+}
+
+/// The render buffer functions shared by the runtimes, managing output in
+/// natively allocated memory.
+macro_rules! native_buffer {
+    () => {
+        r#"static bool buffer_init(struct buffer *this) {
+    const size_t capacity = STACHE_BUFFER_CAPACITY;
+    char *data = malloc(capacity);
+    if (!data) {
+        return false;
+    }
+    this->data = data;
+    this->capacity = capacity;
+    this->length = 0;
+    this->indent = NULL;
+    this->line_start = false;
+    STACHE_POISON(data, capacity);
+    return true;
+}
+
+static void buffer_destroy(struct buffer *this) {
+    if (this->data) {
+        STACHE_UNPOISON(this->data, this->capacity);
+#ifdef STACHE_SANITIZE
+        memset(this->data, 0xdd, this->capacity);
+#endif
+    }
+    free(this->data);
+    this->data = NULL;
+    this->capacity = 0;
+    this->length = 0;
+}
+
+STACHE_API void buffer_clear(struct buffer *this) {
+    this->length = 0;
+    this->indent = NULL;
+    this->line_start = false;
+    STACHE_POISON(this->data, this->capacity);
+}
+
+STACHE_API void buffer_trim(struct buffer *this, bool whitespace) {
+    const size_t length = this->length;
+    if (whitespace) {
+        while (this->length > 0 && memchr(" \t\r\n\f\v", this->data[this->length - 1], 6)) {
+            this->length--;
+        }
+    } else if (this->length > 0 && this->data[this->length - 1] == '\n') {
+        this->length--;
+        if (this->length > 0 && this->data[this->length - 1] == '\r') {
+            this->length--;
+        }
+    }
+    STACHE_POISON(this->data + this->length, length - this->length);
+}
+
+static bool buffer_resize(struct buffer *this, size_t capacity) {
+    STACHE_UNPOISON(this->data, this->capacity);
+    void *data = realloc(this->data, capacity);
+    if (!data) {
+        STACHE_POISON(this->data + this->length, this->capacity - this->length);
+        return false;
+    }
+    this->data = data;
+    this->capacity = capacity;
+    STACHE_POISON(this->data + this->length, this->capacity - this->length);
+    return true;
+}
+
+STACHE_API bool buffer_reserve(struct buffer *this, size_t length) {
+    if (length > SIZE_MAX - this->length) {
+        return false;
+    }
+
+    size_t min = this->length + length;
+    if (this->capacity < min) {
+        size_t scale = this->capacity / 100;
+        size_t ideal = (scale <= SIZE_MAX / STACHE_BUFFER_GROWTH) ? scale * STACHE_BUFFER_GROWTH : SIZE_MAX;
+        size_t padded = (min / 10 <= SIZE_MAX - min) ? min + min / 10 : min;
+        size_t capacity = (min < ideal) ? ideal : padded;
+        return buffer_resize(this, capacity);
+    }
+    return true;
+}
+
+STACHE_API bool buffer_append(struct buffer *this, const char *value, size_t length) {
+    if (!buffer_reserve(this, length)) {
+#if defined(STACHE_LIBRARY) || defined(STACHE_ADDON)
+        this->failed = true;
+#endif
+        return false;
+    }
+    STACHE_ASSERT(this->length + length <= this->capacity);
+    STACHE_UNPOISON(this->data + this->length, length);
+    memcpy(this->data + this->length, value, length);
+    this->length += length;
+    return true;
+}
+
+"#
+    };
+}
+
+/// HTML escaping into the render buffer, shared by the runtimes without Ruby
+/// strings to escape.
+macro_rules! escaped_append {
+    () => {
+        r#"
+static const char *DOT = ".";
+
+static void append_escaped(struct buffer *buf, const char *value, size_t length) {
+    size_t start = 0;
+    for (size_t i = 0; i < length; i++) {
+        const char *entity;
+        switch (value[i]) {
+            case '\'':
+                entity = "&#39;";
+                break;
+            case '&':
+                entity = "&amp;";
+                break;
+            case '"':
+                entity = "&quot;";
+                break;
+            case '<':
+                entity = "&lt;";
+                break;
+            case '>':
+                entity = "&gt;";
+                break;
+            default:
+                continue;
+        }
+        buffer_append(buf, value + start, i - start);
+        buffer_append(buf, entity, strlen(entity));
+        start = i + 1;
+    }
+    buffer_append(buf, value + start, length - start);
+}
+"#
+    };
+}
+
+/// Standalone partial indentation shared by the runtimes. Each indented
+/// partial call pushes its indentation onto the render buffer, and the
+/// indentation of every enclosing partial is added to the start of each
+/// line of the partial's template content, but not within interpolated
+/// values.
+macro_rules! indentation {
+    () => {
+        r#"
+static void write_indent(struct buffer *buf, const struct indent *indent) {
+    if (indent) {
+        write_indent(buf, indent->parent);
+        buffer_append(buf, indent->text, indent->length);
+    }
+}
+
+/* Adds the indentation of the partials being rendered when output begins a
+   new line of a partial's template. */
+static void begin_line(struct buffer *buf) {
+    if (buf->line_start) {
+        buf->line_start = false;
+        write_indent(buf, buf->indent);
+    }
+}
+
+STACHE_API void indent_push(struct buffer *buf, struct indent *indent, const char *text, size_t length) {
+    indent->text = text;
+    indent->length = length;
+    indent->parent = buf->indent;
+    buf->indent = indent;
+    buf->line_start = true;
+}
+
+STACHE_API void indent_pop(struct buffer *buf, const struct indent *indent) {
+    buf->indent = indent->parent;
+    buf->line_start = true;
+}
+
+STACHE_API bool append_content(struct buffer *buf, const char *value, size_t length) {
+    if (!buf->indent) {
+        return buffer_append(buf, value, length);
+    }
+
+    bool ok = true;
+    size_t start = 0;
+    while (start < length) {
+        begin_line(buf);
+        const char *newline = memchr(value + start, '\n', length - start);
+        const size_t end = newline ? (size_t)(newline - value) + 1 : length;
+        ok = buffer_append(buf, value + start, end - start) && ok;
+        buf->line_start = newline != NULL;
+        start = end;
+    }
+    return ok;
+}
+"#
+    };
+}
+
+/// Declarations shared by the runtime and the generated template functions:
+/// the context stack, render buffer, and key path types, along with the
+/// runtime functions that template functions call.
+///
+/// Runtime functions are declared with `STACHE_API`, which is `static` when
+/// the runtime is emitted into the same file as the templates, or a hidden
+/// external symbol when it's compiled as a separate source file.
+pub const HEADER: &str = concat!(
+    r#"
+#include "ruby.h"
+#include "ruby/encoding.h"
+#include <stdbool.h>
+#include <limits.h>
+#include <stdint.h>
+#include <string.h>
+
+"#,
+    sanitizer!(),
+    r#"/* A context frame. A list item's frame records its position in the list,
+   read by the @index, @first, and @last keys, with a zero length for any
+   other frame. A hash value's frame also records its key, read by @key, and
+   a list item's key is Qundef. */
+struct stack {
+    VALUE data;
+    const struct stack *parent;
+    long index;
+    long length;
+    VALUE key;
+};
+
+struct indent {
+    const char *text;
+    size_t length;
+    const struct indent *parent;
+};
+
+struct buffer {
+#if defined(STACHE_STRING_BUFFER)
+    VALUE output;
+#else
+    char *data;
+    size_t capacity;
+    size_t length;
+#endif
+    VALUE memo;
+    VALUE cache;
+    VALUE helpers;
+    VALUE translator;
+    VALUE trace;
+    const struct indent *indent;
+    bool line_start;
+};
+
+struct path {
+    const char *keys[16];
+    int length;
+};
+
+STACHE_API void buffer_clear(struct buffer *this);
+STACHE_API void buffer_trim(struct buffer *this, bool whitespace);
+STACHE_API bool buffer_reserve(struct buffer *this, size_t length);
+STACHE_API bool buffer_append(struct buffer *this, const char *value, size_t length);
+STACHE_API VALUE buffer_string(struct buffer *this);
+STACHE_API bool append_content(struct buffer *buf, const char *value, size_t length);
+STACHE_API void indent_push(struct buffer *buf, struct indent *indent, const char *text, size_t length);
+STACHE_API void indent_pop(struct buffer *buf, const struct indent *indent);
+STACHE_API void append_value(struct buffer *buf, const struct stack *stack, const struct path *path, bool escape);
+STACHE_API void append_required(struct buffer *buf, const struct stack *stack, const struct path *path, bool escape);
+STACHE_API void append_filtered(struct buffer *buf, const struct stack *stack, const struct path *path, const char *const *filters, int count);
+STACHE_API void append_translation(struct buffer *buf, const char *key, size_t length);
+STACHE_API bool partial_name(struct buffer *buf, const struct stack *stack, const struct path *path, char *name, size_t size, size_t *length);
+STACHE_API void section(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *), const char *source, size_t length);
+STACHE_API void inverted(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *));
+STACHE_API void memoize(struct buffer *buf, const struct stack *stack, const char *name, void (*block)(struct buffer *, const struct stack *));
+STACHE_API void partial_with(struct buffer *buf, const struct stack *stack, const char *const *keys, const struct path *paths, int count, void (*block)(struct buffer *, const struct stack *));
+STACHE_API struct buffer *templates_get_buf(VALUE self);
+STACHE_API void init_templates(VALUE namespace);
+STACHE_API VALUE render(VALUE self, VALUE name, VALUE context);
+"#
+);
+
+/// The runtime function definitions.
+pub const RUNTIME: &str = concat!(
+    r#"
+static void html_escaped_cat(VALUE str, char c) {
+    switch (c) {
+        case '\'':
+            rb_str_cat_cstr(str, "&#39;");
+            break;
+        case '&':
+            rb_str_cat_cstr(str, "&amp;");
+            break;
+        case '"':
+            rb_str_cat_cstr(str, "&quot;");
+            break;
+        case '<':
+            rb_str_cat_cstr(str, "&lt;");
+            break;
+        case '>':
+            rb_str_cat_cstr(str, "&gt;");
+            break;
+    }
+}
+
+static VALUE optimized_escape_html(VALUE str) {
+    long beg = 0;
+    VALUE dest = 0;
+
+    const long len = RSTRING_LEN(str);
+    const char *cstr = RSTRING_PTR(str);
+
+    for (long i = 0; i < len; i++) {
+        switch (cstr[i]) {
+          case '\'':
+          case '&':
+          case '"':
+          case '<':
+          case '>':
+            if (!dest) {
+                dest = rb_str_buf_new(len);
+            }
+
+            rb_str_cat(dest, cstr + beg, i - beg);
+            beg = i + 1;
+
+            html_escaped_cat(dest, cstr[i]);
+            break;
+        }
+    }
+
+    if (dest) {
+        rb_str_cat(dest, cstr + beg, len - beg);
+        return dest;
+    } else {
+        return str;
+    }
+}
+
+static const char *DOT = ".";
+
+static ID id_to_s;
+static ID id_call;
+static ID id_keys;
+static ID id_miss;
+static ID id_buf;
+static VALUE Buffer;
+
+#if defined(STACHE_STRING_BUFFER)
+static bool buffer_init(struct buffer *this) {
+    this->output = Qnil;
+    this->indent = NULL;
+    this->line_start = false;
+    return true;
+}
+
+static void buffer_destroy(struct buffer *this) {
+    this->output = Qnil;
+}
+
+STACHE_API void buffer_clear(struct buffer *this) {
+    if (NIL_P(this->output)) {
+        this->output = rb_str_buf_new(STACHE_BUFFER_CAPACITY);
+    } else {
+        rb_str_set_len(this->output, 0);
+    }
+    this->indent = NULL;
+    this->line_start = false;
+}
+
+STACHE_API void buffer_trim(struct buffer *this, bool whitespace) {
+    const char *data = RSTRING_PTR(this->output);
+    long length = RSTRING_LEN(this->output);
+    if (whitespace) {
+        while (length > 0 && memchr(" \t\r\n\f\v", data[length - 1], 6)) {
+            length--;
+        }
+    } else if (length > 0 && data[length - 1] == '\n') {
+        length--;
+        if (length > 0 && data[length - 1] == '\r') {
+            length--;
+        }
+    }
+    rb_str_set_len(this->output, length);
+}
+
+STACHE_API bool buffer_reserve(struct buffer *this, size_t length) {
+    if (length > LONG_MAX - (size_t)RSTRING_LEN(this->output)) {
+        return false;
+    }
+    rb_str_modify_expand(this->output, (long)length);
+    return true;
+}
+
+STACHE_API bool buffer_append(struct buffer *this, const char *value, size_t length) {
+    if (length > LONG_MAX) {
+        return false;
+    }
+    rb_str_cat(this->output, value, (long)length);
+    return true;
+}
+
+static size_t buffer_length(const struct buffer *this) {
+    return (size_t)RSTRING_LEN(this->output);
+}
+
+static VALUE buffer_slice(const struct buffer *this, size_t start) {
+    return rb_str_substr(this->output, (long)start, RSTRING_LEN(this->output) - (long)start);
+}
+
+STACHE_API VALUE buffer_string(struct buffer *this) {
+    VALUE output = this->output;
+    this->output = Qnil;
+    return output;
+}
+#else
+"#,
+    native_buffer!(),
+    r#"static size_t buffer_length(const struct buffer *this) {
+    return this->length;
+}
+
+static VALUE buffer_slice(const struct buffer *this, size_t start) {
+    STACHE_ASSERT(this->length >= start);
+    return rb_str_new(this->data + start, (long)(this->length - start));
+}
+
+STACHE_API VALUE buffer_string(struct buffer *this) {
+    return rb_str_new(this->data, (long)this->length);
+}
+#endif
+"#,
+    indentation!(),
+    r#"
+/* Parses a key made only of digits, like the 0 in items.0, as a list index. */
+static bool list_index(const char *key, long *index) {
+    long value = 0;
+    for (const char *c = key; *c; c++) {
+        if (*c < '0' || *c > '9' || value > (LONG_MAX - 9) / 10) {
+            return false;
+        }
+        value = value * 10 + (*c - '0');
+    }
+    *index = value;
+    return *key != '\0';
+}
+
+static VALUE fetch(VALUE context, const char *key) {
+    if (strlen(key) == 1 && strncmp(key, DOT, 1) == 0) {
+        return context;
+    }
+
+    switch (rb_type(context)) {
+        case T_HASH: {
+            VALUE miss = ID2SYM(id_miss);
+            VALUE sym = ID2SYM(rb_intern(key));
+            VALUE value = rb_hash_lookup2(context, sym, miss);
+            if (value == miss) {
+                VALUE key_str = rb_str_new_cstr(key);
+                value = rb_hash_lookup2(context, key_str, miss);
+                if (value == miss) {
+                    value = Qundef;
+                }
+            }
+            return value;
+        }
+        case T_FALSE:
+            return Qfalse;
+        case T_NIL:
+        case T_UNDEF:
+            return Qundef;
+        case T_ARRAY: {
+            long index;
+            if (list_index(key, &index)) {
+                return index < RARRAY_LEN(context) ? RARRAY_AREF(context, index) : Qundef;
+            }
+        }
+        /* fall through */
+        default: {
+            ID method = rb_intern(key);
+            if (rb_respond_to(context, method)) {
+                return rb_funcall(context, method, 0);
+            } else {
+                return Qundef;
+            }
+        }
+    }
+}
+
+/* Finds a loop metadata key's value for the innermost list item. */
+static VALUE loop_fetch(const struct stack *stack, const char *key, int *frame) {
+    *frame = 0;
+    do {
+        if (stack->length > 0) {
+            if (strcmp(key, "@index") == 0) {
+                return LONG2NUM(stack->index);
+            } else if (strcmp(key, "@first") == 0) {
+                return stack->index == 0 ? Qtrue : Qfalse;
+            } else if (strcmp(key, "@last") == 0) {
+                return stack->index == stack->length - 1 ? Qtrue : Qfalse;
+            } else if (strcmp(key, "@key") == 0) {
+                return stack->key;
+            }
+            break;
+        }
+        (*frame)++;
+    } while ((stack = stack->parent));
+
+    *frame = -1;
+    return Qundef;
+}
+
+static VALUE context_fetch(const struct stack *stack, const char *key, int *frame) {
+    if (key[0] == '@') {
+        return loop_fetch(stack, key, frame);
+    }
+
+    *frame = 0;
+    do {
+        VALUE value = fetch(stack->data, key);
+        if (value != Qundef) {
+            return value;
+        }
+        (*frame)++;
+    } while ((stack = stack->parent));
+
+    *frame = -1;
+    return Qundef;
+}
+
+/* Joins the path's keys with dots, naming a missing value or traced lookup.
+   STRICT templates' `append_required` calls it in every configuration, not
+   only with STACHE_TRACE or STACHE_MISSING_RAISE, so it's always defined. */
+static VALUE path_name(const struct path *path) {
+    VALUE name = rb_str_new_cstr(path->keys[0]);
+    for (int i = 1; i < path->length; i++) {
+        rb_str_cat_cstr(name, DOT);
+        rb_str_cat_cstr(name, path->keys[i]);
+    }
+    return name;
+}
+
+#if defined(STACHE_TRACE)
+static void trace_lookup(struct buffer *buf, const struct path *path, int frame, VALUE value) {
+    if (NIL_P(buf->trace)) {
+        return;
+    }
+
+    VALUE lookup = rb_hash_new();
+    rb_hash_aset(lookup, ID2SYM(rb_intern("path")), path_name(path));
+    rb_hash_aset(lookup, ID2SYM(rb_intern("frame")), frame < 0 ? Qnil : INT2FIX(frame));
+    rb_hash_aset(lookup, ID2SYM(rb_intern("found")), value == Qundef ? Qfalse : Qtrue);
+    rb_hash_aset(lookup, ID2SYM(rb_intern("value")), value == Qundef ? Qnil : value);
+    rb_ary_push(buf->trace, lookup);
+}
+#endif
+
+/* Resolves a dotted name's first key through the context stack, and each
+   later key only within the value found, so a broken chain is missing
+   rather than falling back to an outer frame. */
+static VALUE fetch_path(struct buffer *buf, const struct stack *stack, const struct path *path) {
+    STACHE_ASSERT(path->length > 0 && path->length <= 16);
+    int frame;
+    VALUE value = context_fetch(stack, path->keys[0], &frame);
+    for (int i = 1; i < path->length && value != Qundef; i++) {
+        value = fetch(value, path->keys[i]);
+    }
+#if defined(STACHE_TRACE)
+    trace_lookup(buf, path, frame, value);
+#else
+    (void)buf;
+#endif
+    return value;
+}
+
+static void missing_value(struct buffer *buf, const struct path *path) {
+#if defined(STACHE_MISSING_RAISE)
+    VALUE name = path_name(path);
+    buffer_clear(buf);
+    rb_raise(rb_eKeyError, "Missing template value: %s", StringValueCStr(name));
+#elif defined(STACHE_MISSING_PLACEHOLDER)
+    if (!buffer_append(buf, STACHE_MISSING_PLACEHOLDER, sizeof(STACHE_MISSING_PLACEHOLDER) - 1)) {
+        buffer_clear(buf);
+        rb_raise(rb_eRuntimeError, "Memory allocation failed");
+    }
+#else
+    (void)buf;
+    (void)path;
+#endif
+}
+
+static bool falsy(VALUE value) {
+    switch (rb_type(value)) {
+        case T_ARRAY:
+            return RARRAY_LEN(value) == 0;
+#if defined(STACHE_ITERATE_HASHES)
+        case T_HASH:
+            return RHASH_SIZE(value) == 0;
+#endif
+        case T_NIL:
+        case T_UNDEF:
+        case T_FALSE:
+            return true;
+        default:
+            return false;
+    }
+}
+
+/* The deepest nesting of sections and lambda calls in the template text
+   returned by lambdas, which is interpreted recursively. */
+#ifndef STACHE_LAMBDA_DEPTH
+#define STACHE_LAMBDA_DEPTH 100
+#endif
+
+static bool lambda(VALUE value) {
+    return RTEST(rb_obj_is_proc(value));
+}
+
+static VALUE expand(struct buffer *buf, const struct stack *stack, VALUE template, int depth);
+
+/* Converts an interpolated value to its text, or nil when it's missing. A
+   lambda is called and its result rendered as a template, nested in the
+   lambda text being rendered at the depth. */
+static VALUE value_text(struct buffer *buf, const struct stack *stack, VALUE value, int depth) {
+    switch (rb_type(value)) {
+        case T_NIL:
+        case T_UNDEF:
+            return Qnil;
+        case T_STRING:
+            return value;
+        default:
+            if (lambda(value)) {
+                return expand(buf, stack, rb_funcall(value, id_call, 0), depth + 1);
+            }
+            return rb_funcall(value, id_to_s, 0);
+    }
+}
+
+/* A tag found in a lambda's template text. The sigil is one of `^#/!&{`, or
+   zero for a variable, and the name is trimmed of surrounding whitespace. */
+struct tag {
+    char sigil;
+    long start;
+    long end;
+    const char *name;
+    long length;
+};
+
+static const char *find_text(const char *text, long length, const char *needle) {
+    const long size = (long)strlen(needle);
+    for (long i = 0; i + size <= length; i++) {
+        if (memcmp(text + i, needle, (size_t)size) == 0) {
+            return text + i;
+        }
+    }
+    return NULL;
+}
+
+static bool next_tag(const char *text, long length, long offset, struct tag *tag) {
+    const char *open = find_text(text + offset, length - offset, "{{");
+    if (!open) {
+        return false;
+    }
+
+    tag->start = open - text;
+    long inner = tag->start + 2;
+    tag->sigil = (inner < length && text[inner] && strchr("^#/!&{", text[inner])) ? text[inner] : 0;
+    if (tag->sigil) {
+        inner++;
+    }
+
+    const char *delimiter = tag->sigil == '{' ? "}}}" : "}}";
+    const char *close = find_text(text + inner, length - inner, delimiter);
+    if (!close) {
+        return false;
+    }
+    tag->end = (close - text) + (long)strlen(delimiter);
+
+    const char *name = text + inner;
+    while (name < close && memchr(" \t\r\n", *name, 4)) {
+        name++;
+    }
+    while (close > name && memchr(" \t\r\n", close[-1], 4)) {
+        close--;
+    }
+    tag->name = name;
+    tag->length = close - name;
+    return true;
+}
+
+/* Finds the tag closing a section, skipping nested sections of the same name. */
+static bool section_end(const char *text, long length, const struct tag *open, struct tag *close) {
+    int depth = 1;
+    long offset = open->end;
+    while (next_tag(text, length, offset, close)) {
+        offset = close->end;
+        if (close->length != open->length || memcmp(close->name, open->name, (size_t)open->length) != 0) {
+            continue;
+        }
+        if (close->sigil == '#' || close->sigil == '^') {
+            depth++;
+        } else if (close->sigil == '/' && --depth == 0) {
+            return true;
+        }
+    }
+    return false;
+}
+
+/* Splits a tag's dotted name into path keys, copied into the storage. */
+static bool tag_path(const struct tag *tag, char *storage, size_t size, struct path *path) {
+    if (tag->length == 0 || (size_t)tag->length >= size) {
+        return false;
+    }
+    memcpy(storage, tag->name, (size_t)tag->length);
+    storage[tag->length] = '\0';
+
+    path->length = 0;
+    if (strcmp(storage, DOT) == 0) {
+        path->keys[path->length++] = storage;
+        return true;
+    }
+
+    char *key = storage;
+    for (char *c = storage;; c++) {
+        if (*c != '.' && *c != '\0') {
+            continue;
+        }
+        if (c == key || path->length == 16) {
+            return false;
+        }
+        const bool last = *c == '\0';
+        *c = '\0';
+        path->keys[path->length++] = key;
+        if (last) {
+            return true;
+        }
+        key = c + 1;
+    }
+}
+
+static void expand_text(struct buffer *buf, const struct stack *stack, const char *text, long length, VALUE output, int depth);
+
+static void expand_block(struct buffer *buf, const struct stack *stack, VALUE value, const char *text, long length, VALUE output, int depth) {
+    switch (rb_type(value)) {
+        case T_ARRAY: {
+            struct stack frame = { .parent = stack, .key = Qundef };
+            for (long i = 0; i < RARRAY_LEN(value); i++) {
+                frame.data = RARRAY_AREF(value, i);
+                frame.index = i;
+                frame.length = RARRAY_LEN(value);
+                expand_text(buf, &frame, text, length, output, depth);
+            }
+            break;
+        }
+#if defined(STACHE_ITERATE_HASHES)
+        case T_HASH: {
+            VALUE keys = rb_funcall(value, id_keys, 0);
+            struct stack frame = { .parent = stack };
+            for (long i = 0; i < RARRAY_LEN(keys); i++) {
+                frame.key = RARRAY_AREF(keys, i);
+                frame.data = rb_hash_lookup(value, frame.key);
+                frame.index = i;
+                frame.length = RARRAY_LEN(keys);
+                expand_text(buf, &frame, text, length, output, depth);
+            }
+            RB_GC_GUARD(keys);
+            break;
+        }
+#endif
+        case T_NIL:
+        case T_UNDEF:
+        case T_FALSE:
+            break;
+        case T_TRUE:
+            expand_text(buf, stack, text, length, output, depth);
+            break;
+        default: {
+            if (lambda(value)) {
+                VALUE result = expand(buf, stack, rb_funcall(value, id_call, 1, rb_str_new(text, length)), depth + 1);
+                rb_str_cat(output, RSTRING_PTR(result), RSTRING_LEN(result));
+                break;
+            }
+            const struct stack frame = { .data = value, .parent = stack };
+            expand_text(buf, &frame, text, length, output, depth);
+            break;
+        }
+    }
+}
+
+/* Renders template text returned by a lambda into the output string. The
+   text is interpreted rather than compiled, supporting variable, section,
+   inverted section, and comment tags with the default delimiters. Text
+   nested more than `STACHE_LAMBDA_DEPTH` levels deep raises rather than
+   overflowing the stack. */
+static void expand_text(struct buffer *buf, const struct stack *stack, const char *text, long length, VALUE output, int depth) {
+    if (depth > STACHE_LAMBDA_DEPTH) {
+        buffer_clear(buf);
+        rb_raise(rb_eRuntimeError, "Lambda template nested more than %d levels deep", STACHE_LAMBDA_DEPTH);
+    }
+
+    long offset = 0;
+    struct tag tag;
+    while (next_tag(text, length, offset, &tag)) {
+        rb_str_cat(output, text + offset, tag.start - offset);
+        offset = tag.end;
+
+        char storage[256];
+        struct path path;
+        if (tag.sigil == '!' || tag.sigil == '/' || !tag_path(&tag, storage, sizeof(storage), &path)) {
+            continue;
+        }
+
+        VALUE value = fetch_path(buf, stack, &path);
+        if (tag.sigil == '#' || tag.sigil == '^') {
+            struct tag close;
+            if (!section_end(text, length, &tag, &close)) {
+                continue;
+            }
+            offset = close.end;
+            if (tag.sigil == '#') {
+                expand_block(buf, stack, value, text + tag.end, close.start - tag.end, output, depth + 1);
+            } else if (falsy(value)) {
+                expand_text(buf, stack, text + tag.end, close.start - tag.end, output, depth + 1);
+            }
+            continue;
+        }
+
+        VALUE string = value_text(buf, stack, value, depth);
+        if (!NIL_P(string)) {
+            string = tag.sigil ? string : optimized_escape_html(string);
+            rb_str_cat(output, RSTRING_PTR(string), RSTRING_LEN(string));
+        }
+    }
+    rb_str_cat(output, text + offset, length - offset);
+}
+
+static VALUE expand(struct buffer *buf, const struct stack *stack, VALUE template, int depth) {
+    VALUE source = rb_obj_as_string(template);
+    VALUE output = rb_str_buf_new(RSTRING_LEN(source));
+    expand_text(buf, stack, RSTRING_PTR(source), RSTRING_LEN(source), output, depth);
+    RB_GC_GUARD(source);
+    return output;
+}
+
+STACHE_API void append_value(struct buffer *buf, const struct stack *stack, const struct path *path, bool escape) {
+    begin_line(buf);
+    VALUE value = value_text(buf, stack, fetch_path(buf, stack, path), 0);
+    if (NIL_P(value)) {
+        missing_value(buf, path);
+        return;
+    }
+
+    value = escape ? optimized_escape_html(value) : value;
+
+    if (!buffer_append(buf, RSTRING_PTR(value), (size_t)RSTRING_LEN(value))) {
+        buffer_clear(buf);
+        rb_raise(rb_eRuntimeError, "Memory allocation failed");
+    }
+}
+
+/* Appends a variable's value like `append_value`, but raises for a missing
+   value regardless of the configured behavior. Templates with the STRICT
+   pragma call this for each of their variable tags. */
+STACHE_API void append_required(struct buffer *buf, const struct stack *stack, const struct path *path, bool escape) {
+    begin_line(buf);
+    VALUE value = value_text(buf, stack, fetch_path(buf, stack, path), 0);
+    if (NIL_P(value)) {
+        VALUE name = path_name(path);
+        buffer_clear(buf);
+        rb_raise(rb_eKeyError, "Missing template value: %s", StringValueCStr(name));
+    }
+
+    value = escape ? optimized_escape_html(value) : value;
+
+    if (!buffer_append(buf, RSTRING_PTR(value), (size_t)RSTRING_LEN(value))) {
+        buffer_clear(buf);
+        rb_raise(rb_eRuntimeError, "Memory allocation failed");
+    }
+}
+
+/* Passes a variable's value through each helper registered for the filter
+   names, in order, and appends the result escaped. A missing value is
+   passed to the first helper as nil. */
+STACHE_API void append_filtered(struct buffer *buf, const struct stack *stack, const struct path *path, const char *const *filters, int count) {
+    begin_line(buf);
+    VALUE value = fetch_path(buf, stack, path);
+    value = value == Qundef ? Qnil : value;
+    for (int i = 0; i < count; i++) {
+        VALUE helper = rb_hash_lookup2(buf->helpers, rb_str_new_cstr(filters[i]), Qundef);
+        if (helper == Qundef) {
+            buffer_clear(buf);
+            rb_raise(rb_eKeyError, "Unknown template helper: %s", filters[i]);
+        }
+        value = rb_funcall(helper, id_call, 1, value);
+    }
+
+    value = value_text(buf, stack, value, 0);
+    if (NIL_P(value)) {
+        missing_value(buf, path);
+        return;
+    }
+
+    value = optimized_escape_html(value);
+    if (!buffer_append(buf, RSTRING_PTR(value), (size_t)RSTRING_LEN(value))) {
+        buffer_clear(buf);
+        rb_raise(rb_eRuntimeError, "Memory allocation failed");
+    }
+}
+
+/* Appends the text the translator returns for the key, escaped, or the key
+   itself when no translator is set. */
+STACHE_API void append_translation(struct buffer *buf, const char *key, size_t length) {
+    begin_line(buf);
+    VALUE value = rb_str_new(key, (long)length);
+    if (!NIL_P(buf->translator)) {
+        value = rb_obj_as_string(rb_funcall(buf->translator, id_call, 1, value));
+    }
+
+    value = optimized_escape_html(value);
+    if (!buffer_append(buf, RSTRING_PTR(value), (size_t)RSTRING_LEN(value))) {
+        buffer_clear(buf);
+        rb_raise(rb_eRuntimeError, "Memory allocation failed");
+    }
+}
+
+/* Copies a dynamic partial's template name into the storage, failing when
+   it's missing or too long to name any template. */
+STACHE_API bool partial_name(struct buffer *buf, const struct stack *stack, const struct path *path, char *name, size_t size, size_t *length) {
+    VALUE value = value_text(buf, stack, fetch_path(buf, stack, path), 0);
+    if (NIL_P(value) || (size_t)RSTRING_LEN(value) >= size) {
+        return false;
+    }
+    *length = (size_t)RSTRING_LEN(value);
+    memcpy(name, RSTRING_PTR(value), *length);
+    RB_GC_GUARD(value);
+    return true;
+}
+
+STACHE_API void section(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *), const char *source, size_t length) {
+    VALUE value = fetch_path(buf, stack, path);
+    switch (rb_type(value)) {
+        case T_ARRAY: {
+            struct stack frame = { .parent = stack, .key = Qundef };
+            for (long i = 0; i < RARRAY_LEN(value); i++) {
+                frame.data = RARRAY_AREF(value, i);
+                frame.index = i;
+                frame.length = RARRAY_LEN(value);
+                block(buf, &frame);
+            }
+            break;
+        }
+#if defined(STACHE_ITERATE_HASHES)
+        case T_HASH: {
+            VALUE keys = rb_funcall(value, id_keys, 0);
+            struct stack frame = { .parent = stack };
+            for (long i = 0; i < RARRAY_LEN(keys); i++) {
+                frame.key = RARRAY_AREF(keys, i);
+                frame.data = rb_hash_lookup(value, frame.key);
+                frame.index = i;
+                frame.length = RARRAY_LEN(keys);
+                block(buf, &frame);
+            }
+            RB_GC_GUARD(keys);
+            break;
+        }
+#endif
+        case T_NIL:
+        case T_UNDEF:
+        case T_FALSE:
+            break;
+        case T_TRUE:
+            block(buf, stack);
+            break;
+        default: {
+            if (lambda(value)) {
+                if (!source) {
+                    buffer_clear(buf);
+                    rb_raise(rb_eRuntimeError, "Section lambdas require templates compiled with --lambdas");
+                }
+                VALUE text = rb_str_new(source, (long)length);
+                VALUE output = expand(buf, stack, rb_funcall(value, id_call, 1, text), 1);
+                begin_line(buf);
+                if (!buffer_append(buf, RSTRING_PTR(output), (size_t)RSTRING_LEN(output))) {
+                    buffer_clear(buf);
+                    rb_raise(rb_eRuntimeError, "Memory allocation failed");
+                }
+                break;
+            }
+            const struct stack frame = { .data = value, .parent = stack };
+            block(buf, &frame);
+            break;
+        }
+    }
+}
+
+STACHE_API void inverted(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *)) {
+    if (falsy(fetch_path(buf, stack, path))) {
+        block(buf, stack);
+    }
+}
+
+STACHE_API void memoize(struct buffer *buf, const struct stack *stack, const char *name, void (*block)(struct buffer *, const struct stack *)) {
+    VALUE cache;
+    VALUE key = rb_str_new_cstr(name);
+    VALUE explicit = fetch(stack->data, "cache_key");
+
+    if (explicit != Qundef && explicit != Qnil) {
+        cache = buf->cache;
+        rb_str_cat(key, "\0", 1);
+        rb_str_append(key, rb_obj_as_string(explicit));
+    } else {
+        cache = buf->memo;
+        for (const struct stack *frame = stack; frame; frame = frame->parent) {
+            rb_str_cat(key, (const char *)&frame->data, sizeof(VALUE));
+        }
+    }
+
+    VALUE value = rb_hash_lookup2(cache, key, Qundef);
+    if (value == Qundef) {
+        const size_t start = buffer_length(buf);
+        block(buf, stack);
+        rb_hash_aset(cache, key, buffer_slice(buf, start));
+        return;
+    }
+
+    if (!buffer_append(buf, RSTRING_PTR(value), (size_t)RSTRING_LEN(value))) {
+        buffer_clear(buf);
+        rb_raise(rb_eRuntimeError, "Memory allocation failed");
+    }
+}
+
+/* Renders a partial with a frame holding each argument path's value, found
+   in the caller's context, under its key. A missing value is nil, hiding
+   the key in outer frames. */
+STACHE_API void partial_with(struct buffer *buf, const struct stack *stack, const char *const *keys, const struct path *paths, int count, void (*block)(struct buffer *, const struct stack *)) {
+    VALUE data = rb_hash_new();
+    for (int i = 0; i < count; i++) {
+        VALUE value = fetch_path(buf, stack, &paths[i]);
+        rb_hash_aset(data, ID2SYM(rb_intern(keys[i])), value == Qundef ? Qnil : value);
+    }
+
+    const struct stack frame = { .data = data, .parent = stack };
+    block(buf, &frame);
+    RB_GC_GUARD(data);
+}
+
+static void buffer_mark(void *ptr) {
+    struct buffer *buf = ptr;
+#if defined(STACHE_STRING_BUFFER)
+    rb_gc_mark(buf->output);
+#endif
+    rb_gc_mark(buf->memo);
+    rb_gc_mark(buf->cache);
+    rb_gc_mark(buf->helpers);
+    rb_gc_mark(buf->translator);
+    rb_gc_mark(buf->trace);
+}
+
+static void buffer_free(void *ptr) {
+    buffer_destroy(ptr);
+    free(ptr);
+}
+
+static size_t buffer_memsize(const void *ptr) {
+    return sizeof(struct buffer);
+}
+
+static const rb_data_type_t buffer_data_type = {
+    STACHE_BUFFER_TYPE,
+    {
+        buffer_mark,
+        buffer_free,
+        buffer_memsize
+    },
+    0, 0, RUBY_TYPED_FREE_IMMEDIATELY
+};
+
+static VALUE templates_init(VALUE self) {
+    struct buffer *buf = calloc(1, sizeof(struct buffer));
+    if (!buf) {
+        rb_raise(rb_eRuntimeError, "Memory allocation failed");
+    }
+    buffer_init(buf);
+    buf->memo = Qnil;
+    buf->cache = Qnil;
+    buf->helpers = Qnil;
+    buf->translator = Qnil;
+    buf->trace = Qnil;
+
+    VALUE wrapper = TypedData_Wrap_Struct(Buffer, &buffer_data_type, buf);
+    rb_ivar_set(self, id_buf, wrapper);
+
+    buf->memo = rb_hash_new();
+    buf->cache = rb_hash_new();
+    buf->helpers = rb_hash_new();
+    return self;
+}
+
+STACHE_API struct buffer *templates_get_buf(VALUE self) {
+    VALUE wrapper = rb_ivar_get(self, id_buf);
+    struct buffer *buf;
+    TypedData_Get_Struct(wrapper, struct buffer, &buffer_data_type, buf);
+    return buf;
+}
+
+static VALUE templates_clear_cache(VALUE self) {
+    struct buffer *buf = templates_get_buf(self);
+    rb_hash_clear(buf->cache);
+    return self;
+}
+
+/* Registers the callable a filter name in variable tags calls, like
+   `{{ name | upcase }}`, with the value to transform. */
+static VALUE templates_register_helper(VALUE self, VALUE name, VALUE helper) {
+    struct buffer *buf = templates_get_buf(self);
+    rb_hash_aset(buf->helpers, rb_obj_as_string(name), helper);
+    return self;
+}
+
+/* Sets the callable translation tags, like `{{t "checkout.title"}}`, call
+   with their key for the text to render, or nil to render keys as is. */
+static VALUE templates_set_translator(VALUE self, VALUE translator) {
+    templates_get_buf(self)->translator = translator;
+    return translator;
+}
+
+#if defined(STACHE_TRACE)
+static VALUE trace_render(VALUE args) {
+    const VALUE *argv = (const VALUE *)args;
+    return render(argv[0], argv[1], argv[2]);
+}
+
+static VALUE trace_stop(VALUE self) {
+    templates_get_buf(self)->trace = Qnil;
+    return Qnil;
+}
+
+static VALUE templates_render_trace(VALUE self, VALUE name, VALUE context) {
+    struct buffer *buf = templates_get_buf(self);
+    VALUE trace = rb_ary_new();
+    buf->trace = trace;
+
+    VALUE args[3] = { self, name, context };
+    VALUE output = rb_ensure(trace_render, (VALUE)args, trace_stop, self);
+    return rb_ary_new_from_args(2, output, trace);
+}
+#endif
+
+static VALUE escape_html(VALUE self, VALUE value) {
+    VALUE str = rb_obj_as_string(value);
+    VALUE escaped = optimized_escape_html(str);
+    if (escaped == str) {
+        return rb_str_dup(str);
+    }
+    rb_enc_copy(escaped, str);
+    return escaped;
+}
+
+STACHE_API void init_templates(VALUE namespace) {
+    rb_define_module_function(namespace, "escape_html", escape_html, 1);
+
+    VALUE Templates = rb_define_class_under(namespace, STACHE_CLASS, rb_cObject);
+    rb_define_method(Templates, "initialize", templates_init, 0);
+    rb_define_method(Templates, "render", render, 2);
+    rb_define_method(Templates, "clear_cache", templates_clear_cache, 0);
+    rb_define_method(Templates, "register_helper", templates_register_helper, 2);
+    rb_define_method(Templates, "translator=", templates_set_translator, 1);
+#if defined(STACHE_TRACE)
+    rb_define_method(Templates, "render_trace", templates_render_trace, 2);
+#endif
+
+    Buffer = rb_define_class_under(namespace, "Buffer", rb_cData);
+
+    id_to_s = rb_intern("to_s");
+    id_call = rb_intern("call");
+    id_keys = rb_intern("keys");
+    id_miss = rb_intern("__stache__miss__");
+    id_buf = rb_intern("@buf");
+}
+"#
+);
+
+/// The public interface of a compiled C library: the context value types
+/// applications build to render templates with.
+///
+/// Context types are guarded separately from the rest of the library header,
+/// so several bundles' headers may be included into one source file.
+pub const LIBRARY_API: &str = r#"
+#include <stdbool.h>
+#include <stddef.h>
+#include <string.h>
+
+#ifndef STACHE_CTX
+#define STACHE_CTX
+
+enum ctx_type {
+    CTX_NULL,
+    CTX_BOOL,
+    CTX_INTEGER,
+    CTX_STRING,
+    CTX_LIST,
+    CTX_MAP
+};
+
+struct ctx_entry;
+
+struct ctx {
+    enum ctx_type type;
+    size_t length;
+    union {
+        bool boolean;
+        long long integer;
+        const char *string;
+        const struct ctx *items;
+        const struct ctx_entry *entries;
+    } as;
+};
+
+struct ctx_entry {
+    const char *key;
+    struct ctx value;
+};
+
+static inline struct ctx ctx_null(void) {
+    struct ctx value = { .type = CTX_NULL };
+    return value;
+}
+
+static inline struct ctx ctx_bool(bool boolean) {
+    struct ctx value = { .type = CTX_BOOL, .as.boolean = boolean };
+    return value;
+}
+
+static inline struct ctx ctx_integer(long long integer) {
+    struct ctx value = { .type = CTX_INTEGER, .as.integer = integer };
+    return value;
+}
+
+static inline struct ctx ctx_string(const char *string) {
+    struct ctx value = { .type = CTX_STRING, .length = strlen(string), .as.string = string };
+    return value;
+}
+
+static inline struct ctx ctx_list(const struct ctx *items, size_t length) {
+    struct ctx value = { .type = CTX_LIST, .length = length, .as.items = items };
+    return value;
+}
+
+static inline struct ctx ctx_map(const struct ctx_entry *entries, size_t length) {
+    struct ctx value = { .type = CTX_MAP, .length = length, .as.entries = entries };
+    return value;
+}
+
+#endif
+"#;
+
+/// Declarations shared by the C library runtime and the generated template
+/// functions. The stack, buffer, and path types match the Ruby extension's,
+/// so template functions are generated identically for both, with context
+/// frames holding library context values rather than Ruby objects.
+pub const LIBRARY_HEADER: &str = concat!(
+    r#"
+#include <stdint.h>
+#include <stdlib.h>
+"#,
+    sanitizer!(),
+    r#"
+struct stack {
+    const struct ctx *data;
+    const struct stack *parent;
+};
+
+struct indent {
+    const char *text;
+    size_t length;
+    const struct indent *parent;
+};
+
+struct buffer {
+    char *data;
+    size_t capacity;
+    size_t length;
+    bool failed;
+    const struct indent *indent;
+    bool line_start;
+};
+
+struct path {
+    const char *keys[16];
+    int length;
+};
+
+STACHE_API void buffer_clear(struct buffer *this);
+STACHE_API void buffer_trim(struct buffer *this, bool whitespace);
+STACHE_API bool buffer_reserve(struct buffer *this, size_t length);
+STACHE_API bool buffer_append(struct buffer *this, const char *value, size_t length);
+STACHE_API char *buffer_finish(struct buffer *this);
+STACHE_API char *string_copy(const char *value, size_t length);
+STACHE_API bool append_content(struct buffer *buf, const char *value, size_t length);
+STACHE_API void indent_push(struct buffer *buf, struct indent *indent, const char *text, size_t length);
+STACHE_API void indent_pop(struct buffer *buf, const struct indent *indent);
+STACHE_API void append_value(struct buffer *buf, const struct stack *stack, const struct path *path, bool escape);
+STACHE_API bool partial_name(struct buffer *buf, const struct stack *stack, const struct path *path, char *name, size_t size, size_t *length);
+STACHE_API void section(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *));
+STACHE_API void inverted(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *));
+"#
+);
+
+/// The C library runtime function definitions. Allocation failures are
+/// recorded in the buffer, which discards the output when rendering
+/// finishes rather than returning it incomplete.
+pub const LIBRARY_RUNTIME: &str = concat!(
+    native_buffer!(),
+    r#"
+STACHE_API char *buffer_finish(struct buffer *this) {
+    if (!this->failed && buffer_append(this, "", 1)) {
+        char *data = this->data;
+        STACHE_UNPOISON(data, this->capacity);
+        this->data = NULL;
+        this->capacity = 0;
+        this->length = 0;
+        return data;
+    }
+    buffer_destroy(this);
+    return NULL;
+}
+
+STACHE_API char *string_copy(const char *value, size_t length) {
+    char *copy = malloc(length + 1);
+    if (copy) {
+        memcpy(copy, value, length);
+        copy[length] = '\0';
+    }
+    return copy;
+}
+"#,
+    indentation!(),
+    escaped_append!(),
+    r#"
+static size_t format_integer(char *digits, size_t size, long long integer) {
+    unsigned long long magnitude = integer < 0 ? 0ULL - (unsigned long long)integer : (unsigned long long)integer;
+    size_t start = size;
+    do {
+        digits[--start] = (char)('0' + magnitude % 10);
+        magnitude /= 10;
+    } while (magnitude > 0);
+    if (integer < 0) {
+        digits[--start] = '-';
+    }
+    return start;
+}
+
+/* Parses a key made only of digits, like the 0 in items.0, as a list index. */
+static bool list_index(const char *key, size_t *index) {
+    size_t value = 0;
+    for (const char *c = key; *c; c++) {
+        if (*c < '0' || *c > '9' || value > (SIZE_MAX - 9) / 10) {
+            return false;
+        }
+        value = value * 10 + (size_t)(*c - '0');
+    }
+    *index = value;
+    return *key != '\0';
+}
+
+static const struct ctx *fetch(const struct ctx *context, const char *key) {
+    if (strcmp(key, DOT) == 0) {
+        return context;
+    }
+
+    if (!context) {
+        return NULL;
+    }
+
+    size_t index;
+    switch (context->type) {
+        case CTX_MAP:
+            for (size_t i = 0; i < context->length; i++) {
+                if (strcmp(context->as.entries[i].key, key) == 0) {
+                    return &context->as.entries[i].value;
+                }
+            }
+            return NULL;
+        case CTX_LIST:
+            if (list_index(key, &index) && index < context->length) {
+                return &context->as.items[index];
+            }
+            return NULL;
+        default:
+            return NULL;
+    }
+}
+
+static const struct ctx *context_fetch(const struct stack *stack, const char *key) {
+    do {
+        const struct ctx *value = fetch(stack->data, key);
+        if (value) {
+            return value;
+        }
+    } while ((stack = stack->parent));
+    return NULL;
+}
+
+static const struct ctx *fetch_path(const struct stack *stack, const struct path *path) {
+    STACHE_ASSERT(path->length > 0 && path->length <= 16);
+    const struct ctx *value = context_fetch(stack, path->keys[0]);
+    for (int i = 1; i < path->length && value; i++) {
+        value = fetch(value, path->keys[i]);
+    }
+    return value;
+}
+
+static bool falsy(const struct ctx *value) {
+    if (!value) {
+        return true;
+    }
+    switch (value->type) {
+        case CTX_NULL:
+            return true;
+        case CTX_BOOL:
+            return !value->as.boolean;
+        case CTX_LIST:
+            return value->length == 0;
+        default:
+            return false;
+    }
+}
+
+STACHE_API void append_value(struct buffer *buf, const struct stack *stack, const struct path *path, bool escape) {
+    begin_line(buf);
+    const struct ctx *value = fetch_path(stack, path);
+    char digits[24];
+    const char *text;
+    size_t length;
+
+    switch (value ? value->type : CTX_NULL) {
+        case CTX_NULL:
+#if defined(STACHE_MISSING_PLACEHOLDER)
+            buffer_append(buf, STACHE_MISSING_PLACEHOLDER, sizeof(STACHE_MISSING_PLACEHOLDER) - 1);
+#endif
+            return;
+        case CTX_BOOL:
+            text = value->as.boolean ? "true" : "false";
+            length = strlen(text);
+            break;
+        case CTX_INTEGER: {
+            size_t start = format_integer(digits, sizeof(digits), value->as.integer);
+            text = digits + start;
+            length = sizeof(digits) - start;
+            break;
+        }
+        case CTX_STRING:
+            text = value->as.string;
+            length = value->length;
+            break;
+        default:
+            return;
+    }
+
+    if (escape) {
+        append_escaped(buf, text, length);
+    } else {
+        buffer_append(buf, text, length);
+    }
+}
+
+STACHE_API bool partial_name(struct buffer *buf, const struct stack *stack, const struct path *path, char *name, size_t size, size_t *length) {
+    (void)buf;
+    const struct ctx *value = fetch_path(stack, path);
+    if (!value || value->type != CTX_STRING || value->length >= size) {
+        return false;
+    }
+    *length = value->length;
+    memcpy(name, value->as.string, *length);
+    return true;
+}
+
+STACHE_API void section(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *)) {
+    const struct ctx *value = fetch_path(stack, path);
+    if (falsy(value)) {
+        return;
+    }
+
+    switch (value->type) {
+        case CTX_LIST: {
+            struct stack frame = { .parent = stack };
+            for (size_t i = 0; i < value->length; i++) {
+                frame.data = &value->as.items[i];
+                block(buf, &frame);
+            }
+            break;
+        }
+        case CTX_BOOL:
+            block(buf, stack);
+            break;
+        default: {
+            const struct stack frame = { .data = value, .parent = stack };
+            block(buf, &frame);
+            break;
+        }
+    }
+}
+
+STACHE_API void inverted(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *)) {
+    if (falsy(fetch_path(stack, path))) {
+        block(buf, stack);
+    }
+}
+"#
+);
+
+/// Declarations shared by the Node.js addon runtime and the generated
+/// template functions. Context frames hold JavaScript values, which are
+/// fetched through the N-API environment carried by the render buffer.
+pub const ADDON_HEADER: &str = concat!(
+    r#"
+#include <node_api.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+"#,
+    sanitizer!(),
+    r#"
+struct stack {
+    napi_value data;
+    const struct stack *parent;
+};
+
+struct indent {
+    const char *text;
+    size_t length;
+    const struct indent *parent;
+};
+
+struct buffer {
+    char *data;
+    size_t capacity;
+    size_t length;
+    bool failed;
+    napi_env env;
+    const struct indent *indent;
+    bool line_start;
+};
+
+struct path {
+    const char *keys[16];
+    int length;
+};
+
+STACHE_API void buffer_clear(struct buffer *this);
+STACHE_API void buffer_trim(struct buffer *this, bool whitespace);
+STACHE_API bool buffer_reserve(struct buffer *this, size_t length);
+STACHE_API bool buffer_append(struct buffer *this, const char *value, size_t length);
+STACHE_API napi_value string_value(napi_env env, const char *value, size_t length);
+STACHE_API napi_value buffer_result(struct buffer *this);
+STACHE_API void template_not_found(napi_env env, napi_value name);
+STACHE_API bool append_content(struct buffer *buf, const char *value, size_t length);
+STACHE_API void indent_push(struct buffer *buf, struct indent *indent, const char *text, size_t length);
+STACHE_API void indent_pop(struct buffer *buf, const struct indent *indent);
+STACHE_API void append_value(struct buffer *buf, const struct stack *stack, const struct path *path, bool escape);
+STACHE_API bool partial_name(struct buffer *buf, const struct stack *stack, const struct path *path, char *name, size_t size, size_t *length);
+STACHE_API void section(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *));
+STACHE_API void inverted(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *));
+STACHE_API napi_value render(napi_env env, napi_callback_info info);
+"#
+);
+
+/// The Node.js addon runtime function definitions. Context lookups follow
+/// the JavaScript backend: a key is read as a property of the frame's value,
+/// calling it as a method when it's a function.
+pub const ADDON_RUNTIME: &str = concat!(
+    native_buffer!(),
+    indentation!(),
+    escaped_append!(),
+    r#"
+STACHE_API napi_value string_value(napi_env env, const char *value, size_t length) {
+    napi_value result;
+    if (napi_create_string_utf8(env, value, length, &result) != napi_ok) {
+        return NULL;
+    }
+    return result;
+}
+
+static napi_valuetype type_of(napi_env env, napi_value value) {
+    napi_valuetype type;
+    if (!value || napi_typeof(env, value, &type) != napi_ok) {
+        return napi_undefined;
+    }
+    return type;
+}
+
+static napi_value fetch(napi_env env, napi_value context, const char *key) {
+    if (strcmp(key, DOT) == 0) {
+        return context;
+    }
+
+    napi_value object;
+    switch (type_of(env, context)) {
+        case napi_undefined:
+        case napi_null:
+            return NULL;
+        case napi_boolean: {
+            bool value;
+            napi_get_value_bool(env, context, &value);
+            if (!value) {
+                return context;
+            }
+        }
+        /* fall through */
+        default:
+            if (napi_coerce_to_object(env, context, &object) != napi_ok) {
+                return NULL;
+            }
+            break;
+    }
+
+    napi_value value;
+    if (napi_get_named_property(env, object, key, &value) != napi_ok) {
+        return NULL;
+    }
+
+    switch (type_of(env, value)) {
+        case napi_undefined:
+            return NULL;
+        case napi_function:
+            if (napi_call_function(env, context, value, 0, NULL, &value) != napi_ok) {
+                return NULL;
+            }
+            return value;
+        default:
+            return value;
+    }
+}
+
+static napi_value context_fetch(napi_env env, const struct stack *stack, const char *key) {
+    do {
+        napi_value value = fetch(env, stack->data, key);
+        if (value && type_of(env, value) != napi_undefined) {
+            return value;
+        }
+    } while ((stack = stack->parent));
+    return NULL;
+}
+
+static napi_value fetch_path(napi_env env, const struct stack *stack, const struct path *path) {
+    STACHE_ASSERT(path->length > 0 && path->length <= 16);
+    napi_value value = context_fetch(env, stack, path->keys[0]);
+    for (int i = 1; i < path->length && value; i++) {
+        value = fetch(env, value, path->keys[i]);
+    }
+    return value;
+}
+
+static bool falsy(napi_env env, napi_value value) {
+    switch (type_of(env, value)) {
+        case napi_undefined:
+        case napi_null:
+            return true;
+        case napi_boolean: {
+            bool result;
+            napi_get_value_bool(env, value, &result);
+            return !result;
+        }
+        default:
+            return false;
+    }
+}
+
+static bool append_string(struct buffer *buf, napi_value value, bool escape) {
+    napi_env env = buf->env;
+    size_t length;
+    if (napi_coerce_to_string(env, value, &value) != napi_ok ||
+            napi_get_value_string_utf8(env, value, NULL, 0, &length) != napi_ok) {
+        return false;
+    }
+
+    char *text = malloc(length + 1);
+    if (!text) {
+        buf->failed = true;
+        return false;
+    }
+
+    bool ok = napi_get_value_string_utf8(env, value, text, length + 1, &length) == napi_ok;
+    if (ok && escape) {
+        append_escaped(buf, text, length);
+    } else if (ok) {
+        buffer_append(buf, text, length);
+    }
+    free(text);
+    return ok;
+}
+
+STACHE_API void append_value(struct buffer *buf, const struct stack *stack, const struct path *path, bool escape) {
+    begin_line(buf);
+    napi_value value = fetch_path(buf->env, stack, path);
+    switch (type_of(buf->env, value)) {
+        case napi_undefined:
+        case napi_null:
+#if defined(STACHE_MISSING_PLACEHOLDER)
+            buffer_append(buf, STACHE_MISSING_PLACEHOLDER, sizeof(STACHE_MISSING_PLACEHOLDER) - 1);
+#endif
+            return;
+        default:
+            append_string(buf, value, escape);
+            return;
+    }
+}
+
+STACHE_API bool partial_name(struct buffer *buf, const struct stack *stack, const struct path *path, char *name, size_t size, size_t *length) {
+    napi_env env = buf->env;
+    napi_value value = fetch_path(env, stack, path);
+    if (falsy(env, value) ||
+            napi_coerce_to_string(env, value, &value) != napi_ok ||
+            napi_get_value_string_utf8(env, value, NULL, 0, length) != napi_ok ||
+            *length >= size) {
+        return false;
+    }
+    return napi_get_value_string_utf8(env, value, name, size, length) == napi_ok;
+}
+
+STACHE_API void section(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *)) {
+    napi_env env = buf->env;
+    napi_value value = fetch_path(env, stack, path);
+    if (falsy(env, value)) {
+        return;
+    }
+
+    bool array = false;
+    napi_is_array(env, value, &array);
+    if (array) {
+        uint32_t length = 0;
+        napi_get_array_length(env, value, &length);
+        struct stack frame = { .parent = stack };
+        for (uint32_t i = 0; i < length; i++) {
+            napi_handle_scope scope;
+            if (napi_open_handle_scope(env, &scope) != napi_ok) {
+                return;
+            }
+            if (napi_get_element(env, value, i, &frame.data) == napi_ok) {
+                block(buf, &frame);
+            }
+            napi_close_handle_scope(env, scope);
+        }
+    } else if (type_of(env, value) == napi_boolean) {
+        block(buf, stack);
+    } else {
+        const struct stack frame = { .data = value, .parent = stack };
+        block(buf, &frame);
+    }
+}
+
+STACHE_API void inverted(struct buffer *buf, const struct stack *stack, const struct path *path, void (*block)(struct buffer *, const struct stack *)) {
+    napi_env env = buf->env;
+    napi_value value = fetch_path(env, stack, path);
+
+    bool array = false;
+    napi_is_array(env, value, &array);
+    if (array) {
+        uint32_t length = 0;
+        napi_get_array_length(env, value, &length);
+        if (length == 0) {
+            block(buf, stack);
+        }
+    } else if (falsy(env, value)) {
+        block(buf, stack);
+    }
+}
+
+STACHE_API napi_value buffer_result(struct buffer *this) {
+    napi_value result = NULL;
+    if (this->failed) {
+        napi_throw_error(this->env, NULL, "Memory allocation failed");
+    } else {
+        result = string_value(this->env, this->data, this->length);
+    }
+    buffer_destroy(this);
+    return result;
+}
+
+STACHE_API void template_not_found(napi_env env, napi_value name) {
+    static const char message[] = "Template not found: ";
+    struct buffer buffer = { .failed = false, .env = env };
+    if (buffer_init(&buffer) && buffer_append(&buffer, message, sizeof(message) - 1) &&
+            append_string(&buffer, name, false) && buffer_append(&buffer, "", 1)) {
+        napi_throw_error(env, NULL, buffer.data);
+    } else {
+        napi_throw_error(env, NULL, "Template not found");
+    }
+    buffer_destroy(&buffer);
+}
+
+static napi_value escape_html(napi_env env, napi_callback_info info) {
+    size_t argc = 1;
+    napi_value value;
+    if (napi_get_cb_info(env, info, &argc, &value, NULL, NULL) != napi_ok) {
+        return NULL;
+    }
+    if (argc < 1) {
+        napi_get_undefined(env, &value);
+    }
+
+    struct buffer buffer = { .failed = false, .env = env };
+    if (!buffer_init(&buffer)) {
+        napi_throw_error(env, NULL, "Memory allocation failed");
+        return NULL;
+    }
+
+    napi_value result = NULL;
+    if (append_string(&buffer, value, true)) {
+        result = string_value(env, buffer.data, buffer.length);
+    } else if (buffer.failed) {
+        napi_throw_error(env, NULL, "Memory allocation failed");
+    }
+    buffer_destroy(&buffer);
+    return result;
+}
+
+static napi_value init_templates(napi_env env, napi_value exports) {
+    const napi_property_descriptor properties[] = {
+        { "render", NULL, render, NULL, NULL, NULL, napi_enumerable, NULL },
+        { "escapeHtml", NULL, escape_html, NULL, NULL, NULL, napi_enumerable, NULL },
+    };
+    if (napi_define_properties(env, exports, 2, properties) != napi_ok) {
+        return NULL;
+    }
+    return exports;
+}
+
+NAPI_MODULE_INIT() {
+    return init_templates(env, exports);
+}
+"#
+);
@@ -0,0 +1,781 @@
+//! Translates templates into plain Ruby source, defining the same
+//! `Templates#render` interface as the C extension without a native build
+//! step, for Ruby implementations like JRuby and TruffleRuby that can't load
+//! MRI extensions.
+
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{self, Write};
+
+use stache_core::ir::{self, Op};
+use stache_core::{Backend, Compile, Error, Path, Template};
+
+use super::{banner, manifest, validate_aliases, Missing, Options, Trim};
+
+/// The runtime methods called by the generated template methods, mirroring
+/// the C extension's runtime: context stack lookups, HTML escaping, and
+/// section iteration. Indented for the body of the `Templates` class.
+const RUNTIME: &str = r#"def clear_cache
+  self
+end
+
+def register_helper(name, helper)
+  helpers[name.to_s] = helper
+  self
+end
+
+attr_writer :translator
+
+private
+
+def escape_html(value)
+  value.gsub(ESCAPE, ESCAPES)
+end
+
+def fetch(context, key)
+  return context if key == "."
+
+  case context
+  when Hash
+    context.fetch(key.to_sym) { context.fetch(key, MISS) }
+  when false
+    false
+  when nil, MISS
+    MISS
+  when Array
+    if key.match?(/\A[0-9]+\z/)
+      context.fetch(key.to_i, MISS)
+    else
+      context.respond_to?(key) ? context.public_send(key) : MISS
+    end
+  else
+    context.respond_to?(key) ? context.public_send(key) : MISS
+  end
+end
+
+def lookup(stack, keys)
+  value = MISS
+  stack.reverse_each do |frame|
+    value = fetch(frame, keys[0])
+    break unless MISS.equal?(value)
+  end
+  1.upto(keys.length - 1) do |i|
+    break if MISS.equal?(value)
+    value = fetch(value, keys[i])
+  end
+  value
+end
+
+def append(buf, stack, keys, escape)
+  value = lookup(stack, keys)
+  if value.nil? || MISS.equal?(value)
+    missing(buf, keys)
+  else
+    value = value.to_s
+    begin_line(buf)
+    buf << (escape ? escape_html(value) : value)
+  end
+end
+
+def append_required(buf, stack, keys, escape)
+  value = lookup(stack, keys)
+  if value.nil? || MISS.equal?(value)
+    raise KeyError, "Missing template value: #{keys.join(".")}"
+  end
+
+  value = value.to_s
+  begin_line(buf)
+  buf << (escape ? escape_html(value) : value)
+end
+
+def helpers
+  @helpers ||= {}
+end
+
+def filtered(buf, stack, keys, filters)
+  value = lookup(stack, keys)
+  value = nil if MISS.equal?(value)
+  filters.each do |name|
+    helper = helpers.fetch(name) { raise KeyError, "Unknown template helper: #{name}" }
+    value = helper.call(value)
+  end
+  if value.nil?
+    missing(buf, keys)
+  else
+    begin_line(buf)
+    buf << escape_html(value.to_s)
+  end
+end
+
+def translate(buf, key)
+  value = @translator ? @translator.call(key).to_s : key
+  begin_line(buf)
+  buf << escape_html(value)
+end
+
+def begin_line(buf)
+  return unless buf.line_start
+
+  buf.line_start = false
+  buf << buf.indent
+end
+
+def indent_push(buf, text)
+  buf.indent += text
+  buf.line_start = true
+end
+
+def indent_pop(buf, text)
+  buf.indent = buf.indent[0, buf.indent.length - text.length]
+  buf.line_start = true
+end
+
+def content(buf, text)
+  return buf << text if buf.indent.empty?
+
+  text.each_line do |line|
+    begin_line(buf)
+    buf << line
+    buf.line_start = line.end_with?("\n")
+  end
+end
+
+def section(stack, keys)
+  value = lookup(stack, keys)
+  case value
+  when Array
+    meta = {}
+    stack.push(meta)
+    value.each_with_index do |item, i|
+      meta[:@index] = i
+      meta[:@first] = i.zero?
+      meta[:@last] = i == value.length - 1
+      stack.push(item)
+      yield
+      stack.pop
+    end
+    stack.pop
+  when nil, false, MISS
+    nil
+  when true
+    yield
+  else
+    stack.push(value)
+    yield
+    stack.pop
+  end
+end
+
+def partial_with(stack, keys, paths)
+  frame = {}
+  keys.each_with_index do |key, i|
+    value = lookup(stack, paths[i])
+    frame[key] = MISS.equal?(value) ? nil : value
+  end
+  stack.push(frame)
+  yield
+  stack.pop
+end
+
+def inverted(stack, keys)
+  value = lookup(stack, keys)
+  case value
+  when Array
+    yield if value.empty?
+  when nil, false, MISS
+    yield
+  end
+end
+"#;
+
+/// The output string of a render call, holding the indentation of the
+/// standalone partials being rendered, added to each line they output.
+const BUFFER: &str = r#"class Buffer < String
+  attr_accessor :indent, :line_start
+
+  def initialize
+    super("")
+    @indent = ""
+    @line_start = false
+  end
+end
+private_constant :Buffer
+"#;
+
+/// The HTML escaping constants and module function defined in the
+/// namespace, like the extension's `escape_html`.
+const ESCAPING: &str = r#"ESCAPE = /['&"<>]/.freeze
+ESCAPES = {
+  "'" => "&#39;",
+  "&" => "&amp;",
+  '"' => "&quot;",
+  "<" => "&lt;",
+  ">" => "&gt;"
+}.freeze
+private_constant :ESCAPE, :ESCAPES
+
+def self.escape_html(value)
+  value.to_s.gsub(ESCAPE, ESCAPES)
+end
+"#;
+
+/// A program is the final result of translating Mustache templates into
+/// Ruby source defining a `Templates` class, like the extension's.
+#[derive(Debug)]
+pub struct Program {
+    methods: Vec<Method>,
+    options: Options,
+}
+
+/// A template translated into the body of its private render method.
+#[derive(Debug)]
+struct Method {
+    export: String,
+    id: String,
+    body: String,
+    trim: Trim,
+}
+
+impl Compile for Program {
+    /// Writes the namespace modules enclosing the escaping functions and the
+    /// `Templates` class with its runtime and template methods.
+    fn emit(&self, buf: &mut dyn Write) -> io::Result<()> {
+        let _span = info_span!("emit", target = "ruby-source").entered();
+        writeln!(buf, "# frozen_string_literal: true")?;
+        let lines = banner(&self.options);
+        if !lines.is_empty() {
+            writeln!(buf)?;
+            for line in lines {
+                if line.is_empty() {
+                    writeln!(buf, "#")?;
+                } else {
+                    writeln!(buf, "# {}", line)?;
+                }
+            }
+        }
+        writeln!(buf)?;
+
+        let namespace = self.options.namespace();
+        for (depth, module) in namespace.iter().enumerate() {
+            writeln!(buf, "{}module {}", "  ".repeat(depth), module)?;
+        }
+
+        let depth = namespace.len();
+        let mut body = String::new();
+        if let Some(ref provenance) = self.options.provenance {
+            let templates = self.methods.iter().map(|method| &method.export);
+            let manifest = manifest(provenance, templates);
+            body.push_str(&format!("MANIFEST = {}.freeze\n\n", string(&manifest)));
+        }
+        body.push_str(ESCAPING);
+        body.push_str(&format!("\nclass {}\n", self.options.class()));
+        body.push_str(&indent(&self.class(), 1));
+        body.push_str("end\n");
+        buf.write_all(indent(&body, depth).as_bytes())?;
+
+        for depth in (0..depth).rev() {
+            writeln!(buf, "{}end", "  ".repeat(depth))?;
+        }
+        Ok(())
+    }
+}
+
+impl Program {
+    /// Returns the body of the `Templates` class: the public render method
+    /// dispatching on template name, the runtime, and the template methods.
+    fn class(&self) -> String {
+        let mut out = String::from("MISS = Object.new.freeze\nprivate_constant :MISS\n\n");
+        out.push_str(BUFFER);
+        out.push('\n');
+        out.push_str("def render(name, context)\n");
+        out.push_str("  name = String.try_convert(name)\n");
+        out.push_str("  raise TypeError, \"Template name must be a String\" unless name\n\n");
+        out.push_str("  stack = [context]\n");
+        out.push_str("  buf = Buffer.new\n");
+        out.push_str("  case name\n");
+        for method in &self.methods {
+            out.push_str(&format!("  when {}\n", self.names(method)));
+            out.push_str(&format!("    render_{}(buf, stack)\n", method.id));
+            match method.trim {
+                Trim::Keep => (),
+                Trim::Newline => out.push_str("    buf.chomp!(\"\\n\")\n"),
+                Trim::Whitespace => out.push_str("    buf.sub!(/[ \\t\\r\\n\\f\\v]+\\z/, \"\")\n"),
+            }
+        }
+        out.push_str("  else\n");
+        out.push_str("    raise ArgumentError, \"Template not found\"\n");
+        out.push_str("  end\n");
+        out.push_str("  String.new(buf)\n");
+        out.push_str("end\n\n");
+
+        out.push_str(RUNTIME);
+        out.push_str("\ndef missing(buf, keys)\n");
+        match self.options.missing {
+            Missing::Empty => out.push_str("  nil\n"),
+            Missing::Placeholder(ref text) => {
+                out.push_str("  begin_line(buf)\n");
+                out.push_str(&format!("  buf << {}\n", string(text)));
+            }
+            Missing::Raise => {
+                out.push_str("  raise KeyError, \"Missing template value: #{keys.join(\".\")}\"\n")
+            }
+        }
+        out.push_str("end\n");
+
+        if !self.methods.is_empty() {
+            out.push_str("\ndef dynamic_partial(buf, stack, keys)\n");
+            out.push_str("  value = lookup(stack, keys)\n");
+            out.push_str("  return if value.nil? || MISS.equal?(value)\n\n");
+            out.push_str("  case value.to_s\n");
+            for method in &self.methods {
+                out.push_str(&format!("  when {}\n", self.names(method)));
+                out.push_str(&format!("    render_{}(buf, stack)\n", method.id));
+            }
+            out.push_str("  end\n");
+            out.push_str("end\n");
+        }
+
+        for method in &self.methods {
+            out.push_str(&format!("\ndef render_{}(buf, stack)\n", method.id));
+            out.push_str(&method.body);
+            out.push_str("end\n");
+        }
+        out
+    }
+
+    /// Returns the string literals of the names a template is rendered by:
+    /// its own and its aliases.
+    fn names(&self, method: &Method) -> String {
+        let names: Vec<_> = Some(&method.export)
+            .into_iter()
+            .chain(
+                self.options
+                    .aliases
+                    .iter()
+                    .filter(|(_, name)| *name == method.export)
+                    .map(|(alias, _)| alias),
+            )
+            .map(|name| string(name))
+            .collect();
+        names.join(", ")
+    }
+}
+
+/// The `ruby-source` backend, compiling templates into plain Ruby source.
+#[derive(Debug)]
+pub struct Compiler {
+    pub options: Options,
+}
+
+impl Backend for Compiler {
+    fn name(&self) -> &str {
+        "ruby-source"
+    }
+
+    fn link(&self, templates: &[Template]) -> Result<Box<dyn Compile>, Error> {
+        Ok(Box::new(link(templates, &self.options)?))
+    }
+}
+
+/// Transforms the AST of each parsed template into a Ruby render method,
+/// linking partial calls to the methods of other templates.
+pub fn link(templates: &[Template], options: &Options) -> Result<Program, Error> {
+    let _span = info_span!("link", templates = templates.len()).entered();
+    let defined = Template::define_all(templates, &options.defines);
+    let templates = defined.as_deref().unwrap_or(templates);
+
+    let program = ir::lower(templates)?;
+    validate_aliases(templates, &options.aliases)?;
+    validate(options)?;
+
+    // Template content is appended line by line when any partial is indented,
+    // since any template may be rendered as that partial.
+    let indented = program.functions.iter().any(|fun| ir::indented(&fun.body));
+    let methods = program
+        .functions
+        .iter()
+        .map(|fun| {
+            let _span = debug_span!("transform", template = %fun.name).entered();
+            let mut body = String::new();
+            transform(&fun.body, options, indented, 1, &mut body).expect("write to string");
+            Method {
+                export: fun.name.clone(),
+                id: options.name(&fun.name).id().to_string(),
+                body,
+                trim: options.trim(&fun.name),
+            }
+        })
+        .collect();
+
+    Ok(Program {
+        methods,
+        options: options.clone(),
+    })
+}
+
+/// Ensures the options only use extension features the Ruby source
+/// provides.
+fn validate(options: &Options) -> Result<(), Error> {
+    let unsupported = [
+        ("Memoized partials are", !options.memoize.is_empty()),
+        ("Lookup tracing is", options.trace_lookups),
+        ("Iterating hashes is", options.iterate_hashes),
+        ("Init function names are", options.init.is_some()),
+    ];
+    let errors = unsupported
+        .iter()
+        .filter(|&&(_, used)| used)
+        .map(|&(option, _)| {
+            Error::Backend(format!(
+                "{} not supported by the ruby-source target",
+                option
+            ))
+        })
+        .collect();
+    Error::all(errors)
+}
+
+/// Writes the Ruby statements performing each operation into the body of
+/// the enclosing method.
+///
+/// Sections are translated into blocks the runtime yields to once for each
+/// item the section's path resolves to, with the item pushed onto the
+/// context stack. Partials call the render method of the template providing
+/// them, and dynamic partials dispatch on the template name found in the
+/// context. A standalone partial's padding indents each line it renders,
+/// which requires content to be appended line by line when the program has
+/// one.
+fn transform(
+    ops: &[Op],
+    options: &Options,
+    indented: bool,
+    depth: usize,
+    out: &mut String,
+) -> fmt::Result {
+    let indent = "  ".repeat(depth);
+    for op in ops {
+        match *op {
+            Op::Section(ref path, ref body, _) | Op::Inverted(ref path, ref body) => {
+                let fun = match *op {
+                    Op::Section(..) => "section",
+                    _ => "inverted",
+                };
+                writeln!(out, "{}{}(stack, {}) do", indent, fun, array(path))?;
+                transform(body, options, indented, depth + 1, out)?;
+                writeln!(out, "{}end", indent)?;
+            }
+            Op::Partial(ref name, ref padding, ref arguments) => {
+                let name = options.name(name);
+                indent_push(padding, &indent, out)?;
+                if arguments.is_empty() {
+                    writeln!(out, "{}render_{}(buf, stack)", indent, name.id())?;
+                } else {
+                    let keys: Vec<_> = arguments.iter().map(|(key, _)| string(key)).collect();
+                    let paths: Vec<_> = arguments.iter().map(|(_, path)| array(path)).collect();
+                    writeln!(
+                        out,
+                        "{}partial_with(stack, [{}], [{}]) do",
+                        indent,
+                        keys.join(", "),
+                        paths.join(", ")
+                    )?;
+                    writeln!(out, "{}  render_{}(buf, stack)", indent, name.id())?;
+                    writeln!(out, "{}end", indent)?;
+                }
+                indent_pop(padding, &indent, out)?;
+            }
+            Op::DynamicPartial(ref path, ref padding) => {
+                indent_push(padding, &indent, out)?;
+                writeln!(
+                    out,
+                    "{}dynamic_partial(buf, stack, {})",
+                    indent,
+                    array(path)
+                )?;
+                indent_pop(padding, &indent, out)?;
+            }
+            Op::Content(ref text) if indented => {
+                writeln!(out, "{}content(buf, {})", indent, string(text))?;
+            }
+            Op::Content(ref text) => {
+                writeln!(out, "{}buf << {}", indent, string(text))?;
+            }
+            Op::Translate(ref key) => {
+                writeln!(out, "{}translate(buf, {})", indent, string(key))?;
+            }
+            Op::Filtered(ref path, ref filters) => {
+                let names: Vec<_> = filters.iter().map(|name| string(name)).collect();
+                writeln!(
+                    out,
+                    "{}filtered(buf, stack, {}, [{}])",
+                    indent,
+                    array(path),
+                    names.join(", ")
+                )?;
+            }
+            Op::Append(ref path, escape) => {
+                writeln!(
+                    out,
+                    "{}append(buf, stack, {}, {})",
+                    indent,
+                    array(path),
+                    escape
+                )?;
+            }
+            Op::Require(ref path, escape) => {
+                writeln!(
+                    out,
+                    "{}append_required(buf, stack, {}, {})",
+                    indent,
+                    array(path),
+                    escape
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds a standalone partial's padding to the indentation of the lines it
+/// renders, or nothing for a partial called inline.
+fn indent_push(padding: &Option<String>, indent: &str, out: &mut String) -> fmt::Result {
+    match *padding {
+        Some(ref padding) => writeln!(out, "{}indent_push(buf, {})", indent, string(padding)),
+        None => Ok(()),
+    }
+}
+
+/// Removes the padding added by `indent_push` after the partial's call.
+fn indent_pop(padding: &Option<String>, indent: &str, out: &mut String) -> fmt::Result {
+    match *padding {
+        Some(ref padding) => writeln!(out, "{}indent_pop(buf, {})", indent, string(padding)),
+        None => Ok(()),
+    }
+}
+
+/// Formats the text as a double-quoted Ruby string literal, escaping `#` so
+/// it can't begin an interpolation.
+fn string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '#' => out.push_str("\\#"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Formats the path's keys as a Ruby array literal.
+fn array(path: &Path) -> String {
+    let keys: Vec<_> = path.keys.iter().map(|key| string(key)).collect();
+    format!("[{}]", keys.join(", "))
+}
+
+/// Indents each non-empty line of the source by the nesting depth.
+fn indent(source: &str, depth: usize) -> String {
+    let indent = "  ".repeat(depth);
+    source
+        .split_inclusive('\n')
+        .map(|line| {
+            if line == "\n" {
+                line.to_string()
+            } else {
+                format!("{}{}", indent, line)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{link, string, transform};
+    use stache_core::ir::lower_tree;
+    use stache_core::{Compile, Error, Statement, Template};
+    use {Missing, Options, Provenance, Trim};
+
+    fn emit(templates: &[Template], options: &Options) -> String {
+        let mut buf = Vec::new();
+        link(templates, options).unwrap().emit(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn transforms_tree_into_statements() {
+        let tree = Statement::parse(
+            "<p>{{#robots}}{{name.first}}{{> bio}}{{/robots}}{{^robots}}None{{/robots}}{{{bio}}}</p>",
+        )
+        .unwrap();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), &Options::default(), false, 1, &mut out).unwrap();
+
+        let expected = r#"  buf << "<p>"
+  section(stack, ["robots"]) do
+    append(buf, stack, ["name", "first"], true)
+    render_bio(buf, stack)
+  end
+  inverted(stack, ["robots"]) do
+    buf << "None"
+  end
+  append(buf, stack, ["bio"], false)
+  buf << "</p>"
+"#;
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn transforms_filtered_variables() {
+        let tree = Statement::parse("{{ name | upcase | truncate }}").unwrap();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), &Options::default(), false, 1, &mut out).unwrap();
+        assert_eq!(
+            "  filtered(buf, stack, [\"name\"], [\"upcase\", \"truncate\"])\n",
+            out
+        );
+    }
+
+    #[test]
+    fn transforms_partial_arguments() {
+        let tree = Statement::parse("{{> card title=user.name }}").unwrap();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), &Options::default(), false, 1, &mut out).unwrap();
+        let expected = r#"  partial_with(stack, ["title"], [["user", "name"]]) do
+    render_card(buf, stack)
+  end
+"#;
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn transforms_standalone_partials() {
+        let tree = Statement::parse("<ul>\n  {{> item}}\n  {{>*kind}}\n</ul>").unwrap();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), &Options::default(), true, 1, &mut out).unwrap();
+        let expected = r#"  content(buf, "<ul>\n")
+  indent_push(buf, "  ")
+  render_item(buf, stack)
+  indent_pop(buf, "  ")
+  indent_push(buf, "  ")
+  dynamic_partial(buf, stack, ["kind"])
+  indent_pop(buf, "  ")
+  content(buf, "</ul>")
+"#;
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn transforms_strict_variables() {
+        let tree = Statement::parse("{{%STRICT}}{{name}}").unwrap();
+        let mut out = String::new();
+        transform(&lower_tree(&tree), &Options::default(), false, 1, &mut out).unwrap();
+        assert_eq!("  append_required(buf, stack, [\"name\"], true)\n", out);
+    }
+
+    #[test]
+    fn transforms_translations() {
+        let tree = Statement::Translation(String::from("checkout.title"));
+        let mut out = String::new();
+        transform(&lower_tree(&tree), &Options::default(), false, 1, &mut out).unwrap();
+        assert_eq!("  translate(buf, \"checkout.title\")\n", out);
+    }
+
+    #[test]
+    fn escapes_string_literals() {
+        assert_eq!(
+            r##""a \"quote\"\n\\ \#{x} \u{0} ✓""##,
+            string("a \"quote\"\n\\ #{x} \u{0} ✓")
+        );
+    }
+
+    #[test]
+    fn defines_templates_class() {
+        let templates = vec![
+            Template::from_source("robots", "{{> includes/header}}{{>*name}}\n").unwrap(),
+            Template::from_source("includes/header", "<h1>Robots</h1>").unwrap(),
+        ];
+        let options = Options {
+            aliases: vec![(String::from("robots/old"), String::from("robots"))],
+            trim: Trim::Newline,
+            trim_only: vec![String::from("robots")],
+            ..Options::default()
+        };
+        let source = emit(&templates, &options);
+
+        assert!(source.starts_with("# frozen_string_literal: true\n\nmodule Stache\n  ESCAPE = "));
+        assert!(source.contains("\n  class Templates\n"));
+        assert!(source.contains(
+            "      when \"robots\", \"robots/old\"\n        render_robots(buf, stack)\n        buf.chomp!(\"\\n\")\n"
+        ));
+        assert!(source.contains(
+            "      when \"includes/header\"\n        render_includes_header(buf, stack)\n      else\n"
+        ));
+        assert!(source.contains(
+            "    def render_robots(buf, stack)\n      render_includes_header(buf, stack)\n      dynamic_partial(buf, stack, [\"name\"])\n"
+        ));
+        assert!(source.contains(
+            "      case value.to_s\n      when \"robots\", \"robots/old\"\n        render_robots(buf, stack)\n      when"
+        ));
+        assert!(source.contains("    def missing(buf, keys)\n      nil\n    end\n"));
+        assert!(source.ends_with("  end\nend\n"));
+    }
+
+    #[test]
+    fn nests_bundle_namespace() {
+        let templates = vec![Template::from_source("robot", "{{name}}").unwrap()];
+        let options = Options {
+            bundle: Some(String::from("admin_tools")),
+            missing: Missing::Placeholder(String::from("#missing")),
+            provenance: Some(Provenance {
+                source: String::from("app/templates"),
+                revision: None,
+            }),
+            ..Options::default()
+        };
+        let source = emit(&templates, &options);
+
+        assert!(source.contains("\nmodule Stache\n  module AdminTools\n    MANIFEST = \"{"));
+        assert!(source.contains("\\\"templates\\\":[\\\"robot\\\"],"));
+        assert!(source.contains("      def missing(buf, keys)\n        begin_line(buf)\n        buf << \"\\#missing\"\n"));
+        assert!(source.contains("# Generated by stache "));
+    }
+
+    #[test]
+    fn names_module_and_class() {
+        let templates = vec![Template::from_source("robot", "{{name}}").unwrap()];
+        let options = Options {
+            module: Some(String::from("Admin::Views")),
+            class: Some(String::from("Renderer")),
+            ..Options::default()
+        };
+        let source = emit(&templates, &options);
+
+        assert!(source.contains("\nmodule Admin\n  module Views\n"));
+        assert!(source.contains("\n    class Renderer\n"));
+        assert!(!source.contains("Stache"));
+    }
+
+    #[test]
+    fn rejects_unsupported_options() {
+        let templates = vec![Template::from_source("robot", "{{name}}").unwrap()];
+        let options = Options {
+            memoize: vec![String::from("robot")],
+            ..Options::default()
+        };
+        match link(&templates, &options) {
+            Err(Error::Backend(ref message)) => {
+                assert_eq!(
+                    "Memoized partials are not supported by the ruby-source target",
+                    message
+                )
+            }
+            other => panic!("expected unsupported option error: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+extern crate stache;
+
+use std::env;
+use std::path::PathBuf;
+use std::process::exit;
+
+use stache::fixtures::Set;
+
+/// Writes a benchmark template set to a directory, for timing the compiler
+/// binary: `cargo run --example fixtures -- wide /tmp/wide`.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        println!("Usage: fixtures (small|deep|wide) DIR");
+        exit(1);
+    }
+
+    let set = match args[1].as_str() {
+        "small" => Set::Small,
+        "deep" => Set::Deep,
+        "wide" => Set::Wide,
+        _ => {
+            println!("Unknown fixture set: {}", args[1]);
+            exit(1);
+        }
+    };
+
+    if let Err(e) = set.write(&PathBuf::from(&args[2])) {
+        println!("{}", e);
+        exit(1);
+    }
+}
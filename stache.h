@@ -0,0 +1,78 @@
+#ifndef STACHE_H
+#define STACHE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <string.h>
+
+#ifndef STACHE_CTX
+#define STACHE_CTX
+
+enum ctx_type {
+    CTX_NULL,
+    CTX_BOOL,
+    CTX_INTEGER,
+    CTX_STRING,
+    CTX_LIST,
+    CTX_MAP
+};
+
+struct ctx_entry;
+
+struct ctx {
+    enum ctx_type type;
+    size_t length;
+    union {
+        bool boolean;
+        long long integer;
+        const char *string;
+        const struct ctx *items;
+        const struct ctx_entry *entries;
+    } as;
+};
+
+struct ctx_entry {
+    const char *key;
+    struct ctx value;
+};
+
+static inline struct ctx ctx_null(void) {
+    struct ctx value = { .type = CTX_NULL };
+    return value;
+}
+
+static inline struct ctx ctx_bool(bool boolean) {
+    struct ctx value = { .type = CTX_BOOL, .as.boolean = boolean };
+    return value;
+}
+
+static inline struct ctx ctx_integer(long long integer) {
+    struct ctx value = { .type = CTX_INTEGER, .as.integer = integer };
+    return value;
+}
+
+static inline struct ctx ctx_string(const char *string) {
+    struct ctx value = { .type = CTX_STRING, .length = strlen(string), .as.string = string };
+    return value;
+}
+
+static inline struct ctx ctx_list(const struct ctx *items, size_t length) {
+    struct ctx value = { .type = CTX_LIST, .length = length, .as.items = items };
+    return value;
+}
+
+static inline struct ctx ctx_map(const struct ctx_entry *entries, size_t length) {
+    struct ctx value = { .type = CTX_MAP, .length = length, .as.entries = entries };
+    return value;
+}
+
+#endif
+
+/*
+ * Renders the named template, returning a string allocated with malloc
+ * that the caller frees, or NULL if the template isn't found or memory
+ * allocation fails.
+ */
+char *stache_render(const char *name, const struct ctx *context);
+
+#endif
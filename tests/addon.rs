@@ -0,0 +1,59 @@
+extern crate stache;
+extern crate tempdir;
+
+use std::env;
+use std::process::Command;
+use tempdir::TempDir;
+
+use stache::ruby::{self, Options, Target};
+use stache::{Compile, Error, Template};
+
+#[test]
+fn addon() {
+    let build = build("tests/fixtures/templates").unwrap();
+    let script = "./tests/fixtures/test-addon.mjs";
+
+    let output = Command::new(script).arg(build.path()).output().unwrap();
+    if !output.status.success() {
+        let out = String::from_utf8(output.stdout).unwrap();
+        let err = String::from_utf8(output.stderr).unwrap();
+        panic!("{}{}", out, err);
+    }
+}
+
+/// Compile the template directory into a Node.js addon.
+///
+/// Returns the addon's temporary directory to be passed to the Node.js test
+/// script.
+fn build(path: &str) -> Result<TempDir, Error> {
+    let build = TempDir::new("stache-build").map_err(|e| Error::io(env::temp_dir(), e))?;
+    let source = build.path().join("stache.c");
+
+    let options = Options {
+        target: Target::Addon,
+        ..Options::default()
+    };
+    let templates = Template::parse(path)?;
+    let program = ruby::link_with(&templates, &options)?;
+    program.write(&source).map_err(|e| Error::io(&source, e))?;
+
+    let cc = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+    let status = Command::new(cc)
+        .args(["-std=c99", "-shared", "-fPIC", "-I"])
+        .arg(include())
+        .arg("-o")
+        .arg(build.path().join("stache.node"))
+        .arg(&source)
+        .status()
+        .map_err(|e| Error::io(&source, e))?;
+    assert!(status.success(), "failed to compile the addon");
+
+    Ok(build)
+}
+
+/// Returns the directory of the Node.js headers installed with `node`.
+fn include() -> String {
+    let script = "console.log(require('path').join(process.execPath, '../../include/node'))";
+    let output = Command::new("node").args(["-e", script]).output().unwrap();
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
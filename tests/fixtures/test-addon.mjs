@@ -0,0 +1,107 @@
+#!/usr/bin/env node
+
+import assert from "node:assert/strict";
+import { createRequire } from "node:module";
+import { join } from "node:path";
+import { describe, it } from "node:test";
+
+// Temporary build directory.
+const dir = process.argv[2];
+
+// Load compiled addon.
+const require = createRequire(import.meta.url);
+const stache = require(join(dir, "stache.node"));
+
+class Robot {
+  constructor(login) {
+    this.name = { login };
+  }
+
+  bio() {
+    return { html: "<p>A customizable, life embetterment robot.</p>" };
+  }
+
+  disposition() {
+    return "friendly";
+  }
+}
+
+describe("variable tag", () => {
+  it("replaces with object context", () => {
+    const context = {
+      name: { login: "hubot", real: "Hubot" },
+      bio: { html: "<p>A customizable, life embetterment robot.</p>" },
+    };
+    const value = stache.render("robot", context);
+    assert.match(value, /<strong>hubot<\/strong>/);
+    assert.match(value, /Hubot/);
+    assert.match(value, /<p>A customizable/);
+  });
+
+  it("replaces with object method", () => {
+    const value = stache.render("robot", new Robot("hubot"));
+    assert.match(value, /friendly/);
+    assert.match(value, /<strong>hubot<\/strong>/);
+    assert.match(value, /<p>A customizable/);
+  });
+
+  it("escapes html", () => {
+    const value = stache.render("robot", { name: { login: "<b>'hubot' & \"co\"</b>" } });
+    assert.match(value, /&lt;b&gt;&#39;hubot&#39; &amp; &quot;co&quot;&lt;\/b&gt;/);
+  });
+
+  it("does not replace missing values", () => {
+    const value = stache.render("robot", {});
+    assert.match(value, /<strong><\/strong>/);
+    assert.doesNotMatch(value, /undefined|null/);
+  });
+
+  it("renders multibyte text", () => {
+    const value = stache.render("robot", { name: { login: "hübot ✓" } });
+    assert.match(value, /<strong>hübot ✓<\/strong>/);
+  });
+});
+
+describe("section tag", () => {
+  it("renders partials for each item", () => {
+    const context = { title: "Robots", robots: [new Robot("hubot"), new Robot("bender")] };
+    const value = stache.render("robots", context);
+    assert.match(value, /<title>Robots<\/title>/);
+    assert.match(value, /<strong>hubot<\/strong>[\s\S]*<strong>bender<\/strong>/);
+    assert.doesNotMatch(value, /No robots/);
+  });
+
+  it("renders inverted section for empty list", () => {
+    const value = stache.render("robots", { robots: [] });
+    assert.match(value, /No robots/);
+  });
+
+  it("integer key value pushes onto context stack", () => {
+    const value = stache.render("sections/dot", { value: 42, abs: "outer" });
+    assert.equal(value, "42 outer\n");
+  });
+
+  it("true key value does not push context stack", () => {
+    const value = stache.render("sections/true", { value: true });
+    assert.equal(value, "[object Object]\n");
+  });
+});
+
+describe("render", () => {
+  it("escapes characters in template text", () => {
+    assert.equal(stache.render("escape", {}), '<kbd>" \\n "</kbd>\n');
+  });
+
+  it("throws for template not found", () => {
+    assert.throws(() => stache.render("bogus", {}), /Template not found: bogus/);
+    assert.throws(() => stache.render("robots/and/more/robots", {}), /Template not found/);
+  });
+
+  it("throws for template names that are not strings", () => {
+    assert.throws(() => stache.render(42, {}), TypeError);
+  });
+
+  it("exposes the html escaper", () => {
+    assert.equal(stache.escapeHtml("<a href='#'>"), "&lt;a href=&#39;#&#39;&gt;");
+  });
+});
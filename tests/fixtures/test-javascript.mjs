@@ -0,0 +1,105 @@
+#!/usr/bin/env node
+
+import assert from "node:assert/strict";
+import { pathToFileURL } from "node:url";
+import { join } from "node:path";
+import { describe, it } from "node:test";
+
+// Temporary build directory.
+const dir = process.argv[2];
+
+// Load compiled module.
+const stache = await import(pathToFileURL(join(dir, "stache.mjs")));
+
+class Robot {
+  constructor(login) {
+    this.name = { login };
+  }
+
+  bio() {
+    return { html: "<p>A customizable, life embetterment robot.</p>" };
+  }
+
+  disposition() {
+    return "friendly";
+  }
+}
+
+describe("variable tag", () => {
+  it("replaces with object context", () => {
+    const context = {
+      name: { login: "hubot", real: "Hubot" },
+      bio: { html: "<p>A customizable, life embetterment robot.</p>" },
+    };
+    const value = stache.render("robot", context);
+    assert.match(value, /<strong>hubot<\/strong>/);
+    assert.match(value, /Hubot/);
+    assert.match(value, /<p>A customizable/);
+  });
+
+  it("replaces with object method", () => {
+    const value = stache.render("robot", new Robot("hubot"));
+    assert.match(value, /friendly/);
+    assert.match(value, /<strong>hubot<\/strong>/);
+    assert.match(value, /<p>A customizable/);
+  });
+
+  it("escapes html", () => {
+    const value = stache.render("robot", { name: { login: "<b>'hubot' & \"co\"</b>" } });
+    assert.match(value, /&lt;b&gt;&#39;hubot&#39; &amp; &quot;co&quot;&lt;\/b&gt;/);
+  });
+
+  it("does not replace missing values", () => {
+    const value = stache.render("robot", {});
+    assert.match(value, /<strong><\/strong>/);
+    assert.doesNotMatch(value, /undefined|null/);
+  });
+});
+
+describe("section tag", () => {
+  it("renders partials for each item", () => {
+    const context = { title: "Robots", robots: [new Robot("hubot"), new Robot("bender")] };
+    const value = stache.render("robots", context);
+    assert.match(value, /<title>Robots<\/title>/);
+    assert.match(value, /<strong>hubot<\/strong>[\s\S]*<strong>bender<\/strong>/);
+    assert.doesNotMatch(value, /No robots/);
+  });
+
+  it("indents standalone partials", () => {
+    const value = stache.render("robots", { robots: [new Robot("hubot")] });
+    assert.match(value, /\n    <li>\n      friendly\n      <strong>hubot<\/strong>\n/);
+  });
+
+  it("renders inverted section for empty list", () => {
+    const value = stache.render("robots", { robots: [] });
+    assert.match(value, /No robots/);
+  });
+
+  it("integer key value pushes onto context stack", () => {
+    const value = stache.render("sections/dot", { value: 42, abs: "outer" });
+    assert.equal(value, "42 outer\n");
+  });
+
+  it("true key value does not push context stack", () => {
+    const value = stache.render("sections/true", { value: true });
+    assert.equal(value, "[object Object]\n");
+  });
+});
+
+describe("render", () => {
+  it("exports a function for each template", () => {
+    assert.equal(stache.render_escape({}), stache.render("escape", {}));
+  });
+
+  it("escapes characters in template text", () => {
+    assert.equal(stache.render("escape", {}), '<kbd>" \\n "</kbd>\n');
+  });
+
+  it("throws for template not found", () => {
+    assert.throws(() => stache.render("bogus", {}), /Template not found: bogus/);
+  });
+
+  it("exposes the html escaper", () => {
+    assert.equal(stache.escapeHtml("<a href='#'>"), "&lt;a href=&#39;#&#39;&gt;");
+  });
+});
@@ -0,0 +1,107 @@
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "stache.h"
+
+static int failures = 0;
+
+static void check(const char *name, char *output, const char *expected, int contains) {
+    if (!output) {
+        printf("%s: render returned NULL\n", name);
+        failures++;
+        return;
+    }
+    if (contains ? !strstr(output, expected) : strstr(output, expected) != NULL) {
+        printf("%s: expected output %s `%s`:\n%s\n", name, contains ? "to contain" : "not to contain", expected, output);
+        failures++;
+    }
+    free(output);
+}
+
+static void variables(void) {
+    struct ctx_entry name[] = {
+        { "login", ctx_string("hubot") },
+        { "real", ctx_string("Hubot") },
+    };
+    struct ctx_entry bio[] = {
+        { "html", ctx_string("<p>A customizable, life embetterment robot.</p>") },
+    };
+    struct ctx_entry robot[] = {
+        { "name", ctx_map(name, 2) },
+        { "bio", ctx_map(bio, 1) },
+        { "disposition", ctx_integer(-42) },
+    };
+    struct ctx context = ctx_map(robot, 3);
+
+    check("replaces values", stache_render("robot", &context), "<strong>hubot</strong>", 1);
+    check("renders unescaped values", stache_render("robot", &context), "<p>A customizable", 1);
+    check("formats integers", stache_render("robot", &context), "-42", 1);
+
+    struct ctx_entry html[] = {
+        { "login", ctx_string("<b>'hubot' & \"co\"</b>") },
+    };
+    struct ctx_entry escaped[] = {
+        { "name", ctx_map(html, 1) },
+    };
+    context = ctx_map(escaped, 1);
+    check("escapes html", stache_render("robot", &context),
+          "&lt;b&gt;&#39;hubot&#39; &amp; &quot;co&quot;&lt;/b&gt;", 1);
+
+    context = ctx_null();
+    check("skips missing values", stache_render("robot", &context), "<strong></strong>", 1);
+}
+
+static void sections(void) {
+    struct ctx_entry hubot[] = { { "name", ctx_map((struct ctx_entry[]) { { "login", ctx_string("hubot") } }, 1) } };
+    struct ctx_entry bender[] = { { "name", ctx_map((struct ctx_entry[]) { { "login", ctx_string("bender") } }, 1) } };
+    struct ctx robots[] = { ctx_map(hubot, 1), ctx_map(bender, 1) };
+    struct ctx_entry list[] = {
+        { "title", ctx_string("Robots") },
+        { "robots", ctx_list(robots, 2) },
+    };
+    struct ctx context = ctx_map(list, 2);
+
+    check("renders partials", stache_render("robots", &context), "<title>Robots</title>", 1);
+    check("renders each item", stache_render("robots", &context), "<strong>bender</strong>", 1);
+    check("skips inverted sections", stache_render("robots", &context), "No robots", 0);
+
+    struct ctx_entry empty[] = { { "robots", ctx_list(NULL, 0) } };
+    context = ctx_map(empty, 1);
+    check("renders inverted sections", stache_render("robots", &context), "No robots", 1);
+
+    struct ctx numbers[] = { ctx_integer(1), ctx_integer(2) };
+    struct ctx_entry items[] = { { "value", ctx_list(numbers, 2) } };
+    context = ctx_map(items, 1);
+    check("renders the implicit iterator", stache_render("sections/true", &context), "12", 1);
+
+    struct ctx_entry dot[] = { { "value", ctx_string("<b>") } };
+    context = ctx_map(dot, 1);
+    check("escapes the implicit iterator", stache_render("sections/dot", &context), "&lt;b&gt;", 1);
+
+    struct ctx_entry no[] = { { "value", ctx_bool(false) } };
+    context = ctx_map(no, 1);
+    check("skips false sections", stache_render("sections/dot", &context), "&lt;", 0);
+
+    struct ctx_entry first[] = { { "name", ctx_string("i1") } };
+    struct ctx indexed[] = { ctx_map(first, 1) };
+    struct ctx_entry index[] = { { "items", ctx_list(indexed, 1) } };
+    context = ctx_map(index, 1);
+    check("looks up list indexes", stache_render("sections/index", &context), "i1", 1);
+}
+
+static void templates(void) {
+    struct ctx context = ctx_null();
+    check("renders constant templates", stache_render("includes/footer", &context), "</html>", 1);
+    if (stache_render("missing", &context) != NULL) {
+        printf("unknown template: expected NULL\n");
+        failures++;
+    }
+}
+
+int main(void) {
+    variables();
+    sections();
+    templates();
+    return failures ? 1 : 0;
+}
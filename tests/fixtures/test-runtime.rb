@@ -6,10 +6,13 @@ require 'ostruct'
 # Temporary build directory.
 dir = ARGV[0]
 
-# Compile extension into shared object.
-Dir.chdir(dir) do
-  `ruby -r mkmf -e '$CFLAGS = "-std=c99 -O3"; create_makefile("stache")'`
-  `make`
+# Compile extension into shared object, unless the templates were compiled
+# into Ruby source.
+if File.exist?(File.join(dir, 'stache.c'))
+  Dir.chdir(dir) do
+    `ruby -r mkmf -e '$CFLAGS = "-std=c99 -O3"; create_makefile("stache")'`
+    `make`
+  end
 end
 
 # Load compiled extension.
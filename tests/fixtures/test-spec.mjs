@@ -0,0 +1,27 @@
+#!/usr/bin/env node
+
+import { readFileSync } from "node:fs";
+import { pathToFileURL } from "node:url";
+import { join } from "node:path";
+
+// Temporary build directory.
+const dir = process.argv[2];
+
+// Load compiled module and specification test data.
+const stache = await import(pathToFileURL(join(dir, "stache.mjs")));
+const tests = JSON.parse(readFileSync(join(dir, "tests.json"), "utf8"));
+
+// Report test failures to cargo.
+let success = true;
+for (const test of tests) {
+  const result = stache.render(test.template, test.data);
+  if (result !== test.expected) {
+    console.log(`\n${test.template} - ${test.name}`);
+    console.log(`expect: ${JSON.stringify(test.expected)}`);
+    console.log(`actual: ${JSON.stringify(result)}`);
+    success = false;
+  }
+}
+
+// Pass or fail the cargo test suite.
+process.exit(success ? 0 : 1);
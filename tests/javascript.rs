@@ -0,0 +1,37 @@
+extern crate stache;
+extern crate tempdir;
+
+use std::env;
+use std::process::Command;
+use tempdir::TempDir;
+
+use stache::javascript;
+use stache::{Compile, Error, Template};
+
+#[test]
+fn javascript() {
+    let build = build("tests/fixtures/templates").unwrap();
+    let script = "./tests/fixtures/test-javascript.mjs";
+
+    let output = Command::new(script).arg(build.path()).output().unwrap();
+    if !output.status.success() {
+        let out = String::from_utf8(output.stdout).unwrap();
+        let err = String::from_utf8(output.stderr).unwrap();
+        panic!("{}{}", out, err);
+    }
+}
+
+/// Compile the template directory into a JavaScript module.
+///
+/// Returns the module's temporary directory to be passed to the Node.js test
+/// script.
+fn build(path: &str) -> Result<TempDir, Error> {
+    let build = TempDir::new("stache-build").map_err(|e| Error::io(env::temp_dir(), e))?;
+    let source = build.path().join("stache.mjs");
+
+    let templates = Template::parse(path)?;
+    let program = javascript::link(&templates)?;
+    program.write(&source).map_err(|e| Error::io(source, e))?;
+
+    Ok(build)
+}
@@ -0,0 +1,58 @@
+extern crate stache;
+extern crate tempdir;
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use tempdir::TempDir;
+
+use stache::ruby::{self, Options, Target};
+use stache::{Compile, Error, Template};
+
+#[test]
+fn library() {
+    let build = build("tests/fixtures/templates").unwrap();
+    let test = Path::new("tests/fixtures/test-library.c");
+    let binary = build.path().join("test-library");
+
+    let cc = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+    let status = Command::new(cc)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-o"])
+        .arg(&binary)
+        .arg("-I")
+        .arg(build.path())
+        .arg(build.path().join("templates.c"))
+        .arg(test)
+        .status()
+        .unwrap();
+    assert!(status.success(), "failed to compile the C library");
+
+    let output = Command::new(&binary).output().unwrap();
+    if !output.status.success() {
+        let out = String::from_utf8(output.stdout).unwrap();
+        let err = String::from_utf8(output.stderr).unwrap();
+        panic!("{}{}", out, err);
+    }
+}
+
+/// Compile the template directory into C library source and its header.
+///
+/// Returns the library's temporary directory, in which the test program is
+/// compiled.
+fn build(path: &str) -> Result<TempDir, Error> {
+    let build = TempDir::new("stache-build").map_err(|e| Error::io(env::temp_dir(), e))?;
+    let source = build.path().join("templates.c");
+
+    let options = Options {
+        target: Target::Library,
+        ..Options::default()
+    };
+    let templates = Template::parse(path)?;
+    let program = ruby::link_with(&templates, &options)?;
+    program.write(&source).map_err(|e| Error::io(&source, e))?;
+    program
+        .write_header(build.path())
+        .map_err(|e| Error::io(build.path(), e))?;
+
+    Ok(build)
+}
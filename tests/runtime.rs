@@ -1,12 +1,12 @@
 extern crate stache;
 extern crate tempdir;
 
-use std::io::Error;
+use std::env;
 use std::process::Command;
 use tempdir::TempDir;
 
 use stache::ruby;
-use stache::{Compile, Template};
+use stache::{Compile, Error, Template};
 
 #[test]
 fn ruby() {
@@ -21,6 +21,19 @@ fn ruby() {
     }
 }
 
+#[test]
+fn ruby_source() {
+    let build = build_source("tests/fixtures/templates").unwrap();
+    let script = "./tests/fixtures/test-runtime.rb";
+
+    let output = Command::new(script).arg(build.path()).output().unwrap();
+    if !output.status.success() {
+        let out = String::from_utf8(output.stdout).unwrap();
+        let err = String::from_utf8(output.stderr).unwrap();
+        panic!("{}{}", out, err);
+    }
+}
+
 #[ignore]
 #[test]
 fn bench_ruby() {
@@ -38,12 +51,27 @@ fn bench_ruby() {
 /// Returns the source file's temporary directory to be passed to the Ruby
 /// test scripts for final compilation.
 fn build(path: &str) -> Result<TempDir, Error> {
-    let build = TempDir::new("stache-build")?;
+    let build = TempDir::new("stache-build").map_err(|e| Error::io(env::temp_dir(), e))?;
     let source = build.path().join("stache.c");
 
     let templates = Template::parse(path)?;
-    let program = ruby::link(&templates).unwrap();
-    program.write(source)?;
+    let program = ruby::link(&templates)?;
+    program.write(&source).map_err(|e| Error::io(source, e))?;
+
+    Ok(build)
+}
+
+/// Compile the template directory into a plain Ruby source file.
+///
+/// Returns the source file's temporary directory to be passed to the Ruby
+/// test scripts, which load it in place of the extension.
+fn build_source(path: &str) -> Result<TempDir, Error> {
+    let build = TempDir::new("stache-build").map_err(|e| Error::io(env::temp_dir(), e))?;
+    let source = build.path().join("stache.rb");
+
+    let templates = Template::parse(path)?;
+    let program = ruby::source::link(&templates, &ruby::Options::default())?;
+    program.write(&source).map_err(|e| Error::io(source, e))?;
 
     Ok(build)
 }
@@ -1,14 +1,18 @@
+#[macro_use]
+extern crate serde_json;
 extern crate stache;
 extern crate tempdir;
 extern crate yaml_rust;
 
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use stache::ruby;
-use stache::{Compile, Statement, Template};
+use serde_json::{Map, Number, Value};
+use stache::render::Renderer;
+use stache::{javascript, ruby};
+use stache::{Block, Compile, Statement, Template};
 
 use tempdir::TempDir;
 use yaml_rust::{Yaml, YamlLoader};
@@ -31,10 +35,90 @@ fn ruby() {
     }
 }
 
-/// Parses templates provided by the Mustache specification suite.
+#[test]
+fn javascript() {
+    let build = TempDir::new("stache-build").unwrap();
+    let source = build.path().join("stache.mjs");
+    let script = "./tests/fixtures/test-spec.mjs";
+
+    // Dotted names resolve their first key through the context stack and
+    // each later key only within the value found. Standalone partials indent
+    // each line they render.
+    let (mut templates, tests): (Vec<_>, Vec<_>) =
+        suite(&["interpolation", "partials"]).into_iter().unzip();
+    let cases: Vec<_> = templates
+        .iter()
+        .zip(&tests)
+        .map(|(template, test)| {
+            json!({
+                "template": template.name,
+                "name": test["name"].as_str().unwrap(),
+                "data": json(&test["data"]),
+                "expected": test["expected"].as_str().unwrap(),
+            })
+        })
+        .collect();
+    templates.extend(partials());
+    let program = javascript::link(&templates).unwrap();
+    program.write(source).unwrap();
+
+    let mut file = File::create(build.path().join("tests.json")).unwrap();
+    file.write_all(Value::Array(cases).to_string().as_bytes())
+        .unwrap();
+
+    let output = Command::new(script).arg(build.path()).output().unwrap();
+    if !output.status.success() {
+        let out = String::from_utf8(output.stdout).unwrap();
+        let err = String::from_utf8(output.stderr).unwrap();
+        panic!("{}{}", out, err);
+    }
+}
+
+#[test]
+fn native() {
+    let (mut templates, tests): (Vec<_>, Vec<_>) = tests().into_iter().unzip();
+    templates.extend(partials());
+    let renderer = Renderer::new(&templates).unwrap();
+
+    for (template, test) in templates.iter().zip(&tests) {
+        let context = json(&test["data"]);
+        let expected = test["expected"].as_str().unwrap();
+        let output = renderer.render(&template.name, &context).unwrap();
+        assert_eq!(
+            expected,
+            output,
+            "{}: {}",
+            template.name,
+            test["name"].as_str().unwrap()
+        );
+    }
+}
+
+/// Parses templates provided by the Mustache specification suite, along with
+/// the partials they call.
 fn templates() -> Vec<Template> {
+    tests()
+        .into_iter()
+        .map(|(template, _)| template)
+        .chain(partials())
+        .collect()
+}
+
+/// Parses each test of the Mustache specification suite into its template,
+/// paired with the test's data and expected output.
+fn tests() -> Vec<(Template, Yaml)> {
+    suite(&[
+        "comments",
+        "interpolation",
+        "inverted",
+        "partials",
+        "sections",
+    ])
+}
+
+/// Parses the tests of the named specification files.
+fn suite(files: &[&str]) -> Vec<(Template, Yaml)> {
     let base = PathBuf::from("ext/spec/specs");
-    let files = vec!["comments", "interpolation", "inverted", "sections"];
     files
         .iter()
         .flat_map(|name| {
@@ -46,15 +130,105 @@ fn templates() -> Vec<Template> {
                 .enumerate()
                 .map(|(index, test)| {
                     let template = test["template"].as_str().unwrap();
-                    let tree = Statement::parse(template).unwrap();
-                    let fake = path.with_file_name(format!("{}{}", name, index));
-                    Template::new(&base, fake, tree)
+                    let scope = format!("{}{}", name, index);
+                    let tree = scoped(Statement::parse(template).unwrap(), &scope);
+                    let fake = path.with_file_name(scope);
+                    (Template::new(&base, fake, tree), test.clone())
                 })
                 .collect::<Vec<_>>()
         })
         .collect()
 }
 
+/// Parses the partials of each partials spec test into templates named
+/// within the test's scope, like `partials0/text`. Partials a test calls but
+/// doesn't define render nothing, so they're linked as empty templates.
+fn partials() -> Vec<Template> {
+    let base = PathBuf::from("ext/spec/specs");
+    let path = base.join("partials.yml");
+    let spec = document(&path);
+    let tests = spec["tests"].as_vec().unwrap();
+
+    let mut templates = Vec::new();
+    for (index, test) in tests.iter().enumerate() {
+        let scope = format!("partials{}", index);
+        let mut sources = vec![(scope.clone(), test["template"].as_str().unwrap())];
+        if let Some(partials) = test["partials"].as_hash() {
+            for (name, source) in partials {
+                let name = format!("{}/{}", scope, name.as_str().unwrap());
+                sources.push((name, source.as_str().unwrap()));
+            }
+        }
+
+        let mut called = Vec::new();
+        for (name, source) in &sources {
+            let tree = scoped(Statement::parse(source).unwrap(), &scope);
+            called.extend(tree.partials().into_iter().map(String::from));
+            if *name != scope {
+                let fake = path.with_file_name(name);
+                templates.push(Template::new(&base, fake, tree));
+            }
+        }
+
+        called.sort();
+        called.dedup();
+        for name in called {
+            if !sources.iter().any(|(defined, _)| *defined == name) {
+                let tree = Statement::parse("").unwrap();
+                templates.push(Template::new(&base, path.with_file_name(name), tree));
+            }
+        }
+    }
+    templates
+}
+
+/// Renames the partials the tree calls into the test's scope, so tests can
+/// define partials of the same name.
+fn scoped(node: Statement, scope: &str) -> Statement {
+    let block = |block: Block| Block {
+        statements: block
+            .statements
+            .into_iter()
+            .map(|stmt| scoped(stmt, scope))
+            .collect(),
+        ..block
+    };
+    match node {
+        Statement::Program(b) => Statement::Program(block(b)),
+        Statement::Section(path, b) => Statement::Section(path, block(b)),
+        Statement::Inverted(path, b) => Statement::Inverted(path, block(b)),
+        Statement::Partial(name, padding, arguments) => {
+            Statement::Partial(format!("{}/{}", scope, name), padding, arguments)
+        }
+        other => other,
+    }
+}
+
+/// Converts a YAML spec value into a JSON context value.
+fn json(yaml: &Yaml) -> Value {
+    match *yaml {
+        Yaml::String(ref text) => Value::String(text.clone()),
+        Yaml::Integer(n) => Value::from(n),
+        Yaml::Real(ref n) => n
+            .parse()
+            .ok()
+            .and_then(Number::from_f64)
+            .map_or(Value::Null, Value::Number),
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Array(ref items) => Value::Array(items.iter().map(json).collect()),
+        Yaml::Hash(ref hash) => {
+            let mut map = Map::new();
+            for (key, value) in hash {
+                if let Some(key) = key.as_str() {
+                    map.insert(key.to_string(), json(value));
+                }
+            }
+            Value::Object(map)
+        }
+        _ => Value::Null,
+    }
+}
+
 /// Parses the YAML document at the given path.
 fn document(path: &Path) -> Yaml {
     let mut file = File::open(path).unwrap();